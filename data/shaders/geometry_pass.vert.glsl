@@ -21,8 +21,54 @@ void main()
     vec3 view_vector;
     #endif
     texture_uv = vec2(v_tex1.x, 1.0 - v_tex1.y);
+    #ifdef DEFORMED
+    vec3 pos = v_pos;
+    vec3 nor = v_nor;
+    vec3 tan = v_tan;
+    vec3 bin = v_bin;
+    // morph.info.x - число целей морфинга, morph.info.y - число вершин меша
+    if (morph.info.x > 0u) {
+        vec3 delta_pos = vec3(0.0), delta_nor = vec3(0.0), delta_tan = vec3(0.0);
+        for (uint i = 0u; i < min(morph.info.x, uint(MAX_MORPH_TARGETS)); i++) {
+            float weight = morph.weights[i >> 2u][i & 3u];
+            if (weight != 0.0) {
+                MorphDelta delta = morph_deltas[i * morph.info.y + uint(gl_VertexIndex)];
+                delta_pos += delta.position.xyz * weight;
+                delta_nor += delta.normal.xyz * weight;
+                delta_tan += delta.tangent.xyz * weight;
+            }
+        }
+        if (delta_pos != vec3(0.0) || delta_nor != vec3(0.0) || delta_tan != vec3(0.0)) {
+            float handedness = dot(cross(nor, tan), bin) < 0.0 ? -1.0 : 1.0;
+            pos += delta_pos;
+            nor += delta_nor;
+            tan += delta_tan;
+            nor = length(nor) > 0.0 ? normalize(nor) : nor;
+            tan = length(tan) > 0.0 ? normalize(tan) : tan;
+            bin = cross(nor, tan) * handedness;
+        }
+    }
+    float weight_sum = v_wgt.x + v_wgt.y + v_wgt.z;
+    mat4 skin = mat4(1.0);
+    if (weight_sum > 0.0) {
+        uvec3 bones = min(v_grp, uvec3(MAX_BONES - 1));
+        skin = (skeleton.bones[bones.x] * v_wgt.x +
+                skeleton.bones[bones.y] * v_wgt.y +
+                skeleton.bones[bones.z] * v_wgt.z) / weight_sum;
+    }
+    pos = (skin * vec4(pos, 1.0)).xyz;
+    mat3 skin_rotation = mat3(skin);
+    nor = skin_rotation * nor;
+    tan = skin_rotation * tan;
+    bin = skin_rotation * bin;
+    #else
+    vec3 pos = v_pos;
+    vec3 nor = v_nor;
+    vec3 tan = v_tan;
+    vec3 bin = v_bin;
+    #endif
     mat4 model = camera.transform_inverted * transform;
-    position = camera.projection * model * vec4(v_pos, 1.0);
+    position = camera.projection * model * vec4(pos, 1.0);
 
     #ifdef SUPER_RESOLUTION
     vec2 dither_angle = dither[timer.frame & 0x3];
@@ -33,13 +79,13 @@ void main()
     #endif
 
     #ifndef SHADOWMAP
-    position_prev = camera.projection * camera.transform_prev_inverted * transform_prev * vec4(v_pos, 1.0);
-    float nLength = length(v_nor);
-    TBN = mat3(v_tan, -v_bin, v_nor);
+    position_prev = camera.projection * camera.transform_prev_inverted * transform_prev * vec4(pos, 1.0);
+    float nLength = length(nor);
+    TBN = mat3(tan, -bin, nor);
     TBN = mat3(transform[0].xyz, transform[1].xyz, transform[2].xyz) * TBN;
     #endif
     triangle_index = gl_InstanceIndex;
-    world_position = (model * vec4(v_pos, 1.0)).xyz;
+    world_position = (model * vec4(pos, 1.0)).xyz;
     view_vector = (camera.transform_inverted * position).xyz;
     #ifdef SHADOWMAP
     //position.xy -= random_angles[timer.frame & 0xF] / vec2(resolution.dimensions) * position.w;
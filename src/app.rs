@@ -0,0 +1,607 @@
+//! Общий каркас приложений на движке.
+//!
+//! [`App`] открывает окно, загружает сцену из конфигурации и крутит цикл событий.
+//! [`render_offscreen`] рендерит сцену без окна в последовательность PNG-файлов.
+use crate::error::DsgeError;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::memory::allocator::StandardMemoryAllocator;
+use vulkano::swapchain::Surface;
+use vulkano::{Version, VulkanLibrary};
+use winit::dpi::PhysicalPosition;
+use winit::event::{
+    DeviceEvent, ElementState, Event, KeyboardInput, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+use winit::event_loop::{ControlFlow, EventLoop, EventLoopWindowTarget};
+use winit::window::{CursorGrabMode, Fullscreen, Window, WindowBuilder};
+
+use crate::command_buffer::CommandBufferFather;
+use crate::config::AppConfig;
+use crate::console::{Console, Cvars};
+use crate::game_logic::events::*;
+use crate::game_logic::mouse_look::MouseLook;
+use crate::game_logic::AbstractEvent;
+use crate::game_object::GameObjectRef;
+use crate::references::*;
+use crate::renderer::Renderer;
+use crate::resource_manager::{ResourceManager, ResourceManagerRef};
+use crate::scene::{Scene, SceneRef};
+use crate::texture::Texture;
+use crate::time;
+
+#[derive(Clone)]
+pub struct Mouse {
+    surface: Arc<Surface>,
+    mouse_delta: [i32; 2],
+    cursor_position: [i32; 2],
+    grabbed: bool,
+    visible: bool,
+}
+
+impl Mouse {
+    fn window(&self) -> &Window
+    {
+        self.surface.object().unwrap().downcast_ref::<Window>().unwrap()
+    }
+
+    fn new_with_surface(surface: Arc<Surface>) -> Self {
+        Self {
+            surface,
+            mouse_delta: [0, 0],
+            cursor_position: [0, 0],
+            grabbed: false,
+            visible: true,
+        }
+    }
+
+    pub fn set_cursor_position(&self, x: i32, y: i32) -> Result<(), DsgeError> {
+        let cur_pos = PhysicalPosition::new(x, y);
+        match self.window().set_cursor_position(cur_pos) {
+            Ok(_) => Ok(()),
+            Err(error) => Err(format!("Ошибка установки курсора: {:?}", error).into()),
+        }
+    }
+
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        self.visible = visible;
+        self.window().set_cursor_visible(visible);
+    }
+
+    pub fn set_cursor_grab(&mut self, grab: bool) -> Result<(), DsgeError> {
+        let en_grab = match grab {
+            true => CursorGrabMode::Locked,
+            false => CursorGrabMode::None,
+        };
+        match self.window().set_cursor_grab(en_grab) {
+            Ok(_) => {
+                self.grabbed = grab;
+                Ok(())
+            }
+            Err(error) => Err(format!("Ошибка установки курсора: {:?}", error).into()),
+        }
+    }
+
+    #[inline]
+    pub fn cursor_grab(&mut self) -> bool {
+        self.grabbed
+    }
+
+    #[inline]
+    pub fn mouse_delta(&self) -> [i32; 2] {
+        self.mouse_delta
+    }
+
+    #[inline]
+    pub fn cursor_position(&self) -> [i32; 2] {
+        self.cursor_position
+    }
+}
+
+pub struct Time {
+    frame_time: f64,
+    up_time: f64,
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        Self {
+            frame_time: 1.0 / 60.0,
+            up_time: 0.0,
+        }
+    }
+}
+
+impl Time {
+    #[inline]
+    pub fn time_delta(&self) -> f64 {
+        self.frame_time
+    }
+
+    #[inline]
+    pub fn up_time(&self) -> f64 {
+        self.up_time
+    }
+}
+
+/// Текстуры, которые передаются во все стадии постобработки
+pub fn load_static_inputs(
+    command_buffer_father: &CommandBufferFather,
+    allocator: Arc<StandardMemoryAllocator>,
+) -> Result<HashMap<String, Texture>, DsgeError> {
+    let blue_noise = Texture::from_file(
+        command_buffer_father,
+        allocator.clone(),
+        "data/blue_noise_1024.png",
+        false,
+        false
+    )?.0;
+    let screen_font = Texture::from_file(
+        command_buffer_father,
+        allocator,
+        "data/texture/shadertoy_font.png",
+        false,
+        false
+    )?.0;
+    Ok([
+        ("blue_noise".to_owned(), blue_noise),
+        ("font".to_owned(), screen_font),
+    ]
+    .into_iter()
+    .collect::<HashMap<_, _>>())
+}
+
+/// Камера сцены: объект с именем `name`, если оно указано, иначе активная камера из файла сцены
+fn scene_camera(
+    scene: &SceneRef,
+    default_camera: Option<GameObjectRef>,
+    name: Option<&str>,
+) -> Result<GameObjectRef, DsgeError> {
+    let camera = match name {
+        Some(name) => scene
+            .lock()
+            .find_object(name)
+            .ok_or_else(|| DsgeError::AssetNotFound(format!("Объект \"{name}\" не найден в сцене.")))?,
+        None => default_camera.ok_or_else(|| DsgeError::AssetNotFound("В сцене нет активной камеры.".to_owned()))?,
+    };
+    if camera.lock().camera().is_none() {
+        return Err(DsgeError::InvalidArgument(format!(
+            "Объект \"{}\" не содержит компонента камеры.",
+            camera.lock().name()
+        )));
+    }
+    Ok(camera)
+}
+
+/// Оконное приложение: сцена из конфигурации и камера, управляемая мышью
+pub struct App {
+    resource_manager: ResourceManagerRef,
+    renderer: Renderer,
+    scene: SceneRef,
+    camera: GameObjectRef,
+    event_pump: Option<EventLoop<()>>,
+    mouse: RcBox<Mouse>,
+    time: Time,
+    console: Console,
+}
+
+impl App {
+    pub fn time(&self) -> &Time {
+        &self.time
+    }
+
+    pub fn mouse(&self) -> RcBox<Mouse> {
+        self.mouse.clone()
+    }
+
+    pub fn scene(&self) -> &SceneRef {
+        &self.scene
+    }
+
+    pub fn camera(&self) -> &GameObjectRef {
+        &self.camera
+    }
+
+    pub fn resource_manager(&self) -> &ResourceManagerRef {
+        &self.resource_manager
+    }
+
+    /// Консольные переменные движка, поведения могут регистрировать в них свои
+    pub fn cvars(&self) -> &Cvars {
+        self.console.cvars()
+    }
+
+    pub fn console(&self) -> &Console {
+        &self.console
+    }
+}
+
+impl App {
+    /// Открывает окно и загружает сцену `config.scene`.
+    /// К камере `camera` (или к активной камере сцены) добавляется [`MouseLook`].
+    /// Цикл событий читает консольные команды из stdin.
+    pub fn new(title: &str, config: &AppConfig, camera: Option<&str>) -> Result<Self, DsgeError> {
+        let event_loop = EventLoop::new();
+        let library = VulkanLibrary::new().map_err(|err| DsgeError::vulkan("Failed to load Vulkan library", err))?;
+        let required_extensions = Surface::required_extensions(&event_loop);
+        let vk_instance = Instance::new(
+            library,
+            InstanceCreateInfo {
+                enabled_extensions: required_extensions,
+                max_api_version: Some(Version::major_minor(1, 2)),
+                ..Default::default()
+            },
+        )
+        .map_err(|err| DsgeError::vulkan("Failed to create Vulkan instance", err))?;
+        // let mut monitors = event_loop.available_monitors();
+        // let monitor = monitors.next().expect("no monitor found!");
+        // let mut video_modes = monitor.video_modes();
+        // video_modes.next();
+
+        let wsize = winit::dpi::PhysicalSize {
+            width: config.width,
+            height: config.height,
+        };
+        let window = WindowBuilder::new()
+            .with_title(title)
+            //.with_fullscreen(if config.fullscreen { Some(Fullscreen::Exclusive(video_modes.next().unwrap())) } else { None } )
+            .with_fullscreen(if config.fullscreen {Some(Fullscreen::Borderless(None))} else {None})
+            .with_inner_size(wsize)
+            .build(&event_loop)
+            .map_err(|err| DsgeError::Message(format!("Failed to create window: {err}")))?;
+        let surface = Surface::from_window(
+            vk_instance.clone(), Arc::new(window)
+        ).map_err(|err| DsgeError::vulkan("Failed to create surface", err))?;
+
+        let (device, mut queues) = Renderer::default_device(vk_instance.clone())
+            .map_err(|err| DsgeError::vulkan("Failed to create device", err))?;
+        let queue = queues.next().unwrap();
+
+        // Инициализация менеджера ресурсов
+        let resource_manager = ResourceManager::new(
+            device.clone(),
+            queue.clone(),
+            config.resource_manager_config(),
+            config.super_resolution,
+        )?;
+        let resource_manager = RcBox::construct(resource_manager);
+        // Инициализация рендера
+        let mut renderer = Renderer::winit(
+            vk_instance,
+            resource_manager.clone(),
+            surface.clone(),
+            [config.width, config.height],
+            config.vsync,
+            config.super_resolution,
+            config.fxaa,
+        );
+
+        let console = Console::new(Cvars::new());
+        renderer.register_cvars(console.cvars())?;
+
+        let (scene, default_camera) = Scene::from_file(config.scene_path().as_str(), &mut resource_manager.lock());
+        let camera = scene_camera(&scene, default_camera, camera)?;
+        {
+            let mut _camera = camera.lock();
+            if let Some(mouse_look) = _camera.add_component(MouseLook::new(0.001, false)) {
+                MouseLook::bind_cvars(&mouse_look, console.cvars())?;
+            }
+            _camera.set_static(false);
+        }
+        renderer.set_camera(camera.clone());
+        renderer.update_swapchain(Some([config.width, config.height]));
+        Ok(Self {
+            scene,
+            camera,
+            renderer,
+            resource_manager,
+            event_pump: Some(event_loop),
+            mouse: RcBox::construct(Mouse::new_with_surface(surface)),
+            time: Time::default(),
+            console,
+        })
+    }
+
+    pub fn event_loop(mut self) /*-> Arc<EventLoop<()>>*/
+    {
+        let queue = self.renderer.queue().clone();
+        let command_buffer_father = CommandBufferFather::new(queue.clone());
+        let allocator = Arc::new(StandardMemoryAllocator::new_default(queue.device().clone()));
+        let static_input = load_static_inputs(&command_buffer_father, allocator.clone()).unwrap();
+
+        let mut timer = time::Timer::new();
+        let mut take_screenshot = false;
+        let mut grab_coords = [0i32, 0i32];
+        //surface.window().set_cursor_grab(true);
+        let event_pump;
+        (event_pump, self.event_pump) = (self.event_pump, None);
+
+        let event_processor = self.scene.lock().event_processor().clone();
+        self.console.spawn_stdin();
+
+        let app = RcBox::construct(self);
+        let app2 = app.clone();
+
+        let mut fps_timer = time::Timer::new();
+        event_pump.unwrap().run(
+            move |event: Event<()>,
+                  _wtar: &EventLoopWindowTarget<()>,
+                  control_flow: &mut ControlFlow| {
+                //*control_flow = ControlFlow::Wait;
+                let a = app.clone();
+                match event {
+                    Event::WindowEvent {
+                        event: WindowEvent::CloseRequested,
+                        ..
+                    } => {
+                        *control_flow = ControlFlow::Exit;
+                    }
+                    Event::WindowEvent {
+                        event: WindowEvent::Resized(_),
+                        ..
+                    } => {
+                        log::info!(target: "renderer", "Window resized, recreating swapchain");
+                        a.lock().renderer.update_swapchain(None)
+                    }
+                    Event::WindowEvent {
+                        event: WindowEvent::CursorMoved { position, .. },
+                        ..
+                    } => a.lock().mouse.lock().cursor_position = [position.x as _, position.y as _],
+                    Event::DeviceEvent { event, .. } => match event {
+                        DeviceEvent::Key(KeyboardInput {
+                            virtual_keycode: Some(VirtualKeyCode::F11),
+                            state: ElementState::Pressed,
+                            ..
+                        }) => {
+                            take_screenshot = true;
+                        }
+                        DeviceEvent::Key(KeyboardInput {
+                            virtual_keycode: Some(VirtualKeyCode::Escape),
+                            state: ElementState::Pressed,
+                            ..
+                        }) => {
+                            control_flow.set_exit();
+                        }
+                        DeviceEvent::Key(KeyboardInput {
+                            virtual_keycode: Some(virtual_keycode),
+                            state,
+                            ..
+                        }) => {
+                            let state = match state {
+                                ElementState::Pressed => 1,
+                                ElementState::Released => -1,
+                            };
+                            event_processor.send_event(AbstractEvent::Keyboard(KeyboardEvent {
+                                key_id: virtual_keycode,
+                                state,
+                            }));
+                        }
+                        DeviceEvent::MouseWheel { delta } => {
+                            let (mwdx, mwdy): (i32, i32) = match delta {
+                                MouseScrollDelta::LineDelta(x, y) => (x as _, y as _),
+                                MouseScrollDelta::PixelDelta(PhysicalPosition { x, y }) => {
+                                    (x as _, y as _)
+                                }
+                            };
+                            let mwdx = match mwdx {
+                                0 => 0,
+                                (1..) => 1,
+                                _ => -1,
+                            };
+                            let mwdy = match mwdy {
+                                0 => 0,
+                                (1..) => 1,
+                                _ => -1,
+                            };
+                            event_processor.send_event(AbstractEvent::MouseClick(
+                                MouseClickEvent {
+                                    wheel: (mwdx, mwdy),
+                                    ..Default::default()
+                                },
+                            ));
+                        }
+                        DeviceEvent::Button { button, state } => {
+                            let dstate = match state {
+                                ElementState::Pressed => 1,
+                                ElementState::Released => -1,
+                            };
+                            event_processor.send_event(AbstractEvent::MouseClick(
+                                MouseClickEvent {
+                                    lmb: if button == 1 { dstate } else { 0 },
+                                    mmb: if button == 2 { dstate } else { 0 },
+                                    rmb: if button == 3 { dstate } else { 0 },
+                                    ..Default::default()
+                                },
+                            ));
+                            match (button, state) {
+                                (1, ElementState::Pressed) => {
+                                    let slf = a.lock();
+                                    let mut mouse = slf.mouse.lock();
+                                    grab_coords = mouse.cursor_position;
+                                    if mouse.set_cursor_grab(true).is_ok() {
+                                        mouse.set_cursor_visible(false);
+                                    }
+                                }
+                                (1, ElementState::Released) => {
+                                    let slf = a.lock();
+                                    let mut mouse = slf.mouse.lock();
+                                    if mouse.cursor_grab() && mouse.set_cursor_grab(false).is_ok() {
+                                        drop(mouse.set_cursor_position(
+                                            grab_coords[0],
+                                            grab_coords[1],
+                                        ));
+                                        mouse.set_cursor_visible(true);
+                                    }
+                                }
+                                _ => (),
+                            }
+                        }
+                        DeviceEvent::MouseMotion { delta: (x, y) } => {
+                            a.lock().mouse.lock().mouse_delta = [x as _, y as _];
+                            event_processor.send_event(AbstractEvent::MouseMove(MouseMoveEvent {
+                                dx: x as _,
+                                dy: y as _,
+                            }));
+                        }
+                        _ => (),
+                    },
+                    Event::RedrawEventsCleared => {
+                        let begin_render_time = SystemTime::now();
+                        let tu = timer.next_frame();
+                        let mut slf = a.lock();
+                        slf.time.up_time = tu.uptime() as _;
+                        slf.time.frame_time = tu.delta() as _;
+                        slf.renderer.update_timer(tu);
+                        slf.renderer.begin_geametry_pass();
+                        drop(slf);
+                        let scene = app2.lock().scene.clone();
+                        scene.lock().step();
+                        let objects = a.lock().scene.lock().root_objects();
+                        let _begin_render_time =
+                            begin_render_time.elapsed().unwrap().as_secs_f64() * 1000.0;
+                        let mut _push_to_draw_list_time = 0.0f64;
+                        for obj in objects {
+                            let t2 = SystemTime::now();
+                            a.lock().renderer.draw(obj.clone());
+                            let t2 = t2.elapsed().unwrap().as_secs_f64();
+                            _push_to_draw_list_time += t2;
+                        }
+                        _push_to_draw_list_time *= 1000.0;
+                        let event_processor = a.lock().scene.lock().event_processor().clone();
+                        let game_logic_thread = std::thread::spawn(move || {
+                            event_processor.execute();
+                        });
+                        if take_screenshot {
+                            let mut slf = a.lock();
+                            slf.renderer.wait();
+                            take_screenshot = false;
+                            let img = slf
+                                .renderer
+                                .postprocessor()
+                                .get_output("swapchain_out".to_owned())
+                                .unwrap()
+                                .clone();
+
+                            let fnames = std::fs::read_dir("./screenshots")
+                                .unwrap()
+                                .map(|fname| {
+                                    fname.unwrap().file_name().to_str().unwrap().to_owned()
+                                })
+                                .collect::<HashSet<_>>();
+                            let mut i = 0;
+                            let fname = loop {
+                                i += 1;
+                                let fname = format!("screenshot_{i}.png");
+                                if !fnames.contains(&fname) {
+                                    break fname;
+                                }
+                            };
+                            img.save(
+                                &command_buffer_father,
+                                allocator.clone(),
+                                format!("./screenshots/{fname}"),
+                            )
+                            .unwrap();
+                        }
+                        let _rendering_time = {
+                            let rendering_time = SystemTime::now();
+                            let mut app = a.lock();
+                            let rm = app.resource_manager.clone();
+                            app.renderer.execute(&static_input, &rm);
+                            let rendering_time =
+                                rendering_time.elapsed().unwrap().as_secs_f64() * 1000.0;
+                            game_logic_thread.join().unwrap();
+                            app.mouse.lock().mouse_delta = [0, 0];
+                            rendering_time
+                        };
+
+                        let fps_time = fps_timer.next_frame();
+
+                        if fps_time.uptime() > 1.0 {
+                            log::debug!(target: "renderer", "fps {:?}", fps_time.frame());
+                            //println!("begin_render_time {begin_render_time:.3} ms");
+                            //println!("push_to_draw_list_time {push_to_draw_list_time:.3} ms");
+                            //println!("rendering_time {rendering_time:.3} ms");
+                            fps_timer = time::Timer::new();
+                        };
+                    }
+                    _ => {}
+                }
+            },
+        );
+    }
+}
+
+/// Рендер `frames` кадров сцены `config.scene` без окна.
+/// Кадры сохраняются в `output` под именами `frame_0000.png`, `frame_0001.png` и т.д.
+/// Если `prefer_cpu`, то используется программная реализация Vulkan (если она есть).
+pub fn render_offscreen(
+    config: &AppConfig,
+    camera: Option<&str>,
+    frames: u32,
+    output: &Path,
+    prefer_cpu: bool,
+) -> Result<(), DsgeError> {
+    let library = VulkanLibrary::new().map_err(|err| DsgeError::vulkan("Failed to load Vulkan library", err))?;
+    let vk_instance = Instance::new(
+        library,
+        InstanceCreateInfo {
+            max_api_version: Some(Version::major_minor(1, 2)),
+            ..Default::default()
+        },
+    )
+    .map_err(|err| DsgeError::vulkan("Failed to create Vulkan instance", err))?;
+    let (device, mut queues) = Renderer::headless_device(vk_instance.clone(), prefer_cpu)
+        .map_err(|err| DsgeError::vulkan("No suitable Vulkan device", err))?;
+    let queue = queues.next().unwrap();
+
+    let resource_manager = ResourceManager::new(
+        device.clone(),
+        queue.clone(),
+        config.resource_manager_config(),
+        config.super_resolution,
+    )?;
+    let resource_manager = RcBox::construct(resource_manager);
+    let dimensions = [config.width, config.height];
+    let mut renderer = Renderer::offscreen(
+        vk_instance,
+        resource_manager.clone(),
+        dimensions,
+        config.super_resolution,
+        config.fxaa,
+    )?;
+
+    let (scene, default_camera) = Scene::from_file(config.scene_path().as_str(), &mut resource_manager.lock());
+    let camera = scene_camera(&scene, default_camera, camera)?;
+    renderer.set_camera(camera);
+    renderer.update_swapchain(Some(dimensions));
+
+    let command_buffer_father = CommandBufferFather::new(queue.clone());
+    let allocator = Arc::new(StandardMemoryAllocator::new_default(device));
+    let static_input = load_static_inputs(&command_buffer_father, allocator.clone())?;
+
+    std::fs::create_dir_all(output).map_err(|err| DsgeError::io(output, err))?;
+
+    let mut timer = time::Timer::new();
+    for frame in 0..frames {
+        renderer.update_timer(timer.next_frame());
+        renderer.begin_geametry_pass();
+        scene.lock().step();
+        for obj in scene.lock().root_objects() {
+            renderer.draw(obj);
+        }
+        let event_processor = scene.lock().event_processor().clone();
+        event_processor.execute();
+
+        renderer.execute(&static_input, &resource_manager);
+        renderer.wait();
+        let path = output.join(format!("frame_{frame:04}.png"));
+        renderer
+            .render_result()
+            .save(&command_buffer_father, allocator.clone(), path.to_string_lossy().into_owned())?;
+    }
+    Ok(())
+}
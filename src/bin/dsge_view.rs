@@ -0,0 +1,109 @@
+//! Просмотрщик сцен.
+//!
+//! В оконном режиме открывает сцену и позволяет облететь её камерой (мышь + WASD).
+//! С ключом `--headless` рендерит заданное количество кадров без окна
+//! и сохраняет их в PNG-файлы, что позволяет запускать его на программной
+//! реализации Vulkan (например, в CI).
+extern crate dsge_vk;
+
+use std::path::PathBuf;
+
+use dsge_vk::app::{render_offscreen, App};
+use dsge_vk::config::AppConfig;
+
+const USAGE: &str = "\
+Использование: dsge-view [ПАРАМЕТРЫ] [СЦЕНА]
+
+Параметры просмотрщика:
+    --headless          рендер без окна в PNG-файлы
+    --camera ИМЯ        объект-камера, через который ведётся рендер
+    --frames N          количество кадров для --headless (по умолчанию 1)
+    --output КАТАЛОГ    каталог для кадров (по умолчанию frames)
+    --cpu               предпочитать программную реализацию Vulkan
+    -h, --help          эта справка
+
+Остальные параметры (--config, -r, --fxaa, --sr и т.д.) те же, что и в конфигурации приложения.";
+
+struct ViewerArgs {
+    headless: bool,
+    camera: Option<String>,
+    frames: u32,
+    output: PathBuf,
+    prefer_cpu: bool,
+    config: AppConfig,
+}
+
+impl ViewerArgs {
+    fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut headless = false;
+        let mut camera = None;
+        let mut frames = 1;
+        let mut output = PathBuf::from("frames");
+        let mut prefer_cpu = false;
+        let mut config_args = Vec::new();
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("После {arg} должно быть указано значение."))
+            };
+            match arg.as_str() {
+                "--headless" => headless = true,
+                "--cpu" => prefer_cpu = true,
+                "--camera" => camera = Some(value()?),
+                "--frames" => {
+                    let val = value()?;
+                    frames = val
+                        .parse()
+                        .map_err(|_| format!("Неправильное количество кадров \"{val}\"."))?;
+                }
+                "--output" => output = PathBuf::from(value()?),
+                "-h" | "--help" => return Err(USAGE.to_owned()),
+                _ => config_args.push(arg),
+            }
+        }
+        Ok(Self {
+            headless,
+            camera,
+            frames,
+            output,
+            prefer_cpu,
+            config: AppConfig::from_args(config_args)?,
+        })
+    }
+}
+
+fn main() {
+    if let Err(err) = dsge_vk::logger::init() {
+        eprintln!("{err}");
+    }
+    let args = match ViewerArgs::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    };
+    if args.headless {
+        if let Err(err) = render_offscreen(
+            &args.config,
+            args.camera.as_deref(),
+            args.frames,
+            &args.output,
+            args.prefer_cpu,
+        ) {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        println!("{} кадр(ов) сохранено в {:?}", args.frames, args.output);
+    } else {
+        match App::new("DSGE View", &args.config, args.camera.as_deref()) {
+            Ok(app) => app.event_loop(),
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::error::DsgeError;
 use std::{
     ops::{Deref, DerefMut},
     sync::Arc,
@@ -44,14 +45,14 @@ impl PrimaryCommandBufferAssembler {
     pub fn execute_after(
         self,
         future: Option<Box<dyn GpuFuture>>,
-    ) -> Result<Box<dyn GpuFuture>, String> {
+    ) -> Result<Box<dyn GpuFuture>, DsgeError> {
         let future = future.unwrap_or(vulkano::sync::now(self.queue.device().clone()).boxed());
         let future = match self.command_buffer_builder.build() {
             Ok(cb) => match cb.execute_after(future, self.queue.clone()) {
                 Ok(future) => future.boxed(),
-                Err(err) => return Err(err.to_string()),
+                Err(err) => return Err(DsgeError::vulkan("Failed to execute command buffer", err)),
             },
-            Err(err) => return Err(err.to_string()),
+            Err(err) => return Err(DsgeError::vulkan("Failed to build command buffer", err)),
         };
         Ok(future)
     }
@@ -78,7 +79,7 @@ impl CommandBufferFather {
     }
 
     #[inline(always)]
-    pub fn new_primary(&self) -> Result<PrimaryCommandBufferAssembler, String> {
+    pub fn new_primary(&self) -> Result<PrimaryCommandBufferAssembler, DsgeError> {
         let pcbb = AutoCommandBufferBuilder::primary(
             self.allocator.as_ref(),
             self.queue.queue_family_index(),
@@ -90,7 +91,7 @@ impl CommandBufferFather {
                 queue: self.queue.clone(),
                 command_buffer_builder: pcbb,
             }),
-            Err(err) => Err(err.to_string()),
+            Err(err) => Err(DsgeError::vulkan("Failed to allocate command buffer", err)),
         }
     }
 
@@ -99,7 +100,7 @@ impl CommandBufferFather {
         &self,
         future: Option<Box<dyn GpuFuture>>,
         f: F,
-    ) -> Result<(T, Box<dyn GpuFuture>), String>
+    ) -> Result<(T, Box<dyn GpuFuture>), DsgeError>
     where
         F: FnOnce(&mut PrimaryCommandBufferAssembler) -> T,
     {
@@ -109,7 +110,7 @@ impl CommandBufferFather {
     }
 
     #[inline(always)]
-    pub fn new_primary_instant<F, T>(&self, f: F) -> Result<(T, Arc<PrimaryAutoCommandBuffer>), String>
+    pub fn new_primary_instant<F, T>(&self, f: F) -> Result<(T, Arc<PrimaryAutoCommandBuffer>), DsgeError>
     where
         F: FnOnce(&mut PrimaryCommandBufferAssembler) -> T,
     {
@@ -143,7 +144,7 @@ impl CommandBufferFather {
     #[inline(always)]
     pub fn build_primary_command_buffer_builder(
         command_buffer_builder: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
-    ) -> Result<PrimaryAutoCommandBuffer, String> {
+    ) -> Result<PrimaryAutoCommandBuffer, DsgeError> {
         match command_buffer_builder.build() {
             Ok(ok) => Ok(ok),
             Err(err) => return Err(format!("{err:?}")),
@@ -155,7 +156,7 @@ impl CommandBufferFather {
         &self,
         command_buffer_builder: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
         future: Option<Box<dyn GpuFuture>>,
-    ) -> Result<Box<dyn GpuFuture>, String> {
+    ) -> Result<Box<dyn GpuFuture>, DsgeError> {
         let device = self.queue.device().clone();
         let future = future.unwrap_or(vulkano::sync::now(device.clone()).boxed());
         match match command_buffer_builder.build() {
@@ -175,7 +176,7 @@ impl CommandBufferFather {
         &self,
         future: Option<Box<dyn GpuFuture>>,
         f: F,
-    ) -> Result<Box<dyn GpuFuture>, String>
+    ) -> Result<Box<dyn GpuFuture>, DsgeError>
     where
         F: FnOnce(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>),
     {
@@ -189,7 +190,7 @@ impl CommandBufferFather {
     pub fn add_to_new_primary_command_buffer<F>(
         &self,
         f: F,
-    ) -> Result<PrimaryAutoCommandBuffer, String>
+    ) -> Result<PrimaryAutoCommandBuffer, DsgeError>
     where
         F: FnOnce(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>),
     {
@@ -203,7 +204,7 @@ pub fn new_cpu_buffer_from_iter<T, I, A>(
     usage: BufferUsage,
     allocator: Arc<GenericMemoryAllocator<A>>,
     iter: I,
-) -> Result<Subbuffer<[T]>, String>
+) -> Result<Subbuffer<[T]>, DsgeError>
 where
     T: BufferContents,
     A: Suballocator + std::marker::Send + 'static,
@@ -225,7 +226,7 @@ where
     /*if let Ok(buffer) = &buffer {
         println!("Создан новый буфер CPU размером {} байт", buffer.size());
     }*/
-    buffer.map_err(|err| err.to_string())
+    buffer.map_err(|err| DsgeError::vulkan("Failed to allocate CPU buffer", err))
     // match buffer {
     //     Ok(buffer) => Ok(buffer),
     //     Err(err) => Err(err.to_string())
@@ -238,7 +239,17 @@ pub trait CommandBufferShortcuts {
         &mut self,
         subbuffer: Subbuffer<[T]>,
         allocator: Arc<GenericMemoryAllocator<A>>,
-    ) -> Result<Subbuffer<[T]>, String>
+    ) -> Result<Subbuffer<[T]>, DsgeError>
+    where
+        T: BufferContents,
+        A: Suballocator + Send + 'static;
+    /// Копирование в новый буфер на устройстве с флагами использования `usage`
+    fn move_buffer_to_device_with_usage<T, A>(
+        &mut self,
+        subbuffer: Subbuffer<[T]>,
+        allocator: Arc<GenericMemoryAllocator<A>>,
+        usage: BufferUsage,
+    ) -> Result<Subbuffer<[T]>, DsgeError>
     where
         T: BufferContents,
         A: Suballocator + Send + 'static;
@@ -247,7 +258,7 @@ pub trait CommandBufferShortcuts {
         usage: BufferUsage,
         allocator: Arc<GenericMemoryAllocator<A>>,
         iter: I,
-    ) -> Result<Subbuffer<[T]>, String>
+    ) -> Result<Subbuffer<[T]>, DsgeError>
     where
         T: BufferContents,
         I: IntoIterator<Item = T>,
@@ -260,7 +271,7 @@ impl CommandBufferShortcuts for PrimaryCommandBufferAssembler {
         &mut self,
         subbuffer: Subbuffer<[T]>,
         allocator: Arc<GenericMemoryAllocator<A>>,
-    ) -> Result<Subbuffer<[T]>, String>
+    ) -> Result<Subbuffer<[T]>, DsgeError>
     where
         A: Suballocator + Send + 'static,
         T: BufferContents,
@@ -269,6 +280,20 @@ impl CommandBufferShortcuts for PrimaryCommandBufferAssembler {
         if usage.intersects(BufferUsage::TRANSFER_SRC) {
             usage = usage.symmetric_difference(BufferUsage::TRANSFER_SRC);
         }
+        self.move_buffer_to_device_with_usage(subbuffer, allocator, usage)
+    }
+
+    fn move_buffer_to_device_with_usage<T, A>(
+        &mut self,
+        subbuffer: Subbuffer<[T]>,
+        allocator: Arc<GenericMemoryAllocator<A>>,
+        usage: BufferUsage,
+    ) -> Result<Subbuffer<[T]>, DsgeError>
+    where
+        A: Suballocator + Send + 'static,
+        T: BufferContents,
+    {
+        let usage = usage | BufferUsage::TRANSFER_DST;
         let device_local_buffer = Buffer::new_slice::<T>(
             allocator,
             BufferCreateInfo {
@@ -291,7 +316,7 @@ impl CommandBufferShortcuts for PrimaryCommandBufferAssembler {
         );
         let device_local_buffer = match device_local_buffer {
             Ok(dlb) => dlb,
-            Err(err) => return Err(err.to_string())
+            Err(err) => return Err(DsgeError::vulkan("Failed to allocate device local buffer", err))
         };
         self.copy_buffer(CopyBufferInfoTyped::buffers(
             subbuffer,
@@ -306,7 +331,7 @@ impl CommandBufferShortcuts for PrimaryCommandBufferAssembler {
         usage: BufferUsage,
         allocator: Arc<GenericMemoryAllocator<A>>,
         iter: I,
-    ) -> Result<Subbuffer<[T]>, String>
+    ) -> Result<Subbuffer<[T]>, DsgeError>
     where
         T: BufferContents,
         A: Suballocator + Send + 'static,
@@ -327,15 +352,15 @@ impl CommandBufferShortcuts for PrimaryCommandBufferAssembler {
         );
         let cpu_buffer = match cpu_buffer {
             Ok(cpuab) => cpuab,
-            Err(err) => return Err(err.to_string())
+            Err(err) => return Err(DsgeError::vulkan("Failed to allocate staging buffer", err))
         };
         if usage.intersects(BufferUsage::VERTEX_BUFFER) {
-            println!("Создан новый вершинный буфер размером {} байт", cpu_buffer.size());
+            log::debug!(target: "renderer", "Created vertex buffer of {} bytes", cpu_buffer.size());
         }
         if usage.intersects(BufferUsage::INDEX_BUFFER) {
-            println!("Создан новый индексный буфер размером {} байт", cpu_buffer.size());
+            log::debug!(target: "renderer", "Created index buffer of {} bytes", cpu_buffer.size());
         }
         //Ok(cpu_buffer)
-        self.move_buffer_to_device(cpu_buffer, allocator.clone())
+        self.move_buffer_to_device_with_usage(cpu_buffer, allocator.clone(), usage)
     }
 }
@@ -0,0 +1,842 @@
+//! Анимация объектов клипами с ключевыми кадрами.
+//!
+//! Клип содержит дорожки смещения, поворота и масштаба объекта, числовые
+//! дорожки параметров материала и мощности источника света, а также метки,
+//! при достижении которых объекту отправляется [`AnimationEvent`].
+//! Клипы загружаются из TOML-файлов и проигрываются компонентом [`AnimationPlayer`].
+use std::collections::HashMap;
+use std::ops::{Add, Mul, Sub};
+use std::path::Path;
+use std::sync::Arc;
+
+use nalgebra::Quaternion;
+use serde::Deserialize;
+
+use super::skeleton::{slerp, BoneTransform, Keyframe, Quat};
+use crate::error::DsgeError;
+use crate::game_logic::{AbstractEvent, AnimationEvent};
+use crate::game_object::GameObjectRef;
+use crate::references::*;
+use crate::types::{Vec3, Vec4};
+
+/// Способ интерполяции между ключевыми кадрами
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    /// Значение предыдущего ключа до следующего
+    Step,
+    #[default]
+    Linear,
+    /// Кубический сплайн Катмулла — Рома
+    Cubic,
+}
+
+/// Поведение дорожки после последнего ключа
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WrapMode {
+    /// Значение последнего ключа
+    #[default]
+    Once,
+    Loop,
+    /// Вперёд и обратно
+    PingPong,
+}
+
+impl WrapMode {
+    /// Время внутри отрезка `[0, duration]`
+    pub fn apply(self, time: f32, duration: f32) -> f32 {
+        if duration <= 0.0 {
+            return 0.0;
+        }
+        match self {
+            Self::Once => time.clamp(0.0, duration),
+            Self::Loop => time.rem_euclid(duration),
+            Self::PingPong => {
+                let time = time.rem_euclid(duration * 2.0);
+                if time > duration {
+                    duration * 2.0 - time
+                } else {
+                    time
+                }
+            }
+        }
+    }
+}
+
+/// Значение, которое можно анимировать
+pub trait Animatable: Copy {
+    fn lerp(a: &Self, b: &Self, t: f32) -> Self;
+
+    /// Интерполяция между `keys[1]` и `keys[2]` с соседними ключами `keys[0]` и `keys[3]`.
+    /// `spans` — длительности трёх интервалов между ними.
+    fn cubic(keys: [&Self; 4], spans: [f32; 3], t: f32) -> Self;
+}
+
+/// Эрмитов сплайн с касательными Катмулла — Рома для неравномерных ключей
+fn catmull_rom<T>(keys: [T; 4], spans: [f32; 3], t: f32) -> T
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>,
+{
+    let [before, a, b, after] = keys;
+    let tangent = |previous: T, next: T, span: f32| if span > 0.0 { (next - previous) * (1.0 / span) } else { (b - a) * 0.0 };
+    let start = tangent(before, b, spans[0] + spans[1]) * spans[1];
+    let end = tangent(a, after, spans[1] + spans[2]) * spans[1];
+    let (t2, t3) = (t * t, t * t * t);
+    a * (2.0 * t3 - 3.0 * t2 + 1.0) + start * (t3 - 2.0 * t2 + t) + b * (3.0 * t2 - 2.0 * t3) + end * (t3 - t2)
+}
+
+impl Animatable for f32 {
+    fn lerp(a: &Self, b: &Self, t: f32) -> Self {
+        a + (b - a) * t
+    }
+
+    fn cubic(keys: [&Self; 4], spans: [f32; 3], t: f32) -> Self {
+        catmull_rom(keys.map(|key| *key), spans, t)
+    }
+}
+
+impl Animatable for Vec3 {
+    fn lerp(a: &Self, b: &Self, t: f32) -> Self {
+        a.lerp(b, t)
+    }
+
+    fn cubic(keys: [&Self; 4], spans: [f32; 3], t: f32) -> Self {
+        catmull_rom(keys.map(|key| *key), spans, t)
+    }
+}
+
+impl Animatable for Quat {
+    fn lerp(a: &Self, b: &Self, t: f32) -> Self {
+        slerp(a, b, t)
+    }
+
+    /// Сплайн по компонентам кватерниона, приведённым к одной полусфере
+    fn cubic(keys: [&Self; 4], spans: [f32; 3], t: f32) -> Self {
+        let mut coords = keys.map(|key| key.coords);
+        for i in 1..4 {
+            if coords[i - 1].dot(&coords[i]) < 0.0 {
+                coords[i] = -coords[i];
+            }
+        }
+        let value: Vec4 = catmull_rom(coords, spans, t);
+        Quat::try_new(Quaternion::from(value), 1.0e-6).unwrap_or_else(|| slerp(keys[1], keys[2], t))
+    }
+}
+
+/// Ключевые кадры одного значения
+#[derive(Clone, Debug)]
+pub struct Track<T> {
+    keys: Vec<Keyframe<T>>,
+    pub interpolation: Interpolation,
+    pub wrap: WrapMode,
+}
+
+impl<T: Animatable> Track<T> {
+    /// Ключи упорядочиваются по времени
+    pub fn new(mut keys: Vec<Keyframe<T>>, interpolation: Interpolation, wrap: WrapMode) -> Self {
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self { keys, interpolation, wrap }
+    }
+
+    #[inline]
+    pub fn keys(&self) -> &[Keyframe<T>] {
+        &self.keys
+    }
+
+    /// Время последнего ключа
+    pub fn duration(&self) -> f32 {
+        self.keys.last().map_or(0.0, |key| key.time)
+    }
+
+    /// Значение в момент `time` с учётом режима повтора
+    pub fn sample(&self, time: f32) -> Option<T> {
+        let time = self.wrap.apply(time, self.duration());
+        let keys = &self.keys;
+        let next = keys.partition_point(|key| key.time <= time);
+        if next == 0 || next == keys.len() {
+            return keys.get(next.min(keys.len().wrapping_sub(1))).map(|key| key.value);
+        }
+        let (a, b) = (&keys[next - 1], &keys[next]);
+        let span = b.time - a.time;
+        let t = if span > 0.0 { (time - a.time) / span } else { 0.0 };
+        Some(match self.interpolation {
+            Interpolation::Step => a.value,
+            Interpolation::Linear => T::lerp(&a.value, &b.value, t),
+            Interpolation::Cubic => {
+                let before = &keys[next.saturating_sub(2)];
+                let after = &keys[(next + 1).min(keys.len() - 1)];
+                let spans = [a.time - before.time, span, after.time - b.time];
+                T::cubic([&before.value, &a.value, &b.value, &after.value], spans, t)
+            }
+        })
+    }
+}
+
+/// Числовое свойство объекта, изменяемое дорожкой клипа
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FloatTarget {
+    /// Скалярный параметр материала. Материал может быть общим для нескольких объектов.
+    MaterialParameter(String),
+    /// Мощность источника света объекта
+    LightPower,
+}
+
+/// Метка времени клипа
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationMarker {
+    pub time: f32,
+    pub name: String,
+}
+
+/// Значения, полученные из клипов
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationPose {
+    pub transform: BoneTransform,
+    pub floats: Vec<(FloatTarget, f32)>,
+}
+
+impl AnimationPose {
+    pub fn new(transform: BoneTransform) -> Self {
+        Self { transform, floats: Vec::new() }
+    }
+
+    /// Смешивание с другой позой: 0 — эта поза, 1 — `other`.
+    /// Числа, которых нет в этой позе, берутся из `other`.
+    pub fn blend(&self, other: &AnimationPose, weight: f32) -> AnimationPose {
+        let mut floats = self.floats.clone();
+        for (target, value) in &other.floats {
+            match floats.iter_mut().find(|(own, _)| own == target) {
+                Some((_, own)) => *own = f32::lerp(own, value, weight),
+                None => floats.push((target.clone(), *value)),
+            }
+        }
+        AnimationPose {
+            transform: self.transform.interpolate(&other.transform, weight),
+            floats,
+        }
+    }
+}
+
+/// Анимация объекта
+#[derive(Clone, Debug)]
+pub struct AnimationClip {
+    pub name: String,
+    /// Режим повтора для меток
+    pub wrap: WrapMode,
+    pub translation: Option<Track<Vec3>>,
+    pub rotation: Option<Track<Quat>>,
+    pub scale: Option<Track<Vec3>>,
+    pub floats: Vec<(FloatTarget, Track<f32>)>,
+    pub markers: Vec<AnimationMarker>,
+}
+
+#[allow(dead_code)]
+impl AnimationClip {
+    pub fn new(name: &str, wrap: WrapMode) -> Self {
+        Self {
+            name: name.to_owned(),
+            wrap,
+            translation: None,
+            rotation: None,
+            scale: None,
+            floats: Vec::new(),
+            markers: Vec::new(),
+        }
+    }
+
+    pub fn with_translation(mut self, track: Track<Vec3>) -> Self {
+        self.translation = Some(track);
+        self
+    }
+
+    pub fn with_rotation(mut self, track: Track<Quat>) -> Self {
+        self.rotation = Some(track);
+        self
+    }
+
+    pub fn with_scale(mut self, track: Track<Vec3>) -> Self {
+        self.scale = Some(track);
+        self
+    }
+
+    pub fn with_float(mut self, target: FloatTarget, track: Track<f32>) -> Self {
+        self.floats.push((target, track));
+        self
+    }
+
+    pub fn with_marker(mut self, time: f32, name: &str) -> Self {
+        self.markers.push(AnimationMarker { time, name: name.to_owned() });
+        self
+    }
+
+    /// Время последнего ключа или метки
+    pub fn duration(&self) -> f32 {
+        [
+            self.translation.as_ref().map(Track::duration),
+            self.rotation.as_ref().map(Track::duration),
+            self.scale.as_ref().map(Track::duration),
+        ]
+        .into_iter()
+        .flatten()
+        .chain(self.floats.iter().map(|(_, track)| track.duration()))
+        .chain(self.markers.iter().map(|marker| marker.time))
+        .fold(0.0, f32::max)
+    }
+
+    /// Есть ли у клипа дорожки преобразования объекта
+    pub fn animates_transform(&self) -> bool {
+        self.transform_channels().contains(&true)
+    }
+
+    /// Каналы преобразования, которые меняет клип: смещение, поворот и масштаб
+    pub fn transform_channels(&self) -> [bool; 3] {
+        [self.translation.is_some(), self.rotation.is_some(), self.scale.is_some()]
+    }
+
+    /// Записывает в `pose` значения дорожек в момент `time`
+    pub fn sample(&self, time: f32, pose: &mut AnimationPose) {
+        if let Some(translation) = self.translation.as_ref().and_then(|track| track.sample(time)) {
+            pose.transform.translation = translation;
+        }
+        if let Some(rotation) = self.rotation.as_ref().and_then(|track| track.sample(time)) {
+            pose.transform.rotation = rotation;
+        }
+        if let Some(scale) = self.scale.as_ref().and_then(|track| track.sample(time)) {
+            pose.transform.scale = scale;
+        }
+        for (target, track) in &self.floats {
+            if let Some(value) = track.sample(time) {
+                match pose.floats.iter_mut().find(|(own, _)| own == target) {
+                    Some((_, own)) => *own = value,
+                    None => pose.floats.push((target.clone(), value)),
+                }
+            }
+        }
+    }
+
+    /// Метки, пройденные при переходе от `from` к `to`, не включая `from`
+    pub fn markers_between(&self, from: f32, to: f32) -> Vec<&str> {
+        let (from, to) = if from <= to { (from, to) } else { (to, from) };
+        let duration = self.duration();
+        let mut result = Vec::new();
+        for marker in &self.markers {
+            // Моменты прохождения метки на каждом повторе клипа
+            let (period, times) = match self.wrap {
+                WrapMode::Loop if duration > 0.0 => (duration, vec![marker.time]),
+                WrapMode::PingPong if duration > 0.0 => (duration * 2.0, vec![marker.time, duration * 2.0 - marker.time]),
+                _ => {
+                    if from < marker.time && marker.time <= to {
+                        result.push(marker.name.as_str());
+                    }
+                    continue;
+                }
+            };
+            let first = (from / period).floor().max(0.0) as i64;
+            let last = (to / period).floor() as i64;
+            for cycle in first..=last {
+                for (i, time) in times.iter().enumerate() {
+                    let time = cycle as f32 * period + time;
+                    // В разворот клипа метка на его конце проходится один раз
+                    let turn = i == 1 && marker.time >= duration;
+                    if from < time && time <= to && !turn {
+                        result.push(marker.name.as_str());
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Разбор клипа в формате TOML:
+    ///
+    /// ```toml
+    /// name = "door"
+    /// wrap = "once"            # для меток: once, loop, ping_pong
+    ///
+    /// [[tracks]]
+    /// target = "rotation"      # translation, rotation, scale, light_power, material
+    /// interpolation = "cubic"  # step, linear, cubic
+    /// wrap = "loop"            # по умолчанию как у клипа
+    /// # Время и значение: три угла Эйлера в градусах либо кватернион x, y, z, w для поворота,
+    /// # вектор для смещения и масштаба, число для остальных дорожек
+    /// keys = [[0.0, 0.0, 0.0, 0.0], [1.0, 0.0, 0.0, 90.0]]
+    ///
+    /// [[tracks]]
+    /// target = "material"
+    /// parameter = "roughness"
+    /// keys = [[0.0, 0.2], [1.0, 0.8]]
+    ///
+    /// [[markers]]
+    /// time = 1.0
+    /// name = "opened"
+    /// ```
+    pub fn from_toml(source: &str) -> Result<Self, DsgeError> {
+        let file: ClipFile = toml::from_str(source)
+            .map_err(|err| DsgeError::InvalidData(format!("Ошибка разбора анимации: {err}")))?;
+        let mut clip = Self::new(&file.name, file.wrap);
+        for track in file.tracks {
+            let wrap = track.wrap.unwrap_or(file.wrap);
+            let width = |expected: &[usize]| {
+                match track.keys.iter().find(|key| !expected.contains(&(key.len().wrapping_sub(1)))) {
+                    Some(key) => Err(DsgeError::InvalidData(format!(
+                        "Ключ {key:?} дорожки {} должен содержать время и {expected:?} значений",
+                        track.target
+                    ))),
+                    None => Ok(()),
+                }
+            };
+            let keys = |value: &dyn Fn(&[f32]) -> Vec3| {
+                track.keys.iter().map(|key| Keyframe { time: key[0], value: value(&key[1..]) }).collect::<Vec<_>>()
+            };
+            let floats = || track.keys.iter().map(|key| Keyframe { time: key[0], value: key[1] }).collect::<Vec<_>>();
+            match (track.target.as_str(), &track.parameter) {
+                ("translation", None) | ("scale", None) => {
+                    width(&[3])?;
+                    let track_keys = Track::new(keys(&|value| Vec3::new(value[0], value[1], value[2])), track.interpolation, wrap);
+                    if track.target == "translation" {
+                        clip.translation = Some(track_keys);
+                    } else {
+                        clip.scale = Some(track_keys);
+                    }
+                }
+                ("rotation", None) => {
+                    width(&[3, 4])?;
+                    let rotations = track
+                        .keys
+                        .iter()
+                        .map(|key| {
+                            let value = match key[1..] {
+                                [x, y, z, w] => Quat::new_normalize(Quaternion::new(w, x, y, z)),
+                                [x, y, z] => Quat::from_euler_angles(x.to_radians(), y.to_radians(), z.to_radians()),
+                                _ => unreachable!(),
+                            };
+                            Keyframe { time: key[0], value }
+                        })
+                        .collect();
+                    clip.rotation = Some(Track::new(rotations, track.interpolation, wrap));
+                }
+                ("light_power", None) => {
+                    width(&[1])?;
+                    clip.floats.push((FloatTarget::LightPower, Track::new(floats(), track.interpolation, wrap)));
+                }
+                ("material", Some(parameter)) => {
+                    width(&[1])?;
+                    let target = FloatTarget::MaterialParameter(parameter.clone());
+                    clip.floats.push((target, Track::new(floats(), track.interpolation, wrap)));
+                }
+                (target, parameter) => {
+                    return Err(DsgeError::InvalidData(format!(
+                        "Неизвестная дорожка анимации {target} с параметром {parameter:?}"
+                    )))
+                }
+            }
+        }
+        clip.markers = file.markers.into_iter().map(|marker| AnimationMarker { time: marker.time, name: marker.name }).collect();
+        Ok(clip)
+    }
+
+    /// Загрузка клипа из файла. Если имя в файле не указано, используется имя файла.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, DsgeError> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|err| DsgeError::io(path, err))?;
+        let mut clip = Self::from_toml(&source).map_err(|err| err.context(format!("{path:?}")))?;
+        if clip.name.is_empty() {
+            clip.name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        }
+        Ok(clip)
+    }
+}
+
+#[derive(Deserialize)]
+struct ClipFile {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    wrap: WrapMode,
+    #[serde(default)]
+    tracks: Vec<TrackFile>,
+    #[serde(default)]
+    markers: Vec<MarkerFile>,
+}
+
+#[derive(Deserialize)]
+struct TrackFile {
+    target: String,
+    parameter: Option<String>,
+    #[serde(default)]
+    interpolation: Interpolation,
+    wrap: Option<WrapMode>,
+    keys: Vec<Vec<f32>>,
+}
+
+#[derive(Deserialize)]
+struct MarkerFile {
+    time: f32,
+    name: String,
+}
+
+struct AnimationLayer {
+    clip: Arc<AnimationClip>,
+    time: f32,
+    speed: f32,
+    /// Метки в начале клипа ещё не пройдены
+    started: bool,
+    weight: f32,
+    /// Изменение веса в секунду
+    fade: f32,
+}
+
+/// Компонент, проигрывающий клипы [`AnimationClip`] с плавными переходами.
+/// Метки клипов отправляются поведениям объекта как [`AbstractEvent::Animation`].
+pub struct AnimationPlayer {
+    clips: HashMap<String, Arc<AnimationClip>>,
+    layers: Vec<AnimationLayer>,
+    /// Преобразование объекта до начала анимации, к нему возвращаются
+    /// анимированные каналы после остановки
+    base: Option<BoneTransform>,
+    pose: Option<AnimationPose>,
+    /// Метки, пройденные при последнем обновлении
+    markers: Vec<String>,
+    /// Параметры материала, которых нет у материала объекта
+    missing_parameters: Vec<String>,
+    /// Каналы преобразования, менявшиеся при последнем обновлении,
+    /// см. [`AnimationClip::transform_channels`]
+    animated_channels: [bool; 3],
+}
+
+impl Default for AnimationPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[allow(dead_code)]
+impl AnimationPlayer {
+    pub fn new() -> Self {
+        Self {
+            clips: HashMap::new(),
+            layers: Vec::new(),
+            base: None,
+            pose: None,
+            markers: Vec::new(),
+            missing_parameters: Vec::new(),
+            animated_channels: [false; 3],
+        }
+    }
+
+    pub fn with_clip(mut self, clip: Arc<AnimationClip>) -> Self {
+        self.add_clip(clip);
+        self
+    }
+
+    /// Добавление клипа, доступного по имени в [`AnimationPlayer::play`]
+    pub fn add_clip(&mut self, clip: Arc<AnimationClip>) {
+        self.clips.insert(clip.name.clone(), clip);
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&Arc<AnimationClip>> {
+        self.clips.get(name)
+    }
+
+    /// Запуск добавленного клипа с плавным переходом от текущих анимаций за `fade_time` секунд
+    pub fn play(&mut self, name: &str, fade_time: f32) -> Result<(), DsgeError> {
+        let clip = self
+            .clips
+            .get(name)
+            .cloned()
+            .ok_or_else(|| DsgeError::AssetNotFound(format!("Анимация {name} не найдена")))?;
+        self.play_clip(clip, fade_time);
+        Ok(())
+    }
+
+    /// Запуск клипа с плавным переходом от текущих анимаций за `fade_time` секунд
+    pub fn play_clip(&mut self, clip: Arc<AnimationClip>, fade_time: f32) {
+        self.stop(fade_time);
+        self.layers.push(AnimationLayer {
+            clip,
+            time: 0.0,
+            speed: 1.0,
+            started: false,
+            weight: if fade_time > 0.0 { 0.0 } else { 1.0 },
+            fade: if fade_time > 0.0 { 1.0 / fade_time } else { 0.0 },
+        });
+    }
+
+    /// Плавная остановка всех анимаций с возвратом к исходному преобразованию объекта.
+    /// Числовые свойства сохраняют последние значения.
+    pub fn stop(&mut self, fade_time: f32) {
+        if fade_time > 0.0 {
+            for layer in &mut self.layers {
+                layer.fade = -1.0 / fade_time;
+            }
+        } else {
+            self.layers.clear();
+        }
+    }
+
+    /// Скорость последнего запущенного клипа
+    pub fn set_speed(&mut self, speed: f32) {
+        if let Some(layer) = self.layers.last_mut() {
+            layer.speed = speed;
+        }
+    }
+
+    /// Имя последнего запущенного клипа
+    pub fn current_clip(&self) -> Option<&str> {
+        self.layers.last().map(|layer| layer.clip.name.as_str())
+    }
+
+    /// Время последнего запущенного клипа без учёта повторов
+    pub fn time(&self) -> f32 {
+        self.layers.last().map_or(0.0, |layer| layer.time)
+    }
+
+    /// Поза после последнего обновления
+    pub fn pose(&self) -> Option<&AnimationPose> {
+        self.pose.as_ref()
+    }
+
+    /// Метки, пройденные при последнем обновлении
+    pub fn markers(&self) -> &[String] {
+        &self.markers
+    }
+
+    /// Продвижение анимаций на `delta` секунд и применение позы к `owner`
+    pub fn advance(&mut self, owner: &GameObjectRef, delta: f32) {
+        self.markers.clear();
+        for layer in &mut self.layers {
+            let from = if layer.started { layer.time } else { -f32::MIN_POSITIVE };
+            layer.time += delta * layer.speed;
+            layer.started = true;
+            layer.weight = (layer.weight + layer.fade * delta).clamp(0.0, 1.0);
+            // Затухающие клипы меток не отправляют
+            if layer.fade >= 0.0 {
+                let markers = layer.clip.markers_between(from, layer.time);
+                self.markers.extend(markers.into_iter().map(str::to_owned));
+            }
+        }
+        self.layers.retain(|layer| layer.weight > 0.0 || layer.fade > 0.0);
+        // После остановки каналы ещё раз записываются, чтобы вернуть исходные значения
+        let mut channels = [false; 3];
+        for layer in &self.layers {
+            for (channel, animated) in channels.iter_mut().zip(layer.clip.transform_channels()) {
+                *channel |= animated;
+            }
+        }
+        let restore = std::mem::replace(&mut self.animated_channels, channels);
+        let written = [0, 1, 2].map(|i| channels[i] || restore[i]);
+
+        let mut obj = owner.lock();
+        // Каналы, которые клипы не меняют, могут двигать другие поведения,
+        // поэтому они каждый кадр берутся из текущего преобразования объекта
+        let current = BoneTransform::from_matrix(&obj.transform().local);
+        let base = with_channels(current, self.base.get_or_insert(current), written);
+        // Взвешенное среднее клипов; недостающий до единицы вес остаётся у исходного преобразования
+        let mut pose = AnimationPose::new(base);
+        let mut total = (1.0 - self.layers.iter().map(|layer| layer.weight).sum::<f32>()).max(0.0);
+        for layer in &self.layers {
+            total += layer.weight;
+            if total <= 0.0 {
+                continue;
+            }
+            let mut layer_pose = AnimationPose::new(base);
+            layer.clip.sample(layer.time, &mut layer_pose);
+            pose = pose.blend(&layer_pose, layer.weight / total);
+        }
+
+        if written.contains(&true) {
+            if let Some(transform) = obj.transform_mut() {
+                transform.local = with_channels(current, &pose.transform, written).to_matrix();
+            }
+        }
+        if self.layers.is_empty() {
+            self.base = None;
+        }
+        for (target, value) in &pose.floats {
+            match target {
+                FloatTarget::LightPower => {
+                    if let Some(light) = obj.light() {
+                        light.lock().unwrap().set_power(*value);
+                    }
+                }
+                FloatTarget::MaterialParameter(name) => {
+                    let Some(visual) = obj.visual() else {
+                        continue;
+                    };
+                    if self.missing_parameters.contains(name) {
+                        continue;
+                    }
+                    if let Err(err) = visual.material().lock().set_parameter(name, (*value).into()) {
+                        log::warn!(target: "animation", "{}: {err}", obj.name());
+                        self.missing_parameters.push(name.clone());
+                    }
+                }
+            }
+        }
+        self.pose = Some(pose);
+
+        let scene = obj.scene.clone();
+        drop(obj);
+        if let Some(scene) = scene {
+            let event_processor = scene.lock().event_processor().clone();
+            for marker in &self.markers {
+                event_processor.send_event(AbstractEvent::Animation(AnimationEvent {
+                    object: owner.box_id(),
+                    name: AnimationEvent::name_id(marker),
+                }));
+            }
+        }
+    }
+
+    fn frame_tick(&mut self, owner: &GameObjectRef, event: AbstractEvent) {
+        if let AbstractEvent::FrameTick(time) = event {
+            self.advance(owner, time.delta());
+        }
+    }
+}
+
+/// `current` с каналами `channels` из `source`, см. [`AnimationClip::transform_channels`]
+fn with_channels(current: BoneTransform, source: &BoneTransform, channels: [bool; 3]) -> BoneTransform {
+    BoneTransform {
+        translation: if channels[0] { source.translation } else { current.translation },
+        rotation: if channels[1] { source.rotation } else { current.rotation },
+        scale: if channels[2] { source.scale } else { current.scale },
+    }
+}
+
+crate::impl_behaviour!(AnimationPlayer {
+    frame_tick: FrameTick
+});
+
+#[test]
+fn animation_track_interpolation_and_wrap() {
+    let keys = vec![
+        Keyframe { time: 2.0, value: 4.0 },
+        Keyframe { time: 0.0, value: 0.0 },
+        Keyframe { time: 1.0, value: 1.0 },
+    ];
+    let step = Track::new(keys.clone(), Interpolation::Step, WrapMode::Once);
+    assert_eq!(step.duration(), 2.0);
+    assert_eq!(step.sample(0.9), Some(0.0));
+    assert_eq!(step.sample(5.0), Some(4.0));
+    assert_eq!(step.sample(-1.0), Some(0.0));
+
+    let linear = Track::new(keys.clone(), Interpolation::Linear, WrapMode::Loop);
+    assert_eq!(linear.sample(1.5), Some(2.5));
+    assert_eq!(linear.sample(2.5), Some(0.5));
+    let ping_pong = Track::new(keys.clone(), Interpolation::Linear, WrapMode::PingPong);
+    assert_eq!(ping_pong.sample(2.5), Some(2.5));
+    assert_eq!(ping_pong.sample(4.5), Some(0.5));
+
+    // Сплайн проходит через ключи и отличается от ломаной между ними
+    let cubic = Track::new(keys, Interpolation::Cubic, WrapMode::Once);
+    assert!((cubic.sample(1.0).unwrap() - 1.0).abs() < 1.0e-6);
+    let middle = cubic.sample(1.5).unwrap();
+    assert!(middle < 2.5 && middle > 1.0, "{middle}");
+    let rotation = Track::new(
+        vec![
+            Keyframe { time: 0.0, value: Quat::identity() },
+            Keyframe { time: 1.0, value: Quat::from_euler_angles(0.0, 0.0, 1.0) },
+            Keyframe { time: 2.0, value: Quat::from_euler_angles(0.0, 0.0, 2.0) },
+            Keyframe { time: 3.0, value: Quat::from_euler_angles(0.0, 0.0, 3.0) },
+        ],
+        Interpolation::Cubic,
+        WrapMode::Once,
+    );
+    assert!((rotation.sample(1.5).unwrap().angle() - 1.5).abs() < 1.0e-2);
+}
+
+#[test]
+fn animation_player_blends_clips_and_fires_markers() {
+    use crate::game_object::GameObject;
+    use crate::types::Mat4;
+    let source = r#"
+        name = "walk"
+        wrap = "loop"
+        [[tracks]]
+        target = "translation"
+        keys = [[0.0, 0.0, 0.0, 0.0], [1.0, 2.0, 0.0, 0.0]]
+        [[tracks]]
+        target = "rotation"
+        interpolation = "step"
+        keys = [[0.0, 0.0, 0.0, 0.0], [0.5, 0.0, 0.0, 90.0], [1.0, 0.0, 0.0, 90.0]]
+        [[tracks]]
+        target = "material"
+        parameter = "roughness"
+        wrap = "ping_pong"
+        keys = [[0.0, 0.0], [1.0, 1.0]]
+        [[markers]]
+        time = 0.5
+        name = "step"
+    "#;
+    let walk = AnimationClip::from_toml(source).unwrap();
+    assert_eq!(walk.duration(), 1.0);
+    assert!(walk.rotation.is_some() && walk.floats.len() == 1);
+    assert_eq!(walk.markers_between(0.0, 2.6), vec!["step", "step", "step"]);
+    assert!(AnimationClip::from_toml("[[tracks]]\ntarget = \"scale\"\nkeys = [[0.0, 1.0]]").is_err());
+    assert!(AnimationClip::from_toml("[[tracks]]\ntarget = \"colour\"\nkeys = []").is_err());
+
+    let lift = AnimationClip::new("lift", WrapMode::Once)
+        .with_translation(Track::new(vec![Keyframe { time: 0.0, value: Vec3::new(0.0, 0.0, 4.0) }], Interpolation::Linear, WrapMode::Once));
+    let object = GameObject::new("door");
+    object.lock().set_static(false);
+    object.lock().transform.local = Mat4::new_translation(&Vec3::new(0.0, 0.0, 1.0));
+    let mut player = AnimationPlayer::new().with_clip(Arc::new(walk)).with_clip(Arc::new(lift));
+    assert!(player.play("run", 0.0).is_err());
+
+    player.play("walk", 0.0).unwrap();
+    player.advance(&object, 0.25);
+    let location = |object: &GameObjectRef| object.lock().transform.local.column(3).xyz();
+    assert!((location(&object) - Vec3::new(0.5, 0.0, 0.0)).norm() < 1.0e-5);
+    assert!(player.markers().is_empty());
+    player.advance(&object, 0.5);
+    assert_eq!(player.markers(), ["step"]);
+    let pose = player.pose().unwrap();
+    assert!((pose.transform.rotation.angle() - 90f32.to_radians()).abs() < 1.0e-4);
+    assert_eq!(pose.floats, vec![(FloatTarget::MaterialParameter("roughness".to_owned()), 0.75)]);
+
+    // Переход за секунду: на середине смещение — среднее двух клипов
+    player.play("lift", 1.0).unwrap();
+    player.advance(&object, 0.5);
+    assert!(player.markers().is_empty());
+    assert!((location(&object) - Vec3::new(0.25, 0.0, 2.0)).norm() < 1.0e-4, "{}", location(&object));
+    player.advance(&object, 0.5);
+    assert_eq!(player.current_clip(), Some("lift"));
+    assert!((location(&object) - Vec3::new(0.0, 0.0, 4.0)).norm() < 1.0e-5);
+
+    // После остановки объект возвращается в исходное положение
+    player.stop(0.0);
+    player.advance(&object, 0.1);
+    assert!((location(&object) - Vec3::new(0.0, 0.0, 1.0)).norm() < 1.0e-5);
+
+    // Клип только с поворотом не мешает другим поведениям двигать объект,
+    // а новый запуск после остановки начинается с текущего положения
+    let spin = AnimationClip::new("spin", WrapMode::Loop).with_rotation(Track::new(
+        vec![
+            Keyframe { time: 0.0, value: Quat::identity() },
+            Keyframe { time: 1.0, value: Quat::from_euler_angles(0.0, 0.0, 1.0) },
+        ],
+        Interpolation::Linear,
+        WrapMode::Once,
+    ));
+    player.add_clip(Arc::new(spin));
+    player.play("spin", 0.0).unwrap();
+    for step in 1..=4 {
+        object.lock().transform.local.m14 += 1.0;
+        player.advance(&object, 0.25);
+        assert!((location(&object) - Vec3::new(step as f32, 0.0, 1.0)).norm() < 1.0e-4, "{}", location(&object));
+    }
+    let rotation = BoneTransform::from_matrix(&object.lock().transform.local).rotation;
+    assert!((rotation.angle() - 1.0).abs() < 1.0e-4);
+    player.stop(0.0);
+    player.advance(&object, 0.1);
+    assert!((location(&object) - Vec3::new(4.0, 0.0, 1.0)).norm() < 1.0e-4);
+    assert!(BoneTransform::from_matrix(&object.lock().transform.local).rotation.angle() < 1.0e-4);
+    object.lock().transform.local = Mat4::new_translation(&Vec3::new(0.0, 5.0, 0.0));
+    player.play("lift", 0.0).unwrap();
+    player.advance(&object, 0.1);
+    player.stop(0.0);
+    player.advance(&object, 0.1);
+    assert!((location(&object) - Vec3::new(0.0, 5.0, 0.0)).norm() < 1.0e-5);
+}
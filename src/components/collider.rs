@@ -0,0 +1,272 @@
+//! Коллайдер: форма объекта для проверки столкновений.
+//!
+//! Сферы и капсулы задаются ядром (точкой, отрезком) и скруглением,
+//! остальные формы скругления не имеют. Проверка пар выполняется
+//! в [`crate::physics`], поиск пар и события триггеров — в сцене.
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::game_object::ALL_LAYERS;
+use crate::mesh::bvh::MeshBvh;
+use crate::mesh::{BoundingBox, MeshView};
+use crate::types::{Mat3, Mat4, Vec3};
+
+/// Форма коллайдера в системе координат объекта
+#[derive(Clone, Debug)]
+pub enum ColliderShape {
+    Sphere { radius: f32 },
+    /// Прямоугольный параллелепипед, с поворотом объекта — OBB
+    Box { half_extents: Vec3 },
+    /// Отрезок вдоль оси Z длиной `2 * half_height`, скруглённый на `radius`
+    Capsule { radius: f32, half_height: f32 },
+    /// Выпуклая оболочка точек
+    ConvexHull(Arc<[Vec3]>),
+    /// Треугольники меша. Сталкивается только с выпуклыми формами
+    TriangleMesh(Arc<MeshBvh>),
+}
+
+impl ColliderShape {
+    /// Выпуклая оболочка вершин меша с сохранённой на CPU копией
+    pub fn convex_hull_from_mesh(mesh: &dyn MeshView) -> Option<Self> {
+        let (vertices, indices) = mesh.cpu_geometry()?;
+        let offset = mesh.vertex_offset() as usize;
+        let mut unique = HashSet::new();
+        let points = indices
+            .iter()
+            .filter_map(|index| vertices.get(*index as usize + offset))
+            .filter(|vertex| unique.insert(vertex.v_pos.map(f32::to_bits)))
+            .map(|vertex| Vec3::from(vertex.v_pos))
+            .collect::<Vec<_>>();
+        (!points.is_empty()).then(|| Self::ConvexHull(points.into()))
+    }
+
+    /// Треугольники меша с сохранённой на CPU копией
+    pub fn triangle_mesh_from_mesh(mesh: &dyn MeshView) -> Option<Self> {
+        MeshBvh::from_mesh(mesh).map(|bvh| Self::TriangleMesh(Arc::new(bvh)))
+    }
+
+    #[inline]
+    pub fn is_convex(&self) -> bool {
+        !matches!(self, Self::TriangleMesh(_))
+    }
+
+    /// Радиус скругления ядра
+    #[inline]
+    pub fn margin(&self) -> f32 {
+        match *self {
+            Self::Sphere { radius } | Self::Capsule { radius, .. } => radius,
+            _ => 0.0,
+        }
+    }
+
+    /// Габариты ядра без скругления
+    pub fn core_bbox(&self) -> BoundingBox {
+        match self {
+            Self::Sphere { .. } => BoundingBox::default(),
+            Self::Box { half_extents } => BoundingBox {
+                begin: -half_extents,
+                end: *half_extents,
+            },
+            Self::Capsule { half_height, .. } => BoundingBox {
+                begin: Vec3::new(0.0, 0.0, -half_height),
+                end: Vec3::new(0.0, 0.0, *half_height),
+            },
+            Self::ConvexHull(points) => {
+                let mut bbox = BoundingBox::initial();
+                bbox.add_points(points);
+                bbox
+            }
+            Self::TriangleMesh(bvh) => bvh.bbox(),
+        }
+    }
+
+    /// Габариты формы
+    pub fn bbox(&self) -> BoundingBox {
+        self.core_bbox().expanded(self.margin())
+    }
+
+    /// Самая дальняя в направлении `direction` точка ядра.
+    /// У треугольного меша — точка габаритов.
+    pub fn core_support(&self, direction: &Vec3) -> Vec3 {
+        let pick = |positive: f32, negative: f32, axis: f32| if axis >= 0.0 { positive } else { negative };
+        match self {
+            Self::Sphere { .. } => Vec3::zeros(),
+            Self::Box { half_extents } => half_extents.zip_map(direction, |extent, axis| pick(extent, -extent, axis)),
+            Self::Capsule { half_height, .. } => Vec3::new(0.0, 0.0, pick(*half_height, -half_height, direction.z)),
+            Self::ConvexHull(points) => points
+                .iter()
+                .copied()
+                .max_by(|a, b| a.dot(direction).total_cmp(&b.dot(direction)))
+                .unwrap_or_default(),
+            Self::TriangleMesh(bvh) => {
+                let bbox = bvh.bbox();
+                Vec3::from_fn(|axis, _| pick(bbox.end[axis], bbox.begin[axis], direction[axis]))
+            }
+        }
+    }
+
+    /// Грань ядра, сильнее всего обращённая в направлении `direction`:
+    /// многоугольник с вершинами по порядку обхода, отрезок или точка.
+    /// Нужна для построения нескольких точек контакта.
+    pub fn supporting_face(&self, direction: &Vec3) -> Vec<Vec3> {
+        /// Допуск отклонения от направления, при котором ребро или вершины считаются гранью
+        const FACE_TOLERANCE: f32 = 0.05;
+        let Some(unit) = direction.try_normalize(f32::EPSILON) else {
+            return vec![self.core_support(direction)];
+        };
+        match self {
+            Self::Box { half_extents } => {
+                let axis = unit.iamax();
+                let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+                let sign = unit[axis].signum();
+                [(1.0, 1.0), (-1.0, 1.0), (-1.0, -1.0), (1.0, -1.0)]
+                    .iter()
+                    .map(|&(su, sv)| {
+                        let mut corner = Vec3::zeros();
+                        corner[axis] = sign * half_extents[axis];
+                        corner[u] = su * half_extents[u];
+                        corner[v] = sv * half_extents[v];
+                        corner
+                    })
+                    .collect()
+            }
+            Self::Capsule { half_height, .. } if unit.z.abs() < FACE_TOLERANCE => {
+                vec![Vec3::new(0.0, 0.0, *half_height), Vec3::new(0.0, 0.0, -half_height)]
+            }
+            Self::ConvexHull(points) => {
+                let bbox = self.core_bbox();
+                let tolerance = FACE_TOLERANCE * (bbox.end - bbox.begin).norm();
+                let max = points.iter().map(|point| point.dot(&unit)).fold(f32::MIN, f32::max);
+                let mut face = points
+                    .iter()
+                    .copied()
+                    .filter(|point| point.dot(&unit) >= max - tolerance)
+                    .collect::<Vec<_>>();
+                if face.len() > 2 {
+                    // Порядок обхода вокруг центра грани
+                    let center = face.iter().sum::<Vec3>() / face.len() as f32;
+                    let tangent = (face[0] - center).try_normalize(f32::EPSILON).unwrap_or_else(|| unit.cross(&Vec3::x()));
+                    let bitangent = unit.cross(&tangent);
+                    let angle = |point: &Vec3| (point - center).dot(&bitangent).atan2((point - center).dot(&tangent));
+                    face.sort_by(|a, b| angle(a).total_cmp(&angle(b)));
+                }
+                face
+            }
+            _ => vec![self.core_support(direction)],
+        }
+    }
+}
+
+/// Компонент коллайдера
+#[derive(Clone, Debug)]
+pub struct Collider {
+    shape: ColliderShape,
+    offset: Mat4,
+    is_trigger: bool,
+    collision_mask: u32,
+}
+
+#[allow(dead_code)]
+impl Collider {
+    /// Твёрдое тело, сталкивающееся с объектами всех слоёв
+    pub fn new(shape: ColliderShape) -> Self {
+        Self {
+            shape,
+            offset: Mat4::identity(),
+            is_trigger: false,
+            collision_mask: ALL_LAYERS,
+        }
+    }
+
+    /// Объём-триггер: вместо столкновений отправляет события входа, пребывания и выхода
+    pub fn trigger(shape: ColliderShape) -> Self {
+        Self {
+            is_trigger: true,
+            ..Self::new(shape)
+        }
+    }
+
+    #[inline]
+    pub fn shape(&self) -> &ColliderShape {
+        &self.shape
+    }
+
+    pub fn set_shape(&mut self, shape: ColliderShape) {
+        self.shape = shape;
+    }
+
+    /// Положение формы относительно объекта
+    #[inline]
+    pub fn offset(&self) -> Mat4 {
+        self.offset
+    }
+
+    pub fn set_offset(&mut self, offset: Mat4) {
+        self.offset = offset;
+    }
+
+    #[inline]
+    pub fn is_trigger(&self) -> bool {
+        self.is_trigger
+    }
+
+    pub fn set_trigger(&mut self, is_trigger: bool) {
+        self.is_trigger = is_trigger;
+    }
+
+    /// Слои объектов, с которыми проверяются столкновения
+    #[inline]
+    pub fn collision_mask(&self) -> u32 {
+        self.collision_mask
+    }
+
+    pub fn set_collision_mask(&mut self, mask: u32) {
+        self.collision_mask = mask;
+    }
+
+    /// Матрица формы в мировых координатах
+    #[inline]
+    pub fn world_matrix(&self, global: &Mat4) -> Mat4 {
+        global * self.offset
+    }
+
+    /// Скругление формы в мировых координатах.
+    /// При неравномерном масштабе берётся наибольший.
+    pub fn world_margin(&self, global: &Mat4) -> f32 {
+        let linear: Mat3 = self.world_matrix(global).fixed_slice::<3, 3>(0, 0).into();
+        let scale = linear.column_iter().map(|column| column.norm()).fold(0.0, f32::max);
+        self.shape.margin() * scale
+    }
+
+    /// Габариты формы в мировых координатах
+    pub fn world_bbox(&self, global: &Mat4) -> BoundingBox {
+        self.shape
+            .core_bbox()
+            .transformed(&self.world_matrix(global))
+            .expanded(self.world_margin(global))
+    }
+}
+
+crate::impl_behaviour!(Collider {});
+
+#[test]
+fn collider_shape_support_and_bbox() {
+    let shape = ColliderShape::Box {
+        half_extents: Vec3::new(1.0, 2.0, 3.0),
+    };
+    assert_eq!(shape.core_support(&Vec3::new(1.0, -1.0, 0.5)), Vec3::new(1.0, -2.0, 3.0));
+    let capsule = ColliderShape::Capsule {
+        radius: 0.5,
+        half_height: 1.0,
+    };
+    assert_eq!(capsule.core_support(&-Vec3::z()), Vec3::new(0.0, 0.0, -1.0));
+    assert_eq!(capsule.bbox().end, Vec3::new(0.5, 0.5, 1.5));
+
+    // Масштаб объекта увеличивает скругление по наибольшей оси
+    let collider = Collider::new(ColliderShape::Sphere { radius: 1.0 });
+    let global = Mat4::new_translation(&Vec3::new(5.0, 0.0, 0.0)) * Mat4::new_nonuniform_scaling(&Vec3::new(1.0, 2.0, 1.0));
+    assert!((collider.world_margin(&global) - 2.0).abs() < 1.0e-6);
+    let bbox = collider.world_bbox(&global);
+    assert_eq!(bbox.begin, Vec3::new(3.0, -2.0, -2.0));
+    assert_eq!(bbox.end, Vec3::new(7.0, 2.0, 2.0));
+}
@@ -60,7 +60,9 @@ pub enum ShadowMapMode {
 
 pub trait AbstractLight: Behaviour + Send + Sync {
     fn color(&self) -> Vec3;
+    fn set_color(&mut self, color: Vec3);
     fn power(&self) -> f32;
+    fn set_power(&mut self, power: f32);
     fn z_near(&self) -> f32;
     fn distance(&self) -> f32;
     fn location(&self) -> Vec3;
@@ -190,7 +192,7 @@ impl GenericLightUniform
 
 pub type Light = RcBox<dyn AbstractLight>;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 pub enum LightType {
     Spot,
     Sun,
@@ -112,10 +112,18 @@ impl AbstractLight for SunLight
         self.base.color()
     }
 
+    fn set_color(&mut self, color: Vec3) {
+        *self.base.color_mut() = color;
+    }
+
     fn power(&self) -> f32 {
         self.base.power()
     }
 
+    fn set_power(&mut self, power: f32) {
+        self.base.set_power(power);
+    }
+
     fn z_near(&self) -> f32 {
         self.base.z_near()
     }
@@ -1,15 +1,23 @@
 /// Компоненты для `GameObject`
 /// Пока в зачаточном состоянии
+pub mod animation;
 pub mod camera;
+pub mod collider;
 pub mod light;
+pub mod rigid_body;
+pub mod skeleton;
 pub mod visual;
 
 use crate::shader::ShaderStructUniform;
 use crate::types::Mat4;
 
 pub use crate::game_object::{GOTransformUniform, GameObject, GameObjectRef};
+pub use animation::{AnimationClip, AnimationPlayer};
 pub use camera::CameraComponent;
+pub use collider::{Collider, ColliderShape};
 pub use light::{Spotlight, SunLight, Light};
+pub use rigid_body::RigidBody;
+pub use skeleton::{SkeletalAnimator, Skeleton};
 pub use visual::{AbstractVisual, MeshVisual};
 
 // Структура для передачи данных шейдерной программе
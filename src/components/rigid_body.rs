@@ -0,0 +1,250 @@
+//! Твёрдое тело: масса, скорости и материал для симуляции сцены.
+//!
+//! Форма тела берётся из [`Collider`](super::Collider) того же объекта.
+//! Симуляция выполняется в [`crate::physics::PhysicsWorld`] и записывает
+//! положение обратно в `GOTransform::local`.
+use crate::components::collider::ColliderShape;
+use crate::types::Vec3;
+
+/// Компонент твёрдого тела
+#[derive(Clone, Debug)]
+pub struct RigidBody {
+    mass: f32,
+    linear_velocity: Vec3,
+    angular_velocity: Vec3,
+    force: Vec3,
+    torque: Vec3,
+    linear_impulse: Vec3,
+    angular_impulse: Vec3,
+    friction: f32,
+    restitution: f32,
+    linear_damping: f32,
+    angular_damping: f32,
+    gravity_scale: f32,
+    can_sleep: bool,
+    sleeping: bool,
+    sleep_timer: f32,
+}
+
+impl Default for RigidBody {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+#[allow(dead_code)]
+impl RigidBody {
+    /// Тело массой `mass` кг. Тело с нулевой массой неподвижно.
+    pub fn new(mass: f32) -> Self {
+        Self {
+            mass,
+            linear_velocity: Vec3::zeros(),
+            angular_velocity: Vec3::zeros(),
+            force: Vec3::zeros(),
+            torque: Vec3::zeros(),
+            linear_impulse: Vec3::zeros(),
+            angular_impulse: Vec3::zeros(),
+            friction: 0.5,
+            restitution: 0.0,
+            linear_damping: 0.05,
+            angular_damping: 0.05,
+            gravity_scale: 1.0,
+            can_sleep: true,
+            sleeping: false,
+            sleep_timer: 0.0,
+        }
+    }
+
+    #[inline]
+    pub fn mass(&self) -> f32 {
+        self.mass
+    }
+
+    pub fn set_mass(&mut self, mass: f32) {
+        self.mass = mass;
+        self.wake_up();
+    }
+
+    #[inline]
+    pub fn is_dynamic(&self) -> bool {
+        self.mass > 0.0
+    }
+
+    #[inline]
+    pub fn inverse_mass(&self) -> f32 {
+        if self.is_dynamic() {
+            1.0 / self.mass
+        } else {
+            0.0
+        }
+    }
+
+    #[inline]
+    pub fn linear_velocity(&self) -> Vec3 {
+        self.linear_velocity
+    }
+
+    pub fn set_linear_velocity(&mut self, velocity: Vec3) {
+        self.linear_velocity = velocity;
+        self.wake_up();
+    }
+
+    /// Угловая скорость в мировых координатах, рад/с
+    #[inline]
+    pub fn angular_velocity(&self) -> Vec3 {
+        self.angular_velocity
+    }
+
+    pub fn set_angular_velocity(&mut self, velocity: Vec3) {
+        self.angular_velocity = velocity;
+        self.wake_up();
+    }
+
+    /// Сила, действующая на центр тела до конца следующего шага симуляции
+    pub fn apply_force(&mut self, force: Vec3) {
+        self.force += force;
+        self.wake_up();
+    }
+
+    /// Сила, приложенная к точке со смещением `offset` от центра тела
+    pub fn apply_force_at(&mut self, force: Vec3, offset: Vec3) {
+        self.torque += offset.cross(&force);
+        self.apply_force(force);
+    }
+
+    pub fn apply_torque(&mut self, torque: Vec3) {
+        self.torque += torque;
+        self.wake_up();
+    }
+
+    /// Мгновенное изменение импульса, применяется на следующем шаге симуляции
+    pub fn apply_impulse(&mut self, impulse: Vec3) {
+        self.linear_impulse += impulse;
+        self.wake_up();
+    }
+
+    /// Импульс, приложенный к точке со смещением `offset` от центра тела
+    pub fn apply_impulse_at(&mut self, impulse: Vec3, offset: Vec3) {
+        self.angular_impulse += offset.cross(&impulse);
+        self.apply_impulse(impulse);
+    }
+
+    pub fn apply_angular_impulse(&mut self, impulse: Vec3) {
+        self.angular_impulse += impulse;
+        self.wake_up();
+    }
+
+    #[inline]
+    pub fn friction(&self) -> f32 {
+        self.friction
+    }
+
+    pub fn set_friction(&mut self, friction: f32) {
+        self.friction = friction;
+    }
+
+    /// Упругость: доля скорости, сохраняемая после удара
+    #[inline]
+    pub fn restitution(&self) -> f32 {
+        self.restitution
+    }
+
+    pub fn set_restitution(&mut self, restitution: f32) {
+        self.restitution = restitution;
+    }
+
+    /// Затухание линейной и угловой скоростей, 1/с
+    #[inline]
+    pub fn damping(&self) -> (f32, f32) {
+        (self.linear_damping, self.angular_damping)
+    }
+
+    pub fn set_damping(&mut self, linear: f32, angular: f32) {
+        self.linear_damping = linear;
+        self.angular_damping = angular;
+    }
+
+    #[inline]
+    pub fn gravity_scale(&self) -> f32 {
+        self.gravity_scale
+    }
+
+    pub fn set_gravity_scale(&mut self, scale: f32) {
+        self.gravity_scale = scale;
+        self.wake_up();
+    }
+
+    #[inline]
+    pub fn is_sleeping(&self) -> bool {
+        self.sleeping
+    }
+
+    #[inline]
+    pub fn can_sleep(&self) -> bool {
+        self.can_sleep
+    }
+
+    pub fn set_can_sleep(&mut self, can_sleep: bool) {
+        self.can_sleep = can_sleep;
+        if !can_sleep {
+            self.wake_up();
+        }
+    }
+
+    pub fn wake_up(&mut self) {
+        self.sleeping = false;
+        self.sleep_timer = 0.0;
+    }
+
+    /// Главные моменты инерции тела формы `shape` с масштабом `scale`.
+    /// Шар и параллелепипед считаются точно, остальные формы — по габаритам.
+    pub fn principal_inertia(&self, shape: Option<&ColliderShape>, scale: &Vec3) -> Vec3 {
+        let box_inertia = |extents: Vec3| {
+            let squared = extents.component_mul(&extents);
+            Vec3::new(squared.y + squared.z, squared.x + squared.z, squared.x + squared.y) * (self.mass / 12.0)
+        };
+        match shape {
+            Some(ColliderShape::Sphere { radius }) => {
+                let radius = radius * scale.max();
+                Vec3::repeat(0.4 * self.mass * radius * radius)
+            }
+            Some(ColliderShape::Box { half_extents }) => box_inertia(half_extents.component_mul(scale) * 2.0),
+            Some(shape) => {
+                let bbox = shape.bbox();
+                box_inertia((bbox.end - bbox.begin).component_mul(scale))
+            }
+            None => Vec3::repeat(0.1 * self.mass),
+        }
+    }
+
+    pub(crate) fn take_impulses(&mut self) -> (Vec3, Vec3) {
+        let impulses = (self.linear_impulse, self.angular_impulse);
+        self.linear_impulse = Vec3::zeros();
+        self.angular_impulse = Vec3::zeros();
+        impulses
+    }
+
+    #[inline]
+    pub(crate) fn forces(&self) -> (Vec3, Vec3) {
+        (self.force, self.torque)
+    }
+
+    pub(crate) fn clear_forces(&mut self) {
+        self.force = Vec3::zeros();
+        self.torque = Vec3::zeros();
+    }
+
+    pub(crate) fn store_motion(&mut self, linear: Vec3, angular: Vec3, sleeping: bool, sleep_timer: f32) {
+        self.linear_velocity = linear;
+        self.angular_velocity = angular;
+        self.sleeping = sleeping;
+        self.sleep_timer = sleep_timer;
+    }
+
+    #[inline]
+    pub(crate) fn sleep_timer(&self) -> f32 {
+        self.sleep_timer
+    }
+}
+
+crate::impl_behaviour!(RigidBody {});
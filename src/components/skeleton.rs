@@ -0,0 +1,544 @@
+//! Скелетная анимация: иерархия костей, позы, клипы с ключевыми кадрами
+//! и компонент, проигрывающий клипы и обновляющий матрицы скининга.
+//!
+//! Вершина деформируется тремя костями из `v_grp` с весами `v_wgt`.
+//! Матрица скининга кости переводит вершину из позы привязки в текущую позу:
+//! `global * inverse_bind`.
+use std::sync::Arc;
+
+use nalgebra::UnitQuaternion;
+
+use crate::error::DsgeError;
+use crate::game_logic::AbstractEvent;
+use crate::game_object::GameObjectRef;
+use crate::mesh::VkVertex;
+use crate::references::*;
+use crate::types::{Mat3, Mat4, Vec3, Vec4};
+
+/// Максимальное число костей, передаваемое в шейдер
+pub const MAX_BONES: usize = 128;
+
+/// Матрицы скининга, общие для компонента анимации и [`super::MeshVisual`]
+pub type SkinPalette = RcBox<Vec<Mat4>>;
+
+pub type Quat = UnitQuaternion<f32>;
+
+/// Локальное преобразование кости относительно родителя
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoneTransform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for BoneTransform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl BoneTransform {
+    pub fn identity() -> Self {
+        Self {
+            translation: Vec3::zeros(),
+            rotation: Quat::identity(),
+            scale: Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn new(translation: Vec3, rotation: Quat, scale: Vec3) -> Self {
+        Self { translation, rotation, scale }
+    }
+
+    pub fn to_matrix(&self) -> Mat4 {
+        Mat4::new_translation(&self.translation)
+            * self.rotation.to_homogeneous()
+            * Mat4::new_nonuniform_scaling(&self.scale)
+    }
+
+    /// Разложение матрицы на смещение, поворот и масштаб. Сдвиг не сохраняется.
+    pub fn from_matrix(matrix: &Mat4) -> Self {
+        let basis = matrix.fixed_slice::<3, 3>(0, 0).into_owned();
+        let mut scale = Vec3::from_fn(|axis, _| basis.column(axis).norm());
+        if basis.determinant() < 0.0 {
+            scale.x = -scale.x;
+        }
+        let rotation = if scale.iter().all(|axis| axis.abs() > 1.0e-12) {
+            Quat::from_matrix(&Mat3::from_fn(|row, column| basis[(row, column)] / scale[column]))
+        } else {
+            Quat::identity()
+        };
+        Self::new(matrix.column(3).xyz(), rotation, scale)
+    }
+
+    /// Линейная интерполяция смещения и масштаба, сферическая — поворота
+    pub fn interpolate(&self, other: &Self, t: f32) -> Self {
+        Self {
+            translation: self.translation.lerp(&other.translation, t),
+            rotation: slerp(&self.rotation, &other.rotation, t),
+            scale: self.scale.lerp(&other.scale, t),
+        }
+    }
+}
+
+/// Интерполяция по кратчайшей дуге. Для почти совпадающих поворотов — нормализованная линейная.
+pub(crate) fn slerp(a: &Quat, b: &Quat, t: f32) -> Quat {
+    let b = if a.coords.dot(&b.coords) < 0.0 {
+        Quat::new_unchecked(-b.into_inner())
+    } else {
+        *b
+    };
+    a.try_slerp(&b, t, 1.0e-6).unwrap_or_else(|| a.nlerp(&b, t))
+}
+
+pub struct Bone {
+    pub name: String,
+    pub parent: Option<usize>,
+    /// Преобразование в позе привязки
+    pub rest: BoneTransform,
+    inverse_bind: Mat4,
+}
+
+impl Bone {
+    #[inline]
+    pub fn inverse_bind(&self) -> &Mat4 {
+        &self.inverse_bind
+    }
+}
+
+/// Иерархия костей. Родитель всегда идёт раньше потомков.
+pub struct Skeleton {
+    bones: Vec<Bone>,
+}
+
+impl Skeleton {
+    /// Кости задаются именем, номером родителя и преобразованием в позе привязки
+    pub fn new(bones: Vec<(String, Option<usize>, BoneTransform)>) -> Result<Self, DsgeError> {
+        if bones.len() > MAX_BONES {
+            return Err(DsgeError::InvalidArgument(format!(
+                "skeleton has {} bones, at most {MAX_BONES} are supported",
+                bones.len()
+            )));
+        }
+        let mut globals: Vec<Mat4> = Vec::with_capacity(bones.len());
+        let mut result = Vec::with_capacity(bones.len());
+        for (index, (name, parent, rest)) in bones.into_iter().enumerate() {
+            let local = rest.to_matrix();
+            let global = match parent {
+                Some(parent) if parent < index => globals[parent] * local,
+                Some(parent) => {
+                    return Err(DsgeError::InvalidArgument(format!(
+                        "bone \"{name}\" #{index} must follow its parent #{parent}"
+                    )))
+                }
+                None => local,
+            };
+            let inverse_bind = global.try_inverse().ok_or_else(|| {
+                DsgeError::InvalidArgument(format!("bone \"{name}\" has a degenerate bind pose"))
+            })?;
+            globals.push(global);
+            result.push(Bone { name, parent, rest, inverse_bind });
+        }
+        Ok(Self { bones: result })
+    }
+
+    #[inline]
+    pub fn bones(&self) -> &[Bone] {
+        &self.bones
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.bones.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bones.is_empty()
+    }
+
+    pub fn bone_index(&self, name: &str) -> Option<usize> {
+        self.bones.iter().position(|bone| bone.name == name)
+    }
+
+    pub fn rest_pose(&self) -> Pose {
+        Pose {
+            bones: self.bones.iter().map(|bone| bone.rest).collect(),
+        }
+    }
+
+    /// Преобразования костей относительно объекта
+    pub fn global_matrices(&self, pose: &Pose) -> Vec<Mat4> {
+        let mut globals: Vec<Mat4> = Vec::with_capacity(self.bones.len());
+        for (bone, transform) in self.bones.iter().zip(&pose.bones) {
+            let local = transform.to_matrix();
+            globals.push(match bone.parent {
+                Some(parent) => globals[parent] * local,
+                None => local,
+            });
+        }
+        globals
+    }
+
+    pub fn skinning_matrices(&self, pose: &Pose) -> Vec<Mat4> {
+        self.global_matrices(pose)
+            .into_iter()
+            .zip(&self.bones)
+            .map(|(global, bone)| global * bone.inverse_bind)
+            .collect()
+    }
+}
+
+/// Локальные преобразования всех костей скелета
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pose {
+    pub bones: Vec<BoneTransform>,
+}
+
+impl Pose {
+    /// Смешивание с другой позой: 0 — эта поза, 1 — `other`
+    pub fn blend(&self, other: &Pose, weight: f32) -> Pose {
+        Pose {
+            bones: self
+                .bones
+                .iter()
+                .zip(&other.bones)
+                .map(|(a, b)| a.interpolate(b, weight))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Keyframe<T> {
+    pub time: f32,
+    pub value: T,
+}
+
+/// Ключевые кадры одной кости. Пустой канал оставляет значение из исходной позы.
+#[derive(Clone, Debug, Default)]
+pub struct BoneTrack {
+    pub bone: usize,
+    pub translation: Vec<Keyframe<Vec3>>,
+    pub rotation: Vec<Keyframe<Quat>>,
+    pub scale: Vec<Keyframe<Vec3>>,
+}
+
+impl BoneTrack {
+    pub fn new(bone: usize) -> Self {
+        Self { bone, ..Default::default() }
+    }
+}
+
+/// Значение в момент `time`. Ключи должны быть упорядочены по времени.
+fn sample_keys<T: Copy>(keys: &[Keyframe<T>], time: f32, lerp: impl Fn(&T, &T, f32) -> T) -> Option<T> {
+    let next = keys.partition_point(|key| key.time <= time);
+    match (keys.get(next.wrapping_sub(1)), keys.get(next)) {
+        (Some(a), Some(b)) => {
+            let span = b.time - a.time;
+            let t = if span > 0.0 { (time - a.time) / span } else { 0.0 };
+            Some(lerp(&a.value, &b.value, t))
+        }
+        (Some(key), None) | (None, Some(key)) => Some(key.value),
+        (None, None) => None,
+    }
+}
+
+/// Анимация скелета
+#[derive(Clone, Debug)]
+pub struct SkeletalClip {
+    pub name: String,
+    pub duration: f32,
+    pub tracks: Vec<BoneTrack>,
+}
+
+impl SkeletalClip {
+    /// Длительность — время последнего ключевого кадра
+    pub fn new(name: &str, tracks: Vec<BoneTrack>) -> Self {
+        let duration = tracks
+            .iter()
+            .flat_map(|track| {
+                let last = |times: Option<f32>| times.unwrap_or(0.0);
+                [
+                    last(track.translation.last().map(|key| key.time)),
+                    last(track.rotation.last().map(|key| key.time)),
+                    last(track.scale.last().map(|key| key.time)),
+                ]
+            })
+            .fold(0.0, f32::max);
+        Self {
+            name: name.to_owned(),
+            duration,
+            tracks,
+        }
+    }
+
+    /// Записывает в `pose` значения каналов в момент `time`
+    pub fn sample(&self, time: f32, pose: &mut Pose) {
+        for track in &self.tracks {
+            let Some(bone) = pose.bones.get_mut(track.bone) else {
+                continue;
+            };
+            if let Some(translation) = sample_keys(&track.translation, time, |a, b, t| a.lerp(b, t)) {
+                bone.translation = translation;
+            }
+            if let Some(rotation) = sample_keys(&track.rotation, time, slerp) {
+                bone.rotation = rotation;
+            }
+            if let Some(scale) = sample_keys(&track.scale, time, |a, b, t| a.lerp(b, t)) {
+                bone.scale = scale;
+            }
+        }
+    }
+}
+
+/// Деформация вершины на CPU, так же как в шейдере `DEFORMED`.
+/// Возвращает позицию и нормаль. Вершина без весов не меняется.
+pub fn skin_vertex(vertex: &VkVertex, palette: &[Mat4]) -> (Vec3, Vec3) {
+    let total = vertex.v_wgt.iter().sum::<f32>();
+    let pos = Vec3::from(vertex.v_pos);
+    let nor = Vec3::from(vertex.v_nor);
+    if total <= 0.0 {
+        return (pos, nor);
+    }
+    let mut skin = Mat4::zeros();
+    for (group, weight) in vertex.v_grp.iter().zip(vertex.v_wgt) {
+        let bone = (*group as usize).min(MAX_BONES - 1);
+        skin += palette.get(bone).copied().unwrap_or_else(Mat4::identity) * (weight / total);
+    }
+    let pos = skin * Vec4::new(pos.x, pos.y, pos.z, 1.0);
+    let nor = skin.fixed_slice::<3, 3>(0, 0) * nor;
+    (pos.xyz(), nor.try_normalize(0.0).unwrap_or(nor))
+}
+
+struct AnimationLayer {
+    clip: Arc<SkeletalClip>,
+    time: f32,
+    speed: f32,
+    looping: bool,
+    weight: f32,
+    /// Изменение веса в секунду
+    fade: f32,
+}
+
+impl AnimationLayer {
+    fn advance(&mut self, delta: f32) {
+        self.time += delta * self.speed;
+        let duration = self.clip.duration;
+        if self.looping && duration > 0.0 {
+            self.time = self.time.rem_euclid(duration);
+        } else {
+            self.time = self.time.clamp(0.0, duration);
+        }
+        self.weight = (self.weight + self.fade * delta).clamp(0.0, 1.0);
+    }
+}
+
+/// Компонент, проигрывающий анимации скелета.
+/// Матрицы скининга обновляются каждый кадр и доступны через [`SkeletalAnimator::skin`];
+/// их нужно передать в [`super::MeshVisual::with_skin`].
+pub struct SkeletalAnimator {
+    skeleton: Arc<Skeleton>,
+    layers: Vec<AnimationLayer>,
+    pose: Pose,
+    palette: SkinPalette,
+}
+
+impl SkeletalAnimator {
+    pub fn new(skeleton: Arc<Skeleton>) -> Self {
+        let pose = skeleton.rest_pose();
+        let palette = RcBox::construct(skeleton.skinning_matrices(&pose));
+        Self {
+            skeleton,
+            layers: Vec::new(),
+            pose,
+            palette,
+        }
+    }
+
+    #[inline]
+    pub fn skeleton(&self) -> &Arc<Skeleton> {
+        &self.skeleton
+    }
+
+    #[inline]
+    pub fn skin(&self) -> SkinPalette {
+        self.palette.clone()
+    }
+
+    /// Поза после последнего обновления
+    #[inline]
+    pub fn pose(&self) -> &Pose {
+        &self.pose
+    }
+
+    /// Запуск клипа с плавным переходом от текущих анимаций за `fade_time` секунд
+    pub fn play(&mut self, clip: Arc<SkeletalClip>, fade_time: f32, looping: bool) {
+        if fade_time > 0.0 {
+            for layer in &mut self.layers {
+                layer.fade = -1.0 / fade_time;
+            }
+        } else {
+            self.layers.clear();
+        }
+        self.layers.push(AnimationLayer {
+            clip,
+            time: 0.0,
+            speed: 1.0,
+            looping,
+            weight: if fade_time > 0.0 { 0.0 } else { 1.0 },
+            fade: if fade_time > 0.0 { 1.0 / fade_time } else { 0.0 },
+        });
+    }
+
+    /// Плавная остановка всех анимаций с возвратом в позу привязки
+    pub fn stop(&mut self, fade_time: f32) {
+        if fade_time > 0.0 {
+            for layer in &mut self.layers {
+                layer.fade = -1.0 / fade_time;
+            }
+        } else {
+            self.layers.clear();
+        }
+    }
+
+    /// Скорость последнего запущенного клипа
+    pub fn set_speed(&mut self, speed: f32) {
+        if let Some(layer) = self.layers.last_mut() {
+            layer.speed = speed;
+        }
+    }
+
+    /// Имя последнего запущенного клипа
+    pub fn current_clip(&self) -> Option<&str> {
+        self.layers.last().map(|layer| layer.clip.name.as_str())
+    }
+
+    /// Продвижение анимаций на `delta` секунд и пересчёт матриц скининга
+    pub fn advance(&mut self, delta: f32) {
+        for layer in &mut self.layers {
+            layer.advance(delta);
+        }
+        self.layers.retain(|layer| layer.weight > 0.0 || layer.fade > 0.0);
+
+        let rest = self.skeleton.rest_pose();
+        let mut pose = rest.clone();
+        for layer in &self.layers {
+            let mut layer_pose = rest.clone();
+            layer.clip.sample(layer.time, &mut layer_pose);
+            pose = pose.blend(&layer_pose, layer.weight);
+        }
+        *self.palette.lock() = self.skeleton.skinning_matrices(&pose);
+        self.pose = pose;
+    }
+
+    fn frame_tick(&mut self, _owner: &GameObjectRef, event: AbstractEvent) {
+        if let AbstractEvent::FrameTick(time) = event {
+            self.advance(time.delta());
+        }
+    }
+}
+
+mod skeletal_animator {
+    use super::*;
+    crate::impl_behaviour!(SkeletalAnimator {
+        frame_tick: FrameTick
+    });
+}
+
+#[cfg(test)]
+fn test_arm() -> Arc<Skeleton> {
+    // Плечо в начале координат и предплечье на единицу выше по Z
+    let bones = vec![
+        ("shoulder".to_owned(), None, BoneTransform::identity()),
+        (
+            "forearm".to_owned(),
+            Some(0),
+            BoneTransform::new(Vec3::new(0.0, 0.0, 1.0), Quat::identity(), Vec3::new(1.0, 1.0, 1.0)),
+        ),
+    ];
+    Arc::new(Skeleton::new(bones).unwrap())
+}
+
+#[cfg(test)]
+fn assert_close(a: Vec3, b: Vec3) {
+    assert!((a - b).norm() < 1e-4, "{a:?} != {b:?}");
+}
+
+#[test]
+fn skeleton_pose_hierarchy() {
+    let skeleton = test_arm();
+    assert_eq!(skeleton.bone_index("forearm"), Some(1));
+    let rest = skeleton.rest_pose();
+    for matrix in skeleton.skinning_matrices(&rest) {
+        assert!((matrix - Mat4::identity()).norm() < 1e-5);
+    }
+
+    // Поворот плеча на 90° вокруг X переносит предплечье с оси Z на ось -Y
+    let mut pose = rest.clone();
+    pose.bones[0].rotation = Quat::from_euler_angles(std::f32::consts::FRAC_PI_2, 0.0, 0.0);
+    let globals = skeleton.global_matrices(&pose);
+    assert_close((globals[1] * Vec4::new(0.0, 0.0, 0.0, 1.0)).xyz(), Vec3::new(0.0, -1.0, 0.0));
+
+    // Вершина на кончике предплечья, привязанная к нему полностью и пополам с плечом
+    let palette = skeleton.skinning_matrices(&pose);
+    let mut vertex = VkVertex {
+        v_pos: [0.0, 0.0, 2.0],
+        v_nor: [0.0, 0.0, 1.0],
+        v_grp: [1, 0, 0],
+        v_wgt: [1.0, 0.0, 0.0],
+        ..Default::default()
+    };
+    let (pos, nor) = skin_vertex(&vertex, &palette);
+    assert_close(pos, Vec3::new(0.0, -2.0, 0.0));
+    assert_close(nor, Vec3::new(0.0, -1.0, 0.0));
+    vertex.v_wgt = [2.0, 2.0, 0.0];
+    let (pos, _) = skin_vertex(&vertex, &palette);
+    assert_close(pos, Vec3::new(0.0, -2.0, 0.0));
+    vertex.v_wgt = [0.0; 3];
+    assert_close(skin_vertex(&vertex, &palette).0, Vec3::new(0.0, 0.0, 2.0));
+
+    let bad = vec![("child".to_owned(), Some(1), BoneTransform::identity())];
+    assert!(matches!(Skeleton::new(bad), Err(DsgeError::InvalidArgument(_))));
+}
+
+#[test]
+fn skeleton_clip_sampling_and_blending() {
+    let skeleton = test_arm();
+    let angle = |degrees: f32| Quat::from_euler_angles(degrees.to_radians(), 0.0, 0.0);
+    let mut track = BoneTrack::new(1);
+    track.rotation = vec![
+        Keyframe { time: 0.0, value: angle(0.0) },
+        Keyframe { time: 1.0, value: angle(90.0) },
+    ];
+    track.translation = vec![Keyframe { time: 0.5, value: Vec3::new(0.0, 0.0, 2.0) }];
+    let bend = Arc::new(SkeletalClip::new("bend", vec![track]));
+    assert_eq!(bend.duration, 1.0);
+
+    let mut pose = skeleton.rest_pose();
+    bend.sample(0.5, &mut pose);
+    assert!((pose.bones[1].rotation.angle() - 45f32.to_radians()).abs() < 1e-4);
+    assert_close(pose.bones[1].translation, Vec3::new(0.0, 0.0, 2.0));
+    // За пределами ключей значение не экстраполируется
+    bend.sample(3.0, &mut pose);
+    assert!((pose.bones[1].rotation.angle() - 90f32.to_radians()).abs() < 1e-4);
+    assert_eq!(pose.bones[0], BoneTransform::identity());
+
+    let half = skeleton.rest_pose().blend(&pose, 0.5);
+    assert!((half.bones[1].rotation.angle() - 45f32.to_radians()).abs() < 1e-4);
+    assert_close(half.bones[1].translation, Vec3::new(0.0, 0.0, 1.5));
+
+    // Проигрывание с зацикливанием и плавный переход к позе привязки
+    let mut animator = SkeletalAnimator::new(skeleton.clone());
+    animator.play(bend.clone(), 0.0, true);
+    animator.advance(1.25);
+    assert!((animator.pose().bones[1].rotation.angle() - 22.5f32.to_radians()).abs() < 1e-4);
+    animator.stop(1.0);
+    animator.advance(0.5);
+    assert!((animator.pose().bones[1].rotation.angle() - 33.75f32.to_radians()).abs() < 1e-3);
+    animator.advance(0.5);
+    assert_eq!(animator.current_clip(), None);
+    assert_eq!(animator.pose(), &skeleton.rest_pose());
+    let skin = animator.skin();
+    assert!(skin.lock().iter().all(|matrix| (matrix - Mat4::identity()).norm() < 1e-5));
+}
@@ -1,8 +1,10 @@
+use crate::error::DsgeError;
 use std::collections::HashMap;
 
+use crate::components::skeleton::SkinPalette;
 use crate::material::{MaterialRef, MaterialShaderProgramType};
-use crate::mesh::{BoundingBox, MeshRef};
-use crate::references::MutexLockBox;
+use crate::mesh::{BoundingBox, MeshRef, VertexFormat};
+use crate::references::*;
 use crate::types::Vec3;
 use crate::utils::RefId;
 
@@ -14,6 +16,12 @@ pub struct MeshVisual {
     material: MaterialRef,
     shader_hashes: HashMap<MaterialShaderProgramType, u64>,
     cast_shadow: bool,
+    lods: Vec<(MeshRef, f32)>,
+    /// Уровень, выбранный рендерером для объекта-владельца, см. [`crate::mesh::lod::LodLevels`]
+    current_lod: usize,
+    skin: Option<SkinPalette>,
+    /// Веса целей морфинга этого экземпляра
+    morph_weights: Vec<f32>,
 }
 
 impl MeshVisual {
@@ -28,16 +36,119 @@ impl MeshVisual {
             material: material.clone(),
             cast_shadow: cast_shadow,
             shader_hashes: shader_hashes,
+            lods: Vec::new(),
+            current_lod: 0,
+            skin: None,
+            morph_weights: vec![0.0; mesh.morph_targets().map(|targets| targets.len()).unwrap_or(0)],
         }
     }
 
+    /// Уровни детализации: меш и размер на экране, ниже которого он используется
+    /// (1.0 — высота экрана). Обычно это [`crate::mesh::SubMesh`] из диапазонов
+    /// [`crate::mesh::MeshBuilder::generate_lods`].
+    pub fn with_lods(mut self, mut lods: Vec<(MeshRef, f32)>) -> Self {
+        lods.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        self.lods = lods;
+        self.current_lod = 0;
+        self
+    }
+
+    /// Деформация меша скелетом: матрицы скининга из [`crate::components::SkeletalAnimator::skin`]
+    pub fn with_skin(mut self, skin: SkinPalette) -> Self {
+        self.skin = Some(skin);
+        self
+    }
+
+    #[inline]
+    pub fn skin(&self) -> Option<&SkinPalette> {
+        self.skin.as_ref()
+    }
+
+    /// Вес цели морфинга меша по её номеру
+    pub fn set_morph_weight(&mut self, target: usize, weight: f32) -> Result<(), DsgeError> {
+        let count = self.morph_weights.len();
+        match self.morph_weights.get_mut(target) {
+            Some(value) => {
+                *value = weight;
+                Ok(())
+            }
+            None => Err(DsgeError::InvalidArgument(format!(
+                "mesh {} has {count} morph targets, got target #{target}",
+                self.mesh.name()
+            ))),
+        }
+    }
+
+    /// Вес цели морфинга меша по её имени
+    pub fn set_morph_weight_by_name(&mut self, name: &str, weight: f32) -> Result<(), DsgeError> {
+        match self.mesh.morph_targets().and_then(|targets| targets.index(name)) {
+            Some(target) => self.set_morph_weight(target, weight),
+            None => Err(DsgeError::InvalidArgument(format!(
+                "mesh {} has no morph target \"{name}\"",
+                self.mesh.name()
+            ))),
+        }
+    }
+
+    /// Веса всех целей морфинга меша
+    #[inline]
+    pub fn morph_weights(&self) -> &[f32] {
+        &self.morph_weights
+    }
+
+    /// Деформируется ли меш скелетом или морфингом
+    #[inline]
+    pub fn is_deformed(&self) -> bool {
+        self.skin.is_some() || self.mesh.morph_targets().is_some()
+    }
+
+    /// Вариант шейдера материала для этого объекта: деформируемые меши
+    /// рисуются с `DEFORMED`, компактные вершины распаковываются в шейдере
+    #[inline]
+    pub fn shader_type(&self, base: MaterialShaderProgramType) -> MaterialShaderProgramType {
+        base.deformed(self.is_deformed())
+            .compact(self.mesh().vertex_format() == VertexFormat::Compact)
+    }
+
+    /// Пороги размера на экране для уровней детализации, начиная с первого упрощённого
+    pub fn lod_thresholds(&self) -> Vec<f32> {
+        self.lods.iter().map(|(_, size)| *size).collect()
+    }
+
+    /// Выбор уровня детализации, 0 — исходный меш
+    #[inline]
+    pub fn set_lod(&mut self, level: usize) {
+        self.current_lod = level.min(self.lods.len());
+    }
+
+    /// Текущий уровень детализации
+    #[inline]
+    pub fn lod(&self) -> usize {
+        self.current_lod
+    }
+
+    #[inline]
+    pub fn lod_count(&self) -> usize {
+        self.lods.len() + 1
+    }
+
+    /// Меш без учёта уровня детализации
+    #[inline]
+    pub fn base_mesh(&self) -> &MeshRef {
+        &self.mesh
+    }
+
     pub fn shader_hash(&self, sh_type: MaterialShaderProgramType) -> u64 {
         self.shader_hashes[&sh_type]
     }
 
+    /// Меш текущего уровня детализации
     #[inline]
     pub fn mesh(&self) -> &MeshRef {
-        &self.mesh
+        match self.lod() {
+            0 => &self.mesh,
+            level => &self.lods[level - 1].0,
+        }
     }
 
     #[inline]
@@ -81,7 +192,7 @@ pub trait AbstractVisual: Sync + Send + 'static {
         _acbb: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
         _new_mesh: bool,
         _new_material: bool
-    ) -> Result<(), String>;*/
+    ) -> Result<(), DsgeError>;*/
 
     /*/// Выполняется при рендеринге на стадии геометрии
     fn on_geometry_pass_secondary(
@@ -92,7 +203,7 @@ pub trait AbstractVisual: Sync + Send + 'static {
         _acbb: &mut AutoCommandBufferBuilder<SecondaryAutoCommandBuffer>,
         _new_mesh: bool,
         _new_material: bool,
-    ) -> Result<(), String>;*/
+    ) -> Result<(), DsgeError>;*/
 
     /*/// Выполняется при рендеринге на стадии карт теней
     fn on_geometry_pass(
@@ -106,7 +217,7 @@ pub trait AbstractVisual: Sync + Send + 'static {
         acbb: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
         new_mesh: bool,
         new_material: bool,
-    ) -> Result<(), String>;*/
+    ) -> Result<(), DsgeError>;*/
 
     /// Должно возвращать уникальный номер материала (необходимо для оптимизации)
     fn material_id(&self) -> i32;
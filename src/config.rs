@@ -0,0 +1,245 @@
+//! Конфигурация приложения.
+//!
+//! Загружается из TOML-файла, после чего может быть переопределена
+//! аргументами командной строки. Конфигурацию можно сохранить обратно
+//! в файл, например, из внутриигрового меню настроек.
+use crate::error::DsgeError;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::resource_manager::ResourceManagerConfig;
+
+/// Файл конфигурации, который загружается, если не указан `--config`.
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+/// Верхний предел для количества буферов теней каждого типа источников света.
+pub const MAX_SHADOW_BUFFERS: u32 = 64;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    pub width: u16,
+    pub height: u16,
+    pub fullscreen: bool,
+    pub vsync: bool,
+    pub super_resolution: bool,
+    pub fxaa: bool,
+
+    /// Имя сцены из `data/scenes` либо путь к файлу сцены.
+    pub scene: String,
+
+    #[serde(rename = "resources")]
+    resource_manager_config: ResourceManagerConfig,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            width: 1280,
+            height: 720,
+            fullscreen: false,
+            vsync: false,
+            super_resolution: false,
+            fxaa: false,
+            scene: "shooting_range".to_owned(),
+
+            resource_manager_config: Default::default(),
+        }
+    }
+}
+
+impl AppConfig {
+    pub fn resource_manager_config(&self) -> ResourceManagerConfig {
+        self.resource_manager_config
+    }
+
+    pub fn resource_manager_config_mut(&mut self) -> &mut ResourceManagerConfig {
+        &mut self.resource_manager_config
+    }
+
+    /// Путь к файлу сцены.
+    /// Если `scene` не является путём к файлу, то сцена ищется в `data/scenes`.
+    pub fn scene_path(&self) -> String {
+        if Path::new(&self.scene).is_file() {
+            self.scene.clone()
+        } else {
+            format!("data/scenes/{}.scene", self.scene)
+        }
+    }
+
+    /// Разбор конфигурации из строки в формате TOML.
+    pub fn from_toml(source: &str) -> Result<Self, DsgeError> {
+        let config: Self = toml::from_str(source)
+            .map_err(|err| DsgeError::InvalidArgument(format!("Ошибка разбора конфигурации: {err}")))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Сериализация конфигурации в TOML.
+    pub fn to_toml(&self) -> Result<String, DsgeError> {
+        toml::to_string_pretty(self)
+            .map_err(|err| DsgeError::Message(format!("Ошибка сериализации конфигурации: {err}")))
+    }
+
+    /// Загрузка конфигурации из файла.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, DsgeError> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|err| DsgeError::io(path, err))?;
+        Self::from_toml(&source).map_err(|err| err.context(format!("{path:?}")))
+    }
+
+    /// Сохранение конфигурации в файл.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), DsgeError> {
+        let path = path.as_ref();
+        self.validate()?;
+        std::fs::write(path, self.to_toml()?).map_err(|err| DsgeError::io(path, err))
+    }
+
+    /// Формирует конфигурацию из аргументов командной строки (без имени программы).
+    ///
+    /// Сначала загружается файл, указанный в `--config`, либо [`DEFAULT_CONFIG_PATH`],
+    /// если он существует. Остальные аргументы переопределяют значения из файла.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Self, DsgeError> {
+        let args = args.into_iter().collect::<Vec<_>>();
+        let mut config_path = None;
+        let mut overrides = Vec::with_capacity(args.len());
+        let mut iter = args.into_iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--config" {
+                match iter.next() {
+                    Some(path) => config_path = Some(path),
+                    None => return Err(DsgeError::InvalidArgument("После --config должен быть указан путь к файлу.".to_owned())),
+                }
+            } else {
+                overrides.push(arg);
+            }
+        }
+        let mut config = match config_path {
+            Some(path) => Self::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_PATH).is_file() => Self::from_file(DEFAULT_CONFIG_PATH)?,
+            None => Self::default(),
+        };
+        config.apply_args(overrides)?;
+        Ok(config)
+    }
+
+    /// Переопределяет параметры аргументами командной строки.
+    /// Аргумент, не являющийся ключом, считается именем сцены.
+    pub fn apply_args<I: IntoIterator<Item = String>>(&mut self, args: I) -> Result<(), DsgeError> {
+        fn value(key: &str, args: &mut impl Iterator<Item = String>) -> Result<String, DsgeError> {
+            args.next()
+                .ok_or_else(|| DsgeError::InvalidArgument(format!("После {key} должно быть указано значение.")))
+        }
+
+        fn number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, DsgeError> {
+            value
+                .parse::<T>()
+                .map_err(|_| DsgeError::InvalidArgument(format!("Неправильное число для {key}: \"{value}\".")))
+        }
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--max-spot-lights" => {
+                    let val = value(&arg, &mut args)?;
+                    self.resource_manager_config.max_spotlights = number(&arg, &val)?;
+                }
+                "--max-sun-lights" => {
+                    let val = value(&arg, &mut args)?;
+                    self.resource_manager_config.max_sun_lights = number(&arg, &val)?;
+                }
+                "--max-point-lights" => {
+                    let val = value(&arg, &mut args)?;
+                    self.resource_manager_config.max_point_lights = number(&arg, &val)?;
+                }
+                "--aniso" => {
+                    let val = value(&arg, &mut args)?;
+                    self.resource_manager_config.anisotrophy = match number::<u32>(&arg, &val)? {
+                        0 => None,
+                        aniso => Some(aniso as f32),
+                    };
+                }
+                "--sr" => self.super_resolution = true,
+                "--fs" => self.fullscreen = true,
+                "--vsync" => self.vsync = true,
+                "--fxaa" => self.fxaa = true,
+                "--resolution" | "-r" => {
+                    let val = value(&arg, &mut args)?;
+                    let (width, height) = val
+                        .split_once('x')
+                        .ok_or_else(|| DsgeError::InvalidArgument(format!("Разрешение должно быть задано в виде ШИРИНАxВЫСОТА, а не \"{val}\".")))?;
+                    self.width = number(&arg, width)?;
+                    self.height = number(&arg, height)?;
+                }
+                key if key.starts_with('-') => {
+                    return Err(DsgeError::InvalidArgument(format!("Неизвестный аргумент {key}.")));
+                }
+                _ => self.scene = arg,
+            }
+        }
+        self.validate()
+    }
+
+    /// Проверка допустимости значений.
+    pub fn validate(&self) -> Result<(), DsgeError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(DsgeError::InvalidArgument(format!(
+                "Неправильное разрешение {}x{}.",
+                self.width, self.height
+            )));
+        }
+        if self.scene.is_empty() {
+            return Err(DsgeError::InvalidArgument("Не указана сцена.".to_owned()));
+        }
+        let rmc = &self.resource_manager_config;
+        for (name, count) in [
+            ("max_spotlights", rmc.max_spotlights),
+            ("max_sun_lights", rmc.max_sun_lights),
+            ("max_point_lights", rmc.max_point_lights),
+        ] {
+            if !(1..=MAX_SHADOW_BUFFERS).contains(&count) {
+                return Err(DsgeError::InvalidArgument(format!(
+                    "{name} должно быть от 1 до {MAX_SHADOW_BUFFERS}, а не {count}."
+                )));
+            }
+        }
+        if let Some(aniso) = rmc.anisotrophy {
+            if !(1.0..=16.0).contains(&aniso) {
+                return Err(DsgeError::InvalidArgument(format!(
+                    "Степень анизотропной фильтрации должна быть от 1 до 16, а не {aniso}."
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn config_toml_round_trip() {
+    let mut config = AppConfig {
+        fxaa: true,
+        super_resolution: true,
+        ..Default::default()
+    };
+    config.resource_manager_config_mut().anisotrophy = Some(8.0);
+    let source = config.to_toml().unwrap();
+    assert_eq!(AppConfig::from_toml(&source).unwrap(), config);
+}
+
+#[test]
+fn config_args_override_and_validation() {
+    let args = ["-r", "640x360", "--fxaa", "--aniso", "4", "level1"].map(String::from);
+    let mut config = AppConfig::default();
+    config.apply_args(args).unwrap();
+    assert_eq!((config.width, config.height), (640, 360));
+    assert!(config.fxaa);
+    assert_eq!(config.resource_manager_config().anisotrophy, Some(4.0));
+    assert_eq!(config.scene, "level1");
+
+    let mut config = AppConfig::default();
+    assert!(config.apply_args(["--aniso", "17"].map(String::from)).is_err());
+    assert!(config.apply_args(["--max-sun-lights", "x"].map(String::from)).is_err());
+    assert!(AppConfig::from_toml("width = 0").is_err());
+    assert!(AppConfig::from_toml("[resources]\nmax_spotlights = 0").is_err());
+}
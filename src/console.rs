@@ -0,0 +1,505 @@
+//! Консоль и консольные переменные (cvars).
+//!
+//! Модули движка и поведения регистрируют в [`Cvars`] именованные типизированные
+//! переменные с описанием и подписываются на их изменение.
+//! [`Console`] разбирает команды `set`, `get`, `list`, `exec` и может читать их
+//! из stdin или получать строки от внутриигрового оверлея.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::error::DsgeError;
+use crate::references::*;
+
+/// Максимальная глубина вложенности `exec`
+const MAX_EXEC_DEPTH: usize = 8;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CvarValue {
+    Bool(bool),
+    Int(i64),
+    Float(f32),
+    Str(String),
+}
+
+impl CvarValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Bool(_) => "bool",
+            Self::Int(_) => "int",
+            Self::Float(_) => "float",
+            Self::Str(_) => "string",
+        }
+    }
+
+    /// Разбор строки в значение того же типа, что и `self`
+    pub fn parse_same(&self, value: &str) -> Result<Self, DsgeError> {
+        let error = || {
+            DsgeError::InvalidArgument(format!(
+                "\"{value}\" is not a valid {} value",
+                self.type_name()
+            ))
+        };
+        Ok(match self {
+            Self::Bool(_) => match value {
+                "1" | "true" | "on" | "yes" => Self::Bool(true),
+                "0" | "false" | "off" | "no" => Self::Bool(false),
+                _ => return Err(error()),
+            },
+            Self::Int(_) => Self::Int(value.parse().map_err(|_| error())?),
+            Self::Float(_) => Self::Float(value.parse().map_err(|_| error())?),
+            Self::Str(_) => Self::Str(value.to_owned()),
+        })
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Self::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f32> {
+        match self {
+            Self::Float(value) => Some(*value),
+            Self::Int(value) => Some(*value as f32),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Str(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CvarValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bool(value) => write!(f, "{}", *value as u8),
+            Self::Int(value) => write!(f, "{value}"),
+            Self::Float(value) => write!(f, "{value}"),
+            Self::Str(value) => write!(f, "\"{value}\""),
+        }
+    }
+}
+
+impl From<bool> for CvarValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i64> for CvarValue {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<i32> for CvarValue {
+    fn from(value: i32) -> Self {
+        Self::Int(value as _)
+    }
+}
+
+impl From<u32> for CvarValue {
+    fn from(value: u32) -> Self {
+        Self::Int(value as _)
+    }
+}
+
+impl From<f32> for CvarValue {
+    fn from(value: f32) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<&str> for CvarValue {
+    fn from(value: &str) -> Self {
+        Self::Str(value.to_owned())
+    }
+}
+
+impl From<String> for CvarValue {
+    fn from(value: String) -> Self {
+        Self::Str(value)
+    }
+}
+
+pub type CvarCallback = Arc<dyn Fn(&CvarValue) + Send + Sync>;
+
+struct Cvar {
+    value: CvarValue,
+    default: CvarValue,
+    description: String,
+    callbacks: Vec<CvarCallback>,
+}
+
+/// Реестр консольных переменных.
+/// Клоны ссылаются на один и тот же реестр.
+#[derive(Clone, Default)]
+pub struct Cvars {
+    vars: RcBox<BTreeMap<String, Cvar>>,
+}
+
+impl Cvars {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Регистрирует переменную.
+    /// Повторная регистрация переменной того же типа оставляет текущее значение,
+    /// поэтому несколько экземпляров поведения могут регистрировать одну переменную.
+    pub fn register<V: Into<CvarValue>>(
+        &self,
+        name: &str,
+        default: V,
+        description: &str,
+    ) -> Result<(), DsgeError> {
+        let default = default.into();
+        let mut vars = self.vars.lock();
+        match vars.get(name) {
+            Some(cvar) if cvar.default.type_name() != default.type_name() => {
+                Err(DsgeError::InvalidArgument(format!(
+                    "cvar {name} is already registered as {}",
+                    cvar.default.type_name()
+                )))
+            }
+            Some(_) => Ok(()),
+            None => {
+                vars.insert(
+                    name.to_owned(),
+                    Cvar {
+                        value: default.clone(),
+                        default,
+                        description: description.to_owned(),
+                        callbacks: Vec::new(),
+                    },
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// Подписка на изменение переменной
+    pub fn on_change<F>(&self, name: &str, callback: F) -> Result<(), DsgeError>
+    where
+        F: Fn(&CvarValue) + Send + Sync + 'static,
+    {
+        match self.vars.lock().get_mut(name) {
+            Some(cvar) => {
+                cvar.callbacks.push(Arc::new(callback));
+                Ok(())
+            }
+            None => Err(Self::not_found(name)),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<CvarValue> {
+        self.vars.lock().get(name).map(|cvar| cvar.value.clone())
+    }
+
+    pub fn description(&self, name: &str) -> Option<String> {
+        self.vars.lock().get(name).map(|cvar| cvar.description.clone())
+    }
+
+    /// Устанавливает значение и вызывает подписчиков.
+    /// Подписчики вызываются после снятия блокировки и могут обращаться к реестру.
+    pub fn set<V: Into<CvarValue>>(&self, name: &str, value: V) -> Result<(), DsgeError> {
+        let value = value.into();
+        let callbacks = {
+            let mut vars = self.vars.lock();
+            let cvar = vars.get_mut(name).ok_or_else(|| Self::not_found(name))?;
+            let value = match (&cvar.default, value) {
+                (CvarValue::Float(_), CvarValue::Int(value)) => CvarValue::Float(value as f32),
+                (default, value) if default.type_name() == value.type_name() => value,
+                (default, value) => {
+                    return Err(DsgeError::InvalidArgument(format!(
+                        "cvar {name} has type {}, not {}",
+                        default.type_name(),
+                        value.type_name()
+                    )))
+                }
+            };
+            if cvar.value == value {
+                return Ok(());
+            }
+            cvar.value = value;
+            cvar.callbacks.clone()
+        };
+        let value = self.get(name).unwrap();
+        log::debug!(target: "console", "{name} = {value}");
+        for callback in callbacks {
+            callback(&value);
+        }
+        Ok(())
+    }
+
+    /// Устанавливает значение из строки, тип определяется зарегистрированной переменной
+    pub fn set_from_str(&self, name: &str, value: &str) -> Result<(), DsgeError> {
+        let value = {
+            let vars = self.vars.lock();
+            let cvar = vars.get(name).ok_or_else(|| Self::not_found(name))?;
+            cvar.default.parse_same(value)?
+        };
+        self.set(name, value)
+    }
+
+    /// Сбрасывает переменную к значению по умолчанию
+    pub fn reset(&self, name: &str) -> Result<(), DsgeError> {
+        let default = self
+            .vars
+            .lock()
+            .get(name)
+            .map(|cvar| cvar.default.clone())
+            .ok_or_else(|| Self::not_found(name))?;
+        self.set(name, default)
+    }
+
+    /// Имена, значения и описания переменных, начинающихся с `prefix`
+    pub fn list(&self, prefix: &str) -> Vec<(String, CvarValue, String)> {
+        self.vars
+            .lock()
+            .iter()
+            .filter(|(name, _)| name.starts_with(prefix))
+            .map(|(name, cvar)| (name.clone(), cvar.value.clone(), cvar.description.clone()))
+            .collect()
+    }
+
+    fn not_found(name: &str) -> DsgeError {
+        DsgeError::AssetNotFound(format!("cvar {name}"))
+    }
+}
+
+/// Разбор и выполнение консольных команд.
+///
+/// Команды:
+/// * `set ИМЯ ЗНАЧЕНИЕ` или `ИМЯ ЗНАЧЕНИЕ` — установить значение;
+/// * `get ИМЯ` или `ИМЯ` — вывести значение и описание;
+/// * `reset ИМЯ` — вернуть значение по умолчанию;
+/// * `list [ПРЕФИКС]` — список переменных;
+/// * `exec ФАЙЛ` — выполнить файл, по одной команде на строку;
+/// * `help` — список команд.
+///
+/// Строки, начинающиеся с `#` или `//`, считаются комментариями.
+#[derive(Clone)]
+pub struct Console {
+    cvars: Cvars,
+}
+
+impl Console {
+    pub fn new(cvars: Cvars) -> Self {
+        Self { cvars }
+    }
+
+    pub fn cvars(&self) -> &Cvars {
+        &self.cvars
+    }
+
+    /// Выполняет строку и возвращает текст для вывода
+    pub fn execute(&self, line: &str) -> Result<String, DsgeError> {
+        self.execute_nested(line, 0)
+    }
+
+    /// Выполняет файл со скриптом
+    pub fn exec_file<P: AsRef<Path>>(&self, path: P) -> Result<String, DsgeError> {
+        self.exec_file_nested(path.as_ref(), 0)
+    }
+
+    /// Запускает поток, который выполняет команды из stdin и печатает результат
+    pub fn spawn_stdin(&self) -> std::thread::JoinHandle<()> {
+        let console = self.clone();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let Ok(line) = line else { break };
+                match console.execute(&line) {
+                    Ok(output) if output.is_empty() => (),
+                    Ok(output) => println!("{output}"),
+                    Err(err) => eprintln!("{err}"),
+                }
+            }
+        })
+    }
+
+    fn execute_nested(&self, line: &str, depth: usize) -> Result<String, DsgeError> {
+        let args = tokenize(line)?;
+        let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+        match args.as_slice() {
+            [] => Ok(String::new()),
+            ["help"] => Ok("set NAME VALUE, get NAME, reset NAME, list [PREFIX], exec FILE".to_owned()),
+            ["list"] => Ok(self.list("")),
+            ["list", prefix] => Ok(self.list(prefix)),
+            ["get", name] | [name] => self.describe(name),
+            ["set", name, value] | [name, value] if *name != "exec" && *name != "reset" => {
+                self.cvars.set_from_str(name, value)?;
+                Ok(String::new())
+            }
+            ["reset", name] => {
+                self.cvars.reset(name)?;
+                Ok(String::new())
+            }
+            ["exec", path] => self.exec_file_nested(Path::new(path), depth + 1),
+            [command, ..] => Err(DsgeError::InvalidArgument(format!(
+                "wrong arguments for \"{command}\", try \"help\""
+            ))),
+        }
+    }
+
+    fn exec_file_nested(&self, path: &Path, depth: usize) -> Result<String, DsgeError> {
+        if depth > MAX_EXEC_DEPTH {
+            return Err(DsgeError::InvalidArgument(format!(
+                "exec nesting is too deep at {path:?}"
+            )));
+        }
+        let script = std::fs::read_to_string(path).map_err(|err| DsgeError::open(path, err))?;
+        let mut output = Vec::new();
+        for (num, line) in script.lines().enumerate() {
+            let result = self
+                .execute_nested(line, depth)
+                .map_err(|err| err.context(format!("{}:{}", path.display(), num + 1)))?;
+            if !result.is_empty() {
+                output.push(result);
+            }
+        }
+        Ok(output.join("\n"))
+    }
+
+    fn describe(&self, name: &str) -> Result<String, DsgeError> {
+        let value = self.cvars.get(name).ok_or_else(|| Cvars::not_found(name))?;
+        let description = self.cvars.description(name).unwrap_or_default();
+        Ok(format!("{name} = {value} ({}) {description}", value.type_name()))
+    }
+
+    fn list(&self, prefix: &str) -> String {
+        self.cvars
+            .list(prefix)
+            .into_iter()
+            .map(|(name, value, description)| format!("{name} = {value}  {description}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Разбивает строку на аргументы с учётом кавычек и комментариев
+fn tokenize(line: &str) -> Result<Vec<String>, DsgeError> {
+    let line = line.trim();
+    if line.starts_with('#') || line.starts_with("//") {
+        return Ok(Vec::new());
+    }
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut has_token = false;
+    for ch in line.chars() {
+        match ch {
+            '"' => {
+                in_quotes = !in_quotes;
+                has_token = true;
+            }
+            ch if ch.is_whitespace() && !in_quotes => {
+                if has_token {
+                    args.push(std::mem::take(&mut current));
+                    has_token = false;
+                }
+            }
+            ch => {
+                current.push(ch);
+                has_token = true;
+            }
+        }
+    }
+    if in_quotes {
+        return Err(DsgeError::InvalidArgument(format!("unterminated quote in \"{line}\"")));
+    }
+    if has_token {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+#[test]
+fn cvars_set_get_and_callbacks() {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    let cvars = Cvars::new();
+    cvars.register("r_fxaa", false, "FXAA").unwrap();
+    cvars.register("m_sensitivity", 0.001f32, "Mouse sensitivity").unwrap();
+    cvars.register("m_sensitivity", 0.5f32, "").unwrap();
+    assert!(cvars.register("m_sensitivity", 1, "").is_err());
+    assert_eq!(cvars.get("m_sensitivity"), Some(CvarValue::Float(0.001)));
+
+    let calls = Arc::new(AtomicU32::new(0));
+    let calls2 = calls.clone();
+    let cvars2 = cvars.clone();
+    cvars
+        .on_change("r_fxaa", move |value| {
+            // Подписчик может обращаться к реестру
+            assert_eq!(cvars2.get("r_fxaa").as_ref(), Some(value));
+            calls2.fetch_add(1, Ordering::SeqCst);
+        })
+        .unwrap();
+    cvars.set("r_fxaa", true).unwrap();
+    cvars.set("r_fxaa", true).unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert!(cvars.set("r_fxaa", 1.5f32).is_err());
+    cvars.set("m_sensitivity", 2).unwrap();
+    assert_eq!(cvars.get("m_sensitivity"), Some(CvarValue::Float(2.0)));
+    cvars.reset("r_fxaa").unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    assert!(cvars.set("unknown", 1).is_err());
+}
+
+#[test]
+fn console_commands_and_scripts() {
+    let console = Console::new(Cvars::new());
+    let cvars = console.cvars();
+    cvars.register("r_fxaa", false, "FXAA").unwrap();
+    cvars.register("r_shadow_sun_size", 1024, "Sun shadow map size").unwrap();
+    cvars.register("name", "player", "Player name").unwrap();
+
+    assert_eq!(console.execute("").unwrap(), "");
+    assert_eq!(console.execute("# comment").unwrap(), "");
+    console.execute("set r_fxaa on").unwrap();
+    console.execute("r_shadow_sun_size 2048").unwrap();
+    console.execute("name \"big boss\"").unwrap();
+    assert_eq!(cvars.get("r_fxaa"), Some(CvarValue::Bool(true)));
+    assert_eq!(cvars.get("r_shadow_sun_size"), Some(CvarValue::Int(2048)));
+    assert_eq!(cvars.get("name"), Some(CvarValue::Str("big boss".to_owned())));
+    assert!(console.execute("get r_fxaa").unwrap().starts_with("r_fxaa = 1 (bool)"));
+    assert_eq!(console.execute("list r_").unwrap().lines().count(), 2);
+    assert!(console.execute("r_fxaa maybe").is_err());
+    assert!(console.execute("name \"unterminated").is_err());
+    assert!(console.execute("set a b c").is_err());
+
+    let dir = std::env::temp_dir().join(format!("dsge_console_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("autoexec.cfg");
+    let nested = dir.join("nested.cfg");
+    std::fs::write(&nested, "r_shadow_sun_size 512\n").unwrap();
+    std::fs::write(
+        &script,
+        format!("// defaults\nr_fxaa 0\nexec \"{}\"\nget r_fxaa\n", nested.display()),
+    )
+    .unwrap();
+    let output = console.exec_file(&script).unwrap();
+    assert!(output.starts_with("r_fxaa = 0"));
+    assert_eq!(cvars.get("r_shadow_sun_size"), Some(CvarValue::Int(512)));
+
+    std::fs::write(&nested, format!("exec \"{}\"\n", nested.display())).unwrap();
+    assert!(console.exec_file(&nested).is_err());
+    std::fs::write(&script, "r_fxaa 0\nr_fxaa yes please\n").unwrap();
+    let err = console.exec_file(&script).unwrap_err().to_string();
+    assert!(err.contains("autoexec.cfg:2"), "{err}");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
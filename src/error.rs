@@ -0,0 +1,176 @@
+//! Общий тип ошибок движка.
+//!
+//! [`DsgeError`] позволяет отличать виды ошибок друг от друга и сохраняет исходную
+//! ошибку (ввода-вывода, Vulkan) в цепочке [`Error::source`].
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use vulkano::{Validated, ValidationError, VulkanError};
+
+#[derive(Debug)]
+pub enum DsgeError {
+    /// Ошибка ввода-вывода
+    Io {
+        path: Option<PathBuf>,
+        source: std::io::Error,
+    },
+    /// Файл или ресурс с указанным именем не найден
+    AssetNotFound(String),
+    /// Формат файла, пикселей или данных не поддерживается
+    FormatUnsupported(String),
+    /// Ошибка компиляции шейдера, `log` содержит вывод компилятора
+    ShaderCompile { name: String, log: String },
+    /// Несоответствие имён, типов или размеров между шейдером и данными,
+    /// либо между стадиями постобработки
+    LayoutMismatch(String),
+    /// Недопустимое значение параметра или аргумента
+    InvalidArgument(String),
+    /// Повреждённые или обрезанные данные файла
+    InvalidData(String),
+    /// Ошибка Vulkan
+    Vulkan {
+        context: String,
+        source: Box<dyn Error + Send + Sync + 'static>,
+    },
+    /// Прочие ошибки
+    Message(String),
+}
+
+impl DsgeError {
+    pub fn io<P: Into<PathBuf>>(path: P, source: std::io::Error) -> Self {
+        Self::Io {
+            path: Some(path.into()),
+            source,
+        }
+    }
+
+    /// Ошибка открытия файла ресурса.
+    /// Отсутствующий файл превращается в [`DsgeError::AssetNotFound`].
+    pub fn open<P: AsRef<Path>>(path: P, source: std::io::Error) -> Self {
+        match source.kind() {
+            std::io::ErrorKind::NotFound => Self::AssetNotFound(path.as_ref().display().to_string()),
+            _ => Self::io(path.as_ref(), source),
+        }
+    }
+
+    pub fn vulkan<C, E>(context: C, source: E) -> Self
+    where
+        C: ToString,
+        E: Error + Send + Sync + 'static,
+    {
+        Self::Vulkan {
+            context: context.to_string(),
+            source: Box::new(source),
+        }
+    }
+
+    /// Добавляет контекст к сообщению об ошибке, сохраняя её вид
+    pub fn context<C: fmt::Display>(self, context: C) -> Self {
+        match self {
+            Self::AssetNotFound(msg) => Self::AssetNotFound(format!("{context}: {msg}")),
+            Self::FormatUnsupported(msg) => Self::FormatUnsupported(format!("{context}: {msg}")),
+            Self::LayoutMismatch(msg) => Self::LayoutMismatch(format!("{context}: {msg}")),
+            Self::InvalidArgument(msg) => Self::InvalidArgument(format!("{context}: {msg}")),
+            Self::InvalidData(msg) => Self::InvalidData(format!("{context}: {msg}")),
+            Self::Message(msg) => Self::Message(format!("{context}: {msg}")),
+            Self::Vulkan { context: inner, source } => Self::Vulkan {
+                context: format!("{context}: {inner}"),
+                source,
+            },
+            err => err,
+        }
+    }
+}
+
+impl fmt::Display for DsgeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path: Some(path), source } => write!(f, "I/O error for {path:?}: {source}"),
+            Self::Io { path: None, source } => write!(f, "I/O error: {source}"),
+            Self::AssetNotFound(name) => write!(f, "asset not found: {name}"),
+            Self::FormatUnsupported(msg) => write!(f, "unsupported format: {msg}"),
+            Self::ShaderCompile { name, log } => write!(f, "failed to compile shader {name}:\n{log}"),
+            Self::LayoutMismatch(msg) => write!(f, "layout mismatch: {msg}"),
+            Self::InvalidArgument(msg) => write!(f, "invalid argument: {msg}"),
+            Self::InvalidData(msg) => write!(f, "invalid data: {msg}"),
+            Self::Vulkan { context, source } => write!(f, "{context}: {source}"),
+            Self::Message(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl Error for DsgeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Vulkan { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for DsgeError {
+    fn from(source: std::io::Error) -> Self {
+        Self::Io { path: None, source }
+    }
+}
+
+impl From<VulkanError> for DsgeError {
+    fn from(source: VulkanError) -> Self {
+        Self::vulkan("Vulkan error", source)
+    }
+}
+
+impl From<Box<ValidationError>> for DsgeError {
+    fn from(source: Box<ValidationError>) -> Self {
+        Self::vulkan("Vulkan validation error", *source)
+    }
+}
+
+impl<E: Error + Send + Sync + 'static> From<Validated<E>> for DsgeError {
+    fn from(source: Validated<E>) -> Self {
+        match source {
+            Validated::Error(err) => Self::vulkan("Vulkan error", err),
+            Validated::ValidationError(err) => err.into(),
+        }
+    }
+}
+
+impl From<String> for DsgeError {
+    fn from(msg: String) -> Self {
+        Self::Message(msg)
+    }
+}
+
+impl From<&str> for DsgeError {
+    fn from(msg: &str) -> Self {
+        Self::Message(msg.to_owned())
+    }
+}
+
+/// Для кода, который по-прежнему возвращает `Result<_, String>`
+impl From<DsgeError> for String {
+    fn from(err: DsgeError) -> Self {
+        err.to_string()
+    }
+}
+
+#[test]
+fn error_source_chaining() {
+    let io = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+    let err = DsgeError::io("data/textures/brick.png", io);
+    assert!(matches!(err, DsgeError::Io { .. }));
+    assert_eq!(err.source().unwrap().to_string(), "no such file");
+    let io = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+    let err = DsgeError::open("data/textures/brick.png", io);
+    assert!(matches!(err, DsgeError::AssetNotFound(ref path) if path == "data/textures/brick.png"));
+
+    let err = DsgeError::from(VulkanError::OutOfDeviceMemory).context("Texture::new");
+    assert!(err.to_string().starts_with("Texture::new: "));
+    assert!(err.source().unwrap().downcast_ref::<VulkanError>().is_some());
+
+    let err = DsgeError::LayoutMismatch("uniform `camera` not found".to_owned()).context("shader");
+    assert!(matches!(err, DsgeError::LayoutMismatch(ref msg) if msg.starts_with("shader: ")));
+    assert!(err.source().is_none());
+}
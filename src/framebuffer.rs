@@ -1,3 +1,4 @@
+use crate::error::DsgeError;
 use std::sync::Arc;
 use vulkano::render_pass::{
     Framebuffer as VkFramebuffer, FramebufferCreateInfo, RenderPass as VkRenderPass,
@@ -65,7 +66,7 @@ impl Framebuffer {
         &mut self,
         att: &Texture,
         default_val: Option<FramebufferAttachmentDefaultValue>,
-    ) -> Result<(), String> {
+    ) -> Result<(), DsgeError> {
         if self._color_attachments.len() < 15 {
             self._color_attachments.push(Attachment {
                 storage: att.clone(),
@@ -74,7 +75,7 @@ impl Framebuffer {
             self._vk_fb = None;
             Ok(())
         } else {
-            Err("Слишком много целей для буфера кадра. Максимум 16.".to_owned())
+            Err(DsgeError::InvalidArgument("Слишком много целей для буфера кадра. Максимум 16.".to_owned()))
         }
     }
 
@@ -108,7 +109,7 @@ impl Framebuffer {
     }
 
     /// Инициализировать буфер кадра для render pass'а
-    pub fn make_vk_fb(&mut self, render_pass: Arc<VkRenderPass>) -> Result<(), String> {
+    pub fn make_vk_fb(&mut self, render_pass: Arc<VkRenderPass>) -> Result<(), DsgeError> {
         let mut attachments = Vec::new();
         for attachment in &self._color_attachments {
             let image_view = attachment.storage.image_view().clone();
@@ -137,7 +138,7 @@ impl Framebuffer {
                 self._vk_fb = Some(fb);
                 Ok(())
             }
-            Err(e) => Err(e.to_string()),
+            Err(e) => Err(DsgeError::vulkan("Failed to create framebuffer", e)),
         }
     }
 }
@@ -154,7 +155,7 @@ pub trait FramebufferBinder {
         render_pass: Arc<RenderPass>,
         secondary: bool,
         flip_y: bool,
-    ) -> Result<&mut Self, String>;
+    ) -> Result<&mut Self, DsgeError>;
 }
 
 impl FramebufferBinder for AutoCommandBufferBuilder<PrimaryAutoCommandBuffer> {
@@ -165,7 +166,7 @@ impl FramebufferBinder for AutoCommandBufferBuilder<PrimaryAutoCommandBuffer> {
         render_pass: Arc<RenderPass>,
         secondary: bool,
         flip_y: bool,
-    ) -> Result<&mut Self, String> {
+    ) -> Result<&mut Self, DsgeError> {
         let mut clear_values = Vec::new();
         if fb._vk_fb.is_none() {
             //println!("Создание буфера кадра");
@@ -200,8 +201,8 @@ impl FramebufferBinder for AutoCommandBufferBuilder<PrimaryAutoCommandBuffer> {
         //fb._viewport.
         self
             .begin_render_pass(rpbi, spbi)
-            .map_err(|e| e.to_string())?
+            .map_err(|e| DsgeError::vulkan("Failed to begin render pass", e))?
             .set_viewport(0, [fb._viewport.clone()].into_iter().collect())
-            .map_err(|e| e.to_string())
+            .map_err(|e| DsgeError::vulkan("Failed to set viewport", *e))
     }
 }
@@ -0,0 +1,503 @@
+//! Кинематический контроллер персонажа.
+//!
+//! Персонаж — вертикальная капсула, которая не участвует в симуляции
+//! твёрдых тел. Перемещение за кадр сдвигает капсулу до первого касания
+//! и скользит вдоль препятствий (collide-and-slide), поднимаясь на ступени
+//! и не заходя на слишком крутые склоны.
+use super::events::*;
+use super::mouse_look::MouseLook;
+use super::movement_keys::MovementKeys;
+use crate::components::collider::{Collider, ColliderShape};
+use crate::game_object::{GameObjectRef, ALL_LAYERS};
+use crate::physics::{self, ShapeHit};
+use crate::references::*;
+use crate::types::{Mat4, Vec3};
+use winit::event::VirtualKeyCode;
+
+/// Зазор между капсулой и препятствиями
+const SKIN: f32 = 0.01;
+/// Наибольшее число скольжений за одно перемещение
+const MAX_SLIDES: usize = 4;
+const MAX_DEPENETRATIONS: usize = 4;
+
+/// Препятствие для капсулы на текущем кадре
+struct Obstacle {
+    collider: Collider,
+    global: Mat4,
+}
+
+/// Ходьба с гравитацией и прыжками. Положение объекта — точка глаз,
+/// капсула стоит под ней. Поворот объекта задаёт направление ходьбы
+/// и не меняется, поэтому контроллер дополняет [`MouseLook`] без полёта.
+/// Клавиша V переключает ходьбу и полёт.
+pub struct CharacterController {
+    keys: MovementKeys,
+    enabled: bool,
+
+    radius: f32,
+    height: f32,
+    eye_height: f32,
+    step_height: f32,
+    /// Наибольший уклон, по которому можно идти, в радианах
+    max_slope: f32,
+
+    gravity: f32,
+    jump_speed: f32,
+    walk_speed: f32,
+    run_factor: f32,
+    /// Слои объектов, с которыми сталкивается персонаж
+    layers: u32,
+
+    vertical_velocity: f32,
+    grounded: bool,
+    ground_normal: Vec3,
+}
+
+impl Default for CharacterController {
+    fn default() -> Self {
+        Self {
+            keys: MovementKeys::default(),
+            enabled: true,
+            radius: 0.3,
+            height: 1.8,
+            eye_height: 1.6,
+            step_height: 0.3,
+            max_slope: 45.0f32.to_radians(),
+            gravity: 9.81,
+            jump_speed: 5.0,
+            walk_speed: 4.0,
+            run_factor: 2.0,
+            layers: ALL_LAYERS,
+            vertical_velocity: 0.0,
+            grounded: false,
+            ground_normal: Vec3::z(),
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl CharacterController {
+    pub fn new(radius: f32, height: f32, eye_height: f32) -> Self {
+        Self {
+            radius,
+            height,
+            eye_height,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Выключенный контроллер не двигает объект, при переключении падение начинается заново
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.vertical_velocity = 0.0;
+        self.grounded = false;
+    }
+
+    pub fn keys(&self) -> &MovementKeys {
+        &self.keys
+    }
+
+    pub fn keys_mut(&mut self) -> &mut MovementKeys {
+        &mut self.keys
+    }
+
+    pub fn step_height(&self) -> f32 {
+        self.step_height
+    }
+
+    pub fn set_step_height(&mut self, step_height: f32) {
+        self.step_height = step_height;
+    }
+
+    pub fn max_slope(&self) -> f32 {
+        self.max_slope
+    }
+
+    pub fn set_max_slope(&mut self, max_slope: f32) {
+        self.max_slope = max_slope;
+    }
+
+    pub fn gravity(&self) -> f32 {
+        self.gravity
+    }
+
+    pub fn set_gravity(&mut self, gravity: f32) {
+        self.gravity = gravity;
+    }
+
+    pub fn jump_speed(&self) -> f32 {
+        self.jump_speed
+    }
+
+    pub fn set_jump_speed(&mut self, jump_speed: f32) {
+        self.jump_speed = jump_speed;
+    }
+
+    pub fn walk_speed(&self) -> f32 {
+        self.walk_speed
+    }
+
+    /// Скорость ходьбы и её множитель при беге
+    pub fn set_walk_speed(&mut self, walk_speed: f32, run_factor: f32) {
+        self.walk_speed = walk_speed;
+        self.run_factor = run_factor;
+    }
+
+    pub fn layers(&self) -> u32 {
+        self.layers
+    }
+
+    pub fn set_layers(&mut self, layers: u32) {
+        self.layers = layers;
+    }
+
+    pub fn is_grounded(&self) -> bool {
+        self.grounded
+    }
+
+    pub fn ground_normal(&self) -> Vec3 {
+        self.ground_normal
+    }
+
+    pub fn vertical_velocity(&self) -> f32 {
+        self.vertical_velocity
+    }
+
+    fn capsule(&self) -> Collider {
+        let radius = self.radius.min(self.height * 0.5);
+        Collider::new(ColliderShape::Capsule {
+            radius,
+            half_height: self.height * 0.5 - radius,
+        })
+    }
+
+    /// Перемещает объект на один кадр длительностью `dt` секунд.
+    /// Объект должен находиться в сцене, препятствия берутся из её коллайдеров.
+    pub fn update(&mut self, owner: &GameObjectRef, dt: f32) {
+        if !self.enabled || dt <= 0.0 {
+            return;
+        }
+        let (local, scene) = {
+            let obj = owner.lock();
+            (obj.transform().local, obj.scene.clone())
+        };
+        let Some(scene) = scene else {
+            return;
+        };
+        let forward = Vec3::new(-local[(0, 2)], -local[(1, 2)], 0.0).try_normalize(f32::EPSILON);
+        let right = Vec3::new(local[(0, 0)], local[(1, 0)], 0.0).try_normalize(f32::EPSILON);
+        let input = self.keys.direction();
+        let mut horizontal = forward.unwrap_or_default() * input.y + right.unwrap_or_default() * input.x;
+        if let Some(direction) = horizontal.try_normalize(f32::EPSILON) {
+            let speed = self.walk_speed * if self.keys.run { self.run_factor } else { 1.0 };
+            horizontal = direction * speed * dt;
+        }
+
+        if self.grounded {
+            self.vertical_velocity = 0.0;
+            if self.keys.jump {
+                self.vertical_velocity = self.jump_speed;
+                self.grounded = false;
+            }
+        } else {
+            self.vertical_velocity -= self.gravity * dt;
+        }
+        let vertical = self.vertical_velocity * dt;
+
+        let capsule = self.capsule();
+        let offset = Vec3::z() * (self.eye_height - self.height * 0.5);
+        let mut center = local.column(3).xyz() - offset;
+
+        // Препятствия собираются один раз на всё перемещение
+        let reach = horizontal.norm() + vertical.abs() + self.step_height * 2.0 + SKIN * 2.0;
+        let bbox = capsule.world_bbox(&Mat4::new_translation(&center)).expanded(reach);
+        let objects = scene.lock().colliders_in_bbox(&bbox, self.layers);
+        let owner_id = owner.box_id();
+        let obstacles = objects
+            .iter()
+            .filter(|object| object.box_id() != owner_id)
+            .filter_map(|object| {
+                let obj = object.lock();
+                let collider = obj.collider().filter(|collider| !collider.is_trigger())?;
+                Some(Obstacle {
+                    collider: collider.clone(),
+                    global: obj.transform().global,
+                })
+            })
+            .collect::<Vec<_>>();
+        let mover = Mover {
+            capsule: &capsule,
+            obstacles: &obstacles,
+            min_ground_z: self.max_slope.cos() - 1.0e-4,
+        };
+
+        center = mover.depenetrate(center);
+
+        // Ходьба с подъёмом на ступени
+        let was_grounded = self.grounded;
+        let (walked, blocked) = mover.slide(center, horizontal, true);
+        center = walked;
+        if blocked && was_grounded && self.step_height > 0.0 {
+            let start = center;
+            let (raised, _) = mover.slide(start, Vec3::z() * self.step_height, false);
+            let (moved, _) = mover.slide(raised, horizontal, true);
+            let climbed = raised.z - start.z;
+            let landing = mover.cast(moved, Vec3::z() * -(climbed + SKIN));
+            let direction = horizontal.normalize();
+            let progress = |point: Vec3| (point - start).xy().dot(&direction.xy());
+            if let Some(hit) = landing.filter(|hit| mover.ground_normal(hit).is_some()) {
+                let stepped = moved - Vec3::z() * ((climbed + SKIN) * hit.fraction - SKIN).max(0.0);
+                if progress(stepped) > progress(walked) + SKIN * 0.5 {
+                    center = stepped;
+                }
+            }
+        }
+
+        // Прыжок и падение
+        if vertical != 0.0 {
+            let (moved, _) = mover.slide(center, Vec3::z() * vertical, false);
+            if vertical > 0.0 && moved.z - center.z < vertical - SKIN {
+                self.vertical_velocity = 0.0;
+            }
+            center = moved;
+        }
+
+        // Опора под ногами, при ходьбе капсула прижимается к спуску
+        self.grounded = false;
+        if self.vertical_velocity <= 0.0 {
+            let distance = if was_grounded { self.step_height } else { SKIN * 2.0 } + SKIN;
+            let ground = mover.cast(center, Vec3::z() * -distance);
+            if let Some((hit, normal)) = ground.and_then(|hit| Some((hit, mover.ground_normal(&hit)?))) {
+                center.z -= (distance * hit.fraction - SKIN).max(0.0);
+                self.grounded = true;
+                self.ground_normal = normal;
+                self.vertical_velocity = 0.0;
+            }
+        }
+
+        if let Some(transform) = owner.lock().transform_mut() {
+            transform.local.set_column(3, &(center + offset).push(1.0));
+        }
+    }
+
+    pub fn motion(&mut self, owner: &GameObjectRef, event: AbstractEvent) {
+        if let AbstractEvent::FrameTick(tick) = event {
+            self.update(owner, tick.delta());
+        }
+    }
+
+    pub fn input(&mut self, owner: &GameObjectRef, event: AbstractEvent) {
+        let AbstractEvent::Keyboard(event) = event else {
+            return;
+        };
+        if self.keys.handle(&event) || event.key_id != VirtualKeyCode::V || event.state != 1 {
+            return;
+        }
+        self.set_enabled(!self.enabled);
+        let obj = owner.lock();
+        if let Some(component) = obj.get_component::<MouseLook>() {
+            if let Some(mouse_look) = component.lock().unwrap().as_mut_any().downcast_mut::<MouseLook>() {
+                mouse_look.set_flying(!self.enabled);
+            }
+        }
+    }
+}
+
+/// Перемещения капсулы среди препятствий кадра
+struct Mover<'a> {
+    capsule: &'a Collider,
+    obstacles: &'a [Obstacle],
+    /// Косинус наибольшего уклона опоры
+    min_ground_z: f32,
+}
+
+impl Mover<'_> {
+    fn is_walkable(&self, normal: &Vec3) -> bool {
+        normal.z >= self.min_ground_z
+    }
+
+    /// Нормаль опоры под касанием или `None`, если на ней не устоять.
+    /// На ребре нормаль касания скруглена капсулой, поэтому уклон
+    /// проверяется маленьким шаром за ребром.
+    fn ground_normal(&self, hit: &ShapeHit) -> Option<Vec3> {
+        if self.is_walkable(&hit.normal) {
+            return Some(hit.normal);
+        }
+        let side = Vec3::new(hit.normal.x, hit.normal.y, 0.0).try_normalize(f32::EPSILON)?;
+        if hit.normal.z <= 0.0 {
+            return None;
+        }
+        let probe = Collider::new(ColliderShape::Sphere { radius: SKIN });
+        let start = Mat4::new_translation(&(hit.point - side * SKIN * 2.0 + Vec3::z() * SKIN * 10.0));
+        let motion = Vec3::z() * (-SKIN * 20.0);
+        self.obstacles
+            .iter()
+            .filter_map(|obstacle| physics::cast_collider(&probe, &start, &motion, &obstacle.collider, &obstacle.global))
+            .min_by(|a, b| a.fraction.total_cmp(&b.fraction))
+            .map(|surface| surface.normal)
+            .filter(|normal| self.is_walkable(normal))
+    }
+
+    /// Ближайшее касание при сдвиге капсулы из `center` на `motion`
+    fn cast(&self, center: Vec3, motion: Vec3) -> Option<ShapeHit> {
+        let global = Mat4::new_translation(&center);
+        self.obstacles
+            .iter()
+            .filter_map(|obstacle| {
+                physics::cast_collider(self.capsule, &global, &motion, &obstacle.collider, &obstacle.global)
+            })
+            .min_by(|a, b| a.fraction.total_cmp(&b.fraction))
+    }
+
+    /// Сдвиг со скольжением вдоль препятствий. При `walking` крутые склоны
+    /// считаются стенами и не поднимают капсулу. Возвращает новое положение
+    /// и признак того, что сдвиг упёрся в препятствие.
+    fn slide(&self, mut center: Vec3, motion: Vec3, walking: bool) -> (Vec3, bool) {
+        let mut remaining = motion;
+        let mut blocked = false;
+        for _ in 0..MAX_SLIDES {
+            let length = remaining.norm();
+            if length < 1.0e-5 {
+                break;
+            }
+            let Some(hit) = self.cast(center, remaining) else {
+                center += remaining;
+                break;
+            };
+            center += remaining / length * (hit.fraction * length - SKIN).max(0.0);
+            let mut normal = hit.normal;
+            if walking && !self.is_walkable(&normal) {
+                normal = Vec3::new(normal.x, normal.y, 0.0).try_normalize(f32::EPSILON).unwrap_or(normal);
+                blocked = true;
+            }
+            remaining *= 1.0 - hit.fraction;
+            remaining -= normal * remaining.dot(&normal).min(0.0);
+        }
+        (center, blocked)
+    }
+
+    /// Выталкивает капсулу из препятствий
+    fn depenetrate(&self, mut center: Vec3) -> Vec3 {
+        for _ in 0..MAX_DEPENETRATIONS {
+            let global = Mat4::new_translation(&center);
+            let deepest = self
+                .obstacles
+                .iter()
+                .flat_map(|obstacle| physics::collide(self.capsule, &global, &obstacle.collider, &obstacle.global))
+                .max_by(|a, b| a.depth.total_cmp(&b.depth));
+            match deepest {
+                Some(contact) if contact.depth > 1.0e-4 => center -= contact.normal * (contact.depth + SKIN),
+                _ => break,
+            }
+        }
+        center
+    }
+}
+
+crate::impl_behaviour!(CharacterController {
+    motion: FrameTick,
+    input: Keyboard
+});
+
+#[cfg(test)]
+fn level_box(scene: &crate::scene::SceneRef, half_extents: Vec3, global: Mat4) {
+    use crate::game_object::GameObject;
+    use crate::mesh::bvh::MeshBvh;
+    use crate::mesh::VkVertex;
+    let vertices = (0..8)
+        .map(|i| VkVertex {
+            v_pos: [0, 1, 2].map(|axis| if i >> axis & 1 == 1 { half_extents[axis] } else { -half_extents[axis] }),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+    let indices = [
+        0, 2, 1, 1, 2, 3, 4, 5, 6, 5, 7, 6, 0, 1, 4, 1, 5, 4, 2, 6, 3, 3, 6, 7, 0, 4, 2, 2, 4, 6, 1, 3, 5, 3, 7, 5,
+    ];
+    let bvh = MeshBvh::new(&vertices, &indices, 0);
+    let object = GameObject::new("level");
+    let mut obj = object.lock();
+    obj.add_component(Collider::new(ColliderShape::TriangleMesh(std::sync::Arc::new(bvh))));
+    obj.transform.local = global;
+    obj.set_static(true);
+    drop(obj);
+    scene.lock().add_object(object).unwrap();
+}
+
+#[test]
+fn character_controller_walks_and_jumps() {
+    use crate::game_object::GameObject;
+    use crate::scene::Scene;
+    use std::f32::consts::FRAC_PI_2;
+    let scene = Scene::new();
+    let translation = |x: f32, y: f32, z: f32| Mat4::new_translation(&Vec3::new(x, y, z));
+    // Пол, ступень высотой 0.25 и стена впереди по +Y
+    level_box(&scene, Vec3::new(20.0, 20.0, 0.5), translation(0.0, 0.0, -0.5));
+    level_box(&scene, Vec3::new(1.0, 1.0, 0.125), translation(0.0, 3.0, 0.125));
+    level_box(&scene, Vec3::new(2.0, 0.5, 2.0), translation(0.0, 7.0, 2.0));
+    // Крутой склон в 60° и пологий в 30° на соседних дорожках
+    let ramp = |x: f32, angle: f32| translation(x, 4.0, 0.0) * Mat4::from_euler_angles(angle, 0.0, 0.0);
+    level_box(&scene, Vec3::new(1.5, 3.0, 0.1), ramp(5.0, 60.0f32.to_radians()));
+    level_box(&scene, Vec3::new(1.5, 3.0, 0.1), ramp(10.0, 30.0f32.to_radians()));
+
+    let player = |x: f32| {
+        let object = GameObject::new("player");
+        let mut obj = object.lock();
+        obj.transform.local = translation(x, 0.0, 4.0) * Mat4::from_euler_angles(FRAC_PI_2, 0.0, 0.0);
+        obj.set_static(false);
+        drop(obj);
+        scene.lock().add_object(object.clone()).unwrap();
+        object
+    };
+    let players = [player(0.0), player(5.0), player(10.0)];
+    scene.lock().step();
+    let location = |object: &GameObjectRef| object.lock().transform.local.column(3).xyz();
+    let mut controllers = players.each_ref().map(|_| CharacterController::default());
+    // Прогоняет кадры и возвращает наибольшую высоту первого игрока
+    let run = |controllers: &mut [CharacterController; 3], frames: usize| {
+        let mut highest = f32::MIN;
+        for _ in 0..frames {
+            for (controller, object) in controllers.iter_mut().zip(&players) {
+                controller.update(object, 1.0 / 60.0);
+            }
+            highest = highest.max(location(&players[0]).z);
+        }
+        highest
+    };
+
+    // Падение с высоты до пола
+    run(&mut controllers, 90);
+    let base = 1.6 + SKIN;
+    for (controller, object) in controllers.iter().zip(&players) {
+        assert!(controller.is_grounded());
+        assert!((location(object).z - base).abs() < 2.0 * SKIN, "{}", location(object));
+    }
+
+    // Прыжок и приземление
+    controllers[0].keys_mut().jump = true;
+    run(&mut controllers, 1);
+    controllers[0].keys_mut().jump = false;
+    let highest = run(&mut controllers, 90);
+    assert!(highest - base > 1.1 && highest - base < 1.4, "{highest}");
+    assert!(controllers[0].is_grounded());
+    assert!((location(&players[0]).z - base).abs() < 2.0 * SKIN);
+
+    // Ходьба: подъём на ступень, спуск и остановка у стены.
+    // На крутой склон персонаж не заходит, на пологий поднимается.
+    for controller in &mut controllers {
+        controller.keys_mut().forward = 1.0;
+    }
+    let on_step = run(&mut controllers, 90);
+    assert!((on_step - base - 0.25).abs() < 2.0 * SKIN, "{on_step}");
+    let [walker, steep, gentle] = players.each_ref().map(location);
+    assert!(gentle.z > base + 0.5, "{gentle}");
+    assert!(steep.z < base + 0.05 && steep.y < 4.0, "{steep}");
+    run(&mut controllers, 90);
+    let walker_end = location(&players[0]);
+    assert!(walker_end.y > walker.y && (walker_end.y - (6.5 - 0.3)).abs() < 2.0 * SKIN, "{walker_end}");
+    assert!((walker_end.z - base).abs() < 2.0 * SKIN);
+    assert!(location(&players[1]).z < base + 0.05);
+}
@@ -10,6 +10,7 @@ use winit::event::VirtualKeyCode;
 pub use winit::event::{DeviceEvent, Event, KeyboardInput, WindowEvent};
 
 use super::behaviour::DynBehaviour;
+use super::scheduler::{Coroutine, Scheduler, TaskId};
 
 pub(crate) type EventHandlerBoxed =
     RcBox<dyn FnMut(&GameObjectRef, &mut dyn Behaviour, AbstractEvent) + Sync + Send>;
@@ -45,6 +46,45 @@ pub struct KeyboardEvent {
     pub state: i32,
 }
 
+/// Пересечение объёма-триггера с другим коллайдером.
+/// Объекты заданы [`MutexLockBox::box_id`], событие получают только их поведения.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TriggerEvent {
+    pub trigger: i32,
+    pub other: i32,
+}
+
+impl TriggerEvent {
+    /// Второй участник события с точки зрения `owner`
+    pub fn counterpart(&self, owner: &GameObjectRef) -> i32 {
+        if owner.box_id() == self.trigger {
+            self.other
+        } else {
+            self.trigger
+        }
+    }
+}
+
+/// Метка клипа, до которой дошёл [`crate::components::AnimationPlayer`] объекта `object`.
+/// Событие получают только поведения этого объекта.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AnimationEvent {
+    pub object: i32,
+    /// Хэш имени метки, см. [`AnimationEvent::name_id`]
+    pub name: u32,
+}
+
+impl AnimationEvent {
+    /// Хэш FNV-1a имени метки
+    pub fn name_id(name: &str) -> u32 {
+        name.bytes().fold(0x811c9dc5, |hash, byte| (hash ^ byte as u32).wrapping_mul(0x01000193))
+    }
+
+    pub fn is(&self, name: &str) -> bool {
+        self.name == Self::name_id(name)
+    }
+}
+
 macro_rules! events_enums {
     {$($variant: ident ($variant_type: tt)),*} => {
         #[derive(Clone, Copy)]
@@ -68,6 +108,18 @@ macro_rules! events_enums {
             {
                 self.event_type() as _
             }
+
+            /// Объекты, которым адресовано событие; `None` — всем
+            pub fn recipients(&self) -> Option<[i32; 2]>
+            {
+                match self {
+                    Self::TriggerEnter(event) | Self::TriggerStay(event) | Self::TriggerExit(event) => {
+                        Some([event.trigger, event.other])
+                    }
+                    Self::Animation(event) => Some([event.object, event.object]),
+                    _ => None,
+                }
+            }
         }
 
         #[derive(Clone, Copy)]
@@ -84,15 +136,20 @@ events_enums! {
     MouseClick(MouseClickEvent),
     Keyboard(KeyboardEvent),
     FrameTick(FrameTick),
-    InitialTick(FrameTick)
+    InitialTick(FrameTick),
+    TriggerEnter(TriggerEvent),
+    TriggerStay(TriggerEvent),
+    TriggerExit(TriggerEvent),
+    Animation(AnimationEvent)
 }
 
 #[derive(Default, Clone)]
 pub struct EventProcessor {
     event_handlers: RcBox<EventsByEventTypeId>,
-    event_stack: RcBox<Vec<AbstractEvent>>,
+    pub(crate) event_stack: RcBox<Vec<AbstractEvent>>,
     pub(crate) timer: Timer,
     pub(crate) time: RcBox<UniformTime>,
+    scheduler: RcBox<Scheduler>,
 }
 
 impl EventProcessor {
@@ -135,6 +192,35 @@ impl EventProcessor {
         for (_eh_id, obj_list) in &mut *self.event_handlers.lock() {
             obj_list.remove(&obj.box_id());
         }
+        self.scheduler.lock().cancel_object(obj.box_id());
+    }
+
+    /// Однократный вызов `callback` через `delay` секунд, пока объект на сцене
+    pub fn set_timeout(&self, owner: &GameObjectRef, delay: f32, callback: impl FnMut(&GameObjectRef) + Send + Sync + 'static) -> TaskId {
+        self.scheduler.lock().set_timeout(owner, delay, callback)
+    }
+
+    /// Вызов `callback` каждые `period` секунд, пока объект на сцене
+    pub fn set_interval(&self, owner: &GameObjectRef, period: f32, callback: impl FnMut(&GameObjectRef) + Send + Sync + 'static) -> TaskId {
+        self.scheduler.lock().set_interval(owner, period, callback)
+    }
+
+    /// Запуск сопрограммы объекта, см. [`Coroutine`]
+    pub fn start_coroutine<F, Fut>(&self, owner: &GameObjectRef, coroutine: F) -> TaskId
+    where
+        F: FnOnce(Coroutine) -> Fut,
+        Fut: std::future::Future<Output = ()> + Send + 'static,
+    {
+        self.scheduler.lock().start_coroutine(owner, coroutine)
+    }
+
+    /// Отмена таймера или сопрограммы
+    pub fn cancel_task(&self, id: TaskId) -> bool {
+        self.scheduler.lock().cancel(id)
+    }
+
+    pub fn is_task_active(&self, id: TaskId) -> bool {
+        self.scheduler.lock().is_active(id)
     }
 
     pub fn send_event(&self, event: AbstractEvent) {
@@ -148,18 +234,27 @@ impl EventProcessor {
 
     pub fn execute(&self) {
         loop {
-            let mut event_stack = self.event_stack.lock();
-            if event_stack.len() == 0 {
-                break;
-            }
-            let event = event_stack.remove(0);
+            // Стек не блокируется на время вызова обработчиков, чтобы они могли отправлять события
+            let event = {
+                let mut event_stack = self.event_stack.lock();
+                if event_stack.is_empty() {
+                    break;
+                }
+                event_stack.remove(0)
+            };
+            let recipients = event.recipients();
             if let Some(event_handlers) = self.event_handlers.lock().get_mut(&event.variant_id()) {
                 for (_, handlers_by_obj_id) in event_handlers {
                     for (_, event_handler) in handlers_by_obj_id {
-                        event_handler.call(event);
+                        if recipients.is_none_or(|ids| ids.contains(&event_handler.owner.box_id())) {
+                            event_handler.call(event);
+                        }
                     }
                 }
             }
+            if let AbstractEvent::FrameTick(time) = event {
+                Scheduler::run(&self.scheduler, time.delta());
+            }
         }
     }
 }
@@ -1,8 +1,14 @@
 pub mod behaviour;
+pub mod character_controller;
 pub mod debug_bbox;
 pub mod events;
 pub mod motion_example;
 pub mod mouse_look;
+pub mod movement_keys;
+pub mod path_follower;
+pub mod scheduler;
+pub mod tween;
 
 pub use behaviour::Behaviour;
-pub use events::{AbstractEvent, EventType};
+pub use events::{AbstractEvent, AnimationEvent, EventType, TriggerEvent};
+pub use scheduler::{Coroutine, TaskId};
@@ -1,4 +1,7 @@
 use super::events::*;
+use super::movement_keys::MovementKeys;
+use crate::console::Cvars;
+use crate::error::DsgeError;
 use crate::game_object::GameObjectRef;
 use crate::references::*;
 use crate::types::{Transform3, Vec4};
@@ -18,15 +21,10 @@ pub struct MouseLook {
     ddx: f32,
     ddy: f32,
 
-    fwd_key: f32,
-    bwd_key: f32,
-    left_key: f32,
-    right_key: f32,
-
-    down_key: f32,
-    up_key: f32,
-
-    accel: f32,
+    keys: MovementKeys,
+    /// Свободный полёт. Без него меняется только поворот,
+    /// а перемещением занимается, например, [`super::character_controller::CharacterController`].
+    fly: bool,
 
     sens: f32,
     dt: f32,
@@ -42,18 +40,44 @@ impl MouseLook {
             look_y_inert: 0.0,
             ddx: 0.0,
             ddy: 0.0,
-            fwd_key: 0.0,
-            bwd_key: 0.0,
-            left_key: 0.0,
-            right_key: 0.0,
-            up_key: 0.0,
-            down_key: 0.0,
-            accel: 1.0,
+            keys: MovementKeys::default(),
+            fly: true,
             dt: 0.0,
             sens: sensitivity,
         }
     }
 
+    pub fn sensitivity(&self) -> f32 {
+        self.sens
+    }
+
+    pub fn set_sensitivity(&mut self, sensitivity: f32) {
+        self.sens = sensitivity;
+    }
+
+    pub fn is_flying(&self) -> bool {
+        self.fly
+    }
+
+    pub fn set_flying(&mut self, fly: bool) {
+        self.fly = fly;
+    }
+
+    /// Связывает чувствительность с переменной `m_sensitivity`.
+    /// Переменная не продлевает жизнь компонента.
+    pub fn bind_cvars(this: &RcBox<Self>, cvars: &Cvars) -> Result<(), DsgeError> {
+        cvars.register("m_sensitivity", this.lock().sens, "Mouse look sensitivity")?;
+        if let Some(sens) = cvars.get("m_sensitivity").and_then(|value| value.as_float()) {
+            this.lock().sens = sens;
+        }
+        let weak = std::sync::Arc::downgrade(this);
+        cvars.on_change("m_sensitivity", move |value| {
+            if let (Some(this), Some(sens)) = (weak.upgrade(), value.as_float()) {
+                this.lock().sens = sens;
+            }
+        })
+    }
+
     pub fn look(&mut self, _owner: &GameObjectRef, event: AbstractEvent) {
         let (dx, dy) = match event {
             MouseMove(MouseMoveEvent { dx, dy, .. }) => (dx as f32, dy as f32),
@@ -99,19 +123,21 @@ impl MouseLook {
             self.look_y = 3.1415926535;
         }
 
-        let direction = self.fwd_key * front
-            + self.bwd_key * back
-            + self.left_key * left
-            + self.right_key * right
-            + self.down_key * down
-            + self.up_key * up;
+        let keys = &self.keys;
+        let direction = keys.forward * front
+            + keys.backward * back
+            + keys.left * left
+            + keys.right * right
+            + keys.down * down
+            + keys.up * up;
+        let accel = if keys.run { 20.0 } else { 1.0 };
 
         let direction_magnitude = if direction.magnitude() == 0.0 {
             1.0
         } else {
             direction.magnitude()
         };
-        let delta = self.dt * self.accel * direction / direction_magnitude;
+        let delta = self.dt * accel * direction / direction_magnitude;
 
         if let Some(transform) = obj.transform_mut() {
             transform.local.set_rotation(&Rotation3::from_euler_angles(
@@ -119,7 +145,9 @@ impl MouseLook {
                 0.0,
                 self.look_x,
             ));
-            transform.local.set_column(3, &(position + delta));
+            if self.fly {
+                transform.local.set_column(3, &(position + delta));
+            }
         }
     }
 
@@ -150,70 +178,9 @@ impl MouseLook {
                 } => {
                     self.look_x_inert -= 0.1;
                 }
-                KeyboardEvent {
-                    key_id: VirtualKeyCode::W,
-                    state,
-                } => {
-                    self.fwd_key = match state {
-                        1 => 1.0,
-                        _ => 0.0,
-                    };
-                }
-                KeyboardEvent {
-                    key_id: VirtualKeyCode::A,
-                    state,
-                } => {
-                    self.left_key = match state {
-                        1 => 1.0,
-                        _ => 0.0,
-                    };
-                }
-                KeyboardEvent {
-                    key_id: VirtualKeyCode::S,
-                    state,
-                } => {
-                    self.bwd_key = match state {
-                        1 => 1.0,
-                        _ => 0.0,
-                    };
-                }
-                KeyboardEvent {
-                    key_id: VirtualKeyCode::D,
-                    state,
-                } => {
-                    self.right_key = match state {
-                        1 => 1.0,
-                        _ => 0.0,
-                    };
-                }
-                KeyboardEvent {
-                    key_id: VirtualKeyCode::Q,
-                    state,
-                } => {
-                    self.down_key = match state {
-                        1 => 1.0,
-                        _ => 0.0,
-                    };
-                }
-                KeyboardEvent {
-                    key_id: VirtualKeyCode::E,
-                    state,
-                } => {
-                    self.up_key = match state {
-                        1 => 1.0,
-                        _ => 0.0,
-                    };
-                }
-                KeyboardEvent {
-                    key_id: VirtualKeyCode::LShift | VirtualKeyCode::RShift,
-                    state,
-                } => {
-                    self.accel = match state {
-                        1 => 20.0,
-                        _ => 1.0,
-                    };
+                event => {
+                    self.keys.handle(&event);
                 }
-                _ => {}
             },
             _ => {}
         }
@@ -0,0 +1,44 @@
+use super::events::KeyboardEvent;
+use crate::types::Vec3;
+use winit::event::VirtualKeyCode;
+
+/// Состояние клавиш перемещения: W/A/S/D, Q/E — вниз и вверх,
+/// Shift — ускорение, пробел — прыжок.
+/// Общее для [`super::mouse_look::MouseLook`] и [`super::character_controller::CharacterController`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MovementKeys {
+    pub forward: f32,
+    pub backward: f32,
+    pub left: f32,
+    pub right: f32,
+    pub up: f32,
+    pub down: f32,
+    pub run: bool,
+    pub jump: bool,
+}
+
+impl MovementKeys {
+    /// Обновляет состояние по событию клавиатуры.
+    /// Возвращает `false`, если клавиша не относится к перемещению.
+    pub fn handle(&mut self, event: &KeyboardEvent) -> bool {
+        let pressed = event.state == 1;
+        let value = if pressed { 1.0 } else { 0.0 };
+        match event.key_id {
+            VirtualKeyCode::W => self.forward = value,
+            VirtualKeyCode::S => self.backward = value,
+            VirtualKeyCode::A => self.left = value,
+            VirtualKeyCode::D => self.right = value,
+            VirtualKeyCode::Q => self.down = value,
+            VirtualKeyCode::E => self.up = value,
+            VirtualKeyCode::LShift | VirtualKeyCode::RShift => self.run = pressed,
+            VirtualKeyCode::Space => self.jump = pressed,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Направление в осях камеры: X вправо, Y вперёд, Z вверх
+    pub fn direction(&self) -> Vec3 {
+        Vec3::new(self.right - self.left, self.forward - self.backward, self.up - self.down)
+    }
+}
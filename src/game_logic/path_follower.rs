@@ -0,0 +1,188 @@
+use std::sync::Arc;
+
+use super::events::*;
+use crate::game_object::{GameObject, GameObjectRef};
+use crate::navigation::NavMesh;
+use crate::references::*;
+use crate::types::{Mat4, Vec3};
+
+/// Мировая матрица родителя объекта: путь задан в мировых координатах, а положение — в родительских
+fn parent_matrix(obj: &GameObject) -> Mat4 {
+    obj.parent_object().map_or_else(Mat4::identity, |parent| parent.lock().transform().global)
+}
+
+/// Ведёт объект по пути на навигационной сетке с постоянной скоростью.
+/// Высота объекта берётся с поверхности сетки под ним.
+pub struct PathFollower {
+    navmesh: Arc<NavMesh>,
+    path: Vec<Vec3>,
+    /// Номер следующей точки пути
+    next: usize,
+    /// Цель, путь к которой ищется на следующем кадре
+    destination: Option<Vec3>,
+    speed: f32,
+    /// Высота положения объекта над поверхностью
+    height: f32,
+}
+
+#[allow(dead_code)]
+impl PathFollower {
+    pub fn new(navmesh: Arc<NavMesh>, speed: f32) -> Self {
+        Self {
+            navmesh,
+            path: Vec::new(),
+            next: 0,
+            destination: None,
+            speed,
+            height: 0.0,
+        }
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+
+    pub fn set_height(&mut self, height: f32) {
+        self.height = height;
+    }
+
+    /// Путь к `target` будет найден на следующем кадре от текущего положения объекта
+    pub fn set_destination(&mut self, target: Vec3) {
+        self.destination = Some(target);
+    }
+
+    /// Сразу ищет путь от положения `owner` до `target`.
+    /// При неудаче объект останавливается и возвращается `false`.
+    pub fn follow(&mut self, owner: &GameObjectRef, target: &Vec3) -> bool {
+        self.destination = None;
+        let start = {
+            let obj = owner.lock();
+            parent_matrix(&obj).transform_point(&obj.transform().local.column(3).xyz().into()).coords - Vec3::z() * self.height
+        };
+        self.path = self.navmesh.find_path(&start, target).unwrap_or_default();
+        self.next = 1.min(self.path.len());
+        !self.path.is_empty()
+    }
+
+    /// Найденный путь, включая пройденные точки
+    pub fn path(&self) -> &[Vec3] {
+        &self.path
+    }
+
+    pub fn is_moving(&self) -> bool {
+        self.next < self.path.len() || self.destination.is_some()
+    }
+
+    pub fn stop(&mut self) {
+        self.path.clear();
+        self.next = 0;
+        self.destination = None;
+    }
+
+    /// Продвигает объект по пути на `dt` секунд
+    pub fn advance(&mut self, owner: &GameObjectRef, dt: f32) {
+        if let Some(target) = self.destination.take() {
+            self.follow(owner, &target);
+        }
+        if self.next >= self.path.len() {
+            return;
+        }
+        let mut obj = owner.lock();
+        let parent = parent_matrix(&obj);
+        let Some(to_parent) = parent.try_inverse() else {
+            return;
+        };
+        let Some(transform) = obj.transform_mut() else {
+            return;
+        };
+        let mut position = parent.transform_point(&transform.local.column(3).xyz().into()).coords - Vec3::z() * self.height;
+        let mut distance = self.speed * dt;
+        while distance > 0.0 && self.next < self.path.len() {
+            let target = self.path[self.next];
+            let offset = (target - position).xy();
+            let length = offset.norm();
+            if length <= distance {
+                position = target;
+                distance -= length;
+                self.next += 1;
+            } else {
+                position.x += offset.x / length * distance;
+                position.y += offset.y / length * distance;
+                distance = 0.0;
+            }
+        }
+        if let Some(height) = self.navmesh.height_at(&position) {
+            position.z = height;
+        }
+        let local = to_parent.transform_point(&(position + Vec3::z() * self.height).into());
+        transform.local.set_column(3, &local.coords.push(1.0));
+    }
+
+    fn motion(&mut self, owner: &GameObjectRef, event: AbstractEvent) {
+        if let AbstractEvent::FrameTick(tick) = event {
+            self.advance(owner, tick.delta());
+        }
+    }
+}
+
+crate::impl_behaviour!(PathFollower { motion: FrameTick });
+
+#[test]
+fn path_follower_walks_around_wall() {
+    use crate::game_object::GameObject;
+    use crate::navigation::{test_box, NavMeshSettings};
+    use crate::types::Mat4;
+    let mut level = test_box(Vec3::new(0.0, 0.0, -0.5), Vec3::new(5.0, 5.0, 0.5));
+    level.extend(test_box(Vec3::new(-1.0, 0.0, 1.0), Vec3::new(4.0, 0.2, 1.0)));
+    let navmesh = Arc::new(NavMesh::build(&level, &NavMeshSettings::default()).unwrap());
+
+    let object = GameObject::new("npc");
+    object.lock().set_static(false);
+    object.lock().transform.local = Mat4::new_translation(&Vec3::new(-3.0, -3.0, 1.0));
+    let mut follower = PathFollower::new(navmesh, 3.0);
+    follower.set_height(1.0);
+    follower.set_destination(Vec3::new(-3.0, 3.0, 0.0));
+    assert!(follower.is_moving());
+
+    let mut previous = object.lock().transform.local.column(3).xyz();
+    for _ in 0..600 {
+        follower.advance(&object, 1.0 / 60.0);
+        let location = object.lock().transform.local.column(3).xyz();
+        // Не быстрее заданной скорости и не сквозь стену
+        assert!((location - previous).xy().norm() <= 3.0 / 60.0 + 1.0e-4);
+        assert!(location.y.abs() > 0.2 || location.x > 3.0, "{location}");
+        assert!((location.z - 1.0).abs() < 0.15);
+        previous = location;
+    }
+    assert!(!follower.is_moving());
+    assert!((previous.xy() - nalgebra::Vector2::new(-3.0, 3.0)).norm() < 1.0e-3);
+    assert!(follower.path().len() >= 3);
+
+    // У вложенного объекта путь и высота считаются в мировых координатах
+    let parent = GameObject::new("cart");
+    let parent_matrix = Mat4::new_translation(&Vec3::new(1.0, 2.0, 0.5)) * Mat4::from_euler_angles(0.0, 0.0, 1.0);
+    parent.lock().transform.local = parent_matrix;
+    parent.lock().transform.global = parent_matrix;
+    let child = GameObject::new("rider");
+    child.lock().set_static(false);
+    child.lock().transform._parent = crate::game_object::GOParent::Object(parent.clone());
+    let start = Vec3::new(-3.0, -3.0, 1.0);
+    child.lock().transform.local = Mat4::new_translation(&parent_matrix.try_inverse().unwrap().transform_point(&start.into()).coords);
+    let mut follower = PathFollower::new(follower.navmesh.clone(), 3.0);
+    follower.set_height(1.0);
+    assert!(follower.follow(&child, &Vec3::new(-3.0, -1.0, 0.0)));
+    assert!((follower.path()[0].xy() - start.xy()).norm() < 1.0e-3);
+    for _ in 0..120 {
+        follower.advance(&child, 1.0 / 60.0);
+    }
+    let world = parent_matrix.transform_point(&child.lock().transform.local.column(3).xyz().into());
+    assert!((world.coords - Vec3::new(-3.0, -1.0, 1.0)).norm() < 0.15, "{world}");
+}
@@ -0,0 +1,333 @@
+//! Отложенные действия поведений: таймеры и сопрограммы.
+//!
+//! Задачи привязаны к объекту: они отменяются при удалении объекта со сцены
+//! и не продлевают его время жизни. Задачи выполняются на `FrameTick`
+//! в [`super::events::EventProcessor::execute`] без блокировки планировщика,
+//! поэтому из них можно запускать и отменять другие задачи.
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll, Waker};
+
+use crate::game_object::{GameObject, GameObjectRef};
+use crate::references::*;
+
+type TimerCallback = Box<dyn FnMut(&GameObjectRef) + Send + Sync>;
+type CoroutineFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Номер задачи планировщика
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TaskId(u32);
+
+/// Время планировщика: сумма длительностей кадров и номер кадра
+#[derive(Clone, Copy, Debug, Default)]
+struct Clock {
+    time: f32,
+    frame: u64,
+}
+
+enum TaskKind {
+    Timer {
+        callback: TimerCallback,
+        due: f32,
+        /// Период повторяющегося таймера
+        period: Option<f32>,
+    },
+    Coroutine(CoroutineFuture),
+}
+
+struct Task {
+    id: TaskId,
+    owner: Weak<std::sync::Mutex<GameObject>>,
+    kind: TaskKind,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Task {
+    /// Выполнение задачи в кадре; `false`, если задача завершена
+    fn run(&mut self, clock: Clock) -> bool {
+        let Some(owner) = self.owner.upgrade() else {
+            return false;
+        };
+        match &mut self.kind {
+            TaskKind::Timer { callback, due, period } => {
+                // Пропущенные за долгий кадр срабатывания повторяющегося таймера выполняются подряд
+                while *due <= clock.time && !self.cancelled.load(Ordering::Relaxed) {
+                    callback(&owner);
+                    match period {
+                        Some(period) if *period > 0.0 => *due += *period,
+                        Some(_) => return true,
+                        None => return false,
+                    }
+                }
+                true
+            }
+            TaskKind::Coroutine(future) => {
+                let mut context = Context::from_waker(Waker::noop());
+                future.as_mut().poll(&mut context).is_pending()
+            }
+        }
+    }
+}
+
+/// Планировщик таймеров и сопрограмм. Принадлежит [`super::events::EventProcessor`].
+#[derive(Default)]
+pub struct Scheduler {
+    tasks: Vec<Task>,
+    /// Флаги отмены активных задач и их объекты
+    handles: HashMap<TaskId, (i32, Arc<AtomicBool>)>,
+    clock: RcBox<Clock>,
+    next_id: u32,
+}
+
+impl Scheduler {
+    fn add(&mut self, owner: &GameObjectRef, kind: TaskKind) -> TaskId {
+        let id = TaskId(self.next_id);
+        self.next_id = self.next_id.wrapping_add(1);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.handles.insert(id, (owner.box_id(), cancelled.clone()));
+        self.tasks.push(Task {
+            id,
+            owner: Arc::downgrade(owner),
+            kind,
+            cancelled,
+        });
+        id
+    }
+
+    /// Однократный вызов через `delay` секунд
+    pub fn set_timeout(&mut self, owner: &GameObjectRef, delay: f32, callback: impl FnMut(&GameObjectRef) + Send + Sync + 'static) -> TaskId {
+        let due = self.clock.lock().time + delay;
+        self.add(owner, TaskKind::Timer { callback: Box::new(callback), due, period: None })
+    }
+
+    /// Вызов каждые `period` секунд, первый — через `period`.
+    /// Период не больше нуля — вызов каждый кадр.
+    pub fn set_interval(&mut self, owner: &GameObjectRef, period: f32, callback: impl FnMut(&GameObjectRef) + Send + Sync + 'static) -> TaskId {
+        let due = self.clock.lock().time + period.max(0.0);
+        self.add(owner, TaskKind::Timer { callback: Box::new(callback), due, period: Some(period) })
+    }
+
+    /// Запуск сопрограммы, созданной `coroutine`. Сопрограмма впервые выполняется в следующем кадре
+    /// и продолжается в каждом кадре до завершения.
+    pub fn start_coroutine<F, Fut>(&mut self, owner: &GameObjectRef, coroutine: F) -> TaskId
+    where
+        F: FnOnce(Coroutine) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let context = Coroutine {
+            owner: Arc::downgrade(owner),
+            clock: self.clock.clone(),
+        };
+        self.add(owner, TaskKind::Coroutine(Box::pin(coroutine(context))))
+    }
+
+    /// Отмена задачи. `false`, если задача уже завершена или отменена.
+    pub fn cancel(&mut self, id: TaskId) -> bool {
+        let Some((_, cancelled)) = self.handles.remove(&id) else {
+            return false;
+        };
+        cancelled.store(true, Ordering::Relaxed);
+        self.tasks.retain(|task| task.id != id);
+        true
+    }
+
+    /// Отмена всех задач объекта
+    pub fn cancel_object(&mut self, object: i32) {
+        self.handles.retain(|_, (owner, cancelled)| {
+            if *owner == object {
+                cancelled.store(true, Ordering::Relaxed);
+            }
+            *owner != object
+        });
+        self.tasks.retain(|task| !task.cancelled.load(Ordering::Relaxed));
+    }
+
+    pub fn is_active(&self, id: TaskId) -> bool {
+        self.handles.contains_key(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.handles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+
+    /// Продвижение времени на `delta` секунд и выполнение задач.
+    /// Планировщик не заблокирован, пока выполняются задачи.
+    pub fn run(scheduler: &RcBox<Scheduler>, delta: f32) {
+        let (mut tasks, clock) = {
+            let mut scheduler = scheduler.lock();
+            let clock = {
+                let mut clock = scheduler.clock.lock();
+                clock.time += delta;
+                clock.frame += 1;
+                *clock
+            };
+            (std::mem::take(&mut scheduler.tasks), clock)
+        };
+        let mut finished = Vec::new();
+        tasks.retain_mut(|task| {
+            let active = !task.cancelled.load(Ordering::Relaxed) && task.run(clock) && !task.cancelled.load(Ordering::Relaxed);
+            if !active {
+                finished.push(task.id);
+            }
+            active
+        });
+        let mut scheduler = scheduler.lock();
+        for id in finished {
+            scheduler.handles.remove(&id);
+        }
+        // Задачи, запущенные во время выполнения, идут после прежних
+        tasks.append(&mut scheduler.tasks);
+        tasks.retain(|task| !task.cancelled.load(Ordering::Relaxed));
+        scheduler.tasks = tasks;
+    }
+}
+
+/// Контекст сопрограммы: ожидание времени, кадров и условий
+#[derive(Clone)]
+pub struct Coroutine {
+    owner: Weak<std::sync::Mutex<GameObject>>,
+    clock: RcBox<Clock>,
+}
+
+/// Ожидание, завершающееся, когда условие выполнено при очередном кадре
+pub struct Wait<F: FnMut() -> bool + Unpin>(F);
+
+impl<F: FnMut() -> bool + Unpin> Future for Wait<F> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _context: &mut Context<'_>) -> Poll<()> {
+        if (self.0)() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Coroutine {
+    /// Объект сопрограммы, если он ещё существует
+    pub fn owner(&self) -> Option<GameObjectRef> {
+        self.owner.upgrade()
+    }
+
+    /// Время планировщика в секундах
+    pub fn time(&self) -> f32 {
+        self.clock.lock().time
+    }
+
+    /// Ожидание `seconds` секунд, отсчитываемых с первой проверки
+    pub fn wait_seconds(&self, seconds: f32) -> Wait<impl FnMut() -> bool + Unpin> {
+        let clock = self.clock.clone();
+        let mut deadline = None;
+        Wait(move || {
+            let now = clock.lock().time;
+            now >= *deadline.get_or_insert(now + seconds)
+        })
+    }
+
+    /// Ожидание `frames` кадров
+    pub fn wait_frames(&self, frames: u64) -> Wait<impl FnMut() -> bool + Unpin> {
+        let clock = self.clock.clone();
+        let mut deadline = None;
+        Wait(move || {
+            let now = clock.lock().frame;
+            now >= *deadline.get_or_insert(now + frames)
+        })
+    }
+
+    /// Ожидание выполнения условия; проверяется раз в кадр
+    pub fn wait_until<F: FnMut() -> bool + Unpin>(&self, condition: F) -> Wait<F> {
+        Wait(condition)
+    }
+}
+
+#[test]
+fn scheduler_timers_and_cancellation() {
+    use std::sync::atomic::AtomicUsize;
+    let scheduler = RcBox::construct(Scheduler::default());
+    let object = GameObject::new("lamp");
+    let counter = |count: &Arc<AtomicUsize>| {
+        let count = count.clone();
+        move |_: &GameObjectRef| {
+            count.fetch_add(1, Ordering::SeqCst);
+        }
+    };
+    let (once, repeat, cancelled) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+    let timeout = scheduler.lock().set_timeout(&object, 0.25, counter(&once));
+    scheduler.lock().set_interval(&object, 0.1, counter(&repeat));
+    let removed = scheduler.lock().set_timeout(&object, 0.05, counter(&cancelled));
+    assert!(scheduler.lock().cancel(removed) && !scheduler.lock().cancel(removed));
+
+    // Таймер, запущенный из другого таймера
+    let nested = once.clone();
+    scheduler.lock().set_timeout(&object, 0.0, {
+        let scheduler = scheduler.clone();
+        move |owner| {
+            scheduler.lock().set_timeout(owner, 0.0, counter(&nested));
+        }
+    });
+    Scheduler::run(&scheduler, 0.2);
+    assert_eq!(once.load(Ordering::SeqCst), 0);
+    assert_eq!(repeat.load(Ordering::SeqCst), 2);
+    Scheduler::run(&scheduler, 0.1);
+    assert_eq!(once.load(Ordering::SeqCst), 2);
+    assert_eq!(repeat.load(Ordering::SeqCst), 3);
+    assert!(!scheduler.lock().is_active(timeout));
+    assert_eq!(scheduler.lock().len(), 1);
+
+    // Задачи отменяются вместе с объектом
+    scheduler.lock().cancel_object(object.box_id());
+    Scheduler::run(&scheduler, 1.0);
+    assert_eq!(repeat.load(Ordering::SeqCst), 3);
+    assert!(scheduler.lock().is_empty());
+    assert_eq!(cancelled.load(Ordering::SeqCst), 0);
+
+    let processor = super::events::EventProcessor::default();
+    let id = processor.set_interval(&object, 1.0, counter(&cancelled));
+    assert!(processor.is_task_active(id));
+    processor.clone().remove_object(object.clone());
+    assert!(!processor.is_task_active(id));
+}
+
+#[test]
+fn scheduler_coroutine_waits() {
+    use std::sync::atomic::AtomicU32;
+    let scheduler = RcBox::construct(Scheduler::default());
+    let object = GameObject::new("door");
+    let stage = Arc::new(AtomicU32::new(0));
+    let flag = Arc::new(AtomicBool::new(false));
+    let (progress, open) = (stage.clone(), flag.clone());
+    let id = scheduler.lock().start_coroutine(&object, move |co| async move {
+        progress.store(1, Ordering::SeqCst);
+        co.wait_seconds(1.0).await;
+        progress.store(2, Ordering::SeqCst);
+        co.wait_frames(2).await;
+        progress.store(3, Ordering::SeqCst);
+        co.wait_until(move || open.load(Ordering::SeqCst)).await;
+        assert!(co.owner().is_some());
+        progress.store(4, Ordering::SeqCst);
+    });
+    assert_eq!(stage.load(Ordering::SeqCst), 0);
+    Scheduler::run(&scheduler, 0.5);
+    assert_eq!(stage.load(Ordering::SeqCst), 1);
+    Scheduler::run(&scheduler, 0.5);
+    assert_eq!(stage.load(Ordering::SeqCst), 1);
+    Scheduler::run(&scheduler, 0.5);
+    assert_eq!(stage.load(Ordering::SeqCst), 2);
+    Scheduler::run(&scheduler, 0.0);
+    Scheduler::run(&scheduler, 0.0);
+    assert_eq!(stage.load(Ordering::SeqCst), 3);
+    Scheduler::run(&scheduler, 0.0);
+    assert_eq!(stage.load(Ordering::SeqCst), 3);
+    flag.store(true, Ordering::SeqCst);
+    Scheduler::run(&scheduler, 0.0);
+    assert_eq!(stage.load(Ordering::SeqCst), 4);
+    assert!(!scheduler.lock().is_active(id));
+}
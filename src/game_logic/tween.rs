@@ -0,0 +1,525 @@
+//! Плавное изменение свойств объекта за заданное время.
+//!
+//! [`Tween`] описывает переход свойства к значению с функцией сглаживания,
+//! паузы, вызовы функций, последовательности и параллельные группы.
+//! Переходы проигрываются компонентом [`Tweener`] на `FrameTick`.
+use std::f32::consts::PI;
+
+use super::events::*;
+use crate::components::skeleton::{slerp, BoneTransform, Quat};
+use crate::game_object::{GameObject, GameObjectRef};
+use crate::material::MaterialSlot;
+use crate::references::*;
+use crate::types::Vec3;
+
+/// Функция сглаживания: отображает долю времени `[0, 1]` в долю пути
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Easing {
+    #[default]
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineIn,
+    SineOut,
+    SineInOut,
+    ExpoIn,
+    ExpoOut,
+    ExpoInOut,
+    /// С отступом назад в начале
+    BackIn,
+    /// С перелётом в конце
+    BackOut,
+    BackInOut,
+    /// Затухающие колебания около конечного значения
+    ElasticOut,
+    /// Отскоки от конечного значения
+    BounceOut,
+}
+
+impl Easing {
+    pub fn apply(self, t: f32) -> f32 {
+        const BACK: f32 = 1.70158;
+        let t = t.clamp(0.0, 1.0);
+        // Симметричная функция из функции ускорения
+        let in_out = |ease_in: fn(f32) -> f32| {
+            if t < 0.5 {
+                ease_in(t * 2.0) * 0.5
+            } else {
+                1.0 - ease_in((1.0 - t) * 2.0) * 0.5
+            }
+        };
+        let expo_in = |t: f32| if t <= 0.0 { 0.0 } else { 2f32.powf(10.0 * t - 10.0) };
+        let back_in = |t: f32| t * t * ((BACK + 1.0) * t - BACK);
+        match self {
+            Self::Linear => t,
+            Self::QuadIn => t * t,
+            Self::QuadOut => 1.0 - (1.0 - t).powi(2),
+            Self::QuadInOut => in_out(|t| t * t),
+            Self::CubicIn => t.powi(3),
+            Self::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Self::CubicInOut => in_out(|t| t.powi(3)),
+            Self::SineIn => 1.0 - (t * PI * 0.5).cos(),
+            Self::SineOut => (t * PI * 0.5).sin(),
+            Self::SineInOut => (1.0 - (t * PI).cos()) * 0.5,
+            Self::ExpoIn => expo_in(t),
+            Self::ExpoOut => 1.0 - expo_in(1.0 - t),
+            Self::ExpoInOut => in_out(|t| if t <= 0.0 { 0.0 } else { 2f32.powf(10.0 * t - 10.0) }),
+            Self::BackIn => back_in(t),
+            Self::BackOut => 1.0 - back_in(1.0 - t),
+            Self::BackInOut => in_out(|t| t * t * ((BACK * 1.525 + 1.0) * t - BACK * 1.525)),
+            Self::ElasticOut => {
+                if t <= 0.0 || t >= 1.0 {
+                    t
+                } else {
+                    2f32.powf(-10.0 * t) * ((t * 10.0 - 0.75) * PI * 2.0 / 3.0).sin() + 1.0
+                }
+            }
+            Self::BounceOut => {
+                let (n, d) = (7.5625, 2.75);
+                if t < 1.0 / d {
+                    n * t * t
+                } else if t < 2.0 / d {
+                    let t = t - 1.5 / d;
+                    n * t * t + 0.75
+                } else if t < 2.5 / d {
+                    let t = t - 2.25 / d;
+                    n * t * t + 0.9375
+                } else {
+                    let t = t - 2.625 / d;
+                    n * t * t + 0.984375
+                }
+            }
+        }
+    }
+}
+
+/// Свойство объекта и его значение
+#[derive(Clone, Debug, PartialEq)]
+pub enum TweenValue {
+    Translation(Vec3),
+    Rotation(Quat),
+    Scale(Vec3),
+    /// Скалярный параметр материала. Материал может быть общим для нескольких объектов.
+    MaterialParameter(String, f32),
+    LightColor(Vec3),
+    LightPower(f32),
+}
+
+impl TweenValue {
+    /// Текущее значение того же свойства у объекта
+    fn read(&self, owner: &GameObjectRef) -> Option<TweenValue> {
+        let obj = owner.lock();
+        let transform = || BoneTransform::from_matrix(&obj.transform().local);
+        Some(match self {
+            Self::Translation(_) => Self::Translation(transform().translation),
+            Self::Rotation(_) => Self::Rotation(transform().rotation),
+            Self::Scale(_) => Self::Scale(transform().scale),
+            Self::MaterialParameter(name, _) => match obj.visual()?.material().lock().parameter(name)? {
+                MaterialSlot::Scalar(value) => Self::MaterialParameter(name.clone(), *value),
+                _ => return None,
+            },
+            Self::LightColor(_) => Self::LightColor(obj.light()?.lock().unwrap().color()),
+            Self::LightPower(_) => Self::LightPower(obj.light()?.lock().unwrap().power()),
+        })
+    }
+
+    /// Значение между `self` и `to`; для разных свойств — `to`
+    fn interpolate(&self, to: &TweenValue, t: f32) -> TweenValue {
+        match (self, to) {
+            (Self::Translation(a), Self::Translation(b)) => Self::Translation(a.lerp(b, t)),
+            (Self::Rotation(a), Self::Rotation(b)) => Self::Rotation(slerp(a, b, t)),
+            (Self::Scale(a), Self::Scale(b)) => Self::Scale(a.lerp(b, t)),
+            (Self::MaterialParameter(_, a), Self::MaterialParameter(name, b)) => Self::MaterialParameter(name.clone(), a + (b - a) * t),
+            (Self::LightColor(a), Self::LightColor(b)) => Self::LightColor(a.lerp(b, t)),
+            (Self::LightPower(a), Self::LightPower(b)) => Self::LightPower(a + (b - a) * t),
+            _ => to.clone(),
+        }
+    }
+
+    /// Запись значения в объект. Об отсутствующем параметре материала
+    /// предупреждает один раз и запоминает его в `missing_parameters`.
+    fn write(&self, owner: &GameObjectRef, missing_parameters: &mut Vec<String>) {
+        let mut obj = owner.lock();
+        let set_transform = |obj: &mut GameObject, change: &dyn Fn(&mut BoneTransform)| {
+            if let Some(transform) = obj.transform_mut() {
+                let mut local = BoneTransform::from_matrix(&transform.local);
+                change(&mut local);
+                transform.local = local.to_matrix();
+            }
+        };
+        match self {
+            Self::Translation(value) => set_transform(&mut obj, &|local| local.translation = *value),
+            Self::Rotation(value) => set_transform(&mut obj, &|local| local.rotation = *value),
+            Self::Scale(value) => set_transform(&mut obj, &|local| local.scale = *value),
+            Self::MaterialParameter(name, value) => {
+                let Some(visual) = obj.visual() else {
+                    return;
+                };
+                if missing_parameters.contains(name) {
+                    return;
+                }
+                if let Err(err) = visual.material().lock().set_parameter(name, (*value).into()) {
+                    log::warn!(target: "tween", "{}: {err}", obj.name());
+                    missing_parameters.push(name.clone());
+                }
+            }
+            Self::LightColor(value) => {
+                if let Some(light) = obj.light() {
+                    light.lock().unwrap().set_color(*value);
+                }
+            }
+            Self::LightPower(value) => {
+                if let Some(light) = obj.light() {
+                    light.lock().unwrap().set_power(*value);
+                }
+            }
+        }
+    }
+}
+
+type TweenCallback = Box<dyn FnMut(&GameObjectRef) + Send + Sync>;
+
+enum TweenNode {
+    Property {
+        from: Option<TweenValue>,
+        to: TweenValue,
+        easing: Easing,
+    },
+    Delay,
+    /// Функция забирается при вызове перехода
+    Call(Option<TweenCallback>),
+    Sequence(Vec<Tween>),
+    Parallel(Vec<Tween>),
+}
+
+/// Переход, пауза, вызов функции или группа переходов
+pub struct Tween {
+    node: TweenNode,
+    duration: f32,
+    elapsed: f32,
+    /// Начальное значение прочитано, функция вызвана
+    started: bool,
+    finished: bool,
+}
+
+#[allow(dead_code)]
+impl Tween {
+    fn with_node(node: TweenNode, duration: f32) -> Self {
+        Self {
+            node,
+            duration: duration.max(0.0),
+            elapsed: 0.0,
+            started: false,
+            finished: false,
+        }
+    }
+
+    /// Переход от значения свойства в момент запуска к `to` за `duration` секунд
+    pub fn to(to: TweenValue, duration: f32, easing: Easing) -> Self {
+        Self::with_node(TweenNode::Property { from: None, to, easing }, duration)
+    }
+
+    /// Переход между заданными значениями одного свойства
+    pub fn from_to(from: TweenValue, to: TweenValue, duration: f32, easing: Easing) -> Self {
+        Self::with_node(TweenNode::Property { from: Some(from), to, easing }, duration)
+    }
+
+    pub fn delay(duration: f32) -> Self {
+        Self::with_node(TweenNode::Delay, duration)
+    }
+
+    /// Вызов функции. Функция выполняется в конце кадра, когда компонент [`Tweener`]
+    /// уже не заблокирован, поэтому из неё можно запускать и отменять переходы.
+    pub fn call(callback: impl FnMut(&GameObjectRef) + Send + Sync + 'static) -> Self {
+        Self::with_node(TweenNode::Call(Some(Box::new(callback))), 0.0)
+    }
+
+    /// Переходы друг за другом
+    pub fn sequence(tweens: Vec<Tween>) -> Self {
+        Self::with_node(TweenNode::Sequence(tweens), 0.0)
+    }
+
+    /// Одновременные переходы; группа завершается вместе с самым долгим
+    pub fn parallel(tweens: Vec<Tween>) -> Self {
+        Self::with_node(TweenNode::Parallel(tweens), 0.0)
+    }
+
+    /// Последовательность из этого перехода и `next`
+    pub fn then(self, next: Tween) -> Self {
+        match self.node {
+            TweenNode::Sequence(mut tweens) if !self.started => {
+                tweens.push(next);
+                Self::sequence(tweens)
+            }
+            _ => Self::sequence(vec![self, next]),
+        }
+    }
+
+    /// Вызов функции после завершения перехода
+    pub fn on_complete(self, callback: impl FnMut(&GameObjectRef) + Send + Sync + 'static) -> Self {
+        self.then(Self::call(callback))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Продвижение на `delta` секунд. Возвращает время, оставшееся после завершения.
+    /// Функции переходов не вызываются, а добавляются в `calls`.
+    fn advance(&mut self, owner: &GameObjectRef, delta: f32, calls: &mut Vec<TweenCallback>, missing_parameters: &mut Vec<String>) -> Option<f32> {
+        if self.finished {
+            return Some(delta);
+        }
+        let first = !self.started;
+        self.started = true;
+        let leftover = match &mut self.node {
+            TweenNode::Sequence(tweens) => {
+                let mut delta = delta;
+                for tween in tweens.iter_mut() {
+                    match tween.advance(owner, delta, calls, missing_parameters) {
+                        Some(rest) => delta = rest,
+                        None => return None,
+                    }
+                }
+                Some(delta)
+            }
+            TweenNode::Parallel(tweens) => {
+                let leftovers = tweens
+                    .iter_mut()
+                    .map(|tween| tween.advance(owner, delta, calls, missing_parameters))
+                    .collect::<Vec<_>>();
+                leftovers.into_iter().try_fold(delta, |least, rest| rest.map(|rest| least.min(rest)))
+            }
+            TweenNode::Call(callback) => {
+                calls.extend(callback.take());
+                Some(delta)
+            }
+            TweenNode::Delay | TweenNode::Property { .. } => {
+                self.elapsed += delta;
+                let leftover = (self.elapsed >= self.duration).then_some(self.elapsed - self.duration);
+                if let TweenNode::Property { from, to, easing } = &mut self.node {
+                    if first && from.is_none() {
+                        *from = to.read(owner);
+                    }
+                    let t = if self.duration > 0.0 { self.elapsed / self.duration } else { 1.0 };
+                    let value = match from {
+                        Some(from) if t < 1.0 => from.interpolate(to, easing.apply(t)),
+                        _ => to.clone(),
+                    };
+                    value.write(owner, missing_parameters);
+                }
+                leftover
+            }
+        };
+        self.finished = leftover.is_some();
+        leftover
+    }
+}
+
+/// Номер перехода, запущенного в [`Tweener`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TweenId(u32);
+
+/// Компонент, проигрывающий переходы [`Tween`] для своего объекта
+#[derive(Default)]
+pub struct Tweener {
+    tweens: Vec<(TweenId, Tween)>,
+    next_id: u32,
+    /// Параметры материала, которых нет у объекта; предупреждение выводится один раз
+    missing_parameters: Vec<String>,
+}
+
+#[allow(dead_code)]
+impl Tweener {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Запуск перехода со следующего обновления
+    pub fn start(&mut self, tween: Tween) -> TweenId {
+        let id = TweenId(self.next_id);
+        self.next_id = self.next_id.wrapping_add(1);
+        self.tweens.push((id, tween));
+        id
+    }
+
+    /// Остановка перехода на текущих значениях без вызова оставшихся функций
+    pub fn cancel(&mut self, id: TweenId) -> bool {
+        let count = self.tweens.len();
+        self.tweens.retain(|(own, _)| *own != id);
+        self.tweens.len() != count
+    }
+
+    pub fn cancel_all(&mut self) {
+        self.tweens.clear();
+    }
+
+    pub fn is_running(&self, id: TweenId) -> bool {
+        self.tweens.iter().any(|(own, _)| *own == id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tweens.is_empty()
+    }
+
+    /// Продвижение переходов на `delta` секунд; возвращает функции, которые нужно вызвать
+    fn advance(&mut self, owner: &GameObjectRef, delta: f32) -> Vec<TweenCallback> {
+        let mut calls = Vec::new();
+        for (_, tween) in &mut self.tweens {
+            tween.advance(owner, delta, &mut calls, &mut self.missing_parameters);
+        }
+        self.tweens.retain(|(_, tween)| !tween.is_finished());
+        calls
+    }
+
+    /// Продвижение переходов объекта вне сцены на `delta` секунд.
+    /// Компонент не заблокирован, пока выполняются функции переходов.
+    pub fn run(tweener: &RcBox<Tweener>, owner: &GameObjectRef, delta: f32) {
+        let calls = tweener.lock().advance(owner, delta);
+        for mut call in calls {
+            call(owner);
+        }
+    }
+
+    fn frame_tick(&mut self, owner: &GameObjectRef, event: AbstractEvent) {
+        if let AbstractEvent::FrameTick(time) = event {
+            let calls = self.advance(owner, time.delta());
+            if calls.is_empty() {
+                return;
+            }
+            // Обработчик вызывается с заблокированным компонентом, поэтому функции
+            // выполняет планировщик сцены после обработки кадра
+            let Some(scene) = owner.lock().scene.clone() else {
+                return;
+            };
+            let event_processor = scene.lock().event_processor().clone();
+            for call in calls {
+                event_processor.set_timeout(owner, 0.0, call);
+            }
+        }
+    }
+}
+
+crate::impl_behaviour!(Tweener { frame_tick: FrameTick });
+
+#[test]
+fn tween_easing_curves() {
+    let curves = [
+        Easing::Linear,
+        Easing::QuadIn,
+        Easing::QuadOut,
+        Easing::QuadInOut,
+        Easing::CubicIn,
+        Easing::CubicOut,
+        Easing::CubicInOut,
+        Easing::SineIn,
+        Easing::SineOut,
+        Easing::SineInOut,
+        Easing::ExpoIn,
+        Easing::ExpoOut,
+        Easing::ExpoInOut,
+        Easing::BackIn,
+        Easing::BackOut,
+        Easing::BackInOut,
+        Easing::ElasticOut,
+        Easing::BounceOut,
+    ];
+    for easing in curves {
+        assert!(easing.apply(0.0).abs() < 1.0e-3, "{easing:?}");
+        assert!((easing.apply(1.0) - 1.0).abs() < 1.0e-3, "{easing:?}");
+    }
+    assert_eq!(Easing::QuadIn.apply(0.5), 0.25);
+    assert_eq!(Easing::QuadOut.apply(0.5), 0.75);
+    assert!((Easing::CubicInOut.apply(0.5) - 0.5).abs() < 1.0e-6);
+    assert!(Easing::BackIn.apply(0.2) < 0.0 && Easing::BackOut.apply(0.8) > 1.0);
+}
+
+#[test]
+fn tween_sequence_parallel_and_cancel() {
+    use crate::types::Mat4;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    let object = GameObject::new("door");
+    object.lock().set_static(false);
+    object.lock().transform.local = Mat4::new_translation(&Vec3::new(1.0, 0.0, 0.0));
+    let location = || object.lock().transform.local.column(3).xyz();
+    let completed = Arc::new(AtomicUsize::new(0));
+
+    // Сдвиг по X, затем одновременно поворот и подъём с паузой перед ним
+    let counter = completed.clone();
+    let tween = Tween::to(TweenValue::Translation(Vec3::new(3.0, 0.0, 0.0)), 1.0, Easing::Linear)
+        .then(Tween::parallel(vec![
+            Tween::to(TweenValue::Rotation(Quat::from_euler_angles(0.0, 0.0, 1.0)), 1.0, Easing::Linear),
+            Tween::delay(0.5).then(Tween::from_to(
+                TweenValue::Translation(Vec3::new(3.0, 0.0, 0.0)),
+                TweenValue::Translation(Vec3::new(3.0, 0.0, 2.0)),
+                1.0,
+                Easing::QuadIn,
+            )),
+        ]))
+        .on_complete(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+    let tweener = RcBox::construct(Tweener::new());
+    let id = tweener.lock().start(tween);
+    Tweener::run(&tweener, &object, 0.5);
+    assert!((location() - Vec3::new(2.0, 0.0, 0.0)).norm() < 1.0e-5);
+    // Остаток времени переходит в следующий шаг
+    Tweener::run(&tweener, &object, 0.75);
+    let rotation = BoneTransform::from_matrix(&object.lock().transform.local).rotation;
+    assert!((rotation.angle() - 0.25).abs() < 1.0e-4);
+    assert!((location() - Vec3::new(3.0, 0.0, 0.0)).norm() < 1.0e-5);
+    Tweener::run(&tweener, &object, 0.75);
+    assert!((location() - Vec3::new(3.0, 0.0, 0.5)).norm() < 1.0e-5, "{}", location());
+    assert!(tweener.lock().is_running(id) && completed.load(Ordering::SeqCst) == 0);
+    Tweener::run(&tweener, &object, 1.0);
+    assert!((location() - Vec3::new(3.0, 0.0, 2.0)).norm() < 1.0e-5);
+    assert!(!tweener.lock().is_running(id) && tweener.lock().is_empty());
+    assert_eq!(completed.load(Ordering::SeqCst), 1);
+
+    // Отменённый переход останавливается на текущем значении без вызова функции
+    let counter = completed.clone();
+    let id = tweener.lock().start(Tween::to(TweenValue::Scale(Vec3::new(3.0, 3.0, 3.0)), 2.0, Easing::Linear).on_complete(move |_| {
+        counter.fetch_add(1, Ordering::SeqCst);
+    }));
+    Tweener::run(&tweener, &object, 1.0);
+    assert!(tweener.lock().cancel(id) && !tweener.lock().cancel(id));
+    Tweener::run(&tweener, &object, 5.0);
+    let scale = BoneTransform::from_matrix(&object.lock().transform.local).scale;
+    assert!((scale - Vec3::new(2.0, 2.0, 2.0)).norm() < 1.0e-4);
+    assert_eq!(completed.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn tween_callback_restarts_tween_on_scene() {
+    use crate::scene::Scene;
+    use crate::time::UniformTime;
+    use crate::types::Mat4;
+    let scene = Scene::new();
+    let object = GameObject::new("platform");
+    object.lock().set_static(false);
+    object.lock().transform.local = Mat4::identity();
+    let tweener = object.lock().add_component(Tweener::new()).unwrap();
+    scene.lock().add_object(object.clone()).unwrap();
+    let location = || object.lock().transform.local.column(3).xyz();
+
+    // Функция по завершении запускает обратный переход в том же компоненте
+    let back = tweener.clone();
+    tweener.lock().start(Tween::to(TweenValue::Translation(Vec3::new(2.0, 0.0, 0.0)), 0.0, Easing::Linear).on_complete(move |_| {
+        back.lock().start(Tween::to(TweenValue::Translation(Vec3::zeros()), 0.0, Easing::Linear));
+    }));
+    let processor = scene.lock().event_processor().clone();
+    let frame = || {
+        processor.send_event(AbstractEvent::FrameTick(UniformTime::default()));
+        processor.execute();
+    };
+    frame();
+    assert!((location() - Vec3::new(2.0, 0.0, 0.0)).norm() < 1.0e-5);
+    assert!(!tweener.lock().is_empty());
+    frame();
+    assert!(location().norm() < 1.0e-5 && tweener.lock().is_empty());
+}
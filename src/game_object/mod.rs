@@ -7,6 +7,7 @@ use crate::components::*;
 use crate::game_logic::behaviour::DynBehaviour;
 use crate::game_logic::Behaviour;
 
+use crate::mesh::BoundingBox;
 use crate::references::*;
 use crate::scene::SceneRef;
 use crate::shader::ShaderStructUniform;
@@ -17,6 +18,11 @@ use vulkano::pipeline::graphics::vertex_input::Vertex;
 pub type GameObjectRef = RcBox<GameObject>;
 pub type Transform = Mat4;
 
+/// Маска слоёв нового объекта
+pub const DEFAULT_LAYERS: u32 = 1;
+/// Маска, проходящая через любые слои
+pub const ALL_LAYERS: u32 = u32::MAX;
+
 #[derive(Clone)]
 pub enum GOParent {
     Object(GameObjectRef),
@@ -166,13 +172,17 @@ pub struct GameObject {
     camera: Option<CameraComponent>,
     mesh_visual: Option<MeshVisual>,
     light: Option<Light>,
+    collider: Option<Collider>,
+    rigid_body: Option<RigidBody>,
     components: Vec<DynBehaviour>,
+    /// Битовая маска слоёв для выборочных запросов к сцене
+    layers: u32,
     //scene: Option<SceneRef>
 }
 
 impl Drop for GameObject {
     fn drop(&mut self) {
-        println!("GameObject dropped");
+        log::trace!(target: "scene", "GameObject {} dropped", self.name);
     }
 }
 
@@ -200,6 +210,53 @@ impl GameObject {
         self.mesh_visual.as_ref()
     }
 
+    pub fn visual_mut(&mut self) -> Option<&mut MeshVisual> {
+        self.mesh_visual.as_mut()
+    }
+
+    pub fn collider(&self) -> Option<&Collider> {
+        self.collider.as_ref()
+    }
+
+    pub fn collider_mut(&mut self) -> Option<&mut Collider> {
+        self.collider.as_mut()
+    }
+
+    pub fn rigid_body(&self) -> Option<&RigidBody> {
+        self.rigid_body.as_ref()
+    }
+
+    pub fn rigid_body_mut(&mut self) -> Option<&mut RigidBody> {
+        self.rigid_body.as_mut()
+    }
+
+    /// Габариты объекта в мировых координатах.
+    /// У объекта без меша — точка его положения.
+    pub fn bbox(&self) -> BoundingBox {
+        let global = self.transform.global;
+        match self.mesh_visual {
+            Some(ref visual) => visual.bbox().transformed(&global),
+            None => {
+                let location = global.column(3).xyz();
+                BoundingBox {
+                    begin: location,
+                    end: location,
+                }
+            }
+        }
+    }
+
+    #[inline]
+    pub fn layers(&self) -> u32 {
+        self.layers
+    }
+
+    /// Слои объекта, битовая маска
+    #[inline]
+    pub fn set_layers(&mut self, layers: u32) {
+        self.layers = layers;
+    }
+
     pub fn light(&self) -> Option<&Light> {
         if let Some(ref light) = self.light {
             light.lock().unwrap().update_transform(&self.transform);
@@ -216,7 +273,10 @@ impl GameObject {
             camera: None,
             mesh_visual: None,
             light: None,
+            collider: None,
+            rigid_body: None,
             components: Vec::new(),
+            layers: DEFAULT_LAYERS,
             scene: None,
         });
         obj.lock_write().transform._owner = Some(obj.clone());
@@ -301,6 +361,14 @@ impl GameObject {
             self.mesh_visual = Some((&component as &dyn std::any::Any).downcast_ref::<MeshVisual>().unwrap().clone());
             return None;
         }
+        if (&component as &dyn std::any::Any).is::<Collider>() {
+            self.collider = Some((&component as &dyn std::any::Any).downcast_ref::<Collider>().unwrap().clone());
+            return None;
+        }
+        if (&component as &dyn std::any::Any).is::<RigidBody>() {
+            self.rigid_body = Some((&component as &dyn std::any::Any).downcast_ref::<RigidBody>().unwrap().clone());
+            return None;
+        }
         if (&component as &dyn std::any::Any).is::<Spotlight>() {
             let light: Spotlight = (&component as &dyn std::any::Any).downcast_ref::<Spotlight>().unwrap().clone(); //downcast_copy(component).unwrap();
             self.light = Some(RcBox::construct(light));
@@ -454,6 +522,9 @@ impl GameObject {
         let fork = Self::new(format!("Fork of {}", self.name()));
         let mut _fork = fork.lock();
         _fork.mesh_visual = self.mesh_visual.clone();
+        _fork.collider = self.collider.clone();
+        _fork.rigid_body = self.rigid_body.clone();
+        _fork.layers = self.layers;
         _fork.set_static(self.is_static());
         drop(_fork);
         if let Some(ref scene) = self.scene {
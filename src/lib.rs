@@ -14,14 +14,21 @@ const VULKANO_BUFFER_ATOMIC_SIZE: usize = 64;
 pub mod teapot;
 pub mod time;
 
+pub mod app;
 pub mod command_buffer;
 pub mod components;
+pub mod config;
+pub mod console;
+pub mod error;
 pub mod framebuffer;
 pub mod game_logic;
 pub mod game_object;
 pub mod glenums;
+pub mod logger;
 pub mod material;
 pub mod mesh;
+pub mod navigation;
+pub mod physics;
 pub mod references;
 pub mod renderer;
 pub mod resource_manager;
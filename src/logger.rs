@@ -0,0 +1,91 @@
+//! Вывод диагностических сообщений движка в stderr.
+//!
+//! Сообщения движка пишутся через фасад `log` с целями по подсистемам:
+//! `scene`, `resource_manager`, `shader`, `renderer`.
+//! Фильтр задаётся переменной окружения [`LOG_ENV`] в виде списка через запятую,
+//! например `DSGE_LOG=warn,renderer=info,shader=debug`.
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Переменная окружения с фильтром сообщений
+pub const LOG_ENV: &str = "DSGE_LOG";
+
+pub struct Logger {
+    default_level: LevelFilter,
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl Logger {
+    /// Разбор фильтра вида `info,shader=debug`.
+    /// Уровень без цели задаёт уровень по умолчанию.
+    pub fn from_filter(filter: &str) -> Result<Self, String> {
+        fn level(value: &str) -> Result<LevelFilter, String> {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| format!("Unknown log level \"{value}\"."))
+        }
+
+        let mut logger = Self {
+            default_level: LevelFilter::Info,
+            targets: Vec::new(),
+        };
+        for item in filter.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            match item.split_once('=') {
+                Some((target, value)) => logger.targets.push((target.trim().to_owned(), level(value)?)),
+                None => logger.default_level = level(item)?,
+            }
+        }
+        Ok(logger)
+    }
+
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .rev()
+            .find(|(prefix, _)| target == prefix || target.starts_with(&format!("{prefix}::")))
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default_level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default_level, |max, level| max.max(level))
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{:<5} {}] {}", record.level(), record.target(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Устанавливает логгер с фильтром из [`LOG_ENV`] (по умолчанию `info`).
+pub fn init() -> Result<(), String> {
+    let filter = std::env::var(LOG_ENV).unwrap_or_default();
+    let logger = Logger::from_filter(&filter)?;
+    log::set_max_level(logger.max_level());
+    log::set_boxed_logger(Box::new(logger)).map_err(|err| err.to_string())
+}
+
+#[test]
+fn logger_filter_parsing() {
+    let logger = Logger::from_filter("warn, shader=debug,renderer=off").unwrap();
+    assert_eq!(logger.level_for("scene"), LevelFilter::Warn);
+    assert_eq!(logger.level_for("shader"), LevelFilter::Debug);
+    assert_eq!(logger.level_for("shader::compiler"), LevelFilter::Debug);
+    assert_eq!(logger.level_for("shaders"), LevelFilter::Warn);
+    assert_eq!(logger.level_for("renderer"), LevelFilter::Off);
+    assert_eq!(logger.max_level(), LevelFilter::Debug);
+    assert_eq!(Logger::from_filter("").unwrap().level_for("scene"), LevelFilter::Info);
+    assert!(Logger::from_filter("scene=loud").is_err());
+}
@@ -1,3 +1,4 @@
+use crate::error::DsgeError;
 use std::sync::Arc;
 use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
 /// `Material` - надстройка над шейдерной программой
@@ -8,9 +9,11 @@ use vulkano::pipeline::graphics::vertex_input::Vertex;
 use vulkano::render_pass::Subpass;
 
 use crate::command_buffer::CommandBufferFather;
+use crate::components::skeleton::MAX_BONES;
 use crate::components::ProjectionUniformData;
 use crate::game_object::GOTransformUniform;
-use crate::mesh::VkVertex;
+use crate::mesh::morph::{MorphDelta, MAX_MORPH_TARGETS};
+use crate::mesh::{VkCompactVertex, VkVertex};
 use crate::references::*;
 use crate::renderer::BumpMemoryAllocator;
 use crate::renderer::RenderResolution;
@@ -29,6 +32,9 @@ pub type MaterialRef = RcBox<Material>;
 pub static SHADER_CAMERA_SET: u32 = 0;
 pub static SHADER_MATERIAL_DATA_SET: u32 = 1;
 pub static SHADER_TEXTURE_SET: u32 = 2;
+pub static SHADER_SKELETON_SET: u32 = 3;
+/// Данные вершинного буфера: распаковка позиций компактных вершин
+pub static SHADER_MESH_SET: u32 = 4;
 //pub static SHADER_VARIABLES_SET : u32 = 0;
 
 /// Слот числовых параметров для материала
@@ -118,11 +124,16 @@ impl MaterialBuilder {
         device: Arc<Device>,
         shadowmap: bool,
         deformable: bool,
+        compact: bool,
         super_resolution: bool,
-    ) -> Result<Shader, String> {
+    ) -> Result<Shader, DsgeError> {
         let mut builder = Shader::builder(ShaderType::Vertex, device.clone());
+        if compact {
+            builder.compact_vertex_attributes();
+        } else {
+            builder.default_vertex_attributes();
+        }
         builder
-            .default_vertex_attributes()
             .instance_attributes()
             .output("triangle_index", AttribType::Int)
             .output("texture_uv", AttribType::FVec2)
@@ -172,10 +183,48 @@ impl MaterialBuilder {
             )
             .unwrap();
         if deformable {
-            builder.include("data/shaders/geometry_pass.vert.glsl");
-        } else {
-            builder.include("data/shaders/geometry_pass.vert.glsl");
+            builder
+                .define("MAX_BONES", &MAX_BONES.to_string())
+                .uniform_structure(
+                    "skeleton",
+                    "Skeleton",
+                    ShaderUniformArrayLength::NotArray,
+                    "{\n    mat4 bones[MAX_BONES];\n}",
+                    SHADER_SKELETON_SET,
+                    0,
+                )
+                .unwrap()
+                .define("MAX_MORPH_TARGETS", &MAX_MORPH_TARGETS.to_string())
+                .uniform_structure(
+                    "morph",
+                    "Morph",
+                    ShaderUniformArrayLength::NotArray,
+                    "{\n    uvec4 info;\n    vec4 weights[MAX_MORPH_TARGETS / 4];\n}",
+                    SHADER_SKELETON_SET,
+                    1,
+                )
+                .unwrap()
+                .storage_buffer::<MorphDelta>(
+                    "morph_deltas",
+                    ShaderUniformArrayLength::Unknown,
+                    SHADER_SKELETON_SET,
+                    2,
+                )
+                .unwrap();
+        }
+        if compact {
+            builder
+                .uniform_structure(
+                    "quantization",
+                    "Quantization",
+                    ShaderUniformArrayLength::NotArray,
+                    "{\n    vec4 offset;\n    vec4 scale;\n}",
+                    SHADER_MESH_SET,
+                    0,
+                )
+                .unwrap();
         }
+        builder.include("data/shaders/geometry_pass.vert.glsl");
         builder.build()?;
         Ok(builder)
     }
@@ -185,7 +234,7 @@ impl MaterialBuilder {
         device: Arc<Device>,
         shadowmap: bool,
         super_resolution: bool,
-    ) -> Result<Shader, String> {
+    ) -> Result<Shader, DsgeError> {
         let mut builder = Shader::builder(ShaderType::Fragment, device.clone());
         if shadowmap {
             builder
@@ -341,13 +390,14 @@ impl MaterialBuilder {
     }
 
     pub fn build(self, device: Arc<Device>, super_resolution: bool) -> Material {
-        let vertex_shaders: [Shader; 4] = MaterialVertexShaderType::combinations()
+        let vertex_shaders: [Shader; 8] = MaterialVertexShaderType::combinations()
             .into_iter()
             .map(|mst| {
                 self.vertex_shader_combination(
                     device.clone(),
                     mst.shadowmap,
                     mst.deformable,
+                    mst.compact,
                     super_resolution,
                 )
                 .unwrap_or_else(|err| panic!("{err}"))
@@ -366,7 +416,7 @@ impl MaterialBuilder {
             .try_into()
             .unwrap();
 
-        let shader_programs: [(ShaderProgram, ShaderProgramUniformBuffer, bool); 4] =
+        let shader_programs: [(ShaderProgram, ShaderProgramUniformBuffer, bool); 8] =
             MaterialShaderProgramType::combinations()
                 .into_iter()
                 .map(|MaterialShaderProgramType { vertex, fragment }| {
@@ -427,7 +477,7 @@ impl Material {
         }
     }
 
-    pub fn replace_texture(&mut self, name: &str, texture: &Texture) -> Result<(), String> {
+    pub fn replace_texture(&mut self, name: &str, texture: &Texture) -> Result<(), DsgeError> {
         for (slot_name, old_texture) in &mut self.texture_slots {
             if slot_name == name {
                 *old_texture = texture.clone();
@@ -437,10 +487,15 @@ impl Material {
                 return Ok(());
             }
         }
-        Err(format!("Материал {} не имеет текстуры {name}.", self.name))
+        Err(DsgeError::LayoutMismatch(format!("Материал {} не имеет текстуры {name}.", self.name)))
+    }
+
+    /// Значение числового параметра
+    pub fn parameter(&self, name: &str) -> Option<&MaterialSlot> {
+        self.numeric_slots.iter().find(|(param_name, _)| param_name == name).map(|(_, value)| value)
     }
 
-    pub fn set_parameter(&mut self, name: &str, value: MaterialSlot) -> Result<(), String> {
+    pub fn set_parameter(&mut self, name: &str, value: MaterialSlot) -> Result<(), DsgeError> {
         for (param_name, param_value) in &mut self.numeric_slots {
             if param_name == name {
                 *param_value = value;
@@ -450,7 +505,7 @@ impl Material {
                 return Ok(());
             }
         }
-        Err(format!("Материал {} не имеет параметра {name}.", self.name))
+        Err(DsgeError::LayoutMismatch(format!("Материал {} не имеет параметра {name}.", self.name)))
     }
 
     fn _shader_mut(
@@ -482,18 +537,22 @@ impl Material {
         let (subpass, new) = {
             let (shader, _, need_to_update) = &mut self._shader_mut(ty);
             shader.cull_faces = CullMode::Front;
+            let vertex_description = if ty.vertex.compact {
+                VkCompactVertex::per_vertex()
+            } else {
+                VkVertex::per_vertex()
+            };
             let (s, n) = shader.use_subpass(
                 subpass,
                 shader.cull_faces,
-                Some([VkVertex::per_vertex(), GOTransformUniform::per_instance()]),
+                Some([vertex_description, GOTransformUniform::per_instance()]),
             );
             (s, n || *need_to_update)
         };
         match subpass {
             PipelineType::Graphics(_) => {
                 if new {
-                    #[cfg(debug)]
-                    println!("Инициализация шейдера материала");
+                    log::debug!(target: "shader", "Initializing material shader");
                     let ub: ShaderProgramUniformBuffer = Material::build_uniform_buffer(
                         command_buffer_father,
                         generic_allocator,
@@ -522,8 +581,7 @@ impl Material {
         material: &Material,
         shader: &ShaderProgram,
     ) -> ShaderProgramUniformBuffer {
-        #[cfg(debug)]
-        println!("Сборка uniform буфера для материала");
+        log::debug!(target: "resource_manager", "Building material uniform buffer");
         let mut uniform_buffer = shader.new_uniform_buffer();
         let mut numeric_data = Vec::<f32>::with_capacity(material.numeric_slots.len());
         for (_, num_slot) in &material.numeric_slots {
@@ -579,7 +637,7 @@ impl Material {
 }
 
 #[allow(dead_code)]
-pub type MaterialShaderSet = [(ShaderProgram, ShaderProgramUniformBuffer, bool); 4];
+pub type MaterialShaderSet = [(ShaderProgram, ShaderProgramUniformBuffer, bool); 8];
 
 static DEFAULT_PBR: &str = "
 void principled() {
@@ -662,6 +720,8 @@ pub enum MaterialShaderType
 pub struct MaterialVertexShaderType {
     deformable: bool,
     shadowmap: bool,
+    /// Вершины в формате [`VkCompactVertex`]
+    compact: bool,
 }
 
 #[derive(Clone, Copy, Hash, PartialEq, PartialOrd, Eq)]
@@ -671,30 +731,17 @@ pub struct MaterialFragmentShaderType {
 
 impl MaterialVertexShaderType {
     #[inline(always)]
-    pub fn combinations() -> [Self; 4] {
-        [
-            MaterialVertexShaderType {
-                deformable: false,
-                shadowmap: false,
-            },
-            MaterialVertexShaderType {
-                deformable: false,
-                shadowmap: true,
-            },
-            MaterialVertexShaderType {
-                deformable: true,
-                shadowmap: false,
-            },
-            MaterialVertexShaderType {
-                deformable: true,
-                shadowmap: true,
-            },
-        ]
+    pub fn combinations() -> [Self; 8] {
+        std::array::from_fn(|index| MaterialVertexShaderType {
+            deformable: index & 2 != 0,
+            shadowmap: index & 1 != 0,
+            compact: index & 4 != 0,
+        })
     }
 
     #[inline(always)]
     pub fn as_index(&self) -> usize {
-        ((self.deformable as usize) << 1) | (self.shadowmap as usize)
+        ((self.compact as usize) << 2) | ((self.deformable as usize) << 1) | (self.shadowmap as usize)
     }
 }
 
@@ -721,27 +768,12 @@ pub struct MaterialShaderProgramType {
 
 impl MaterialShaderProgramType {
     #[inline(always)]
-    pub fn combinations() -> [Self; 4] {
-        let vcomb = MaterialVertexShaderType::combinations();
+    pub fn combinations() -> [Self; 8] {
         let fcomb = MaterialFragmentShaderType::combinations();
-        [
-            MaterialShaderProgramType {
-                vertex: vcomb[0],
-                fragment: fcomb[0],
-            },
-            MaterialShaderProgramType {
-                vertex: vcomb[1],
-                fragment: fcomb[1],
-            },
-            MaterialShaderProgramType {
-                vertex: vcomb[2],
-                fragment: fcomb[0],
-            },
-            MaterialShaderProgramType {
-                vertex: vcomb[3],
-                fragment: fcomb[1],
-            },
-        ]
+        MaterialVertexShaderType::combinations().map(|vertex| MaterialShaderProgramType {
+            vertex,
+            fragment: fcomb[vertex.shadowmap as usize],
+        })
     }
 
     #[inline(always)]
@@ -755,17 +787,37 @@ impl MaterialShaderProgramType {
             vertex: MaterialVertexShaderType {
                 deformable: false,
                 shadowmap: false,
+                compact: false,
             },
             fragment: MaterialFragmentShaderType { shadowmap: false },
         }
     }
 
+    /// Тот же тип с деформацией скелетом или без неё
+    #[inline(always)]
+    pub fn deformed(self, deformable: bool) -> Self {
+        Self {
+            vertex: MaterialVertexShaderType { deformable, ..self.vertex },
+            ..self
+        }
+    }
+
+    /// Тот же тип для компактного или обычного формата вершин
+    #[inline(always)]
+    pub fn compact(self, compact: bool) -> Self {
+        Self {
+            vertex: MaterialVertexShaderType { compact, ..self.vertex },
+            ..self
+        }
+    }
+
     #[inline(always)]
     pub fn base_shadowmap() -> Self {
         Self {
             vertex: MaterialVertexShaderType {
                 deformable: false,
                 shadowmap: true,
+                compact: false,
             },
             fragment: MaterialFragmentShaderType { shadowmap: true },
         }
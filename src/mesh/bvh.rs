@@ -0,0 +1,512 @@
+//! Иерархия ограничивающих объёмов (BVH) по треугольникам меша.
+//!
+//! Дерево строится по эвристике площади поверхности (SAH) с разбиением
+//! центров треугольников на корзины вдоль самой длинной оси. Узлы хранятся
+//! в одном массиве, потомки внутреннего узла лежат подряд.
+//! Номера треугольников в результатах совпадают с номерами в индексах меша,
+//! как у [`super::raycast::raycast_triangles`].
+use super::raycast::{MeshHit, Ray};
+use super::{BoundingBox, MeshBuilder, MeshView, VkVertex};
+use crate::types::{Vec2, Vec3};
+
+/// Число корзин при поиске разбиения
+const SAH_BINS: usize = 12;
+/// Узлы с таким числом треугольников не делятся
+const MAX_LEAF_TRIANGLES: usize = 4;
+
+#[derive(Clone, Copy, Debug)]
+struct BvhNode {
+    bbox: BoundingBox,
+    /// Лист: первый элемент в `order`; внутренний узел: левый потомок
+    start: u32,
+    /// Число треугольников листа, 0 у внутреннего узла
+    count: u32,
+}
+
+/// BVH по треугольникам меша
+#[derive(Clone, Debug, Default)]
+pub struct MeshBvh {
+    nodes: Vec<BvhNode>,
+    /// Номера треугольников в порядке листьев
+    order: Vec<u32>,
+    triangles: Vec<[Vec3; 3]>,
+    uvs: Vec<[Vec2; 3]>,
+}
+
+fn triangle_bbox(triangle: &[Vec3; 3]) -> BoundingBox {
+    let mut bbox = BoundingBox::initial();
+    bbox.add_points(triangle);
+    bbox
+}
+
+/// Ближайшая к `point` точка треугольника
+pub fn closest_point_on_triangle(point: Vec3, [a, b, c]: [Vec3; 3]) -> Vec3 {
+    let ab = b - a;
+    let ac = c - a;
+    let ap = point - a;
+    let d1 = ab.dot(&ap);
+    let d2 = ac.dot(&ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+    let bp = point - b;
+    let d3 = ab.dot(&bp);
+    let d4 = ac.dot(&bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+    let cp = point - c;
+    let d5 = ab.dot(&cp);
+    let d6 = ac.dot(&cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+    let denominator = 1.0 / (va + vb + vc);
+    a + ab * (vb * denominator) + ac * (vc * denominator)
+}
+
+/// Пересечение треугольника с габаритами по теореме о разделяющей оси
+pub fn triangle_overlaps_bbox(triangle: [Vec3; 3], bbox: &BoundingBox) -> bool {
+    let center = (bbox.begin + bbox.end) * 0.5;
+    let half = (bbox.end - bbox.begin) * 0.5;
+    let v = triangle.map(|p| p - center);
+    let edges = [v[1] - v[0], v[2] - v[1], v[0] - v[2]];
+    let separated = |axis: Vec3| {
+        let projections = v.map(|p| p.dot(&axis));
+        let radius = half.x * axis.x.abs() + half.y * axis.y.abs() + half.z * axis.z.abs();
+        let min = projections[0].min(projections[1]).min(projections[2]);
+        let max = projections[0].max(projections[1]).max(projections[2]);
+        min > radius || max < -radius
+    };
+    // Оси габаритов, нормаль треугольника и произведения рёбер на оси
+    let axes = [Vec3::x(), Vec3::y(), Vec3::z()];
+    if axes.iter().any(|axis| separated(*axis)) || separated(edges[0].cross(&edges[1])) {
+        return false;
+    }
+    !axes.iter().any(|axis| edges.iter().any(|edge| separated(axis.cross(edge))))
+}
+
+impl MeshBvh {
+    /// Дерево по треугольникам `indices`, которые отсчитываются от `vertex_offset`.
+    /// Треугольники с индексами за пределами `vertices` пропускаются.
+    pub fn new(vertices: &[VkVertex], indices: &[u32], vertex_offset: u32) -> Self {
+        let mut bvh = Self::default();
+        for corners in indices.chunks_exact(3) {
+            let corner = |i: usize| vertices.get(corners[i] as usize + vertex_offset as usize);
+            match (corner(0), corner(1), corner(2)) {
+                (Some(a), Some(b), Some(c)) => {
+                    bvh.order.push(bvh.triangles.len() as u32);
+                    bvh.triangles.push([a, b, c].map(|v| Vec3::from(v.v_pos)));
+                    bvh.uvs.push([a, b, c].map(|v| Vec2::from(v.v_tex1)));
+                }
+                _ => {
+                    bvh.triangles.push([Vec3::zeros(); 3]);
+                    bvh.uvs.push([Vec2::zeros(); 3]);
+                }
+            }
+        }
+        if bvh.order.is_empty() {
+            return bvh;
+        }
+        let boxes = bvh.triangles.iter().map(triangle_bbox).collect::<Vec<_>>();
+        let centroids = boxes.iter().map(|b| (b.begin + b.end) * 0.5).collect::<Vec<_>>();
+        let count = bvh.order.len() as u32;
+        let root = bvh.node(0, count, &boxes);
+        bvh.nodes.push(root);
+        bvh.subdivide(0, &boxes, &centroids);
+        bvh
+    }
+
+    /// Дерево меша по его копиям на CPU, `None`, если копии не сохранены
+    pub fn from_mesh(mesh: &dyn MeshView) -> Option<Self> {
+        let (vertices, indices) = mesh.cpu_geometry()?;
+        Some(Self::new(vertices, indices, mesh.vertex_offset()))
+    }
+
+    fn node(&self, start: u32, count: u32, boxes: &[BoundingBox]) -> BvhNode {
+        let mut bbox = BoundingBox::initial();
+        for id in &self.order[start as usize..(start + count) as usize] {
+            bbox.add(&boxes[*id as usize]);
+        }
+        BvhNode { bbox, start, count }
+    }
+
+    fn subdivide(&mut self, index: usize, boxes: &[BoundingBox], centroids: &[Vec3]) {
+        let BvhNode { bbox, start, count } = self.nodes[index];
+        if count as usize <= MAX_LEAF_TRIANGLES {
+            return;
+        }
+        let range = start as usize..(start + count) as usize;
+        let mut centroid_bounds = BoundingBox::initial();
+        for id in &self.order[range.clone()] {
+            centroid_bounds.add_point(centroids[*id as usize]);
+        }
+        let extent = centroid_bounds.end - centroid_bounds.begin;
+        let axis = extent.imax();
+        if extent[axis] <= 0.0 {
+            return;
+        }
+        let bin_of = |id: u32| {
+            let offset = (centroids[id as usize][axis] - centroid_bounds.begin[axis]) / extent[axis];
+            ((offset * SAH_BINS as f32) as usize).min(SAH_BINS - 1)
+        };
+        let mut bins = [(BoundingBox::initial(), 0usize); SAH_BINS];
+        for id in &self.order[range.clone()] {
+            let bin = &mut bins[bin_of(*id)];
+            bin.0.add(&boxes[*id as usize]);
+            bin.1 += 1;
+        }
+        // Стоимость разбиения после каждой корзины: площадь, умноженная на число треугольников
+        let mut left_costs = [0.0f32; SAH_BINS - 1];
+        let (mut left_box, mut left_count) = (BoundingBox::initial(), 0);
+        for split in 0..SAH_BINS - 1 {
+            left_box.add(&bins[split].0);
+            left_count += bins[split].1;
+            left_costs[split] = left_box.surface_area() * left_count as f32;
+        }
+        let (mut right_box, mut right_count) = (BoundingBox::initial(), 0);
+        let mut best = (f32::INFINITY, 0);
+        for split in (0..SAH_BINS - 1).rev() {
+            right_box.add(&bins[split + 1].0);
+            right_count += bins[split + 1].1;
+            let cost = left_costs[split] + right_box.surface_area() * right_count as f32;
+            if cost < best.0 {
+                best = (cost, split);
+            }
+        }
+        let parent_area = bbox.surface_area();
+        let split_cost = 1.0 + best.0 / parent_area.max(f32::MIN_POSITIVE);
+        if split_cost >= count as f32 {
+            return;
+        }
+        let slice = &mut self.order[range];
+        let mut left_count = 0;
+        for i in 0..slice.len() {
+            if bin_of(slice[i]) <= best.1 {
+                slice.swap(i, left_count);
+                left_count += 1;
+            }
+        }
+        if left_count == 0 || left_count == slice.len() {
+            return;
+        }
+        let left_count = left_count as u32;
+        let left = self.nodes.len();
+        let left_node = self.node(start, left_count, boxes);
+        let right_node = self.node(start + left_count, count - left_count, boxes);
+        self.nodes.push(left_node);
+        self.nodes.push(right_node);
+        self.nodes[index] = BvhNode { bbox, start: left as u32, count: 0 };
+        self.subdivide(left, boxes, centroids);
+        self.subdivide(left + 1, boxes, centroids);
+    }
+
+    #[inline]
+    pub fn triangle_count(&self) -> usize {
+        self.triangles.len()
+    }
+
+    #[inline]
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Вершины треугольника по его номеру в меше
+    #[inline]
+    pub fn triangle(&self, index: usize) -> [Vec3; 3] {
+        self.triangles[index]
+    }
+
+    /// Габариты всех треугольников
+    pub fn bbox(&self) -> BoundingBox {
+        self.nodes.first().map_or(BoundingBox::initial(), |node| node.bbox)
+    }
+
+    fn leaf(&self, node: &BvhNode) -> &[u32] {
+        &self.order[node.start as usize..(node.start + node.count) as usize]
+    }
+
+    /// Обход узлов, габариты которых проходят `visit_node`; для треугольников листьев
+    /// вызывается `visit_triangle`, обход прерывается, если он вернёт `false`
+    fn traverse(&self, mut visit_node: impl FnMut(&BoundingBox) -> bool, mut visit_triangle: impl FnMut(usize) -> bool) {
+        let mut stack = Vec::with_capacity(64);
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !visit_node(&node.bbox) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.push(node.start as usize + 1);
+                stack.push(node.start as usize);
+                continue;
+            }
+            for id in self.leaf(node) {
+                if !visit_triangle(*id as usize) {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Ближайшее пересечение луча с треугольниками не дальше `max_distance`
+    pub fn closest_hit(&self, ray: &Ray, max_distance: f32) -> Option<MeshHit> {
+        let mut nearest: Option<(usize, f32, Vec3)> = None;
+        let mut limit = max_distance;
+        let mut stack = Vec::with_capacity(64);
+        if !self.nodes.is_empty() {
+            stack.push((0, 0.0));
+        }
+        while let Some((index, entry)) = stack.pop() {
+            if entry > limit {
+                continue;
+            }
+            let node = &self.nodes[index];
+            if node.count > 0 {
+                for id in self.leaf(node) {
+                    let id = *id as usize;
+                    if let Some((t, barycentric)) = ray.intersect_triangle(self.triangles[id]) {
+                        if t <= limit {
+                            limit = t;
+                            nearest = Some((id, t, barycentric));
+                        }
+                    }
+                }
+                continue;
+            }
+            // Ближний потомок проверяется первым
+            let [near, far] = {
+                let [left, right] = [node.start as usize, node.start as usize + 1]
+                    .map(|child| (child, ray.intersect_bbox(&self.nodes[child].bbox)));
+                if right.1.unwrap_or(f32::INFINITY) < left.1.unwrap_or(f32::INFINITY) {
+                    [right, left]
+                } else {
+                    [left, right]
+                }
+            };
+            for (child, entry) in [far, near] {
+                if let Some(entry) = entry {
+                    stack.push((child, entry));
+                }
+            }
+        }
+        let (triangle, distance, barycentric) = nearest?;
+        let [a, b, c] = self.triangles[triangle];
+        let normal = (b - a).cross(&(c - a));
+        let uvs = self.uvs[triangle];
+        Some(MeshHit {
+            triangle,
+            distance,
+            point: ray.at(distance),
+            normal: normal.try_normalize(1.0e-12).unwrap_or(normal),
+            barycentric,
+            uv: uvs[0] * barycentric.x + uvs[1] * barycentric.y + uvs[2] * barycentric.z,
+        })
+    }
+
+    /// Есть ли пересечение луча с каким-либо треугольником не дальше `max_distance`
+    pub fn any_hit(&self, ray: &Ray, max_distance: f32) -> bool {
+        let mut found = false;
+        self.traverse(
+            |bbox| ray.intersect_bbox(bbox).is_some_and(|t| t <= max_distance),
+            |id| {
+                found = ray
+                    .intersect_triangle(self.triangles[id])
+                    .is_some_and(|(t, _)| t <= max_distance);
+                !found
+            },
+        );
+        found
+    }
+
+    /// Номера треугольников, пересекающих сферу, по возрастанию
+    pub fn overlap_sphere(&self, center: Vec3, radius: f32) -> Vec<usize> {
+        let mut result = Vec::new();
+        let radius_squared = radius * radius;
+        self.traverse(
+            |bbox| bbox.overlaps_sphere(center, radius),
+            |id| {
+                let closest = closest_point_on_triangle(center, self.triangles[id]);
+                if (closest - center).norm_squared() <= radius_squared {
+                    result.push(id);
+                }
+                true
+            },
+        );
+        result.sort_unstable();
+        result
+    }
+
+    /// Номера треугольников, пересекающих габариты, по возрастанию
+    pub fn overlap_bbox(&self, bbox: &BoundingBox) -> Vec<usize> {
+        let mut result = Vec::new();
+        self.traverse(
+            |node| node.overlaps(bbox),
+            |id| {
+                if triangle_overlaps_bbox(self.triangles[id], bbox) {
+                    result.push(id);
+                }
+                true
+            },
+        );
+        result.sort_unstable();
+        result
+    }
+}
+
+impl MeshBuilder {
+    /// BVH по всем треугольникам построителя
+    pub fn build_bvh(&self) -> MeshBvh {
+        MeshBvh::new(&self._vertices, &self._indices, 0)
+    }
+}
+
+#[cfg(test)]
+fn test_teapot() -> MeshBvh {
+    let mut builder = super::Mesh::builder("teapot");
+    builder.push_teapot().unwrap();
+    builder.build_bvh()
+}
+
+#[cfg(test)]
+fn random_rays(count: usize, bbox: &BoundingBox) -> Vec<Ray> {
+    use rand::{Rng, SeedableRng};
+    let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+    let center = (bbox.begin + bbox.end) * 0.5;
+    let radius = (bbox.end - bbox.begin).norm();
+    let mut random_point = |scale: f32| {
+        center + Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)) * scale
+    };
+    (0..count)
+        .map(|_| {
+            let origin = random_point(radius);
+            Ray::new(origin, random_point(radius * 0.3) - origin)
+        })
+        .collect()
+}
+
+#[test]
+fn mesh_bvh_matches_brute_force() {
+    let mut builder = super::Mesh::builder("teapot");
+    builder.push_teapot().unwrap();
+    let data = builder.mesh_data();
+    let bvh = test_teapot();
+    assert_eq!(bvh.triangle_count(), data.indices.len() / 3);
+    assert!(bvh.node_count() > 1);
+
+    let mut hits = 0;
+    for ray in random_rays(500, &bvh.bbox()) {
+        let expected = super::raycast::raycast_triangles(&ray, &data.vertices, &data.indices, 0, f32::INFINITY);
+        let found = bvh.closest_hit(&ray, f32::INFINITY);
+        assert_eq!(expected.is_some(), found.is_some());
+        assert_eq!(expected.is_some(), bvh.any_hit(&ray, f32::INFINITY));
+        if let (Some(expected), Some(found)) = (expected, found) {
+            hits += 1;
+            assert!((expected.distance - found.distance).abs() < 1.0e-4);
+            assert!((expected.uv - found.uv).norm() < 1.0e-3 || expected.triangle != found.triangle);
+            assert!(!bvh.any_hit(&ray, found.distance * 0.99));
+        }
+    }
+    assert!(hits > 50);
+
+    let bbox = bvh.bbox();
+    let center = (bbox.begin + bbox.end) * 0.5;
+    let size = bbox.end - bbox.begin;
+    for (offset, radius) in [(Vec3::zeros(), 0.4), (size * 0.3, 0.2), (size, 0.1)] {
+        let expected = (0..bvh.triangle_count())
+            .filter(|id| (closest_point_on_triangle(center + offset, bvh.triangle(*id)) - center - offset).norm() <= radius)
+            .collect::<Vec<_>>();
+        assert_eq!(bvh.overlap_sphere(center + offset, radius), expected);
+
+        let query = BoundingBox {
+            begin: center + offset - Vec3::repeat(radius),
+            end: center + offset + Vec3::repeat(radius),
+        };
+        let expected = (0..bvh.triangle_count())
+            .filter(|id| triangle_overlaps_bbox(bvh.triangle(*id), &query))
+            .collect::<Vec<_>>();
+        assert_eq!(bvh.overlap_bbox(&query), expected);
+    }
+}
+
+#[test]
+fn mesh_bvh_triangle_primitives() {
+    let triangle = [Vec3::zeros(), Vec3::x(), Vec3::y()];
+    assert_eq!(closest_point_on_triangle(Vec3::new(0.25, 0.25, 1.0), triangle), Vec3::new(0.25, 0.25, 0.0));
+    assert_eq!(closest_point_on_triangle(Vec3::new(-1.0, -1.0, 0.0), triangle), Vec3::zeros());
+    assert_eq!(closest_point_on_triangle(Vec3::new(1.0, 1.0, 0.0), triangle), Vec3::new(0.5, 0.5, 0.0));
+
+    let unit = |center: Vec3| BoundingBox {
+        begin: center - Vec3::repeat(0.1),
+        end: center + Vec3::repeat(0.1),
+    };
+    assert!(triangle_overlaps_bbox(triangle, &unit(Vec3::new(0.2, 0.2, 0.0))));
+    // Габариты треугольника пересекаются, но сам треугольник проходит мимо
+    assert!(!triangle_overlaps_bbox(triangle, &unit(Vec3::new(0.65, 0.65, 0.0))));
+    assert!(!triangle_overlaps_bbox(triangle, &unit(Vec3::new(0.2, 0.2, 0.2))));
+}
+
+#[test]
+fn mesh_bvh_skips_invalid_triangles() {
+    let vertices = [Vec3::zeros(), Vec3::x(), Vec3::y(), Vec3::z()].map(|v| VkVertex {
+        v_pos: v.into(),
+        ..Default::default()
+    });
+    // Индекс со смещением выходит за пределы u32
+    let bvh = MeshBvh::new(&vertices, &[0, 1, 2, u32::MAX, 0, 1, 5, 0, 1], 1);
+    assert_eq!(bvh.triangle_count(), 3);
+    let hit = bvh.closest_hit(&Ray::new(Vec3::new(0.0, 0.25, 0.25) + Vec3::x() * 2.0, -Vec3::x()), f32::INFINITY);
+    assert_eq!(hit.map(|hit| hit.triangle), Some(0));
+}
+
+/// Сравнение скорости с перебором: `cargo test --release mesh_bvh_benchmark -- --ignored --nocapture`
+#[test]
+#[ignore]
+fn mesh_bvh_benchmark() {
+    let mut builder = super::Mesh::builder("spheres");
+    for i in 0..16 {
+        let first_vertex = builder._vertices.len();
+        builder.push_uv_sphere(1.0, 64, 32);
+        let offset = Vec3::new((i % 4) as f32 * 2.5, (i / 4) as f32 * 2.5, 0.0);
+        for vertex in &mut builder._vertices[first_vertex..] {
+            vertex.v_pos = (Vec3::from(vertex.v_pos) + offset).into();
+        }
+    }
+    let data = builder.mesh_data();
+    let started = std::time::Instant::now();
+    let bvh = builder.build_bvh();
+    let build_time = started.elapsed();
+    let rays = random_rays(2000, &bvh.bbox());
+
+    let started = std::time::Instant::now();
+    let bvh_hits = rays.iter().filter(|ray| bvh.closest_hit(ray, f32::INFINITY).is_some()).count();
+    let bvh_time = started.elapsed();
+    let started = std::time::Instant::now();
+    let brute_hits = rays
+        .iter()
+        .filter(|ray| super::raycast::raycast_triangles(ray, &data.vertices, &data.indices, 0, f32::INFINITY).is_some())
+        .count();
+    let brute_time = started.elapsed();
+    assert_eq!(bvh_hits, brute_hits);
+    println!(
+        "{} triangles, {} nodes: build {build_time:?}, {} rays: BVH {bvh_time:?}, brute force {brute_time:?}",
+        bvh.triangle_count(),
+        bvh.node_count(),
+        rays.len()
+    );
+    assert!(bvh_time < brute_time);
+}
@@ -0,0 +1,346 @@
+//! Компактный формат вершин: 32 байта вместо 88 у [`VkVertex`].
+//!
+//! * позиция — snorm16 в пределах габаритов меша, в `w` знак бинормали;
+//!   шейдер распаковывает её по [`PositionQuantization`] из uniform-структуры `quantization`;
+//! * нормаль и касательная — октаэдрическая развёртка в snorm16,
+//!   бинормаль восстанавливается как `cross(нормаль, касательная) * знак`;
+//! * обе развёртки — half float;
+//! * три кости — u8, веса — unorm8, нормированные на сумму.
+//!
+//! Шейдер получает прежние `v_pos`, `v_nor` и остальные атрибуты через макросы
+//! из [`crate::shader::Shader::compact_vertex_attributes`].
+use bytemuck::{Pod, Zeroable};
+use half::f16;
+
+use super::{BoundingBox, VkVertex};
+use crate::error::DsgeError;
+use crate::types::{Vec2, Vec3};
+
+/// Формат вершинного буфера меша
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum VertexFormat {
+    /// [`VkVertex`]
+    #[default]
+    Full,
+    /// [`VkCompactVertex`]
+    Compact,
+}
+
+/// Представление компактной вершины для vulkano
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Pod, Zeroable, vulkano::pipeline::graphics::vertex_input::Vertex)]
+pub struct VkCompactVertex {
+    #[format(R16G16B16A16_SNORM)]
+    pub c_pos: [i16; 4],
+    #[format(R16G16B16A16_SNORM)]
+    pub c_nt: [i16; 4],
+    #[format(R16G16B16A16_SFLOAT)]
+    pub c_tex: [u16; 4],
+    #[format(R8G8B8A8_UINT)]
+    pub c_grp: [u8; 4],
+    #[format(R8G8B8A8_UNORM)]
+    pub c_wgt: [u8; 4],
+}
+
+/// Отображение габаритов меша на диапазон snorm16:
+/// позиция вершины равна `offset + c_pos.xyz * scale`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PositionQuantization {
+    pub offset: Vec3,
+    pub scale: Vec3,
+}
+
+impl Default for PositionQuantization {
+    fn default() -> Self {
+        Self {
+            offset: Vec3::zeros(),
+            scale: Vec3::repeat(1.0),
+        }
+    }
+}
+
+impl PositionQuantization {
+    /// Центр и половина размеров габаритов; по вырожденным осям масштаб единичный
+    pub fn from_bbox(bbox: &BoundingBox) -> Self {
+        let half_size = (bbox.end - bbox.begin) * 0.5;
+        Self {
+            offset: (bbox.begin + bbox.end) * 0.5,
+            scale: half_size.map(|half| if half > 0.0 { half } else { 1.0 }),
+        }
+    }
+
+    pub fn encode(&self, position: Vec3) -> [i16; 3] {
+        (position - self.offset).component_div(&self.scale).map(snorm16).into()
+    }
+
+    pub fn decode(&self, packed: [i16; 3]) -> Vec3 {
+        self.offset + Vec3::from(packed.map(unsnorm16)).component_mul(&self.scale)
+    }
+
+    /// Содержимое uniform-структуры `quantization`: `vec4 offset`, `vec4 scale`
+    pub fn uniform_data(&self) -> [f32; 8] {
+        [
+            self.offset.x, self.offset.y, self.offset.z, 0.0,
+            self.scale.x, self.scale.y, self.scale.z, 0.0,
+        ]
+    }
+}
+
+/// Допустимые ошибки квантования
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QuantizationTolerance {
+    /// Ошибка позиции в долях наибольшего размера габаритов меша
+    pub position: f32,
+    /// Угол отклонения нормали и касательной в радианах
+    pub direction: f32,
+    /// Ошибка текстурных координат
+    pub uv: f32,
+    /// Ошибка нормированных весов костей
+    pub weight: f32,
+}
+
+impl Default for QuantizationTolerance {
+    fn default() -> Self {
+        Self {
+            position: 1.0e-3,
+            direction: 1.0e-3,
+            uv: 1.0e-3,
+            weight: 4.0e-3,
+        }
+    }
+}
+
+/// Наибольшие ошибки квантования меша в тех же единицах, что и [`QuantizationTolerance`]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct QuantizationError {
+    pub position: f32,
+    pub direction: f32,
+    pub uv: f32,
+    pub weight: f32,
+}
+
+impl QuantizationError {
+    fn add(&mut self, other: &Self) {
+        self.position = self.position.max(other.position);
+        self.direction = self.direction.max(other.direction);
+        self.uv = self.uv.max(other.uv);
+        self.weight = self.weight.max(other.weight);
+    }
+}
+
+fn half(value: f32) -> u16 {
+    f16::from_f32(value).to_bits()
+}
+
+fn unhalf(bits: u16) -> f32 {
+    f16::from_bits(bits).to_f32()
+}
+
+fn snorm16(value: f32) -> i16 {
+    (value.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16
+}
+
+fn unsnorm16(value: i16) -> f32 {
+    (value as f32 / i16::MAX as f32).max(-1.0)
+}
+
+fn sign_not_zero(value: f32) -> f32 {
+    if value >= 0.0 { 1.0 } else { -1.0 }
+}
+
+/// Октаэдрическая развёртка единичного вектора в квадрат [-1; 1]
+pub fn oct_encode(direction: Vec3) -> Vec2 {
+    let l1 = direction.x.abs() + direction.y.abs() + direction.z.abs();
+    if l1 == 0.0 {
+        return Vec2::zeros();
+    }
+    let n = direction / l1;
+    if n.z >= 0.0 {
+        Vec2::new(n.x, n.y)
+    } else {
+        Vec2::new(
+            (1.0 - n.y.abs()) * sign_not_zero(n.x),
+            (1.0 - n.x.abs()) * sign_not_zero(n.y),
+        )
+    }
+}
+
+/// Обратное [`oct_encode`] преобразование, как `oct_decode` в шейдере
+pub fn oct_decode(encoded: Vec2) -> Vec3 {
+    let mut n = Vec3::new(encoded.x, encoded.y, 1.0 - encoded.x.abs() - encoded.y.abs());
+    let t = (-n.z).max(0.0);
+    n.x += if n.x >= 0.0 { -t } else { t };
+    n.y += if n.y >= 0.0 { -t } else { t };
+    n.normalize()
+}
+
+/// Сжатие вершины с позицией в пределах `quantization`.
+/// Ошибка, если номер кости не помещается в u8.
+pub fn compress(vertex: &VkVertex, quantization: &PositionQuantization) -> Result<VkCompactVertex, DsgeError> {
+    let (nor, tan, bin) = (Vec3::from(vertex.v_nor), Vec3::from(vertex.v_tan), Vec3::from(vertex.v_bin));
+    let handedness = sign_not_zero(nor.cross(&tan).dot(&bin));
+    let [nx, ny] = oct_encode(nor).into();
+    let [tx, ty] = oct_encode(tan).into();
+    let mut c_grp = [0u8; 4];
+    for (packed, group) in c_grp.iter_mut().zip(vertex.v_grp) {
+        *packed = u8::try_from(group).map_err(|_| {
+            DsgeError::InvalidData(format!("bone index {group} does not fit into the compact vertex format"))
+        })?;
+    }
+    let weight_sum = vertex.v_wgt.iter().sum::<f32>();
+    let mut c_wgt = [0u8; 4];
+    if weight_sum > 0.0 {
+        for (packed, weight) in c_wgt.iter_mut().zip(vertex.v_wgt) {
+            *packed = (weight / weight_sum * 255.0).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    let [px, py, pz] = quantization.encode(vertex.v_pos.into());
+    Ok(VkCompactVertex {
+        c_pos: [px, py, pz, snorm16(handedness)],
+        c_nt: [snorm16(nx), snorm16(ny), snorm16(tx), snorm16(ty)],
+        c_tex: [
+            half(vertex.v_tex1[0]),
+            half(vertex.v_tex1[1]),
+            half(vertex.v_tex2[0]),
+            half(vertex.v_tex2[1]),
+        ],
+        c_grp,
+        c_wgt,
+    })
+}
+
+/// Распаковка вершины так же, как в шейдере
+pub fn decompress(vertex: &VkCompactVertex, quantization: &PositionQuantization) -> VkVertex {
+    let [nx, ny, tx, ty] = vertex.c_nt.map(unsnorm16);
+    let nor = oct_decode(Vec2::new(nx, ny));
+    let tan = oct_decode(Vec2::new(tx, ty));
+    let [px, py, pz, handedness] = vertex.c_pos;
+    VkVertex {
+        v_pos: quantization.decode([px, py, pz]).into(),
+        v_nor: nor.into(),
+        v_tan: tan.into(),
+        v_bin: (nor.cross(&tan) * unsnorm16(handedness)).into(),
+        v_tex1: [unhalf(vertex.c_tex[0]), unhalf(vertex.c_tex[1])],
+        v_tex2: [unhalf(vertex.c_tex[2]), unhalf(vertex.c_tex[3])],
+        v_grp: [vertex.c_grp[0] as u32, vertex.c_grp[1] as u32, vertex.c_grp[2] as u32],
+        v_wgt: [0, 1, 2].map(|i| vertex.c_wgt[i] as f32 / 255.0),
+    }
+}
+
+/// Угол между направлениями; нулевые векторы считаются незаданными
+fn angle_between(a: [f32; 3], b: [f32; 3]) -> f32 {
+    match (Vec3::from(a).try_normalize(1.0e-12), Vec3::from(b).try_normalize(1.0e-12)) {
+        (Some(a), Some(b)) => a.dot(&b).clamp(-1.0, 1.0).acos(),
+        _ => 0.0,
+    }
+}
+
+fn normalized_weights(weights: [f32; 3]) -> [f32; 3] {
+    let sum = weights.iter().sum::<f32>();
+    if sum > 0.0 { weights.map(|w| w / sum) } else { [0.0; 3] }
+}
+
+fn max_difference(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max)
+}
+
+/// Ошибка квантования одной вершины; `scale` — наибольший размер габаритов меша
+fn vertex_error(source: &VkVertex, packed: &VkVertex, scale: f32) -> QuantizationError {
+    let uv = |v: &VkVertex| [v.v_tex1[0], v.v_tex1[1], v.v_tex2[0], v.v_tex2[1]];
+    QuantizationError {
+        position: max_difference(&source.v_pos, &packed.v_pos) / scale,
+        direction: angle_between(source.v_nor, packed.v_nor).max(angle_between(source.v_tan, packed.v_tan)),
+        uv: max_difference(&uv(source), &uv(packed)),
+        weight: max_difference(&normalized_weights(source.v_wgt), &normalized_weights(packed.v_wgt)),
+    }
+}
+
+/// Сжатие вершин меша с проверкой ошибок квантования.
+/// Позиции квантуются в пределах габаритов всех вершин.
+pub fn compress_vertices(
+    vertices: &[VkVertex],
+    tolerance: &QuantizationTolerance,
+) -> Result<(Vec<VkCompactVertex>, PositionQuantization, QuantizationError), DsgeError> {
+    let mut bbox = BoundingBox::initial();
+    for vertex in vertices {
+        bbox.add_point(vertex.v_pos.into());
+    }
+    let (quantization, scale) = if vertices.is_empty() {
+        (PositionQuantization::default(), 1.0)
+    } else {
+        (PositionQuantization::from_bbox(&bbox), (bbox.end - bbox.begin).max().max(f32::MIN_POSITIVE))
+    };
+    let mut error = QuantizationError::default();
+    let mut compact = Vec::with_capacity(vertices.len());
+    for (index, vertex) in vertices.iter().enumerate() {
+        let packed = compress(vertex, &quantization).map_err(|err| err.context(format!("vertex {index}")))?;
+        error.add(&vertex_error(vertex, &decompress(&packed, &quantization), scale));
+        compact.push(packed);
+    }
+    let exceeded = [
+        ("position", error.position, tolerance.position),
+        ("normal/tangent direction", error.direction, tolerance.direction),
+        ("uv", error.uv, tolerance.uv),
+        ("bone weight", error.weight, tolerance.weight),
+    ]
+    .into_iter()
+    .find(|(_, value, limit)| value.is_nan() || value > limit);
+    match exceeded {
+        Some((attribute, value, limit)) => Err(DsgeError::InvalidData(format!(
+            "compact vertex {attribute} quantization error {value} exceeds {limit}"
+        ))),
+        None => Ok((compact, quantization, error)),
+    }
+}
+
+#[test]
+fn mesh_compact_vertex_roundtrip() {
+    assert_eq!(std::mem::size_of::<VkCompactVertex>(), 32);
+    assert_eq!(std::mem::size_of::<VkVertex>(), 88);
+
+    let s = std::f32::consts::FRAC_1_SQRT_2;
+    let vertex = |pos: [f32; 3], nor: [f32; 3], tan: [f32; 3], bin: [f32; 3]| VkVertex {
+        v_pos: pos,
+        v_nor: nor,
+        v_tan: tan,
+        v_bin: bin,
+        v_tex1: [0.25, 0.75],
+        v_tex2: [0.5, 1.0],
+        v_grp: [3, 255, 0],
+        v_wgt: [2.0, 2.0, 0.0],
+    };
+    let vertices = vec![
+        vertex([0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        // Нижняя полусфера и левый базис
+        vertex([1.0, -2.0, 0.5], [0.0, -s, -s], [1.0, 0.0, 0.0], [0.0, s, -s]),
+        vertex([-1.0, 2.0, 3.0], [0.6, 0.0, -0.8], [0.0, 1.0, 0.0], [-0.8, 0.0, -0.6]),
+    ];
+    let (compact, quantization, error) = compress_vertices(&vertices, &QuantizationTolerance::default()).unwrap();
+    assert!(error.position < 2.0e-5 && error.direction < 1.0e-3 && error.uv == 0.0);
+    assert_eq!(quantization.offset, Vec3::new(0.0, 0.0, 1.5));
+    let close = |a: [f32; 3], b: [f32; 3]| (Vec3::from(a) - Vec3::from(b)).norm() < 1.0e-3;
+    for (source, packed) in vertices.iter().zip(&compact) {
+        let decoded = decompress(packed, &quantization);
+        assert!(close(decoded.v_nor, source.v_nor));
+        assert!(close(decoded.v_tan, source.v_tan));
+        assert!(close(decoded.v_bin, source.v_bin));
+        assert_eq!(decoded.v_grp, [3, 255, 0]);
+        // Веса нормируются на сумму и хранятся с точностью 1/255
+        assert!((Vec3::from(decoded.v_wgt) - Vec3::new(0.5, 0.5, 0.0)).norm() < 4.0e-3);
+    }
+
+    // Позиции отсчитываются от габаритов, поэтому удаление от начала координат не влияет на точность
+    let far = vertices
+        .iter()
+        .map(|v| VkVertex { v_pos: [v.v_pos[0] + 1000.3, v.v_pos[1], v.v_pos[2]], ..*v })
+        .collect::<Vec<_>>();
+    let (compact, quantization, error) = compress_vertices(&far, &QuantizationTolerance::default()).unwrap();
+    assert!(error.position < 2.0e-5, "{error:?}");
+    assert!((Vec3::from(decompress(&compact[1], &quantization).v_pos) - Vec3::new(1001.3, -2.0, 0.5)).norm() < 1.0e-3);
+    let mut many_bones = vertices.clone();
+    many_bones[2].v_grp[1] = 256;
+    assert!(matches!(
+        compress_vertices(&many_bones, &QuantizationTolerance::default()),
+        Err(DsgeError::InvalidData(_))
+    ));
+}
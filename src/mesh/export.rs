@@ -0,0 +1,286 @@
+//! Экспорт мешей в OBJ и бинарный PLY для просмотра во внешних программах.
+//!
+//! OBJ хранит позиции, нормали и первый слой текстурных координат.
+//! PLY дополнительно хранит касательные (`tx ty tz`), бинормали (`bx by bz`),
+//! второй слой текстурных координат (`s2 t2`), группы вершин и их веса.
+//! Координата V переворачивается обратно, как при импорте OBJ.
+use std::collections::HashMap;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::CopyBufferInfoTyped;
+use vulkano::memory::allocator::{AllocationCreateInfo, GenericMemoryAllocator, MemoryTypeFilter, Suballocator};
+use vulkano::sync::GpuFuture;
+
+use super::format::{self, MeshData};
+use super::compact;
+use super::{MeshView, VertexBufferRef, VkVertex};
+use crate::command_buffer::CommandBufferFather;
+use crate::error::DsgeError;
+
+pub fn write_obj<W: Write>(writer: &mut W, mesh: &MeshData) -> Result<(), DsgeError> {
+    mesh.validate()?;
+    writeln!(writer, "# DSGE mesh export")?;
+    writeln!(writer, "# {} vertices, {} triangles", mesh.vertices.len(), mesh.indices.len() / 3)?;
+    for v in &mesh.vertices {
+        writeln!(writer, "v {} {} {}", v.v_pos[0], v.v_pos[1], v.v_pos[2])?;
+    }
+    for v in &mesh.vertices {
+        writeln!(writer, "vt {} {}", v.v_tex1[0], 1.0 - v.v_tex1[1])?;
+    }
+    for v in &mesh.vertices {
+        writeln!(writer, "vn {} {} {}", v.v_nor[0], v.v_nor[1], v.v_nor[2])?;
+    }
+    for triangle in mesh.indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0] + 1, triangle[1] + 1, triangle[2] + 1];
+        writeln!(writer, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}")?;
+    }
+    Ok(())
+}
+
+pub fn write_ply<W: Write>(writer: &mut W, mesh: &MeshData) -> Result<(), DsgeError> {
+    mesh.validate()?;
+    let mut header = format!(
+        "ply\nformat binary_little_endian 1.0\ncomment DSGE mesh export\nelement vertex {}\n",
+        mesh.vertices.len()
+    );
+    let mut properties = vec!["x", "y", "z", "nx", "ny", "nz", "tx", "ty", "tz", "bx", "by", "bz", "s", "t"];
+    if mesh.uv_count > 1 {
+        properties.extend(["s2", "t2"]);
+    }
+    for name in properties {
+        header += &format!("property float {name}\n");
+    }
+    if mesh.deformed {
+        header += "property uint group0\nproperty uint group1\nproperty uint group2\n";
+        header += "property float weight0\nproperty float weight1\nproperty float weight2\n";
+    }
+    header += &format!(
+        "element face {}\nproperty list uchar uint vertex_indices\nend_header\n",
+        mesh.indices.len() / 3
+    );
+    writer.write_all(header.as_bytes())?;
+
+    let mut data = Vec::new();
+    for v in &mesh.vertices {
+        let uv = [v.v_tex1[0], 1.0 - v.v_tex1[1]];
+        for value in [&v.v_pos[..], &v.v_nor, &v.v_tan, &v.v_bin, &uv].concat() {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        if mesh.uv_count > 1 {
+            for value in [v.v_tex2[0], 1.0 - v.v_tex2[1]] {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        if mesh.deformed {
+            for group in v.v_grp {
+                data.extend_from_slice(&group.to_le_bytes());
+            }
+            for weight in v.v_wgt {
+                data.extend_from_slice(&weight.to_le_bytes());
+            }
+        }
+    }
+    for triangle in mesh.indices.chunks_exact(3) {
+        data.push(3);
+        for index in triangle {
+            data.extend_from_slice(&index.to_le_bytes());
+        }
+    }
+    writer.write_all(&data)?;
+    Ok(())
+}
+
+/// Сохранение меша в файл, формат выбирается по расширению:
+/// `.obj`, `.ply`, иначе собственный формат движка (см. [`format`])
+pub fn save_mesh<P: AsRef<Path>>(path: P, mesh: &MeshData) -> Result<(), DsgeError> {
+    let path = path.as_ref();
+    let file = std::fs::File::create(path).map_err(|err| DsgeError::io(path, err))?;
+    let mut writer = BufWriter::new(file);
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "obj" => write_obj(&mut writer, mesh),
+        "ply" => write_ply(&mut writer, mesh),
+        _ => format::write_mesh(&mut writer, mesh),
+    }
+    .map_err(|err| err.context(path.display()))?;
+    writer.flush().map_err(|err| DsgeError::io(path, err))
+}
+
+/// Меш из диапазона индексов: в результат попадают только используемые вершины,
+/// индексы пересчитываются относительно них
+pub fn extract_range(vertices: &[VkVertex], indices: &[u32], vertex_offset: u32) -> Result<MeshData, DsgeError> {
+    let mut remap = HashMap::new();
+    let mut mesh = MeshData {
+        uv_count: 1,
+        version: format::VERSION,
+        ..Default::default()
+    };
+    for index in indices {
+        let source = (*index + vertex_offset) as usize;
+        let vertex = vertices.get(source).ok_or_else(|| {
+            DsgeError::InvalidData(format!("index {source} is out of range for {} vertices", vertices.len()))
+        })?;
+        let local = *remap.entry(source).or_insert_with(|| {
+            mesh.vertices.push(*vertex);
+            mesh.vertices.len() as u32 - 1
+        });
+        mesh.indices.push(local);
+    }
+    mesh.deformed = mesh.vertices.iter().any(|v| v.v_grp != [0; 3] || v.v_wgt != [0.0; 3]);
+    if mesh.vertices.iter().any(|v| v.v_tex2 != [0.0; 2]) {
+        mesh.uv_count = 2;
+    }
+    Ok(mesh)
+}
+
+/// Чтение меша или подмеша из буферов на GPU.
+/// Буферы должны быть созданы с `TRANSFER_SRC`, как в [`super::MeshBuilder::build`].
+/// Копируются только индексы подмеша и диапазон вершин, на которые они ссылаются.
+/// Вершины компактного формата распаковываются.
+pub fn read_back<A>(
+    mesh: &dyn MeshView,
+    command_buffer_father: &CommandBufferFather,
+    allocator: Arc<GenericMemoryAllocator<A>>,
+) -> Result<MeshData, DsgeError>
+where
+    A: Suballocator + Send + 'static,
+{
+    let command = mesh.indirect_command(0, 1);
+    let first_index = command.first_index as u64;
+    let index_range = first_index..first_index + command.index_count as u64;
+    if index_range.end > mesh.index_buffer().len() {
+        return Err(DsgeError::InvalidArgument(format!(
+            "mesh {} index range {index_range:?} is out of the index buffer",
+            mesh.name()
+        )));
+    }
+    let indices = download(
+        mesh.index_buffer().clone().slice(index_range),
+        command_buffer_father,
+        allocator.clone(),
+    )?;
+    let (Some(&min), Some(&max)) = (indices.iter().min(), indices.iter().max()) else {
+        return extract_range(&[], &[], 0);
+    };
+    let vertex_offset = command.vertex_offset as u64;
+    let vertex_range = vertex_offset + min as u64..vertex_offset + max as u64 + 1;
+    if vertex_range.end > mesh.vertex_buffer().len() {
+        return Err(DsgeError::InvalidData(format!(
+            "mesh {} vertex range {vertex_range:?} is out of the vertex buffer",
+            mesh.name()
+        )));
+    }
+    let vertices = match mesh.vertex_buffer() {
+        VertexBufferRef::Full(buffer) => {
+            download(buffer.clone().slice(vertex_range), command_buffer_father, allocator)?
+        }
+        VertexBufferRef::Compact(buffer, quantization) => {
+            download(buffer.clone().slice(vertex_range), command_buffer_father, allocator)?
+                .iter()
+                .map(|vertex| compact::decompress(vertex, quantization))
+                .collect()
+        }
+    };
+    let indices = indices.iter().map(|index| index - min).collect::<Vec<_>>();
+    extract_range(&vertices, &indices, 0).map_err(|err| err.context(mesh.name()))
+}
+
+/// Копирует диапазон буфера на хост и дожидается завершения копирования
+fn download<T, A>(
+    source: Subbuffer<[T]>,
+    command_buffer_father: &CommandBufferFather,
+    allocator: Arc<GenericMemoryAllocator<A>>,
+) -> Result<Vec<T>, DsgeError>
+where
+    T: BufferContents + Copy,
+    A: Suballocator + Send + 'static,
+{
+    let target = readable_copy(&source, allocator)?;
+    let (copied, future) = command_buffer_father.execute_in_new_primary(None, |cbb| {
+        cbb.copy_buffer(CopyBufferInfoTyped::buffers(source, target.clone()))?;
+        Ok::<_, DsgeError>(())
+    })?;
+    copied?;
+    future
+        .then_signal_fence_and_flush()
+        .map_err(|err| DsgeError::vulkan("Failed to submit mesh readback", err))?
+        .wait(None)
+        .map_err(|err| DsgeError::vulkan("Failed to wait for mesh readback", err))?;
+    let data = target
+        .read()
+        .map_err(|err| DsgeError::vulkan("Failed to map mesh readback buffer", err))?;
+    Ok(data.to_vec())
+}
+
+fn readable_copy<T, A>(buffer: &Subbuffer<[T]>, allocator: Arc<GenericMemoryAllocator<A>>) -> Result<Subbuffer<[T]>, DsgeError>
+where
+    T: BufferContents,
+    A: Suballocator + Send + 'static,
+{
+    Buffer::new_slice(
+        allocator,
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_DST,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::HOST_RANDOM_ACCESS | MemoryTypeFilter::PREFER_HOST,
+            ..Default::default()
+        },
+        buffer.len(),
+    )
+    .map_err(|err| DsgeError::vulkan("Failed to allocate readback buffer", err))
+}
+
+#[cfg(test)]
+fn test_quad() -> MeshData {
+    super::obj::parse_obj("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvn 0 0 1\nf 1/1/1 2/2/1 3/3/1 4/4/1\n")
+        .unwrap()
+        .mesh
+}
+
+#[test]
+fn mesh_export_obj_roundtrip() {
+    let mesh = test_quad();
+    let mut obj = Vec::new();
+    write_obj(&mut obj, &mesh).unwrap();
+    let read = super::obj::parse_obj(std::str::from_utf8(&obj).unwrap()).unwrap().mesh;
+    assert_eq!(read.indices, mesh.indices);
+    assert!(bytemuck::cast_slice::<_, u8>(&read.vertices) == bytemuck::cast_slice::<_, u8>(&mesh.vertices));
+}
+
+#[test]
+fn mesh_export_ply() {
+    let mut mesh = test_quad();
+    mesh.uv_count = 2;
+    let mut ply = Vec::new();
+    write_ply(&mut ply, &mesh).unwrap();
+    let header_end = ply.windows(11).position(|w| w == b"end_header\n").unwrap() + 11;
+    let header = std::str::from_utf8(&ply[..header_end]).unwrap();
+    assert!(header.contains("element vertex 4\n"));
+    assert!(header.contains("property float s2\n"));
+    assert!(header.contains("element face 2\n"));
+    assert!(!header.contains("group0"));
+    assert_eq!(ply.len() - header_end, 4 * 16 * 4 + 2 * (1 + 3 * 4));
+    // Координата t второй вершины перевёрнута обратно
+    let t = &ply[header_end + 16 * 4 + 13 * 4..][..4];
+    assert_eq!(f32::from_le_bytes(t.try_into().unwrap()), 0.0);
+}
+
+#[test]
+fn mesh_export_extract_range() {
+    let mut vertices = test_quad().vertices;
+    vertices.insert(0, VkVertex::default());
+    // Второй треугольник квадрата, вершины смещены на одну
+    let mesh = extract_range(&vertices, &[0, 2, 3], 1).unwrap();
+    assert_eq!(mesh.indices, [0, 1, 2]);
+    assert_eq!(mesh.vertices[1].v_pos, [1.0, 1.0, 0.0]);
+    assert!(!mesh.deformed);
+    assert!(extract_range(&vertices, &[0, 4, 1], 1).is_err());
+}
@@ -0,0 +1,624 @@
+//! Формат файлов мешей.
+//!
+//! Версия 3 (все числа little-endian):
+//!
+//! | поле              | тип                                    |
+//! |-------------------|----------------------------------------|
+//! | сигнатура         | `b"DSGM"`                              |
+//! | версия            | `u16`                                  |
+//! | число атрибутов   | `u16`                                  |
+//! | число вершин      | `u32`                                  |
+//! | число индексов    | `u32`                                  |
+//! | атрибуты          | `[семантика u8, тип u8, компоненты u8, 0u8]` |
+//! | CRC32 заголовка   | `u32`                                  |
+//! | потоки атрибутов  | по потоку на атрибут в порядке описания |
+//! | CRC32 вершин      | `u32`                                  |
+//! | индексы           | `[u32]`                                |
+//! | CRC32 индексов    | `u32`                                  |
+//! | число целей морфинга | `u32`                               |
+//! | цели морфинга     | по блоку на цель                       |
+//!
+//! Блок цели морфинга: длина имени `u16`, имя в UTF-8, смещения позиций,
+//! нормалей и касательных всех вершин (`[f32; 3]` на вершину для каждого потока)
+//! и CRC32 блока.
+//!
+//! Версия 3 добавила цели морфинга. Версия 2 добавила атрибут весов групп. У файлов версии 1 и старого формата
+//! с группами вся вершина привязывается к первой группе.
+//!
+//! Файлы без сигнатуры читаются как старый формат без заголовка (версия 0).
+use std::io::{self, Read, Write};
+
+use super::morph::{self, MorphTarget};
+use super::{Vertex, VkVertex};
+use crate::error::DsgeError;
+use crate::types::*;
+
+pub const MAGIC: [u8; 4] = *b"DSGM";
+/// Последняя поддерживаемая версия формата
+pub const VERSION: u16 = 3;
+/// Первая версия с целями морфинга
+const MORPH_VERSION: u16 = 3;
+/// Версия, которая присваивается файлам старого формата без заголовка
+pub const LEGACY_VERSION: u16 = 0;
+
+/// Семантика атрибута вершины
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VertexAttribute {
+    Position = 0,
+    Normal = 1,
+    Bitangent = 2,
+    Tangent = 3,
+    TexCoord1 = 4,
+    TexCoord2 = 5,
+    Groups = 6,
+    Weights = 7,
+}
+
+/// Тип компонент атрибута
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComponentType {
+    F32 = 0,
+    U32 = 1,
+}
+
+impl VertexAttribute {
+    const ALL: [Self; 8] = [
+        Self::Position,
+        Self::Normal,
+        Self::Bitangent,
+        Self::Tangent,
+        Self::TexCoord1,
+        Self::TexCoord2,
+        Self::Groups,
+        Self::Weights,
+    ];
+
+    fn from_u8(value: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|attr| *attr as u8 == value)
+    }
+
+    /// Тип и количество компонент, в которых атрибут хранится в файле
+    pub fn layout(self) -> (ComponentType, u8) {
+        match self {
+            Self::Position | Self::Normal | Self::Bitangent | Self::Tangent | Self::Weights => {
+                (ComponentType::F32, 3)
+            }
+            Self::TexCoord1 | Self::TexCoord2 => (ComponentType::F32, 2),
+            Self::Groups => (ComponentType::U32, 3),
+        }
+    }
+
+    fn components(self) -> usize {
+        self.layout().1 as usize
+    }
+
+    fn get(self, vertex: &VkVertex) -> &[f32] {
+        match self {
+            Self::Position => &vertex.v_pos,
+            Self::Normal => &vertex.v_nor,
+            Self::Bitangent => &vertex.v_bin,
+            Self::Tangent => &vertex.v_tan,
+            Self::TexCoord1 => &vertex.v_tex1,
+            Self::TexCoord2 => &vertex.v_tex2,
+            Self::Groups => bytemuck::cast_slice(&vertex.v_grp),
+            Self::Weights => &vertex.v_wgt,
+        }
+    }
+
+    fn get_mut(self, vertex: &mut VkVertex) -> &mut [f32] {
+        match self {
+            Self::Position => &mut vertex.v_pos,
+            Self::Normal => &mut vertex.v_nor,
+            Self::Bitangent => &mut vertex.v_bin,
+            Self::Tangent => &mut vertex.v_tan,
+            Self::TexCoord1 => &mut vertex.v_tex1,
+            Self::TexCoord2 => &mut vertex.v_tex2,
+            Self::Groups => bytemuck::cast_slice_mut(&mut vertex.v_grp),
+            Self::Weights => &mut vertex.v_wgt,
+        }
+    }
+}
+
+/// Содержимое файла меша
+#[derive(Clone, Default)]
+pub struct MeshData {
+    pub vertices: Vec<VkVertex>,
+    /// Индексы относительно начала `vertices`
+    pub indices: Vec<u32>,
+    /// Есть ли у вершин группы и веса (для деформации скелетом)
+    pub deformed: bool,
+    /// Количество слоёв текстурных координат (1 или 2)
+    pub uv_count: u8,
+    /// Версия формата, из которой прочитан меш
+    pub version: u16,
+    /// Цели морфинга, смещения заданы для всех вершин
+    pub morph_targets: Vec<MorphTarget>,
+}
+
+impl MeshData {
+    /// Проверка индексов: их количество кратно трём и все они меньше числа вершин
+    pub fn validate(&self) -> Result<(), DsgeError> {
+        if !self.indices.len().is_multiple_of(3) {
+            return Err(DsgeError::InvalidData(format!(
+                "index count {} is not a multiple of 3",
+                self.indices.len()
+            )));
+        }
+        let vertex_count = self.vertices.len();
+        match self.indices.iter().position(|index| *index as usize >= vertex_count) {
+            Some(pos) => Err(DsgeError::InvalidData(format!(
+                "index #{pos} = {} is out of range for {vertex_count} vertices",
+                self.indices[pos]
+            ))),
+            None => morph::validate(&self.morph_targets, vertex_count),
+        }
+    }
+
+    pub fn bbox(&self) -> super::BoundingBox {
+        let mut bbox = super::BoundingBox::initial();
+        for vertex in &self.vertices {
+            bbox.add_point(Vec3::from(vertex.v_pos));
+        }
+        bbox
+    }
+
+    fn attributes(&self) -> Vec<VertexAttribute> {
+        use VertexAttribute::*;
+        let mut attributes = vec![Position, Normal, Bitangent, Tangent, TexCoord1];
+        if self.uv_count > 1 {
+            attributes.push(TexCoord2);
+        }
+        if self.deformed {
+            attributes.push(Groups);
+            attributes.push(Weights);
+        }
+        attributes
+    }
+}
+
+/// Чтение меша любой поддерживаемой версии
+pub fn read_mesh<R: Read>(reader: &mut R) -> Result<MeshData, DsgeError> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic).map_err(read_error("header"))?;
+    let mesh = if magic == MAGIC {
+        read_versioned(reader)?
+    } else {
+        read_legacy(&mut io::Cursor::new(magic).chain(reader))?
+    };
+    mesh.validate()?;
+    Ok(mesh)
+}
+
+/// Запись меша в формате последней версии
+pub fn write_mesh<W: Write>(writer: &mut W, mesh: &MeshData) -> Result<(), DsgeError> {
+    mesh.validate()?;
+    let attributes = mesh.attributes();
+    let mut header = Vec::new();
+    header.extend_from_slice(&MAGIC);
+    header.extend_from_slice(&VERSION.to_le_bytes());
+    header.extend_from_slice(&(attributes.len() as u16).to_le_bytes());
+    header.extend_from_slice(&(mesh.vertices.len() as u32).to_le_bytes());
+    header.extend_from_slice(&(mesh.indices.len() as u32).to_le_bytes());
+    for attr in &attributes {
+        let (component_type, components) = attr.layout();
+        header.extend_from_slice(&[*attr as u8, component_type as u8, components, 0]);
+    }
+    header.extend_from_slice(&crc32(&header).to_le_bytes());
+
+    let mut vertices = Vec::with_capacity(mesh.vertices.len() * std::mem::size_of::<VkVertex>());
+    for attr in &attributes {
+        for vertex in &mesh.vertices {
+            for value in attr.get(vertex) {
+                vertices.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+    vertices.extend_from_slice(&crc32(&vertices).to_le_bytes());
+
+    let mut indices = Vec::with_capacity(mesh.indices.len() * 4 + 4);
+    for index in &mesh.indices {
+        indices.extend_from_slice(&index.to_le_bytes());
+    }
+    indices.extend_from_slice(&crc32(&indices).to_le_bytes());
+
+    for block in [header, vertices, indices] {
+        writer.write_all(&block)?;
+    }
+
+    writer.write_all(&(mesh.morph_targets.len() as u32).to_le_bytes())?;
+    for target in &mesh.morph_targets {
+        let target = target.resized(mesh.vertices.len());
+        let name = target.name.as_bytes();
+        let mut block = Vec::with_capacity(2 + name.len() + target.positions.len() * 36 + 4);
+        block.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        block.extend_from_slice(name);
+        for values in [&target.positions, &target.normals, &target.tangents] {
+            for value in values.iter().flatten() {
+                block.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        block.extend_from_slice(&crc32(&block).to_le_bytes());
+        writer.write_all(&block)?;
+    }
+    Ok(())
+}
+
+fn read_morph_targets<R: Read>(reader: &mut R, vertex_count: usize) -> Result<Vec<MorphTarget>, DsgeError> {
+    let count = read_u32(reader, "morph target count")? as usize;
+    if count > morph::MAX_MORPH_TARGETS {
+        return Err(DsgeError::InvalidData(format!(
+            "{count} morph targets, at most {} are supported",
+            morph::MAX_MORPH_TARGETS
+        )));
+    }
+    let mut targets = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mut name_len = [0u8; 2];
+        reader.read_exact(&mut name_len).map_err(read_error("morph target name"))?;
+        let mut block = name_len.to_vec();
+        let name = read_block(reader, u16::from_le_bytes(name_len) as usize, 1, "morph target name")?;
+        block.extend_from_slice(&name);
+        let name = String::from_utf8(name)
+            .map_err(|_| DsgeError::InvalidData("morph target name is not valid UTF-8".to_owned()))?;
+        let data = read_block(reader, vertex_count, 36, "morph target deltas")?;
+        block.extend_from_slice(&data);
+        verify_crc(reader, &block, "morph target")?;
+
+        let mut values = data
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()));
+        let mut stream = || {
+            (0..vertex_count)
+                .map(|_| [0; 3].map(|_| values.next().unwrap()))
+                .collect::<Vec<_>>()
+        };
+        targets.push(MorphTarget {
+            name,
+            positions: stream(),
+            normals: stream(),
+            tangents: stream(),
+        });
+    }
+    Ok(targets)
+}
+
+fn read_versioned<R: Read>(reader: &mut R) -> Result<MeshData, DsgeError> {
+    let mut header = MAGIC.to_vec();
+    let mut fixed = [0u8; 12];
+    reader.read_exact(&mut fixed).map_err(read_error("header"))?;
+    header.extend_from_slice(&fixed);
+    let version = u16::from_le_bytes([fixed[0], fixed[1]]);
+    if version == LEGACY_VERSION || version > VERSION {
+        return Err(DsgeError::FormatUnsupported(format!(
+            "mesh format version {version}, supported versions are 1..={VERSION}"
+        )));
+    }
+    let attribute_count = u16::from_le_bytes([fixed[2], fixed[3]]) as usize;
+    let vertex_count = u32::from_le_bytes(fixed[4..8].try_into().unwrap()) as usize;
+    let index_count = u32::from_le_bytes(fixed[8..12].try_into().unwrap()) as usize;
+
+    let mut descriptions = vec![0u8; attribute_count * 4];
+    reader.read_exact(&mut descriptions).map_err(read_error("attribute layout"))?;
+    header.extend_from_slice(&descriptions);
+    verify_crc(reader, &header, "header")?;
+
+    let mut attributes = Vec::with_capacity(attribute_count);
+    for desc in descriptions.chunks_exact(4) {
+        let attr = VertexAttribute::from_u8(desc[0]).ok_or_else(|| {
+            DsgeError::FormatUnsupported(format!("unknown vertex attribute {}", desc[0]))
+        })?;
+        let (component_type, components) = attr.layout();
+        if desc[1] != component_type as u8 || desc[2] != components {
+            return Err(DsgeError::LayoutMismatch(format!(
+                "attribute {attr:?} is stored as type {} x{}, expected {component_type:?} x{components}",
+                desc[1], desc[2]
+            )));
+        }
+        if attributes.contains(&attr) {
+            return Err(DsgeError::InvalidData(format!("attribute {attr:?} is declared twice")));
+        }
+        attributes.push(attr);
+    }
+    if !attributes.contains(&VertexAttribute::Position) {
+        return Err(DsgeError::InvalidData("mesh has no vertex positions".to_owned()));
+    }
+
+    let stride = attributes.iter().map(|attr| attr.components() * 4).sum::<usize>();
+    let vertex_bytes = read_block(reader, vertex_count, stride, "vertices")?;
+    verify_crc(reader, &vertex_bytes, "vertex data")?;
+    let mut vertices = vec![VkVertex::default(); vertex_count];
+    let mut offset = 0;
+    for attr in &attributes {
+        for vertex in vertices.iter_mut() {
+            for value in attr.get_mut(vertex) {
+                *value = f32::from_le_bytes(vertex_bytes[offset..offset + 4].try_into().unwrap());
+                offset += 4;
+            }
+        }
+    }
+
+    let deformed = attributes.contains(&VertexAttribute::Groups);
+    if deformed && !attributes.contains(&VertexAttribute::Weights) {
+        bind_to_first_group(&mut vertices);
+    }
+
+    let index_bytes = read_block(reader, index_count, 4, "indices")?;
+    verify_crc(reader, &index_bytes, "index data")?;
+    let indices = index_bytes
+        .chunks_exact(4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .collect();
+    let morph_targets = if version >= MORPH_VERSION {
+        read_morph_targets(reader, vertex_count)?
+    } else {
+        Vec::new()
+    };
+
+    Ok(MeshData {
+        vertices,
+        indices,
+        deformed,
+        uv_count: if attributes.contains(&VertexAttribute::TexCoord2) { 2 } else { 1 },
+        version,
+        morph_targets,
+    })
+}
+
+/// Старый формат: `f64`, признак деформации, признак второго слоя UV,
+/// индексы и вершины без описания раскладки и контрольных сумм.
+/// Старые файлы писались в порядке байт x86, т.е. тоже little-endian.
+fn read_legacy<R: Read>(reader: &mut R) -> Result<MeshData, DsgeError> {
+    let mut fixed = [0u8; 10];
+    reader.read_exact(&mut fixed).map_err(read_error("header"))?;
+    let flag = |value: u8, name: &str| match value {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(DsgeError::FormatUnsupported(format!(
+            "not a mesh file (legacy flag `{name}` = {value})"
+        ))),
+    };
+    let deformed = flag(fixed[8], "deformed")?;
+    let uv_count = if flag(fixed[9], "uv2")? { 2 } else { 1 };
+
+    let index_count = read_u32(reader, "index count")? as usize;
+    let indices = read_block(reader, index_count, 4, "indices")?
+        .chunks_exact(4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .collect();
+
+    let vertex_count = read_u32(reader, "vertex count")? as usize;
+    let mut stride = 14 * 4;
+    if deformed {
+        stride += 3 * 4;
+    }
+    if uv_count == 2 {
+        stride += 2 * 4;
+    }
+    let vertex_bytes = read_block(reader, vertex_count, stride, "vertices")?;
+    let vertices = vertex_bytes
+        .chunks_exact(stride)
+        .map(|bytes| {
+            let mut values = bytes
+                .chunks_exact(4)
+                .map(|value| value.try_into().unwrap());
+            let mut f = || f32::from_le_bytes(values.next().unwrap());
+            let mut vertex = Vertex {
+                v_pos: Vec3::new(f(), f(), f()),
+                v_nor: Vec3::new(f(), f(), f()),
+                v_tex1: Vec2::new(f(), f()),
+                v_bin: Vec3::new(f(), f(), f()),
+                v_tan: Vec3::new(f(), f(), f()),
+                ..Vertex::empty()
+            };
+            if deformed {
+                let mut u = || u32::from_le_bytes(values.next().unwrap());
+                vertex.v_grp = UVec3::new(u(), u(), u());
+                vertex.v_wgt = Vec3::new(1.0, 0.0, 0.0);
+            }
+            if uv_count == 2 {
+                let mut f = || f32::from_le_bytes(values.next().unwrap());
+                vertex.v_tex2 = Vec2::new(f(), f());
+            }
+            vertex.to_vk_vertex()
+        })
+        .collect();
+
+    Ok(MeshData {
+        vertices,
+        indices,
+        deformed,
+        uv_count,
+        version: LEGACY_VERSION,
+        morph_targets: Vec::new(),
+    })
+}
+
+/// Веса для файлов, в которых их нет: вершина целиком следует за первой группой
+fn bind_to_first_group(vertices: &mut [VkVertex]) {
+    for vertex in vertices {
+        vertex.v_wgt = [1.0, 0.0, 0.0];
+    }
+}
+
+/// Читает `count` элементов по `size` байт.
+/// Память выделяется по мере чтения, чтобы испорченный счётчик не приводил
+/// к огромному выделению до ошибки конца файла.
+fn read_block<R: Read>(reader: &mut R, count: usize, size: usize, what: &str) -> Result<Vec<u8>, DsgeError> {
+    let len = count.checked_mul(size).ok_or_else(|| {
+        DsgeError::InvalidData(format!("{what}: element count {count} is too large"))
+    })?;
+    let mut data = Vec::new();
+    let read = reader.take(len as u64).read_to_end(&mut data)?;
+    if read != len {
+        return Err(DsgeError::InvalidData(format!(
+            "unexpected end of data while reading {what}: expected {len} bytes, got {read}"
+        )));
+    }
+    Ok(data)
+}
+
+fn read_u32<R: Read>(reader: &mut R, what: &str) -> Result<u32, DsgeError> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes).map_err(read_error(what))?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn verify_crc<R: Read>(reader: &mut R, data: &[u8], what: &str) -> Result<(), DsgeError> {
+    let stored = read_u32(reader, &format!("{what} checksum"))?;
+    let actual = crc32(data);
+    if stored != actual {
+        return Err(DsgeError::InvalidData(format!(
+            "{what} checksum mismatch: stored {stored:08x}, computed {actual:08x}"
+        )));
+    }
+    Ok(())
+}
+
+fn read_error(what: &str) -> impl Fn(io::Error) -> DsgeError + '_ {
+    move |err| match err.kind() {
+        io::ErrorKind::UnexpectedEof => {
+            DsgeError::InvalidData(format!("unexpected end of data while reading {what}"))
+        }
+        _ => err.into(),
+    }
+}
+
+/// CRC-32 (IEEE 802.3), как в zip и png
+pub fn crc32(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+    !data.iter().fold(!0u32, |crc, byte| {
+        TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+fn test_mesh() -> MeshData {
+    let vertex = |x: f32, y: f32| {
+        Vertex {
+            v_pos: Vec3::new(x, y, 0.0),
+            v_nor: Vec3::new(0.0, 0.0, 1.0),
+            v_tex1: Vec2::new(x, y),
+            v_tex2: Vec2::new(y, x),
+            v_grp: UVec3::new(1, 2, 3),
+            v_wgt: Vec3::new(0.5, 0.25, 0.25),
+            ..Vertex::empty()
+        }
+        .to_vk_vertex()
+    };
+    MeshData {
+        vertices: vec![vertex(0.0, 0.0), vertex(1.0, 0.0), vertex(1.0, 1.0), vertex(0.0, 1.0)],
+        indices: vec![0, 1, 2, 0, 2, 3],
+        deformed: true,
+        uv_count: 2,
+        version: VERSION,
+        morph_targets: vec![MorphTarget {
+            name: "smile".to_owned(),
+            positions: vec![[0.0, 0.0, 0.5]; 4],
+            normals: vec![[0.0, 0.1, 0.0]; 4],
+            tangents: vec![[0.0; 3]; 4],
+        }],
+    }
+}
+
+#[test]
+fn mesh_format_roundtrip() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    let mesh = test_mesh();
+    let mut bytes = Vec::new();
+    write_mesh(&mut bytes, &mesh).unwrap();
+    let read = read_mesh(&mut bytes.as_slice()).unwrap();
+    assert_eq!(read.version, VERSION);
+    assert_eq!((read.deformed, read.uv_count), (true, 2));
+    assert_eq!(read.indices, mesh.indices);
+    assert!(bytemuck::cast_slice::<_, u8>(&read.vertices) == bytemuck::cast_slice::<_, u8>(&mesh.vertices));
+    assert_eq!(read.morph_targets, mesh.morph_targets);
+
+    // Версия 2 без блока целей морфинга
+    let mut plain = mesh.clone();
+    plain.morph_targets.clear();
+    let mut v2 = Vec::new();
+    write_mesh(&mut v2, &plain).unwrap();
+    v2.truncate(v2.len() - 4);
+    v2[4] = 2;
+    let header_len = 16 + plain.attributes().len() * 4;
+    let crc = crc32(&v2[..header_len]);
+    v2[header_len..header_len + 4].copy_from_slice(&crc.to_le_bytes());
+    let read = read_mesh(&mut v2.as_slice()).unwrap();
+    assert_eq!((read.version, read.morph_targets.len()), (2, 0));
+
+    // Обрезанный файл
+    for len in [2, 10, bytes.len() / 2, bytes.len() - 1] {
+        let err = read_mesh(&mut &bytes[..len]).err().unwrap();
+        assert!(matches!(err, DsgeError::InvalidData(_)), "{len}: {err}");
+    }
+    // Повреждённые вершины
+    let mut corrupted = bytes.clone();
+    corrupted[60] ^= 0xFF;
+    let err = read_mesh(&mut corrupted.as_slice()).err().unwrap().to_string();
+    assert!(err.contains("vertex data checksum mismatch"), "{err}");
+    // Неизвестная версия
+    let mut newer = bytes.clone();
+    newer[4] = 99;
+    assert!(matches!(read_mesh(&mut newer.as_slice()), Err(DsgeError::FormatUnsupported(_))));
+    // Индекс за пределами вершин
+    let mut bad = mesh.clone();
+    bad.indices[4] = 4;
+    assert!(matches!(write_mesh(&mut Vec::new(), &bad), Err(DsgeError::InvalidData(_))));
+}
+
+#[test]
+fn mesh_format_legacy() {
+    let mesh = test_mesh();
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&1.0f64.to_le_bytes());
+    bytes.extend_from_slice(&[1, 1]);
+    bytes.extend_from_slice(&(mesh.indices.len() as u32).to_le_bytes());
+    for index in &mesh.indices {
+        bytes.extend_from_slice(&index.to_le_bytes());
+    }
+    bytes.extend_from_slice(&(mesh.vertices.len() as u32).to_le_bytes());
+    for v in &mesh.vertices {
+        let values = [&v.v_pos[..], &v.v_nor, &v.v_tex1, &v.v_bin, &v.v_tan];
+        for value in values.concat() {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for group in v.v_grp {
+            bytes.extend_from_slice(&group.to_le_bytes());
+        }
+        for value in v.v_tex2 {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    let read = read_mesh(&mut bytes.as_slice()).unwrap();
+    assert_eq!(read.version, LEGACY_VERSION);
+    assert_eq!(read.indices, mesh.indices);
+    assert_eq!(read.vertices[2].v_tex2, [1.0, 1.0]);
+    assert_eq!(read.vertices[3].v_grp, [1, 2, 3]);
+    assert_eq!(read.vertices[3].v_wgt, [1.0, 0.0, 0.0]);
+
+    let mut truncated = bytes[..bytes.len() - 4].to_vec();
+    assert!(matches!(read_mesh(&mut truncated.as_slice()), Err(DsgeError::InvalidData(_))));
+    truncated = bytes.clone();
+    truncated[14 + 4 * 4..14 + 5 * 4].copy_from_slice(&7u32.to_le_bytes());
+    let err = read_mesh(&mut truncated.as_slice()).err().unwrap().to_string();
+    assert!(err.contains("out of range"), "{err}");
+    truncated = bytes.clone();
+    truncated[8] = 7;
+    assert!(matches!(read_mesh(&mut truncated.as_slice()), Err(DsgeError::FormatUnsupported(_))));
+}
@@ -0,0 +1,429 @@
+//! Уровни детализации (LOD) мешей.
+//!
+//! * [`simplify`] упрощает меш стягиванием рёбер по квадрикам ошибки
+//!   (Garland, Heckbert, "Surface Simplification Using Quadric Error Metrics").
+//!   Ребро стягивается в одну из существующих вершин, поэтому уровни ссылаются
+//!   на тот же вершинный буфер и хранятся как дополнительные диапазоны индексов
+//!   (см. [`super::MeshBuilder::generate_lods`]);
+//! * [`projected_size`] и [`select_lod`] выбирают уровень по размеру проекции
+//!   габаритов на экран, [`LodLevels`] хранит выбранные уровни объектов между кадрами.
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use super::VkVertex;
+use crate::types::{DVec3, Mat4, Vec3, Vec4};
+
+/// Вес квадрик открытых краёв, чтобы края меша не «съёживались»
+const BOUNDARY_WEIGHT: f64 = 100.0;
+/// Минимальный косинус между нормалями треугольника до и после стягивания ребра
+const MIN_NORMAL_COS: f64 = 0.2;
+
+/// Настройки выбора уровня детализации
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LodSettings {
+    /// Множитель размера на экране. Больше единицы — детальные уровни держатся дольше
+    pub bias: f32,
+    /// Относительная ширина зоны вокруг порога, в которой уровень не переключается
+    pub hysteresis: f32,
+}
+
+impl Default for LodSettings {
+    fn default() -> Self {
+        Self {
+            bias: 1.0,
+            hysteresis: 0.1,
+        }
+    }
+}
+
+/// Размер проекции габаритов на экран: 1.0 соответствует высоте экрана.
+/// Если часть габаритов позади камеры, возвращается бесконечность.
+pub fn projected_size(corners: &[Vec3], model_view_projection: Mat4) -> f32 {
+    let mut min = [f32::MAX; 2];
+    let mut max = [f32::MIN; 2];
+    for corner in corners {
+        let p = model_view_projection * Vec4::new(corner.x, corner.y, corner.z, 1.0);
+        if p.w <= 0.0 {
+            return f32::INFINITY;
+        }
+        for axis in 0..2 {
+            let ndc = p[axis] / p.w;
+            min[axis] = min[axis].min(ndc);
+            max[axis] = max[axis].max(ndc);
+        }
+    }
+    (max[0] - min[0]).max(max[1] - min[1]) * 0.5
+}
+
+/// Номер уровня детализации. `thresholds[i]` — размер на экране, ниже которого
+/// используется уровень `i + 1` (пороги по убыванию), уровень 0 — исходный меш.
+/// Порог, уже пройденный с уровня `current`, отодвигается на `hysteresis`,
+/// чтобы уровень не мерцал на границе.
+pub fn select_lod(thresholds: &[f32], current: usize, screen_size: f32, settings: LodSettings) -> usize {
+    let size = screen_size * settings.bias;
+    thresholds
+        .iter()
+        .enumerate()
+        .filter(|(level, threshold)| {
+            if current > *level {
+                size <= *threshold * (1.0 + settings.hysteresis)
+            } else {
+                size < *threshold * (1.0 - settings.hysteresis)
+            }
+        })
+        .count()
+}
+
+/// Текущие уровни детализации объектов по их ключам.
+/// Копии одного меша выбирают уровень независимо, гистерезис учитывается для каждого объекта отдельно.
+#[derive(Clone, Debug, Default)]
+pub struct LodLevels {
+    levels: HashMap<i32, usize>,
+}
+
+impl LodLevels {
+    /// Уровень, выбранный объектом в прошлый раз, 0 — если его ещё не было
+    #[inline]
+    pub fn get(&self, key: i32) -> usize {
+        self.levels.get(&key).copied().unwrap_or(0)
+    }
+
+    /// Выбор и запоминание уровня объекта, см. [`select_lod`]
+    pub fn select(&mut self, key: i32, thresholds: &[f32], screen_size: f32, settings: LodSettings) -> usize {
+        let level = select_lod(thresholds, self.get(key), screen_size, settings);
+        self.levels.insert(key, level);
+        level
+    }
+
+    /// Забывает уровни объектов, для которых `f` возвращает `false`
+    pub fn retain<F: FnMut(i32) -> bool>(&mut self, mut f: F) {
+        self.levels.retain(|key, _| f(*key));
+    }
+}
+
+/// Симметричная матрица 4x4 квадрики, хранится верхний треугольник
+#[derive(Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn plane(normal: DVec3, point: DVec3, weight: f64) -> Self {
+        let [a, b, c] = [normal.x, normal.y, normal.z];
+        let d = -normal.dot(&point);
+        Self([a * a, a * b, a * c, a * d, b * b, b * c, b * d, c * c, c * d, d * d].map(|x| x * weight))
+    }
+
+    fn add(&mut self, other: &Self) {
+        for (a, b) in self.0.iter_mut().zip(other.0) {
+            *a += b;
+        }
+    }
+
+    fn error(&self, p: DVec3) -> f64 {
+        let q = &self.0;
+        let (x, y, z) = (p.x, p.y, p.z);
+        q[0] * x * x + q[4] * y * y + q[7] * z * z + q[9]
+            + 2.0 * (q[1] * x * y + q[2] * x * z + q[5] * y * z + q[3] * x + q[6] * y + q[8] * z)
+    }
+}
+
+/// Стягивание группы `from` в группу `to`
+struct Collapse {
+    cost: f64,
+    from: usize,
+    to: usize,
+    versions: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    // BinaryHeap — max-heap, а нужно самое дешёвое стягивание
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+/// Упрощение меша до `target_index_count` индексов.
+/// `indices` ссылаются на `vertices`, результат — подмножество этих же вершин.
+/// Вершины с одинаковой позицией (швы текстурных координат) стягиваются вместе.
+pub fn simplify(vertices: &[VkVertex], indices: &[u32], target_index_count: usize) -> Vec<u32> {
+    // Вершины группируются по позиции, стягиваются группы
+    let mut group_by_position = HashMap::new();
+    let mut local_by_vertex = HashMap::new();
+    let mut vertex_index = Vec::new();
+    let mut vertex_group = Vec::new();
+    let mut positions = Vec::new();
+    let mut members = Vec::<Vec<usize>>::new();
+    let mut triangles = Vec::with_capacity(indices.len() / 3);
+    for triangle in indices.chunks_exact(3) {
+        let mut corners = [0; 3];
+        for (corner, index) in corners.iter_mut().zip(triangle) {
+            *corner = *local_by_vertex.entry(*index).or_insert_with(|| {
+                let pos = vertices[*index as usize].v_pos;
+                let group = *group_by_position.entry(pos.map(f32::to_bits)).or_insert_with(|| {
+                    positions.push(DVec3::new(pos[0] as f64, pos[1] as f64, pos[2] as f64));
+                    members.push(Vec::new());
+                    positions.len() - 1
+                });
+                members[group].push(vertex_index.len());
+                vertex_index.push(*index);
+                vertex_group.push(group);
+                vertex_index.len() - 1
+            });
+        }
+        let [a, b, c] = corners.map(|corner| vertex_group[corner]);
+        if a != b && b != c && a != c {
+            triangles.push(corners);
+        }
+    }
+
+    let group_count = positions.len();
+    let mut quadrics = vec![Quadric::default(); group_count];
+    let mut group_triangles = vec![Vec::new(); group_count];
+    let mut edges = HashMap::<(usize, usize), (u32, usize)>::new();
+    for (t, corners) in triangles.iter().enumerate() {
+        let groups = corners.map(|corner| vertex_group[corner]);
+        let [p0, p1, p2] = groups.map(|group| positions[group]);
+        let normal = (p1 - p0).cross(&(p2 - p0));
+        let area = normal.norm() * 0.5;
+        for (i, group) in groups.iter().enumerate() {
+            if area > 0.0 {
+                quadrics[*group].add(&Quadric::plane(normal.normalize(), p0, area));
+            }
+            group_triangles[*group].push(t);
+            let next = groups[(i + 1) % 3];
+            let edge = edges.entry((*group.min(&next), *group.max(&next))).or_insert((0, t));
+            edge.0 += 1;
+        }
+    }
+    // Квадрики плоскостей, перпендикулярных открытым краям
+    for ((a, b), (count, t)) in &edges {
+        if *count != 1 {
+            continue;
+        }
+        let [p0, p1, p2] = triangles[*t].map(|corner| positions[vertex_group[corner]]);
+        let edge = positions[*b] - positions[*a];
+        let normal = edge.cross(&(p1 - p0).cross(&(p2 - p0)));
+        if normal.norm() > 0.0 {
+            let quadric = Quadric::plane(normal.normalize(), positions[*a], BOUNDARY_WEIGHT * edge.norm_squared());
+            quadrics[*a].add(&quadric);
+            quadrics[*b].add(&quadric);
+        }
+    }
+
+    let mut versions = vec![0u32; group_count];
+    let candidate = |quadrics: &[Quadric], versions: &[u32], a: usize, b: usize| {
+        let mut quadric = quadrics[a];
+        quadric.add(&quadrics[b]);
+        let (cost_ab, cost_ba) = (quadric.error(positions[b]), quadric.error(positions[a]));
+        let (from, to, cost) = if cost_ab <= cost_ba { (a, b, cost_ab) } else { (b, a, cost_ba) };
+        Collapse {
+            cost,
+            from,
+            to,
+            versions: (versions[from], versions[to]),
+        }
+    };
+    let mut heap = edges
+        .keys()
+        .map(|(a, b)| candidate(&quadrics, &versions, *a, *b))
+        .collect::<BinaryHeap<_>>();
+
+    let mut dead = vec![false; triangles.len()];
+    let mut removed = vec![false; group_count];
+    let mut live = triangles.len();
+    while live * 3 > target_index_count {
+        let Some(Collapse { from, to, versions: (from_version, to_version), .. }) = heap.pop() else {
+            break;
+        };
+        if removed[from] || removed[to] || versions[from] != from_version || versions[to] != to_version {
+            continue;
+        }
+        let groups_of = |corners: &[usize; 3]| corners.map(|corner| vertex_group[corner]);
+        // Стягивание не должно переворачивать оставшиеся треугольники
+        let flips = group_triangles[from].iter().any(|t| {
+            let groups = groups_of(&triangles[*t]);
+            if dead[*t] || groups.contains(&to) {
+                return false;
+            }
+            let old = groups.map(|group| positions[group]);
+            let new = groups.map(|group| positions[if group == from { to } else { group }]);
+            let old_normal = (old[1] - old[0]).cross(&(old[2] - old[0]));
+            let new_normal = (new[1] - new[0]).cross(&(new[2] - new[0]));
+            let lengths = old_normal.norm() * new_normal.norm();
+            lengths == 0.0 || old_normal.dot(&new_normal) < MIN_NORMAL_COS * lengths
+        });
+        if flips {
+            continue;
+        }
+
+        for t in std::mem::take(&mut group_triangles[from]) {
+            if dead[t] {
+                continue;
+            }
+            if groups_of(&triangles[t]).contains(&to) {
+                dead[t] = true;
+                live -= 1;
+                continue;
+            }
+            for corner in triangles[t].iter_mut() {
+                if vertex_group[*corner] == from {
+                    *corner = closest_member(vertices, &vertex_index, &members[to], vertex_index[*corner]);
+                }
+            }
+            group_triangles[to].push(t);
+        }
+        removed[from] = true;
+        let from_quadric = quadrics[from];
+        quadrics[to].add(&from_quadric);
+        versions[to] += 1;
+
+        group_triangles[to].retain(|t| !dead[*t]);
+        let mut neighbours = group_triangles[to]
+            .iter()
+            .flat_map(|t| groups_of(&triangles[*t]))
+            .filter(|group| *group != to)
+            .collect::<Vec<_>>();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        for neighbour in neighbours {
+            heap.push(candidate(&quadrics, &versions, neighbour, to));
+        }
+    }
+
+    triangles
+        .iter()
+        .zip(dead)
+        .filter(|(_, dead)| !dead)
+        .flat_map(|(corners, _)| corners.map(|corner| vertex_index[corner]))
+        .collect()
+}
+
+/// Вершина группы, ближайшая к `vertex` по нормали и текстурным координатам
+fn closest_member(vertices: &[VkVertex], vertex_index: &[u32], members: &[usize], vertex: u32) -> usize {
+    let source = &vertices[vertex as usize];
+    let distance = |member: &usize| {
+        let target = &vertices[vertex_index[*member] as usize];
+        let uv = (0..2).map(|i| (target.v_tex1[i] - source.v_tex1[i]).powi(2)).sum::<f32>();
+        let normal = (0..3).map(|i| (target.v_nor[i] - source.v_nor[i]).powi(2)).sum::<f32>();
+        uv + normal
+    };
+    *members
+        .iter()
+        .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+        .unwrap()
+}
+
+#[test]
+fn mesh_lod_simplify_plane() {
+    // Плоская сетка 20x20 с нормалями и текстурными координатами
+    let size = 20;
+    let mut vertices = Vec::new();
+    for y in 0..=size {
+        for x in 0..=size {
+            vertices.push(VkVertex {
+                v_pos: [x as f32, y as f32, 0.0],
+                v_nor: [0.0, 0.0, 1.0],
+                v_tex1: [x as f32 / size as f32, y as f32 / size as f32],
+                ..Default::default()
+            });
+        }
+    }
+    let mut indices = Vec::new();
+    for y in 0..size {
+        for x in 0..size {
+            let i = y * (size + 1) + x;
+            indices.extend_from_slice(&[i, i + 1, i + size + 2, i, i + size + 2, i + size + 1]);
+        }
+    }
+    let simplified = simplify(&vertices, &indices, indices.len() / 4);
+    assert!(simplified.len() <= indices.len() / 4, "{}", simplified.len());
+    assert!(!simplified.is_empty() && simplified.len().is_multiple_of(3));
+    // Углы сетки сохраняются, треугольники не переворачиваются
+    for corner in [0, size, size * (size + 1), (size + 1) * (size + 1) - 1] {
+        assert!(simplified.contains(&corner), "corner {corner}");
+    }
+    for triangle in simplified.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(vertices[triangle[i] as usize].v_pos));
+        assert!((b - a).cross(&(c - a)).z > 0.0);
+    }
+    // Площадь плоскости не меняется
+    let area = simplified
+        .chunks_exact(3)
+        .map(|t| {
+            let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(vertices[t[i] as usize].v_pos));
+            (b - a).cross(&(c - a)).z * 0.5
+        })
+        .sum::<f32>();
+    assert!((area - (size * size) as f32).abs() < 1e-3, "{area}");
+}
+
+#[test]
+fn mesh_lod_selection() {
+    let thresholds = [0.5, 0.25, 0.1];
+    let settings = LodSettings::default();
+    assert_eq!(select_lod(&thresholds, 0, 1.0, settings), 0);
+    assert_eq!(select_lod(&thresholds, 0, 0.3, settings), 1);
+    assert_eq!(select_lod(&thresholds, 0, 0.01, settings), 3);
+    // Гистерезис: около порога уровень сохраняется в обе стороны
+    assert_eq!(select_lod(&thresholds, 0, 0.48, settings), 0);
+    assert_eq!(select_lod(&thresholds, 1, 0.52, settings), 1);
+    assert_eq!(select_lod(&thresholds, 1, 0.6, settings), 0);
+    // Смещение держит детальный уровень дольше
+    let biased = LodSettings { bias: 2.0, ..settings };
+    assert_eq!(select_lod(&thresholds, 0, 0.2, biased), 1);
+
+    let projection = Mat4::identity();
+    let corners = super::BoundingBox { begin: Vec3::new(-0.5, -0.25, 0.5), end: Vec3::new(0.5, 0.25, 0.5) }.corners();
+    assert_eq!(projected_size(&corners, projection), 0.5);
+}
+
+#[test]
+fn mesh_lod_levels_per_object() {
+    // Две копии одного меша на разном расстоянии от камеры
+    let thresholds = [0.5, 0.25, 0.1];
+    let settings = LodSettings::default();
+    let projection = nalgebra::Perspective3::new(1.0, 1.0, 0.1, 100.0).to_homogeneous();
+    let corners = super::BoundingBox { begin: Vec3::new(-0.5, -0.5, -0.5), end: Vec3::new(0.5, 0.5, 0.5) }.corners();
+    let size_at = |distance: f32| projected_size(&corners, projection * Mat4::new_translation(&Vec3::new(0.0, 0.0, -distance)));
+    let mut levels = LodLevels::default();
+    for _ in 0..2 {
+        assert_eq!(levels.select(1, &thresholds, size_at(2.0), settings), 0);
+        assert_eq!(levels.select(2, &thresholds, size_at(20.0), settings), 3);
+    }
+    assert_eq!((levels.get(1), levels.get(2)), (0, 3));
+    // Гистерезис каждого объекта учитывает его собственный уровень
+    assert_eq!(levels.select(1, &thresholds, 0.48, settings), 0);
+    assert_eq!(levels.select(2, &thresholds, 0.48, settings), 1);
+    levels.retain(|key| key == 2);
+    assert_eq!((levels.get(1), levels.get(2)), (0, 1));
+}
+
+#[test]
+fn mesh_lod_builder_chain() {
+    let mut builder = super::Mesh::builder("teapot");
+    let (base, count, _) = builder.push_teapot().unwrap();
+    let vertex_count = builder._vertices.len();
+    let lods = builder.generate_lods(base, count, &[0.5, 0.25]);
+    assert_eq!(lods.len(), 2);
+    assert_eq!(lods[0].0, base + count);
+    assert_eq!(lods[1].0, lods[0].0 + lods[0].1);
+    assert!(lods[0].1 <= count / 2 && lods[1].1 <= count / 4, "{lods:?}");
+    assert!(lods[1].1 > 0 && lods[1].1 < lods[0].1);
+    // Уровни используют вершины исходного меша, новые вершины не добавляются
+    assert_eq!(builder._vertices.len(), vertex_count);
+    assert_eq!(builder._indices.len() as u32, lods[1].0 + lods[1].1);
+    assert!(builder._indices.iter().all(|index| (*index as usize) < vertex_count));
+}
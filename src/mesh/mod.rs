@@ -0,0 +1,1146 @@
+use crate::error::DsgeError;
+use super::types::*;
+use bytemuck::{Pod, Zeroable};
+use nalgebra::{Vector2, Vector3};
+use vulkano::memory::allocator::{GenericMemoryAllocator, StandardMemoryAllocator, Suballocator};
+use std::collections::hash_map::DefaultHasher;
+use std::fs::File;
+use std::io::BufReader;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::sync::Arc;
+use std::vec::Vec;
+use vulkano::buffer::{BufferUsage, Subbuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DrawIndexedIndirectCommand};
+
+use super::teapot::{INDICES, NORMALS, VERTICES};
+use crate::command_buffer::{CommandBufferFather, CommandBufferShortcuts};
+pub use crate::references::*;
+pub use compact::{VertexFormat, VkCompactVertex};
+pub use format::MeshData;
+
+pub mod bvh;
+pub mod compact;
+pub mod export;
+pub mod format;
+pub mod lod;
+pub mod morph;
+pub mod obj;
+pub mod optimize;
+pub mod primitives;
+pub mod raycast;
+pub mod tangents;
+pub type MeshRef = Arc<dyn MeshView>;
+//pub type MeshRef = Arc<Mesh>;
+
+/// Структура вершины
+#[derive(Copy, Clone, Default)]
+pub struct Vertex {
+    pub v_pos: Vec3,  // Координаты вершины
+    pub v_nor: Vec3,  // Нормаль вершины
+    pub v_bin: Vec3,  // Касательная 1
+    pub v_tan: Vec3,  // Касательная 2
+    pub v_tex1: Vec2, // Текстурные координаты 1 слой
+    pub v_tex2: Vec2, // Текстурные координаты 2 слой
+    pub v_grp: UVec3, // Группы першины
+    pub v_wgt: Vec3,  // Веса групп
+}
+
+impl Vertex {
+    pub fn to_vk_vertex(&self) -> VkVertex {
+        VkVertex {
+            v_pos: [self.v_pos.x, self.v_pos.y, self.v_pos.z],
+            v_nor: [self.v_nor.x, self.v_nor.y, self.v_nor.z],
+            v_bin: [self.v_bin.x, self.v_bin.y, self.v_bin.z],
+            v_tan: [self.v_tan.x, self.v_tan.y, self.v_tan.z],
+            v_tex1: [self.v_tex1.x, self.v_tex1.y],
+            v_tex2: [self.v_tex2.x, self.v_tex2.y],
+            v_grp: [self.v_grp.x, self.v_grp.y, self.v_grp.z],
+            v_wgt: [self.v_wgt.x, self.v_wgt.y, self.v_wgt.z],
+        }
+    }
+}
+
+/// Представление вершины для vulkano
+#[repr(C)]
+#[derive(Copy, Clone, Default, Pod, Zeroable, vulkano::pipeline::graphics::vertex_input::Vertex)]
+pub struct VkVertex {
+    #[format(R32G32B32_SFLOAT)]
+    pub v_pos: [f32; 3],
+    #[format(R32G32B32_SFLOAT)]
+    pub v_nor: [f32; 3],
+    #[format(R32G32B32_SFLOAT)]
+    pub v_bin: [f32; 3],
+    #[format(R32G32B32_SFLOAT)]
+    pub v_tan: [f32; 3],
+    #[format(R32G32_SFLOAT)]
+    pub v_tex1: [f32; 2],
+    #[format(R32G32_SFLOAT)]
+    pub v_tex2: [f32; 2],
+    #[format(R32G32B32_UINT)]
+    pub v_grp: [u32; 3],
+    #[format(R32G32B32_SFLOAT)]
+    pub v_wgt: [f32; 3],
+}
+
+impl Hash for VkVertex {
+    fn hash<H: Hasher>(&self, h: &mut H) {
+        for i in 0..3 {
+            h.write_i128((self.v_pos[i] * 1000.0) as _);
+            h.write_i128((self.v_nor[i] * 1000.0) as _);
+            h.write_i128((self.v_bin[i] * 1000.0) as _);
+            h.write_i128((self.v_tan[i] * 1000.0) as _);
+            h.write_u32(self.v_grp[i] as _);
+            h.write_i128((self.v_wgt[i] * 1000.0) as _);
+            if i < 2 {
+                h.write_i128((self.v_tex1[i] * 1000.0) as _);
+                h.write_i128((self.v_tex2[i] * 1000.0) as _);
+            }
+        }
+    }
+}
+
+/// Сравнение с той же точностью, с которой считается хеш
+impl PartialEq for VkVertex {
+    fn eq(&self, other: &Self) -> bool {
+        let q = |value: f32| (value * 1000.0) as i128;
+        let eq = |a: &[f32], b: &[f32]| a.iter().zip(b).all(|(a, b)| q(*a) == q(*b));
+        eq(&self.v_pos, &other.v_pos)
+            && eq(&self.v_nor, &other.v_nor)
+            && eq(&self.v_bin, &other.v_bin)
+            && eq(&self.v_tan, &other.v_tan)
+            && eq(&self.v_tex1, &other.v_tex1)
+            && eq(&self.v_tex2, &other.v_tex2)
+            && self.v_grp == other.v_grp
+            && eq(&self.v_wgt, &other.v_wgt)
+    }
+}
+
+impl Eq for VkVertex {}
+
+#[allow(dead_code)]
+impl VkVertex {
+    pub fn to_vertex(&self) -> Vertex {
+        Vertex {
+            v_pos: Vec3::new(self.v_pos[0], self.v_pos[1], self.v_pos[2]),
+            v_nor: Vec3::new(self.v_nor[0], self.v_nor[1], self.v_nor[2]),
+            v_bin: Vec3::new(self.v_bin[0], self.v_bin[1], self.v_bin[2]),
+            v_tan: Vec3::new(self.v_tan[0], self.v_tan[1], self.v_tan[2]),
+            v_tex1: Vec2::new(self.v_tex1[0], self.v_tex1[1]),
+            v_tex2: Vec2::new(self.v_tex2[0], self.v_tex2[1]),
+            v_grp: UVec3::new(self.v_grp[0], self.v_grp[1], self.v_grp[2]),
+            v_wgt: Vec3::new(self.v_wgt[0], self.v_wgt[1], self.v_wgt[2]),
+        }
+    }
+}
+
+/// Вершинный буфер меша в одном из форматов [`VertexFormat`]
+#[derive(Clone)]
+pub enum VertexBufferRef {
+    Full(Subbuffer<[VkVertex]>),
+    /// Вершины и отображение габаритов, по которому квантованы их позиции
+    Compact(Subbuffer<[VkCompactVertex]>, compact::PositionQuantization),
+}
+
+impl VertexBufferRef {
+    #[inline]
+    pub fn format(&self) -> VertexFormat {
+        match self {
+            Self::Full(_) => VertexFormat::Full,
+            Self::Compact(..) => VertexFormat::Compact,
+        }
+    }
+
+    /// Количество вершин
+    #[inline]
+    pub fn len(&self) -> u64 {
+        match self {
+            Self::Full(buffer) => buffer.len(),
+            Self::Compact(buffer, _) => buffer.len(),
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Буфер без типа для привязки к конвейеру
+    #[inline]
+    pub fn as_bytes(&self) -> &Subbuffer<[u8]> {
+        match self {
+            Self::Full(buffer) => buffer.as_bytes(),
+            Self::Compact(buffer, _) => buffer.as_bytes(),
+        }
+    }
+
+    /// Параметры распаковки позиций компактных вершин
+    #[inline]
+    pub fn quantization(&self) -> Option<&compact::PositionQuantization> {
+        match self {
+            Self::Full(_) => None,
+            Self::Compact(_, quantization) => Some(quantization),
+        }
+    }
+}
+
+/// Псевдоним для индексного буфера
+pub type IndexBufferRef = Subbuffer<[u32]>;
+
+#[allow(dead_code)]
+impl Vertex {
+    pub fn empty() -> Self {
+        Self {
+            v_pos: Vec3::new(0.0, 0.0, 0.0),
+            v_nor: Vec3::new(0.0, 0.0, 0.0),
+            v_bin: Vec3::new(0.0, 0.0, 0.0),
+            v_tan: Vec3::new(0.0, 0.0, 0.0),
+            v_tex1: Vec2::new(0.0, 0.0),
+            v_tex2: Vec2::new(0.0, 0.0),
+            v_grp: UVec3::new(0, 0, 0),
+            v_wgt: Vec3::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct BoundingBox {
+    pub begin: Vec3,
+    pub end: Vec3,
+}
+
+impl BoundingBox {
+    pub fn initial() -> Self {
+        let infinity = Vec3::new(std::f32::INFINITY, std::f32::INFINITY, std::f32::INFINITY);
+        Self {
+            begin: infinity,
+            end: -infinity,
+        }
+    }
+
+    #[inline]
+    pub fn corners(&self) -> [Vec3; 8] {
+        [
+            Vec3::new(self.begin.x, self.begin.y, self.begin.z),
+            Vec3::new(self.end.x, self.begin.y, self.begin.z),
+            Vec3::new(self.begin.x, self.end.y, self.begin.z),
+            Vec3::new(self.end.x, self.end.y, self.begin.z),
+            Vec3::new(self.begin.x, self.begin.y, self.end.z),
+            Vec3::new(self.end.x, self.begin.y, self.end.z),
+            Vec3::new(self.begin.x, self.end.y, self.end.z),
+            Vec3::new(self.end.x, self.end.y, self.end.z),
+        ]
+    }
+
+    #[inline]
+    pub fn add_points(&mut self, points: &[Vec3]) {
+        for point in points {
+            self.add_point(*point);
+        }
+    }
+
+    #[inline]
+    pub fn add_point(&mut self, point: Vec3) {
+        self.begin.x = self.begin.x.min(point.x);
+        self.begin.y = self.begin.y.min(point.y);
+        self.begin.z = self.begin.z.min(point.z);
+        self.end.x = self.end.x.max(point.x);
+        self.end.y = self.end.y.max(point.y);
+        self.end.z = self.end.z.max(point.z);
+    }
+
+    #[inline]
+    pub fn add(&mut self, other: &Self) {
+        self.add_point(other.begin);
+        self.add_point(other.end);
+    }
+
+    /// Объединение двух габаритов
+    #[inline]
+    pub fn union(&self, other: &Self) -> Self {
+        let mut result = *self;
+        result.add(other);
+        result
+    }
+
+    /// Габариты, расширенные на `margin` во все стороны
+    #[inline]
+    pub fn expanded(&self, margin: f32) -> Self {
+        let margin = Vec3::new(margin, margin, margin);
+        Self {
+            begin: self.begin - margin,
+            end: self.end + margin,
+        }
+    }
+
+    /// Габариты преобразованных матрицей углов
+    pub fn transformed(&self, matrix: &Mat4) -> Self {
+        let mut result = Self::initial();
+        for corner in self.corners() {
+            result.add_point((matrix * corner.push(1.0)).xyz());
+        }
+        result
+    }
+
+    /// Площадь поверхности, 0 у пустых габаритов
+    pub fn surface_area(&self) -> f32 {
+        let size = self.end - self.begin;
+        if size.min() < 0.0 {
+            return 0.0;
+        }
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    #[inline]
+    pub fn contains(&self, other: &Self) -> bool {
+        (0..3).all(|axis| self.begin[axis] <= other.begin[axis] && other.end[axis] <= self.end[axis])
+    }
+
+    #[inline]
+    pub fn overlaps(&self, other: &Self) -> bool {
+        (0..3).all(|axis| self.begin[axis] <= other.end[axis] && other.begin[axis] <= self.end[axis])
+    }
+
+    #[inline]
+    pub fn overlaps_sphere(&self, center: Vec3, radius: f32) -> bool {
+        let closest = center.sup(&self.begin).inf(&self.end);
+        (closest - center).norm_squared() <= radius * radius
+    }
+}
+
+#[derive(Default)]
+pub struct MeshBuilder {
+    _name: String,
+    _indices: Vec<u32>,
+    _vertices: Vec<VkVertex>,
+    _vertex_buffer: Option<VertexBufferRef>,
+    _index_buffer: Option<IndexBufferRef>,
+    _bbox: BoundingBox,
+    _morph_targets: Vec<morph::MorphTarget>,
+    _vertex_format: VertexFormat,
+    _quantization_tolerance: compact::QuantizationTolerance,
+    _discard_cpu_copy: bool,
+}
+
+impl Hash for MeshBuilder {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self._vertices.hash(hasher);
+        self._indices.hash(hasher);
+    }
+}
+
+#[allow(dead_code)]
+impl MeshBuilder {
+    pub fn push_quad_coords(&mut self, a: &Vec3, b: &Vec3, c: &Vec3, d: &Vec3) -> &mut Self {
+        // Общая касательная, чтобы вершины на диагонали совпадали и объединялись в optimize
+        let tangent = (b - a).normalize();
+        self.push_triangle_with_tangent(a, b, c, tangent)
+            .push_triangle_with_tangent(a, c, d, tangent)
+    }
+
+    pub fn push_triangle_coords(&mut self, a: &Vec3, b: &Vec3, c: &Vec3) -> &mut Self {
+        self.push_triangle_with_tangent(a, b, c, (b - a).normalize())
+    }
+
+    fn push_triangle_with_tangent(&mut self, a: &Vec3, b: &Vec3, c: &Vec3, tangent: Vec3) -> &mut Self {
+        let dpos1 = b - a;
+        let dpos2 = c - a;
+        let normal = dpos1.cross(&dpos2).normalize();
+        let bitan = normal.cross(&tangent);
+        let vert_a = Vertex {
+            v_pos: a.clone(),
+            v_nor: normal,
+            v_bin: tangent,
+            v_tan: bitan,
+            v_tex1: Vector2::new(0.0, 0.0),
+            v_tex2: Vector2::new(0.0, 0.0),
+            v_grp: Vector3::new(0, 0, 0),
+            v_wgt: Vector3::new(0.0, 0.0, 0.0),
+        };
+        let vert_b = Vertex {
+            v_pos: b.clone(),
+            v_nor: normal,
+            v_bin: tangent,
+            v_tan: bitan,
+            v_tex1: Vector2::new(0.0, 0.0),
+            v_tex2: Vector2::new(0.0, 0.0),
+            v_grp: Vector3::new(0, 0, 0),
+            v_wgt: Vector3::new(0.0, 0.0, 0.0),
+        };
+        let vert_c = Vertex {
+            v_pos: c.clone(),
+            v_nor: normal,
+            v_bin: tangent,
+            v_tan: bitan,
+            v_tex1: Vector2::new(0.0, 0.0),
+            v_tex2: Vector2::new(0.0, 0.0),
+            v_grp: Vector3::new(0, 0, 0),
+            v_wgt: Vector3::new(0.0, 0.0, 0.0),
+        };
+        self._indices.push(self._vertices.len() as u32);
+        self._vertices.push(vert_a.to_vk_vertex());
+        self._indices.push(self._vertices.len() as u32);
+        self._vertices.push(vert_b.to_vk_vertex());
+        self._indices.push(self._vertices.len() as u32);
+        self._vertices.push(vert_c.to_vk_vertex());
+        self._bbox.add_points(&[*a, *b, *c]);
+        self
+    }
+
+    /// Расчёт касательных (`v_tan`) и бинормалей (`v_bin`) по текстурным координатам,
+    /// совместимый с MikkTSpace (см. [`tangents`]).
+    /// Нулевые нормали заменяются сглаженными нормалями граней.
+    pub fn calc_tangent_space(&mut self) -> &mut Self {
+        self.calc_tangent_space_from(0)
+    }
+
+    /// То же, что [`MeshBuilder::calc_tangent_space`], но только для треугольников,
+    /// начиная с индекса `first_index`
+    pub fn calc_tangent_space_from(&mut self, first_index: usize) -> &mut Self {
+        tangents::fill_missing_normals(&mut self._vertices, &mut self._indices[first_index..]);
+        tangents::generate_tangents(&mut self._vertices, &mut self._indices[first_index..]);
+        self
+    }
+
+    /// Пересчёт нормалей. Грани сглаживаются, если угол между ними не больше
+    /// `auto_smooth_angle` (в радианах): 0 — плоское затенение, `PI` — гладкое.
+    /// Касательные после этого нужно пересчитать.
+    pub fn calc_normals(&mut self, auto_smooth_angle: f32) -> &mut Self {
+        self.calc_normals_from(0, auto_smooth_angle)
+    }
+
+    /// То же, что [`MeshBuilder::calc_normals`], но только для треугольников,
+    /// начиная с индекса `first_index`
+    pub fn calc_normals_from(&mut self, first_index: usize, auto_smooth_angle: f32) -> &mut Self {
+        tangents::generate_normals(&mut self._vertices, &mut self._indices[first_index..], auto_smooth_angle);
+        self
+    }
+
+    /// Добавить меш из файла любой поддерживаемой версии (см. [`format`]).
+    /// Возвращает первый индекс, количество индексов и габариты добавленного меша.
+    pub fn push_from_file(&mut self, fname: &str) -> Result<(u32, u32, BoundingBox), DsgeError> {
+        let path = Path::new(fname);
+        let file = File::open(path).map_err(|err| DsgeError::open(path, err))?;
+        let data = format::read_mesh(&mut BufReader::new(file)).map_err(|err| match err {
+            DsgeError::Io { path: None, source } => DsgeError::io(path, source),
+            err => err.context(path.display()),
+        })?;
+        if data.version == format::LEGACY_VERSION {
+            log::debug!(target: "resource_manager", "{fname} uses the legacy mesh format");
+        }
+        Ok(self.push_mesh_data(&data))
+    }
+
+    /// Добавить модель из OBJ-файла.
+    /// Касательные рассчитываются через [`MeshBuilder::calc_tangent_space`],
+    /// у возвращаемых групп `base_index` указан относительно всего построителя.
+    pub fn push_obj(&mut self, fname: &str) -> Result<obj::ObjModel, DsgeError> {
+        let path = Path::new(fname);
+        let source = std::fs::read_to_string(path).map_err(|err| DsgeError::open(path, err))?;
+        let mut model = obj::parse_obj(&source).map_err(|err| err.context(path.display()))?;
+        let (base_index, _, _) = self.push_mesh_data(&model.mesh);
+        self.calc_tangent_space_from(base_index as _);
+        for group in &mut model.groups {
+            group.base_index += base_index;
+        }
+        Ok(model)
+    }
+
+    /// Объединение одинаковых вершин, переупорядочивание треугольников для кэша
+    /// вершин и вершин для локальности выборки. Все индексы считаются одним мешем,
+    /// для построителя с несколькими мешами нужен [`MeshBuilder::optimize_ranges`].
+    pub fn optimize(&mut self) -> optimize::OptimizeStats {
+        let ranges = [(0, self._indices.len() as u32)];
+        self.optimize_ranges(&ranges)
+    }
+
+    /// То же, что [`MeshBuilder::optimize`], но треугольники переставляются только
+    /// внутри диапазонов `(первый индекс, количество индексов)`, возвращённых
+    /// при добавлении мешей, поэтому диапазоны остаются действительными.
+    pub fn optimize_ranges(&mut self, ranges: &[(u32, u32)]) -> optimize::OptimizeStats {
+        let vertices_before = self._vertices.len();
+        let acmr_before = optimize::acmr(&self._indices, optimize::FIFO_CACHE_SIZE);
+        if !self._morph_targets.is_empty() {
+            log::warn!(
+                target: "resource_manager",
+                "Mesh {} has morph targets, optimization would reorder their vertices and is skipped",
+                self._name
+            );
+            return optimize::OptimizeStats {
+                vertices_before,
+                vertices_after: vertices_before,
+                acmr_before,
+                acmr_after: acmr_before,
+            };
+        }
+
+        let (vertices, mut indices) = optimize::weld(&self._vertices, &self._indices);
+        for (base, count) in ranges {
+            let range = *base as usize..(*base + *count) as usize;
+            let optimized = optimize::optimize_vertex_cache(&indices[range.clone()], vertices.len());
+            // Уже хорошо упорядоченные меши (например, из патчей) алгоритм может ухудшить
+            if optimize::acmr(&optimized, optimize::FIFO_CACHE_SIZE)
+                < optimize::acmr(&indices[range.clone()], optimize::FIFO_CACHE_SIZE)
+            {
+                indices[range].copy_from_slice(&optimized);
+            }
+        }
+        (self._vertices, self._indices) = optimize::optimize_vertex_fetch(&vertices, &indices);
+
+        let stats = optimize::OptimizeStats {
+            vertices_before,
+            vertices_after: self._vertices.len(),
+            acmr_before,
+            acmr_after: optimize::acmr(&self._indices, optimize::FIFO_CACHE_SIZE),
+        };
+        log::debug!(target: "resource_manager", "Optimized mesh {}: {stats}", self._name);
+        stats
+    }
+
+    /// Уровни детализации меша из диапазона `(первый индекс, количество индексов)`.
+    /// `ratios` — доли треугольников исходного меша, например `[0.5, 0.25, 0.1]`.
+    /// Индексы уровней добавляются в конец построителя и используют те же вершины,
+    /// возвращаются их диапазоны для [`SubMesh::from_mesh`].
+    /// Сам [`Mesh`] после этого рисует все уровни сразу, поэтому исходный меш
+    /// тоже нужно рисовать через [`SubMesh`].
+    pub fn generate_lods(&mut self, base: u32, count: u32, ratios: &[f32]) -> Vec<(u32, u32)> {
+        let source = self._indices[base as usize..(base + count) as usize].to_vec();
+        let triangles = source.len() / 3;
+        ratios
+            .iter()
+            .map(|ratio| {
+                let target = ((triangles as f32 * ratio.clamp(0.0, 1.0)) as usize).max(1) * 3;
+                let indices = lod::simplify(&self._vertices, &source, target);
+                let lod_base = self._indices.len() as u32;
+                self._indices.extend_from_slice(&indices);
+                log::debug!(
+                    target: "resource_manager",
+                    "LOD {ratio} of mesh {}: {} -> {} triangles",
+                    self._name, triangles, indices.len() / 3
+                );
+                (lod_base, indices.len() as u32)
+            })
+            .collect()
+    }
+
+    /// Вершины и индексы построителя, например для [`export::save_mesh`]
+    pub fn mesh_data(&self) -> MeshData {
+        MeshData {
+            vertices: self._vertices.clone(),
+            indices: self._indices.clone(),
+            deformed: self._vertices.iter().any(|v| v.v_grp != [0; 3] || v.v_wgt != [0.0; 3]),
+            uv_count: if self._vertices.iter().any(|v| v.v_tex2 != [0.0; 2]) { 2 } else { 1 },
+            version: format::VERSION,
+            morph_targets: self
+                ._morph_targets
+                .iter()
+                .map(|target| target.resized(self._vertices.len()))
+                .collect(),
+        }
+    }
+
+    /// Добавить смещения цели морфинга для вершин, начиная с `base_vertex`.
+    /// Смещения целей с одинаковым именем объединяются, поэтому несколько мешей
+    /// построителя могут иметь общие цели.
+    /// Операции, меняющие состав вершин (оптимизация, пересчёт нормалей и касательных),
+    /// нужно выполнять до добавления целей.
+    pub fn add_morph_target(&mut self, base_vertex: u32, target: &morph::MorphTarget) -> Result<&mut Self, DsgeError> {
+        let end = base_vertex as usize + target.len();
+        if end > self._vertices.len() {
+            return Err(DsgeError::InvalidArgument(format!(
+                "morph target \"{}\" covers vertices up to {end}, mesh {} has {}",
+                target.name, self._name, self._vertices.len()
+            )));
+        }
+        let index = match self._morph_targets.iter().position(|existing| existing.name == target.name) {
+            Some(index) => index,
+            None if self._morph_targets.len() < morph::MAX_MORPH_TARGETS => {
+                self._morph_targets.push(morph::MorphTarget::new(&target.name, Vec::new()));
+                self._morph_targets.len() - 1
+            }
+            None => {
+                return Err(DsgeError::InvalidArgument(format!(
+                    "mesh {} already has {} morph targets",
+                    self._name,
+                    morph::MAX_MORPH_TARGETS
+                )))
+            }
+        };
+        self._morph_targets[index].write_at(base_vertex as usize, target);
+        Ok(self)
+    }
+
+    #[inline]
+    pub fn morph_targets(&self) -> &[morph::MorphTarget] {
+        &self._morph_targets
+    }
+
+    /// Формат вершинного буфера собираемого меша
+    pub fn set_vertex_format(&mut self, format: VertexFormat) -> &mut Self {
+        self._vertex_format = format;
+        self
+    }
+
+    #[inline]
+    pub fn vertex_format(&self) -> VertexFormat {
+        self._vertex_format
+    }
+
+    /// Допустимые ошибки квантования для [`VertexFormat::Compact`]
+    pub fn set_quantization_tolerance(&mut self, tolerance: compact::QuantizationTolerance) -> &mut Self {
+        self._quantization_tolerance = tolerance;
+        self
+    }
+
+    /// Ошибки квантования вершин в компактном формате.
+    /// Ошибка, если они превышают допустимые или номер кости не помещается в u8.
+    pub fn check_quantization(&self) -> Result<compact::QuantizationError, DsgeError> {
+        compact::compress_vertices(&self._vertices, &self._quantization_tolerance)
+            .map(|(_, _, error)| error)
+            .map_err(|err| err.context(&self._name))
+    }
+
+    /// Не сохранять в меше копии вершин и индексов на CPU.
+    /// Такой меш не участвует в [`MeshView::raycast`].
+    pub fn discard_cpu_copy(&mut self) -> &mut Self {
+        self._discard_cpu_copy = true;
+        self
+    }
+
+    /// Сохранение содержимого построителя в OBJ, PLY или формат движка
+    pub fn export<P: AsRef<Path>>(&self, path: P) -> Result<(), DsgeError> {
+        export::save_mesh(path, &self.mesh_data())
+    }
+
+    /// Добавить меш из [`MeshData`].
+    /// Возвращает первый индекс, количество индексов и габариты добавленного меша.
+    pub fn push_mesh_data(&mut self, data: &MeshData) -> (u32, u32, BoundingBox) {
+        let base_index = self._indices.len();
+        let base_vertex = self._vertices.len() as u32;
+        self._indices
+            .extend(data.indices.iter().map(|index| base_vertex + index));
+        self._vertices.extend_from_slice(&data.vertices);
+        for target in &data.morph_targets {
+            if let Err(err) = self.add_morph_target(base_vertex, target) {
+                log::warn!(target: "resource_manager", "{err}");
+            }
+        }
+        let bbox = data.bbox();
+        self._bbox.add(&bbox);
+        (base_index as _, data.indices.len() as _, bbox)
+    }
+
+    /// Добавить чайник из Юты
+    pub fn push_teapot(&mut self) -> Result<(u32, u32, BoundingBox), DsgeError> {
+        let rot = nalgebra::Rotation3::<f32>::from_euler_angles(0.0, 0.0, std::f32::consts::PI);
+        let mat = rot.matrix();
+        let self_ind_count = self._indices.len();
+        let mut max_x = -9999.0;
+        let mut max_y = -9999.0;
+        let mut min_x = 9999.0;
+        let mut min_y = 9999.0;
+        let base_index = self._indices.len();
+        //let base_vertex = self._vertices.len();
+        for vert in &VERTICES {
+            if vert.position[0] > max_x {
+                max_x = vert.position[0];
+            }
+            if vert.position[1] > max_y {
+                max_y = vert.position[1];
+            }
+            if vert.position[0] < min_x {
+                min_x = vert.position[0];
+            }
+            if vert.position[1] < min_y {
+                min_y = vert.position[1];
+            }
+        }
+        min_x /= 100.0;
+        min_y /= 100.0;
+        max_x /= 100.0;
+        max_y /= 100.0;
+        let mut bbox = BoundingBox::initial();
+        for i in 0..VERTICES.len() {
+            let pos = Vec3::new(
+                VERTICES[i].position[0] / 100.0,
+                VERTICES[i].position[1] / 100.0,
+                VERTICES[i].position[2] / 100.0,
+            );
+            let nor = Vec3::new(
+                NORMALS[i].normal[0],
+                NORMALS[i].normal[1],
+                NORMALS[i].normal[2],
+            );
+            let vert = Vertex {
+                v_pos: mat * pos,
+                v_nor: mat * nor,
+                v_tan: Vec3::new(0.0, 0.0, 0.0),
+                v_bin: Vec3::new(0.0, 0.0, 0.0),
+                v_tex1: Vec2::new(
+                    (pos.x - min_x) / (max_x - min_x),
+                    1.0 - (pos.y - min_y) / (max_y - min_y),
+                ),
+                v_tex2: Vec2::new(0.0, 0.0),
+                v_grp: UVec3::new(0, 0, 0),
+                v_wgt: Vec3::new(0.0, 0.0, 0.0),
+            };
+            self._vertices.push(vert.to_vk_vertex());
+            bbox.add_point(vert.v_pos);
+        }
+        for i in INDICES {
+            self._indices.push(self_ind_count as u32 + i);
+        }
+        self._bbox.add(&bbox);
+        Ok((base_index as _, INDICES.len() as _, bbox))
+    }
+
+    /// Добавить плоскость
+    pub fn push_screen_plane(&mut self) -> &mut Self {
+        self.push_triangle_coords(
+            &Vec3::new(-1.0, -1.0, 0.0),
+            &Vec3::new(-1.0, 1.0, 0.0),
+            &Vec3::new(1.0, 1.0, 0.0),
+        )
+        .push_triangle_coords(
+            &Vec3::new(-1.0, -1.0, 0.0),
+            &Vec3::new(1.0, 1.0, 0.0),
+            &Vec3::new(1.0, -1.0, 0.0),
+        )
+    }
+
+    pub fn build_mutex<A>(
+        self,
+        command_buffer_father: &CommandBufferFather,
+        allocator: Arc<GenericMemoryAllocator<A>>
+    ) -> Result<MeshRef, DsgeError>
+    where A: Suballocator + Send + 'static
+    {
+        match self.build(command_buffer_father, allocator) {
+            Ok(mesh) => Ok(Arc::new(mesh)),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn build<A>(
+        mut self,
+        //device: Arc<Device>,
+        command_buffer_father: &CommandBufferFather,
+        allocator: Arc<GenericMemoryAllocator<A>>
+    ) -> Result<Mesh, DsgeError>
+    where A: Suballocator + Send + 'static {
+        let mut hasher = DefaultHasher::default();
+        self._vertices.hash(&mut hasher);
+        self._indices.hash(&mut hasher);
+        self._vertex_format.hash(&mut hasher);
+        let hash = hasher.finish();
+        
+        {
+            let usage = BufferUsage::VERTEX_BUFFER | BufferUsage::TRANSFER_SRC;
+            let vertex_buffer = match self._vertex_format {
+                VertexFormat::Full => VertexBufferRef::Full(command_buffer_father.execute_in_new_primary(None, |pcbb| {
+                    pcbb.new_buffer_on_device_from_iter(usage, allocator.clone(), self._vertices.clone()).unwrap()
+                })?.0),
+                VertexFormat::Compact => {
+                    let (vertices, quantization, error) = compact::compress_vertices(&self._vertices, &self._quantization_tolerance)
+                        .map_err(|err| err.context(&self._name))?;
+                    log::debug!(target: "resource_manager", "Mesh {} quantization error: {error:?}", self._name);
+                    VertexBufferRef::Compact(command_buffer_father.execute_in_new_primary(None, |pcbb| {
+                        pcbb.new_buffer_on_device_from_iter(usage, allocator.clone(), vertices).unwrap()
+                    })?.0, quantization)
+                }
+            };
+            self._vertex_buffer = Some(vertex_buffer);
+        }
+        
+        {
+            let index_buffer = command_buffer_father.execute_in_new_primary(None, |pcbb| {
+                pcbb.new_buffer_on_device_from_iter(BufferUsage::INDEX_BUFFER | BufferUsage::TRANSFER_SRC, allocator.clone(), self._indices.clone()).unwrap()
+            })?.0;
+            self._index_buffer = Some(index_buffer);
+        }
+
+        let morph_targets = if self._morph_targets.is_empty() {
+            None
+        } else {
+            let deltas = morph::gpu_deltas(&self._morph_targets, self._vertices.len());
+            let buffer = command_buffer_father.execute_in_new_primary(None, |pcbb| {
+                pcbb.new_buffer_on_device_from_iter(BufferUsage::STORAGE_BUFFER | BufferUsage::TRANSFER_SRC, allocator.clone(), deltas).unwrap()
+            })?.0;
+            let names = self._morph_targets.iter().map(|target| target.name.clone()).collect();
+            Some(Arc::new(morph::MorphTargets::new(names, self._vertices.len() as _, buffer)))
+        };
+
+        //self._vertex_buffer.unwrap().
+
+        let mut bbox = BoundingBox::initial();
+        for vertex in &self._vertices {
+            bbox.add_point(vertex.v_pos.into());
+        }
+        let index_count = self._indices.len() as _;
+        let (vertices, indices) = if self._discard_cpu_copy {
+            (Arc::from([]), Arc::from([]))
+        } else {
+            (Arc::from(self._vertices), Arc::from(self._indices))
+        };
+    
+        let mesh = Mesh {
+            name: self._name.clone(),
+            //device: device.clone(),
+            deformed: false,
+            vertices,
+            indices,
+            vertex_buffer: self._vertex_buffer.as_ref().unwrap().clone(),
+            index_buffer: self._index_buffer.as_ref().unwrap().clone(),
+            index_count,
+            uv_count: 1,
+            hash: hash,
+            bbox,
+            morph_targets,
+        };
+        Ok(mesh)
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone)]
+pub struct Mesh {
+    name: String,
+    hash: u64,
+    /// Копии на CPU для [`MeshView::raycast`], пустые после [`MeshBuilder::discard_cpu_copy`]
+    indices: Arc<[u32]>,
+    vertices: Arc<[VkVertex]>,
+    //device: Arc<Device>,
+    vertex_buffer: VertexBufferRef,
+    index_buffer: IndexBufferRef,
+    index_count: usize,
+    pub bbox: BoundingBox,
+    deformed: bool,
+    uv_count: u8,
+    morph_targets: Option<Arc<morph::MorphTargets>>,
+}
+
+#[allow(dead_code)]
+impl Mesh {
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn builder(name: &str) -> MeshBuilder {
+        let mut res = MeshBuilder::default();
+        res._bbox = BoundingBox::initial();
+        res._name = name.to_owned();
+        res
+    }
+
+    pub fn make_screen_plane<A>(
+        command_buffer_father: &CommandBufferFather,
+        allocator: Arc<GenericMemoryAllocator<A>>
+    ) -> Result<MeshRef, DsgeError> 
+    where A: Suballocator + Send + 'static
+    {
+        let mut plane = Mesh::builder("screen_plane");
+        plane
+            .push_triangle_coords(
+                &Vec3::new(-1.0, -1.0, 0.0),
+                &Vec3::new(-1.0, 1.0, 0.0),
+                &Vec3::new(1.0, 1.0, 0.0),
+            )
+            .push_triangle_coords(
+                &Vec3::new(-1.0, -1.0, 0.0),
+                &Vec3::new(1.0, 1.0, 0.0),
+                &Vec3::new(1.0, -1.0, 0.0),
+            );
+        plane.build_mutex(command_buffer_father, allocator)
+    }
+
+    pub fn make_cube(
+        name: &str,
+        command_buffer_father: &CommandBufferFather,
+        allocator: Arc<StandardMemoryAllocator>
+    ) -> Result<MeshRef, DsgeError> {
+        let mut cube = Mesh::builder(name);
+        cube.push_quad_coords(
+            &Vec3::new(-1.0, -1.0, 1.0),
+            &Vec3::new(-1.0, 1.0, 1.0),
+            &Vec3::new(1.0, 1.0, 1.0),
+            &Vec3::new(1.0, -1.0, 1.0),
+        )
+        .push_quad_coords(
+            &Vec3::new(1.0, -1.0, -1.0),
+            &Vec3::new(1.0, 1.0, -1.0),
+            &Vec3::new(-1.0, 1.0, -1.0),
+            &Vec3::new(-1.0, -1.0, -1.0),
+        )
+        .push_quad_coords(
+            &Vec3::new(-1.0, -1.0, -1.0),
+            &Vec3::new(-1.0, -1.0, 1.0),
+            &Vec3::new(1.0, -1.0, 1.0),
+            &Vec3::new(1.0, -1.0, -1.0),
+        )
+        .push_quad_coords(
+            &Vec3::new(1.0, 1.0, -1.0),
+            &Vec3::new(1.0, 1.0, 1.0),
+            &Vec3::new(-1.0, 1.0, 1.0),
+            &Vec3::new(-1.0, 1.0, -1.0),
+        )
+        .push_quad_coords(
+            &Vec3::new(1.0, -1.0, -1.0),
+            &Vec3::new(1.0, -1.0, 1.0),
+            &Vec3::new(1.0, 1.0, 1.0),
+            &Vec3::new(1.0, 1.0, -1.0),
+        )
+        .push_quad_coords(
+            &Vec3::new(-1.0, 1.0, -1.0),
+            &Vec3::new(-1.0, 1.0, 1.0),
+            &Vec3::new(-1.0, -1.0, 1.0),
+            &Vec3::new(-1.0, -1.0, -1.0),
+        );
+        cube.build_mutex(command_buffer_father, allocator)
+    }
+
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+}
+
+pub struct SubMesh {
+    name: String,
+    mesh: Arc<Mesh>,
+    base_index: u32,
+    index_count: u32,
+    vertex_offset: u32,
+    pub bbox: BoundingBox,
+}
+
+impl SubMesh {
+    pub fn from_mesh(
+        name: String,
+        mesh: &Mesh,
+        bbox: BoundingBox,
+        base: u32,
+        count: u32,
+        offset: u32,
+    ) -> Arc<dyn MeshView> {
+        Arc::new(SubMesh {
+            name: name,
+            mesh: Arc::new(mesh.clone()),
+            base_index: base,
+            index_count: count,
+            vertex_offset: offset,
+            bbox: bbox,
+        })
+    }
+}
+
+unsafe impl Send for SubMesh {}
+unsafe impl Sync for SubMesh {}
+
+pub trait MeshView: Send + Sync + 'static {
+    fn name(&self) -> &String;
+    fn indirect_command(
+        &self,
+        first_instance: u32,
+        instance_count: u32,
+    ) -> DrawIndexedIndirectCommand;
+    fn vertex_buffer(&self) -> &VertexBufferRef;
+    fn index_buffer(&self) -> &IndexBufferRef;
+    fn base_index(&self) -> u32;
+    fn vertex_offset(&self) -> u32;
+    fn buffer_id(&self) -> u32;
+    fn bbox_corners(&self) -> [Vec3; 8];
+    fn bbox(&self) -> BoundingBox;
+    fn as_buffer(&self) -> Option<&Mesh>;
+    /// Цели морфинга вершинного буфера
+    fn morph_targets(&self) -> Option<&Arc<morph::MorphTargets>>;
+    #[inline]
+    fn vertex_format(&self) -> VertexFormat {
+        self.vertex_buffer().format()
+    }
+    /// Копии вершин всего буфера и индексов этого меша на CPU.
+    /// Индексы отсчитываются от [`Self::vertex_offset`].
+    fn cpu_geometry(&self) -> Option<(&[VkVertex], &[u32])>;
+    /// Ближайшее пересечение луча в системе координат меша с его треугольниками
+    fn raycast(&self, ray: &raycast::Ray, max_distance: f32) -> Option<raycast::MeshHit> {
+        let (vertices, indices) = self.cpu_geometry()?;
+        raycast::raycast_triangles(ray, vertices, indices, self.vertex_offset(), max_distance)
+    }
+    #[inline]
+    fn ref_id(&self) -> i32 {
+        self as *const Self as *const i32 as _
+    }
+}
+
+impl MeshView for Mesh {
+    fn as_buffer(&self) -> Option<&Mesh> {
+        Some(self)
+    }
+
+    #[inline]
+    fn morph_targets(&self) -> Option<&Arc<morph::MorphTargets>> {
+        self.morph_targets.as_ref()
+    }
+
+    fn cpu_geometry(&self) -> Option<(&[VkVertex], &[u32])> {
+        if self.vertices.is_empty() {
+            None
+        } else {
+            Some((&self.vertices, &self.indices))
+        }
+    }
+
+    #[inline]
+    fn name(&self) -> &String {
+        &self.name
+    }
+
+    #[inline]
+    fn indirect_command(
+        &self,
+        first_instance: u32,
+        instance_count: u32,
+    ) -> DrawIndexedIndirectCommand {
+        DrawIndexedIndirectCommand {
+            index_count: self.index_buffer.len() as _,
+            instance_count: instance_count,
+            first_index: 0,
+            vertex_offset: 0,
+            first_instance: first_instance,
+        }
+    }
+
+    #[inline]
+    fn vertex_buffer(&self) -> &VertexBufferRef {
+        &self.vertex_buffer
+    }
+
+    #[inline]
+    fn index_buffer(&self) -> &IndexBufferRef {
+        &self.index_buffer
+    }
+
+    #[inline]
+    fn base_index(&self) -> u32 {
+        0
+    }
+
+    #[inline]
+    fn vertex_offset(&self) -> u32 {
+        0
+    }
+
+    #[inline]
+    fn buffer_id(&self) -> u32 {
+        let ib = self.index_buffer.buffer().as_ref() as *const _ as u32;
+        let vb = self.vertex_buffer.as_bytes().buffer().as_ref() as *const _ as u32;
+        vb ^ ib
+    }
+
+    #[inline]
+    fn bbox(&self) -> BoundingBox {
+        self.bbox
+    }
+
+    #[inline]
+    fn bbox_corners(&self) -> [Vec3; 8] {
+        self.bbox.corners()
+    }
+}
+
+impl MeshView for SubMesh {
+    fn as_buffer(&self) -> Option<&Mesh> {
+        None
+    }
+
+    #[inline]
+    fn morph_targets(&self) -> Option<&Arc<morph::MorphTargets>> {
+        self.mesh.morph_targets.as_ref()
+    }
+
+    fn cpu_geometry(&self) -> Option<(&[VkVertex], &[u32])> {
+        let (vertices, indices) = self.mesh.cpu_geometry()?;
+        let range = self.base_index as usize..(self.base_index + self.index_count) as usize;
+        Some((vertices, indices.get(range)?))
+    }
+
+    #[inline]
+    fn name(&self) -> &String {
+        &self.name
+    }
+
+    #[inline]
+    fn indirect_command(
+        &self,
+        first_instance: u32,
+        instance_count: u32,
+    ) -> DrawIndexedIndirectCommand {
+        DrawIndexedIndirectCommand {
+            index_count: self.index_count,
+            instance_count: instance_count,
+            first_index: self.base_index,
+            vertex_offset: self.vertex_offset,
+            first_instance: first_instance,
+        }
+    }
+
+    #[inline]
+    fn vertex_buffer(&self) -> &VertexBufferRef {
+        &self.mesh.vertex_buffer
+    }
+
+    #[inline]
+    fn index_buffer(&self) -> &IndexBufferRef {
+        &self.mesh.index_buffer
+    }
+
+    #[inline]
+    fn base_index(&self) -> u32 {
+        self.base_index
+    }
+
+    #[inline]
+    fn vertex_offset(&self) -> u32 {
+        self.vertex_offset
+    }
+
+    #[inline]
+    fn buffer_id(&self) -> u32 {
+        self.mesh.buffer_id()
+    }
+
+    #[inline]
+    fn bbox_corners(&self) -> [Vec3; 8] {
+        self.bbox.corners()
+    }
+
+    #[inline]
+    fn bbox(&self) -> BoundingBox {
+        self.bbox
+    }
+}
+
+pub trait MeshCommandSet {
+    fn bind_mesh(&mut self, mesh: &dyn MeshView) -> Result<&mut Self, DsgeError>;
+    fn draw_mesh(&mut self, mesh: &dyn MeshView) -> Result<&mut Self, DsgeError>;
+}
+
+impl<T> MeshCommandSet for AutoCommandBufferBuilder<T> {
+    #[inline]
+    fn bind_mesh(&mut self, mesh: &dyn MeshView) -> Result<&mut Self, DsgeError> {
+        let vbo = mesh.vertex_buffer();
+        let ibo = mesh.index_buffer();
+        let result = self
+            .bind_vertex_buffers(0, vbo.as_bytes().clone()).unwrap()
+            .bind_index_buffer(ibo.clone()).unwrap()
+            .draw_indexed(ibo.len() as u32, 1, 0, 0, 0);
+        Ok(result.unwrap())
+    }
+
+    #[inline]
+    fn draw_mesh(&mut self, mesh: &dyn MeshView) -> Result<&mut Self, DsgeError> {
+        let result = self.draw_indexed(mesh.index_buffer().len() as u32, 1, 0, 0, 0);
+        match result {
+            Ok(b) => Ok(b),
+            Err(err) => Err(DsgeError::vulkan("Failed to record draw command", *err)),
+        }
+    }
+}
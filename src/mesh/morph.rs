@@ -0,0 +1,278 @@
+//! Морфинг (blend shapes): смещения позиций, нормалей и касательных вершин,
+//! которые смешиваются с весами из [`crate::components::MeshVisual`].
+//!
+//! На GPU смещения всех целей лежат в одном буфере рядом с вершинным:
+//! смещение вершины `v` цели `t` находится по индексу `t * число вершин + v`,
+//! где `v` — номер вершины во всём вершинном буфере меша (`gl_VertexIndex`).
+use bytemuck::{Pod, Zeroable};
+use vulkano::buffer::Subbuffer;
+
+use super::VkVertex;
+use crate::error::DsgeError;
+use crate::shader::ShaderStructUniform;
+use crate::types::Vec3;
+
+/// Максимальное число целей морфинга у одного меша
+pub const MAX_MORPH_TARGETS: usize = 32;
+
+// Смещения одной вершины для шейдера
+crate::fast_impl_ssu! {
+    #[derive(Default, Pod, Zeroable)]
+    struct MorphDelta {
+        position: [f32; 4],
+        normal: [f32; 4],
+        tangent: [f32; 4]
+    }
+}
+
+/// Цель морфинга: смещения вершин подряд, начиная с первой вершины меша.
+/// Пустой список нормалей или касательных означает отсутствие их смещений.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MorphTarget {
+    pub name: String,
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub tangents: Vec<[f32; 3]>,
+}
+
+impl MorphTarget {
+    pub fn new(name: &str, positions: Vec<[f32; 3]>) -> Self {
+        Self {
+            name: name.to_owned(),
+            positions,
+            ..Default::default()
+        }
+    }
+
+    /// Количество вершин, для которых заданы смещения
+    pub fn len(&self) -> usize {
+        self.positions.len().max(self.normals.len()).max(self.tangents.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Смещение позиции, нормали и касательной вершины
+    pub fn delta(&self, vertex: usize) -> [[f32; 3]; 3] {
+        let get = |values: &Vec<[f32; 3]>| values.get(vertex).copied().unwrap_or_default();
+        [get(&self.positions), get(&self.normals), get(&self.tangents)]
+    }
+
+    /// Дополнение нулями до `vertex_count` вершин
+    pub fn resized(&self, vertex_count: usize) -> Self {
+        let resize = |values: &Vec<[f32; 3]>| {
+            let mut values = values.clone();
+            values.resize(vertex_count, [0.0; 3]);
+            values
+        };
+        Self {
+            name: self.name.clone(),
+            positions: resize(&self.positions),
+            normals: resize(&self.normals),
+            tangents: resize(&self.tangents),
+        }
+    }
+
+    /// Запись смещений `other` начиная с вершины `base_vertex`
+    pub(super) fn write_at(&mut self, base_vertex: usize, other: &MorphTarget) {
+        let end = base_vertex + other.len();
+        let write = |values: &mut Vec<[f32; 3]>, source: &Vec<[f32; 3]>| {
+            if !source.is_empty() {
+                if values.len() < end {
+                    values.resize(end, [0.0; 3]);
+                }
+                values[base_vertex..base_vertex + source.len()].copy_from_slice(source);
+            }
+        };
+        write(&mut self.positions, &other.positions);
+        write(&mut self.normals, &other.normals);
+        write(&mut self.tangents, &other.tangents);
+    }
+}
+
+/// Проверка целей морфинга меша из `vertex_count` вершин
+pub fn validate(targets: &[MorphTarget], vertex_count: usize) -> Result<(), DsgeError> {
+    if targets.len() > MAX_MORPH_TARGETS {
+        return Err(DsgeError::InvalidData(format!(
+            "{} morph targets, at most {MAX_MORPH_TARGETS} are supported",
+            targets.len()
+        )));
+    }
+    for (i, target) in targets.iter().enumerate() {
+        if target.len() > vertex_count {
+            return Err(DsgeError::InvalidData(format!(
+                "morph target \"{}\" has {} vertices, mesh has {vertex_count}",
+                target.name,
+                target.len()
+            )));
+        }
+        if targets[..i].iter().any(|other| other.name == target.name) {
+            return Err(DsgeError::InvalidData(format!(
+                "morph target \"{}\" is declared twice",
+                target.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Смещения всех целей в порядке, в котором их читает шейдер
+pub fn gpu_deltas(targets: &[MorphTarget], vertex_count: usize) -> Vec<MorphDelta> {
+    let extend = |value: [f32; 3]| [value[0], value[1], value[2], 0.0];
+    targets
+        .iter()
+        .flat_map(|target| {
+            (0..vertex_count).map(move |vertex| {
+                let [position, normal, tangent] = target.delta(vertex);
+                MorphDelta {
+                    position: extend(position),
+                    normal: extend(normal),
+                    tangent: extend(tangent),
+                }
+            })
+        })
+        .collect()
+}
+
+/// Цели морфинга, загруженные на GPU
+pub struct MorphTargets {
+    names: Vec<String>,
+    vertex_count: u32,
+    deltas: Subbuffer<[MorphDelta]>,
+}
+
+impl MorphTargets {
+    pub(super) fn new(names: Vec<String>, vertex_count: u32, deltas: Subbuffer<[MorphDelta]>) -> Self {
+        Self { names, vertex_count, deltas }
+    }
+
+    #[inline]
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn index(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|target| target == name)
+    }
+
+    /// Число вершин во всём вершинном буфере меша
+    #[inline]
+    pub fn vertex_count(&self) -> u32 {
+        self.vertex_count
+    }
+
+    #[inline]
+    pub fn deltas(&self) -> &Subbuffer<[MorphDelta]> {
+        &self.deltas
+    }
+}
+
+fn normalize_or_keep(value: Vec3) -> Vec3 {
+    value.try_normalize(1.0e-12).unwrap_or(value)
+}
+
+/// Морфинг вершин на CPU, так же как в шейдере `DEFORMED`.
+/// Нормаль и касательная нормализуются, бинормаль пересчитывается
+/// с сохранением направления базиса.
+pub fn apply_morph_targets(vertices: &[VkVertex], targets: &[MorphTarget], weights: &[f32]) -> Vec<VkVertex> {
+    vertices
+        .iter()
+        .enumerate()
+        .map(|(index, vertex)| {
+            let mut delta = [Vec3::zeros(); 3];
+            for (target, weight) in targets.iter().zip(weights) {
+                if *weight != 0.0 {
+                    for (sum, value) in delta.iter_mut().zip(target.delta(index)) {
+                        *sum += Vec3::from(value) * *weight;
+                    }
+                }
+            }
+            if delta.iter().all(|value| *value == Vec3::zeros()) {
+                return *vertex;
+            }
+            let (nor, tan, bin) = (Vec3::from(vertex.v_nor), Vec3::from(vertex.v_tan), Vec3::from(vertex.v_bin));
+            let handedness = if nor.cross(&tan).dot(&bin) < 0.0 { -1.0 } else { 1.0 };
+            let nor = normalize_or_keep(nor + delta[1]);
+            let tan = normalize_or_keep(tan + delta[2]);
+            VkVertex {
+                v_pos: (Vec3::from(vertex.v_pos) + delta[0]).into(),
+                v_nor: nor.into(),
+                v_tan: tan.into(),
+                v_bin: (nor.cross(&tan) * handedness).into(),
+                ..*vertex
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn mesh_morph_reference() {
+    let vertex = |x: f32| VkVertex {
+        v_pos: [x, 0.0, 0.0],
+        v_nor: [0.0, 0.0, 1.0],
+        v_tan: [1.0, 0.0, 0.0],
+        v_bin: [0.0, 1.0, 0.0],
+        ..Default::default()
+    };
+    let vertices = vec![vertex(0.0), vertex(1.0), vertex(2.0)];
+    let mut raise = MorphTarget::new("raise", vec![[0.0; 3], [0.0, 0.0, 1.0]]);
+    raise.normals = vec![[0.0; 3], [-1.0, 0.0, 0.0]];
+    raise.tangents = vec![[0.0; 3], [0.0, 0.0, 1.0]];
+    let shift = MorphTarget::new("shift", vec![[1.0, 0.0, 0.0]; 3]);
+    let targets = vec![raise, shift];
+    validate(&targets, vertices.len()).unwrap();
+
+    // Нулевые веса не меняют вершины
+    let morphed = apply_morph_targets(&vertices, &targets, &[0.0, 0.0]);
+    assert!(morphed == vertices);
+
+    let morphed = apply_morph_targets(&vertices, &targets, &[1.0, 0.5]);
+    assert_eq!(morphed[0].v_pos, [0.5, 0.0, 0.0]);
+    assert_eq!(morphed[1].v_pos, [1.5, 0.0, 1.0]);
+    assert_eq!(morphed[2].v_pos, [2.5, 0.0, 0.0]);
+    let s = std::f32::consts::FRAC_1_SQRT_2;
+    let close = |a: [f32; 3], b: [f32; 3]| (Vec3::from(a) - Vec3::from(b)).norm() < 1e-5;
+    assert!(close(morphed[1].v_nor, [-s, 0.0, s]));
+    assert!(close(morphed[1].v_tan, [s, 0.0, s]));
+    // Базис остаётся правым, как у исходной вершины
+    assert!(close(morphed[1].v_bin, [0.0, 1.0, 0.0]));
+    assert!(close(morphed[2].v_nor, [0.0, 0.0, 1.0]));
+
+    let deltas = gpu_deltas(&targets, vertices.len());
+    assert_eq!(deltas.len(), 6);
+    assert_eq!(deltas[1].position, [0.0, 0.0, 1.0, 0.0]);
+    assert_eq!(deltas[3 + 2].position, [1.0, 0.0, 0.0, 0.0]);
+
+    let mut duplicate = targets.clone();
+    duplicate[1].name = "raise".to_owned();
+    assert!(matches!(validate(&duplicate, 3), Err(DsgeError::InvalidData(_))));
+    assert!(matches!(validate(&targets, 1), Err(DsgeError::InvalidData(_))));
+
+    // Цели в построителе задаются для диапазона вершин и дополняются нулями
+    let data = super::MeshData {
+        vertices: vertices.clone(),
+        indices: vec![0, 1, 2],
+        ..Default::default()
+    };
+    let mut builder = super::Mesh::builder("face");
+    builder.push_mesh_data(&data);
+    builder.push_mesh_data(&data);
+    builder.add_morph_target(3, &targets[0]).unwrap();
+    let data = builder.mesh_data();
+    assert_eq!(data.morph_targets.len(), 1);
+    assert_eq!(data.morph_targets[0].positions.len(), 6);
+    assert_eq!(data.morph_targets[0].positions[4], [0.0, 0.0, 1.0]);
+    assert_eq!(data.morph_targets[0].positions[1], [0.0; 3]);
+    assert!(builder.add_morph_target(5, &targets[0]).is_err());
+}
@@ -0,0 +1,333 @@
+//! Импорт Wavefront OBJ/MTL.
+//!
+//! Поддерживаются `v`, `vt`, `vn`, `f` (многоугольники разбиваются веером
+//! на треугольники, допускаются отрицательные индексы), `g`, `o`, `usemtl`, `mtllib`.
+//! Каждая комбинация вершины, текстурной координаты и нормали становится
+//! отдельной вершиной [`VkVertex`]. Координата V переворачивается, т.к. в OBJ
+//! начало текстуры внизу.
+use std::collections::HashMap;
+
+use super::format::MeshData;
+use super::{BoundingBox, Vertex};
+use crate::error::DsgeError;
+use crate::types::*;
+
+/// Группа треугольников с общим именем и материалом
+#[derive(Clone, Debug)]
+pub struct ObjGroup {
+    pub name: String,
+    pub material: Option<String>,
+    /// Первый индекс группы в [`ObjModel::mesh`]
+    pub base_index: u32,
+    pub index_count: u32,
+    pub bbox: BoundingBox,
+}
+
+pub struct ObjModel {
+    pub mesh: MeshData,
+    pub groups: Vec<ObjGroup>,
+    /// Файлы из `mtllib` в порядке упоминания
+    pub material_libs: Vec<String>,
+}
+
+/// Материал из MTL-файла
+#[derive(Clone, Debug)]
+pub struct MtlMaterial {
+    pub name: String,
+    /// `Kd` и `d`
+    pub diffuse: [f32; 4],
+    /// Шероховатость, пересчитанная из `Ns`
+    pub roughness: f32,
+    /// `map_Kd`
+    pub diffuse_map: Option<String>,
+    /// `norm`, `map_Bump` или `bump`
+    pub normal_map: Option<String>,
+}
+
+impl MtlMaterial {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            diffuse: [0.8, 0.8, 0.8, 1.0],
+            roughness: 0.5,
+            diffuse_map: None,
+            normal_map: None,
+        }
+    }
+}
+
+/// Перевод степени блеска Фонга (`Ns`, 0..1000) в шероховатость, как в импортёре Blender
+pub fn roughness_from_shininess(ns: f32) -> f32 {
+    (1.0 - ns.clamp(0.0, 900.0).sqrt() / 30.0).clamp(0.0, 1.0)
+}
+
+pub fn parse_obj(source: &str) -> Result<ObjModel, DsgeError> {
+    let mut positions = Vec::<Vec3>::new();
+    let mut uvs = Vec::<Vec2>::new();
+    let mut normals = Vec::<Vec3>::new();
+
+    let mut mesh = MeshData {
+        uv_count: 1,
+        ..Default::default()
+    };
+    let mut corners = HashMap::<(usize, Option<usize>, Option<usize>), u32>::new();
+    let mut groups = Vec::<ObjGroup>::new();
+    let mut material_libs = Vec::new();
+    let mut group_name = "default".to_owned();
+    let mut material = None;
+
+    for (num, line) in source.lines().enumerate() {
+        let error = |msg: String| DsgeError::InvalidData(format!("line {}: {msg}", num + 1));
+        let line = line.split('#').next().unwrap().trim();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else { continue };
+        let args = tokens.collect::<Vec<_>>();
+        let floats = |count: usize| -> Result<Vec<f32>, DsgeError> {
+            if args.len() < count {
+                return Err(error(format!("`{keyword}` needs {count} values")));
+            }
+            args.iter()
+                .map(|arg| arg.parse().map_err(|_| error(format!("bad number \"{arg}\""))))
+                .collect()
+        };
+        match keyword {
+            "v" => {
+                let v = floats(3)?;
+                positions.push(Vec3::new(v[0], v[1], v[2]));
+            }
+            "vt" => {
+                let v = floats(1)?;
+                uvs.push(Vec2::new(v[0], 1.0 - v.get(1).copied().unwrap_or(0.0)));
+            }
+            "vn" => {
+                let v = floats(3)?;
+                normals.push(Vec3::new(v[0], v[1], v[2]));
+            }
+            "g" | "o" => group_name = args.join(" "),
+            "usemtl" => material = Some(args.join(" ")),
+            "mtllib" => material_libs.extend(args.iter().map(|lib| lib.to_string())),
+            "f" => {
+                if args.len() < 3 {
+                    return Err(error("face has less than 3 vertices".to_owned()));
+                }
+                let mut face = Vec::with_capacity(args.len());
+                for arg in &args {
+                    let mut refs = arg.split('/');
+                    let mut index = |len: usize, required: bool| -> Result<Option<usize>, DsgeError> {
+                        match refs.next() {
+                            Some("") | None if !required => Ok(None),
+                            Some(value) => resolve_index(value, len).map(Some).map_err(error),
+                            None => Err(error(format!("bad face vertex \"{arg}\""))),
+                        }
+                    };
+                    let key = (
+                        index(positions.len(), true)?.unwrap(),
+                        index(uvs.len(), false)?,
+                        index(normals.len(), false)?,
+                    );
+                    let vertex_index = *corners.entry(key).or_insert_with(|| {
+                        let vertex = Vertex {
+                            v_pos: positions[key.0],
+                            v_tex1: key.1.map(|i| uvs[i]).unwrap_or_default(),
+                            v_nor: key.2.map(|i| normals[i]).unwrap_or_default(),
+                            ..Vertex::empty()
+                        };
+                        mesh.vertices.push(vertex.to_vk_vertex());
+                        mesh.vertices.len() as u32 - 1
+                    });
+                    face.push(vertex_index);
+                }
+
+                let same_group = groups
+                    .last()
+                    .map(|group| group.name == group_name && group.material == material)
+                    .unwrap_or(false);
+                if !same_group {
+                    groups.push(ObjGroup {
+                        name: group_name.clone(),
+                        material: material.clone(),
+                        base_index: mesh.indices.len() as _,
+                        index_count: 0,
+                        bbox: BoundingBox::initial(),
+                    });
+                }
+                let group = groups.last_mut().unwrap();
+                for i in 1..face.len() - 1 {
+                    mesh.indices.extend_from_slice(&[face[0], face[i], face[i + 1]]);
+                    group.index_count += 3;
+                }
+                for index in &face {
+                    group.bbox.add_point(Vec3::from(mesh.vertices[*index as usize].v_pos));
+                }
+            }
+            // Сглаживание, линии и кривые не поддерживаются
+            _ => (),
+        }
+    }
+    if groups.is_empty() {
+        return Err(DsgeError::InvalidData("OBJ file has no faces".to_owned()));
+    }
+    mesh.validate()?;
+    Ok(ObjModel {
+        mesh,
+        groups,
+        material_libs,
+    })
+}
+
+/// Индексы OBJ начинаются с 1, отрицательные отсчитываются от конца
+fn resolve_index(value: &str, len: usize) -> Result<usize, String> {
+    let index = value
+        .parse::<i64>()
+        .map_err(|_| format!("bad index \"{value}\""))?;
+    let resolved = match index {
+        1.. => index - 1,
+        ..=-1 => len as i64 + index,
+        0 => return Err("index 0 is not allowed".to_owned()),
+    };
+    if resolved < 0 || resolved >= len as i64 {
+        return Err(format!("index {index} is out of range, {len} elements defined"));
+    }
+    Ok(resolved as usize)
+}
+
+pub fn parse_mtl(source: &str) -> Result<Vec<MtlMaterial>, DsgeError> {
+    let mut materials = Vec::<MtlMaterial>::new();
+    for (num, line) in source.lines().enumerate() {
+        let error = |msg: String| DsgeError::InvalidData(format!("line {}: {msg}", num + 1));
+        let line = line.split('#').next().unwrap().trim();
+        let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        if keyword.is_empty() {
+            continue;
+        }
+        if keyword == "newmtl" {
+            materials.push(MtlMaterial::new(rest.trim()));
+            continue;
+        }
+        let Some(material) = materials.last_mut() else {
+            return Err(error(format!("`{keyword}` before `newmtl`")));
+        };
+        let floats = rest
+            .split_whitespace()
+            .map(|arg| arg.parse::<f32>().map_err(|_| error(format!("bad number \"{arg}\""))))
+            .collect::<Result<Vec<_>, _>>();
+        match keyword {
+            "Kd" => {
+                let kd = floats?;
+                let kd = match kd.as_slice() {
+                    [r, g, b, ..] => [*r, *g, *b],
+                    [v] => [*v; 3],
+                    _ => return Err(error("`Kd` needs 3 values".to_owned())),
+                };
+                material.diffuse[..3].copy_from_slice(&kd);
+            }
+            "d" => material.diffuse[3] = *floats?.first().ok_or_else(|| error("`d` needs a value".to_owned()))?,
+            "Tr" => material.diffuse[3] = 1.0 - *floats?.first().ok_or_else(|| error("`Tr` needs a value".to_owned()))?,
+            "Ns" => {
+                let ns = *floats?.first().ok_or_else(|| error("`Ns` needs a value".to_owned()))?;
+                material.roughness = roughness_from_shininess(ns);
+            }
+            "map_Kd" => material.diffuse_map = Some(map_file(rest).ok_or_else(|| error("no file name".to_owned()))?),
+            "norm" | "map_Bump" | "map_bump" | "bump" => {
+                material.normal_map = Some(map_file(rest).ok_or_else(|| error("no file name".to_owned()))?)
+            }
+            // Остальные параметры (Ka, Ks, illum, ...) не соответствуют PBR-материалам движка
+            _ => (),
+        }
+    }
+    Ok(materials)
+}
+
+/// Имя файла текстуры без параметров вида `-bm 0.5`, `-o 0 0 0`
+fn map_file(args: &str) -> Option<String> {
+    let args = args.split_whitespace().collect::<Vec<_>>();
+    let mut i = 0;
+    while i < args.len() {
+        let option_args = match args[i] {
+            "-blendu" | "-blendv" | "-cc" | "-clamp" | "-imfchan" | "-texres" | "-bm" | "-boost" | "-type" => 1,
+            "-mm" => 2,
+            "-o" | "-s" | "-t" => 3,
+            _ => return Some(args[i..].join(" ")),
+        };
+        i += 1 + option_args;
+    }
+    None
+}
+
+#[test]
+fn obj_parsing() {
+    let obj = "\
+mtllib cube.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+g quad
+usemtl red
+f 1/1/1 2/2/1 3/3/1 4/4/1
+g tri
+usemtl blue
+f -5/1 -4/2 -1/3
+";
+    let model = parse_obj(obj).unwrap();
+    assert_eq!(model.material_libs, ["cube.mtl"]);
+    assert_eq!(model.mesh.indices, [0, 1, 2, 0, 2, 3, 4, 5, 6]);
+    // 4 вершины квадрата и 3 вершины треугольника без нормалей
+    assert_eq!(model.mesh.vertices.len(), 7);
+    assert_eq!(model.mesh.vertices[2].v_tex1, [1.0, 0.0]);
+    assert_eq!(model.mesh.vertices[6].v_pos, [0.0, 0.0, 1.0]);
+    assert_eq!(model.mesh.vertices[6].v_nor, [0.0, 0.0, 0.0]);
+    assert_eq!(model.groups.len(), 2);
+    assert_eq!(model.groups[0].name, "quad");
+    assert_eq!(model.groups[0].material.as_deref(), Some("red"));
+    assert_eq!((model.groups[1].base_index, model.groups[1].index_count), (6, 3));
+    assert_eq!(model.groups[0].bbox.end, Vec3::new(1.0, 1.0, 0.0));
+
+    assert!(parse_obj("v 0 0 0\nf 1 2 3\n").is_err());
+    assert!(parse_obj("v 0 0 0\nv 0 0 0\nv 0 0 0\nf 0 1 2\n").is_err());
+    assert!(parse_obj("v 0 0\n").is_err());
+    assert!(parse_obj("v 0 0 0\n").is_err());
+
+    let mtl = "\
+newmtl red
+Kd 1 0 0
+Ns 900
+map_Kd -bm 1.0 textures/red brick.png
+newmtl blue
+d 0.5
+bump -bm 0.3 blue_normal.png
+";
+    let materials = parse_mtl(mtl).unwrap();
+    assert_eq!(materials.len(), 2);
+    assert_eq!(materials[0].diffuse, [1.0, 0.0, 0.0, 1.0]);
+    assert_eq!(materials[0].roughness, 0.0);
+    assert_eq!(materials[0].diffuse_map.as_deref(), Some("textures/red brick.png"));
+    assert_eq!(materials[1].diffuse[3], 0.5);
+    assert_eq!(materials[1].normal_map.as_deref(), Some("blue_normal.png"));
+    assert!(parse_mtl("Kd 1 1 1\n").is_err());
+}
+
+#[test]
+fn obj_tangent_space() {
+    let model = parse_obj("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nf 1/1 2/2 3/3 4/4\n").unwrap();
+    let mut builder = super::Mesh::builder("quad");
+    builder.push_teapot().unwrap();
+    let teapot_vertex = builder._vertices[0];
+    let (base_index, _, _) = builder.push_mesh_data(&model.mesh);
+    builder.calc_tangent_space_from(base_index as _);
+    // Уже добавленные вершины не меняются
+    assert_eq!(builder._vertices[0].v_tan, teapot_vertex.v_tan);
+    for vertex in &builder._vertices[builder._vertices.len() - 4..] {
+        let vertex = vertex.to_vertex();
+        assert!((vertex.v_nor - Vec3::z()).norm() < 1e-5);
+        assert!((vertex.v_tan - Vec3::x()).norm() < 1e-5);
+        // V перевёрнута, поэтому бинормаль направлена против оси Y
+        assert!((vertex.v_bin + Vec3::y()).norm() < 1e-5);
+    }
+}
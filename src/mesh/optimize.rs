@@ -0,0 +1,270 @@
+//! Оптимизация мешей для GPU.
+//!
+//! * [`weld`] объединяет одинаковые вершины (с точностью хеша [`VkVertex`]);
+//! * [`optimize_vertex_cache`] переупорядочивает треугольники по алгоритму
+//!   Тома Форсайта ("Linear-Speed Vertex Cache Optimisation");
+//! * [`optimize_vertex_fetch`] переставляет вершины в порядке первого использования.
+//!
+//! Качество оценивается через ACMR — среднее число промахов кэша вершин на треугольник.
+use std::collections::HashMap;
+use std::fmt;
+
+use super::VkVertex;
+
+/// Размер FIFO-кэша для оценки [`acmr`]
+pub const FIFO_CACHE_SIZE: usize = 16;
+
+/// Размер LRU-кэша, под который оптимизирует алгоритм Форсайта
+const CACHE_SIZE: usize = 32;
+const CACHE_DECAY_POWER: f32 = 1.5;
+const LAST_TRI_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+/// Результат [`super::MeshBuilder::optimize`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OptimizeStats {
+    pub vertices_before: usize,
+    pub vertices_after: usize,
+    pub acmr_before: f32,
+    pub acmr_after: f32,
+}
+
+impl fmt::Display for OptimizeStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "vertices {} -> {}, ACMR {:.3} -> {:.3}",
+            self.vertices_before, self.vertices_after, self.acmr_before, self.acmr_after
+        )
+    }
+}
+
+/// Среднее число промахов FIFO-кэша размером `cache_size` на треугольник
+pub fn acmr(indices: &[u32], cache_size: usize) -> f32 {
+    let triangles = indices.len() / 3;
+    if triangles == 0 {
+        return 0.0;
+    }
+    let mut cache = std::collections::VecDeque::with_capacity(cache_size);
+    let mut misses = 0;
+    for index in indices {
+        if !cache.contains(index) {
+            misses += 1;
+            if cache.len() == cache_size {
+                cache.pop_front();
+            }
+            cache.push_back(*index);
+        }
+    }
+    misses as f32 / triangles as f32
+}
+
+/// Объединение одинаковых вершин.
+/// Возвращает уникальные вершины в порядке первого появления и новые индексы.
+pub fn weld(vertices: &[VkVertex], indices: &[u32]) -> (Vec<VkVertex>, Vec<u32>) {
+    let mut unique = HashMap::with_capacity(vertices.len());
+    let mut welded = Vec::with_capacity(vertices.len());
+    let remap = vertices
+        .iter()
+        .map(|vertex| {
+            *unique.entry(*vertex).or_insert_with(|| {
+                welded.push(*vertex);
+                welded.len() as u32 - 1
+            })
+        })
+        .collect::<Vec<_>>();
+    let indices = indices.iter().map(|index| remap[*index as usize]).collect();
+    (welded, indices)
+}
+
+/// Вершины в порядке первого использования, неиспользуемые вершины отбрасываются
+pub fn optimize_vertex_fetch(vertices: &[VkVertex], indices: &[u32]) -> (Vec<VkVertex>, Vec<u32>) {
+    let mut remap = vec![u32::MAX; vertices.len()];
+    let mut reordered = Vec::with_capacity(vertices.len());
+    let indices = indices
+        .iter()
+        .map(|index| {
+            let new_index = &mut remap[*index as usize];
+            if *new_index == u32::MAX {
+                *new_index = reordered.len() as u32;
+                reordered.push(vertices[*index as usize]);
+            }
+            *new_index
+        })
+        .collect();
+    (reordered, indices)
+}
+
+fn vertex_score(cache_position: Option<usize>, remaining: usize) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+    let cache_score = match cache_position {
+        None => 0.0,
+        Some(pos) if pos < 3 => LAST_TRI_SCORE,
+        Some(pos) => (1.0 - (pos - 3) as f32 / (CACHE_SIZE - 3) as f32).powf(CACHE_DECAY_POWER),
+    };
+    cache_score + VALENCE_BOOST_SCALE * (remaining as f32).powf(-VALENCE_BOOST_POWER)
+}
+
+/// Переупорядочивание треугольников для кэша вершин
+pub fn optimize_vertex_cache(indices: &[u32], vertex_count: usize) -> Vec<u32> {
+    let triangle_count = indices.len() / 3;
+    // Треугольники, ещё не добавленные в результат, для каждой вершины
+    let mut vertex_triangles = vec![Vec::new(); vertex_count];
+    for (triangle, corners) in indices.chunks_exact(3).enumerate() {
+        for index in corners {
+            vertex_triangles[*index as usize].push(triangle as u32);
+        }
+    }
+    let mut cache_position = vec![None; vertex_count];
+    let mut vertex_scores = (0..vertex_count)
+        .map(|vertex| vertex_score(None, vertex_triangles[vertex].len()))
+        .collect::<Vec<_>>();
+    let triangle_score = |vertex_scores: &[f32], triangle: usize| {
+        indices[triangle * 3..triangle * 3 + 3]
+            .iter()
+            .map(|index| vertex_scores[*index as usize])
+            .sum::<f32>()
+    };
+    let mut triangle_scores = (0..triangle_count)
+        .map(|triangle| triangle_score(&vertex_scores, triangle))
+        .collect::<Vec<_>>();
+    let mut added = vec![false; triangle_count];
+
+    let mut result = Vec::with_capacity(indices.len());
+    let mut cache = Vec::<u32>::with_capacity(CACHE_SIZE + 3);
+    let mut best = None;
+    for _ in 0..triangle_count {
+        // Если среди соседей кэша кандидатов нет, ищем по всем треугольникам
+        let triangle = best.unwrap_or_else(|| {
+            (0..triangle_count)
+                .filter(|triangle| !added[*triangle])
+                .max_by(|a, b| triangle_scores[*a].total_cmp(&triangle_scores[*b]))
+                .unwrap()
+        });
+        added[triangle] = true;
+        let corners = &indices[triangle * 3..triangle * 3 + 3];
+        result.extend_from_slice(corners);
+
+        let mut new_cache = corners.to_vec();
+        for index in corners {
+            let triangles = &mut vertex_triangles[*index as usize];
+            if let Some(pos) = triangles.iter().position(|t| *t as usize == triangle) {
+                triangles.swap_remove(pos);
+            }
+        }
+        new_cache.extend(cache.iter().filter(|index| !corners.contains(index)));
+        for (pos, index) in new_cache.iter().enumerate() {
+            cache_position[*index as usize] = (pos < CACHE_SIZE).then_some(pos);
+        }
+        for index in &new_cache {
+            let index = *index as usize;
+            vertex_scores[index] = vertex_score(cache_position[index], vertex_triangles[index].len());
+        }
+
+        best = None;
+        let mut best_score = f32::MIN;
+        for index in &new_cache {
+            for triangle in &vertex_triangles[*index as usize] {
+                let triangle = *triangle as usize;
+                let score = triangle_score(&vertex_scores, triangle);
+                triangle_scores[triangle] = score;
+                if score > best_score {
+                    best_score = score;
+                    best = Some(triangle);
+                }
+            }
+        }
+        new_cache.truncate(CACHE_SIZE);
+        cache = new_cache;
+    }
+    result
+}
+
+#[cfg(test)]
+fn grid(size: u32) -> Vec<u32> {
+    let mut indices = Vec::new();
+    for y in 0..size {
+        for x in 0..size {
+            let i = y * (size + 1) + x;
+            indices.extend_from_slice(&[i, i + 1, i + size + 2, i, i + size + 2, i + size + 1]);
+        }
+    }
+    indices
+}
+
+#[test]
+fn mesh_optimize_vertex_cache() {
+    let size = 30;
+    let vertex_count = ((size + 1) * (size + 1)) as usize;
+    // Треугольники сетки в перемешанном порядке
+    let mut triangles = grid(size).chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect::<Vec<_>>();
+    let mut seed = 12345u32;
+    for i in (1..triangles.len()).rev() {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        triangles.swap(i, (seed >> 8) as usize % (i + 1));
+    }
+    let shuffled = triangles.concat();
+    let optimized = optimize_vertex_cache(&shuffled, vertex_count);
+    assert!(acmr(&optimized, FIFO_CACHE_SIZE) < 0.8, "{}", acmr(&optimized, FIFO_CACHE_SIZE));
+    assert!(acmr(&shuffled, FIFO_CACHE_SIZE) > 2.0);
+
+    // Тот же набор треугольников
+    let normalize = |indices: &[u32]| {
+        let mut triangles = indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect::<Vec<_>>();
+        triangles.sort();
+        triangles
+    };
+    assert_eq!(normalize(&optimized), normalize(&shuffled));
+}
+
+#[test]
+fn mesh_optimize_weld_and_fetch() {
+    let mut builder = super::Mesh::builder("cube");
+    let corners = [-1.0, 1.0];
+    for z in corners {
+        let v = |x, y| crate::types::Vec3::new(x, y, z);
+        builder.push_quad_coords(&v(-1.0, -1.0), &v(1.0, -1.0), &v(1.0, 1.0), &v(-1.0, 1.0));
+    }
+    let (vertices, indices) = weld(&builder._vertices, &builder._indices);
+    assert_eq!(builder._vertices.len(), 12);
+    assert_eq!(vertices.len(), 8);
+    assert_eq!(indices.len(), 12);
+    assert_eq!(acmr(&indices, FIFO_CACHE_SIZE), 8.0 / 4.0);
+
+    let reversed = indices.iter().rev().copied().collect::<Vec<_>>();
+    let (fetched, fetched_indices) = optimize_vertex_fetch(&vertices, &reversed);
+    assert_eq!(fetched_indices[..3], [0, 1, 2]);
+    assert!(fetched[0] == vertices[reversed[0] as usize]);
+}
+
+#[test]
+fn mesh_optimize_builder_ranges() {
+    let mut builder = super::Mesh::builder("teapot");
+    let (teapot_base, teapot_count, _) = builder.push_teapot().unwrap();
+    let quad_base = builder._indices.len() as u32;
+    let v = crate::types::Vec3::new;
+    builder.push_quad_coords(&v(0.0, 0.0, 0.0), &v(1.0, 0.0, 0.0), &v(1.0, 1.0, 0.0), &v(0.0, 1.0, 0.0));
+    let positions = |builder: &super::MeshBuilder, base: u32, count: u32| {
+        let mut triangles = builder._indices[base as usize..(base + count) as usize]
+            .iter()
+            .map(|index| builder._vertices[*index as usize].v_pos.map(|x| (x * 1000.0) as i32))
+            .collect::<Vec<_>>()
+            .chunks_exact(3)
+            .map(|t| t.to_vec())
+            .collect::<Vec<_>>();
+        triangles.sort();
+        triangles
+    };
+    let teapot = positions(&builder, teapot_base, teapot_count);
+    let quad = positions(&builder, quad_base, 6);
+
+    let stats = builder.optimize_ranges(&[(teapot_base, teapot_count), (quad_base, 6)]);
+    assert!(stats.acmr_after <= stats.acmr_before, "{stats}");
+    assert_eq!(stats.vertices_after, builder._vertices.len());
+    assert!(stats.vertices_after < stats.vertices_before);
+    assert_eq!(positions(&builder, teapot_base, teapot_count), teapot);
+    assert_eq!(positions(&builder, quad_base, 6), quad);
+}
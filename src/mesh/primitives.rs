@@ -0,0 +1,352 @@
+//! Процедурные примитивы для прототипов и отладочной визуализации.
+//!
+//! Ось тел вращения — Z, как в сценах из Blender. Каждый метод возвращает
+//! первый индекс, количество индексов и габариты примитива, как
+//! [`MeshBuilder::push_teapot`]. Нормали задаются аналитически,
+//! касательные считаются по текстурным координатам.
+use std::collections::HashMap;
+use std::f32::consts::{PI, TAU};
+
+use super::{BoundingBox, MeshBuilder, Vertex};
+use crate::types::{Vec2, Vec3};
+
+/// Точка на окружности радиуса `radius` в плоскости XY, `u` — доля оборота.
+/// При `u = 1` получается та же позиция, что при `u = 0`, без погрешности синуса.
+fn ring(radius: f32, u: f32) -> Vec3 {
+    let angle = TAU * (u % 1.0);
+    Vec3::new(radius * angle.cos(), radius * angle.sin(), 0.0)
+}
+
+/// Нормаль сферы на широте `phi` (от +Z) и долготе `u`.
+/// Синус отсекается снизу, чтобы вершины на полюсах совпадали точно.
+fn meridian(phi: f32, u: f32) -> Vec3 {
+    ring(phi.sin().max(0.0), u) + Vec3::new(0.0, 0.0, phi.cos())
+}
+
+impl MeshBuilder {
+    /// Сфера с сеткой из меридианов и параллелей. `v = 0` текстуры — северный полюс (+Z)
+    pub fn push_uv_sphere(&mut self, radius: f32, segments: u32, rings: u32) -> (u32, u32, BoundingBox) {
+        let base_index = self._indices.len();
+        self.push_parametric(segments.max(3), rings.max(2), |u, v| {
+            let phi = PI * v;
+            let normal = meridian(phi, u);
+            (normal * radius, normal, Vec2::new(u, v))
+        });
+        self.finish_primitive(base_index)
+    }
+
+    /// Сфера из подразбитого икосаэдра, треугольники примерно одинакового размера.
+    /// Текстурные координаты сферические, как у [`MeshBuilder::push_uv_sphere`].
+    pub fn push_icosphere(&mut self, radius: f32, subdivisions: u32) -> (u32, u32, BoundingBox) {
+        let base_index = self._indices.len();
+        let t = (1.0 + 5f32.sqrt()) * 0.5;
+        let mut points = [
+            [-1.0, t, 0.0], [1.0, t, 0.0], [-1.0, -t, 0.0], [1.0, -t, 0.0],
+            [0.0, -1.0, t], [0.0, 1.0, t], [0.0, -1.0, -t], [0.0, 1.0, -t],
+            [t, 0.0, -1.0], [t, 0.0, 1.0], [-t, 0.0, -1.0], [-t, 0.0, 1.0],
+        ]
+        .map(|p| Vec3::from(p).normalize())
+        .to_vec();
+        let mut faces: Vec<[usize; 3]> = vec![
+            [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+            [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+            [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+            [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+        ];
+        for _ in 0..subdivisions {
+            let mut midpoints = HashMap::new();
+            let mut midpoint = |a: usize, b: usize| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    points.push((points[a] + points[b]).normalize());
+                    points.len() - 1
+                })
+            };
+            faces = faces
+                .iter()
+                .flat_map(|[a, b, c]| {
+                    let (ab, bc, ca) = (midpoint(*a, *b), midpoint(*b, *c), midpoint(*c, *a));
+                    [[*a, ab, ca], [*b, bc, ab], [*c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+
+        // Вершины на шве текстуры и на полюсах дублируются с разными координатами u
+        let mut vertices = HashMap::new();
+        for face in faces {
+            let corners = face.map(|index| points[index]);
+            let mut uv = corners.map(|p| Vec2::new(0.5 + p.y.atan2(p.x) / TAU, p.z.clamp(-1.0, 1.0).acos() / PI));
+            let min_u = uv.iter().map(|uv| uv.x).fold(f32::MAX, f32::min);
+            let max_u = uv.iter().map(|uv| uv.x).fold(f32::MIN, f32::max);
+            if max_u - min_u > 0.5 {
+                uv.iter_mut().filter(|uv| uv.x < 0.5).for_each(|uv| uv.x += 1.0);
+            }
+            for i in 0..3 {
+                if corners[i].xy().norm() < 1e-6 {
+                    uv[i].x = (uv[(i + 1) % 3].x + uv[(i + 2) % 3].x) * 0.5;
+                }
+            }
+            let triangle = [0, 1, 2].map(|i| {
+                *vertices.entry((face[i], uv[i].x.to_bits())).or_insert_with(|| {
+                    self._vertices.push(primitive_vertex(corners[i] * radius, corners[i], uv[i]));
+                    self._vertices.len() as u32 - 1
+                })
+            });
+            self.push_oriented_triangle(triangle);
+        }
+        self.finish_primitive(base_index)
+    }
+
+    /// Цилиндр высотой `height` вдоль оси Z с крышками
+    pub fn push_cylinder(&mut self, radius: f32, height: f32, segments: u32) -> (u32, u32, BoundingBox) {
+        let base_index = self._indices.len();
+        self.push_cylinder_between(radius, -height * 0.5, height * 0.5, segments.max(3));
+        self.finish_primitive(base_index)
+    }
+
+    /// Конус высотой `height` вдоль оси Z, вершина сверху, основание закрыто
+    pub fn push_cone(&mut self, radius: f32, height: f32, segments: u32) -> (u32, u32, BoundingBox) {
+        let base_index = self._indices.len();
+        self.push_cone_between(radius, -height * 0.5, height * 0.5, segments.max(3));
+        self.finish_primitive(base_index)
+    }
+
+    /// Капсула вдоль оси Z: цилиндр высотой `height` с полусферами на концах.
+    /// `rings` — число параллелей на каждой полусфере.
+    pub fn push_capsule(&mut self, radius: f32, height: f32, segments: u32, rings: u32) -> (u32, u32, BoundingBox) {
+        let base_index = self._indices.len();
+        let rings = rings.max(1);
+        // Ряды 0..=rings — верхняя полусфера, остальные — нижняя
+        let rows = rings * 2 + 1;
+        let half_arc = PI * radius * 0.5;
+        let length = half_arc * 2.0 + height;
+        self.push_parametric(segments.max(3), rows, |u, v| {
+            let row = (v * rows as f32).round() as u32;
+            let (phi, offset, arc) = if row <= rings {
+                let phi = PI * 0.5 * row as f32 / rings as f32;
+                (phi, height * 0.5, phi * radius)
+            } else {
+                let phi = PI * 0.5 * (1.0 + (row - rings - 1) as f32 / rings as f32);
+                (phi, -height * 0.5, phi * radius + height)
+            };
+            let normal = meridian(phi, u);
+            (normal * radius + Vec3::new(0.0, 0.0, offset), normal, Vec2::new(u, arc / length))
+        });
+        self.finish_primitive(base_index)
+    }
+
+    /// Тор в плоскости XY: `major_radius` — радиус окружности центров трубки,
+    /// `minor_radius` — радиус трубки
+    pub fn push_torus(
+        &mut self,
+        major_radius: f32,
+        minor_radius: f32,
+        segments: u32,
+        sides: u32,
+    ) -> (u32, u32, BoundingBox) {
+        let base_index = self._indices.len();
+        self.push_parametric(segments.max(3), sides.max(3), |u, v| {
+            let phi = TAU * (v % 1.0);
+            let center = ring(major_radius, u);
+            let normal = ring(phi.cos(), u) + Vec3::new(0.0, 0.0, phi.sin());
+            (center + normal * minor_radius, normal, Vec2::new(u, v))
+        });
+        self.finish_primitive(base_index)
+    }
+
+    /// Плоскость `size` в XY, нормаль +Z, разбитая на `columns` x `rows` квадратов.
+    /// Подходит и как отладочная сетка.
+    pub fn push_plane(&mut self, size: Vec2, columns: u32, rows: u32) -> (u32, u32, BoundingBox) {
+        let base_index = self._indices.len();
+        self.push_parametric(columns.max(1), rows.max(1), |u, v| {
+            let pos = Vec3::new((u - 0.5) * size.x, (0.5 - v) * size.y, 0.0);
+            (pos, Vec3::z(), Vec2::new(u, v))
+        });
+        self.finish_primitive(base_index)
+    }
+
+    /// Стрелка вдоль +Z от начала координат длиной `length`:
+    /// цилиндр `shaft_radius` и конус-наконечник `head_length` x `head_radius`
+    pub fn push_arrow(
+        &mut self,
+        length: f32,
+        shaft_radius: f32,
+        head_length: f32,
+        head_radius: f32,
+        segments: u32,
+    ) -> (u32, u32, BoundingBox) {
+        let base_index = self._indices.len();
+        let segments = segments.max(3);
+        let head_start = (length - head_length).max(0.0);
+        self.push_cylinder_between(shaft_radius, 0.0, head_start, segments);
+        self.push_cone_between(head_radius, head_start, length, segments);
+        self.finish_primitive(base_index)
+    }
+
+    fn push_cylinder_between(&mut self, radius: f32, bottom: f32, top: f32, segments: u32) {
+        self.push_parametric(segments, 1, |u, v| {
+            let normal = ring(1.0, u);
+            let z = top + (bottom - top) * v;
+            (normal * radius + Vec3::new(0.0, 0.0, z), normal, Vec2::new(u, v))
+        });
+        self.push_disc(radius, top, segments, 1.0);
+        self.push_disc(radius, bottom, segments, -1.0);
+    }
+
+    fn push_cone_between(&mut self, radius: f32, bottom: f32, top: f32, segments: u32) {
+        let height = top - bottom;
+        self.push_parametric(segments, 1, |u, v| {
+            let normal = (ring(height, u) + Vec3::new(0.0, 0.0, radius)).normalize();
+            (ring(radius * v, u) + Vec3::new(0.0, 0.0, top - height * v), normal, Vec2::new(u, v))
+        });
+        self.push_disc(radius, bottom, segments, -1.0);
+    }
+
+    /// Круг в плоскости `z`, нормаль `facing` по Z, планарные текстурные координаты
+    fn push_disc(&mut self, radius: f32, z: f32, segments: u32, facing: f32) {
+        self.push_parametric(segments, 1, |u, v| {
+            let pos = ring(radius * v, u) + Vec3::new(0.0, 0.0, z);
+            let uv = Vec2::new(0.5 + 0.5 * v * (TAU * u).cos(), 0.5 - 0.5 * facing * v * (TAU * u).sin());
+            (pos, Vec3::new(0.0, 0.0, facing), uv)
+        });
+    }
+
+    /// Сетка `(columns + 1) x (rows + 1)` вершин по параметрам `u, v` из [0, 1].
+    /// Вырожденные треугольники (на полюсах и вершинах конусов) пропускаются.
+    fn push_parametric<F>(&mut self, columns: u32, rows: u32, surface: F)
+    where
+        F: Fn(f32, f32) -> (Vec3, Vec3, Vec2),
+    {
+        let first_vertex = self._vertices.len() as u32;
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let (pos, normal, uv) = surface(column as f32 / columns as f32, row as f32 / rows as f32);
+                self._vertices.push(primitive_vertex(pos, normal, uv));
+            }
+        }
+        for row in 0..rows {
+            for column in 0..columns {
+                let a = first_vertex + row * (columns + 1) + column;
+                let d = a + columns + 1;
+                self.push_oriented_triangle([a, a + 1, d + 1]);
+                self.push_oriented_triangle([a, d + 1, d]);
+            }
+        }
+    }
+
+    /// Треугольник с обходом против часовой стрелки относительно нормалей вершин
+    fn push_oriented_triangle(&mut self, triangle: [u32; 3]) {
+        let [a, b, c] = triangle.map(|index| self._vertices[index as usize].to_vertex());
+        if a.v_pos == b.v_pos || b.v_pos == c.v_pos || a.v_pos == c.v_pos {
+            return;
+        }
+        let face_normal = (b.v_pos - a.v_pos).cross(&(c.v_pos - a.v_pos));
+        if face_normal.dot(&(a.v_nor + b.v_nor + c.v_nor)) < 0.0 {
+            self._indices.extend_from_slice(&[triangle[0], triangle[2], triangle[1]]);
+        } else {
+            self._indices.extend_from_slice(&triangle);
+        }
+    }
+
+    fn finish_primitive(&mut self, base_index: usize) -> (u32, u32, BoundingBox) {
+        self.calc_tangent_space_from(base_index);
+        let mut bbox = BoundingBox::initial();
+        for index in &self._indices[base_index..] {
+            bbox.add_point(self._vertices[*index as usize].v_pos.into());
+        }
+        self._bbox.add(&bbox);
+        (base_index as _, (self._indices.len() - base_index) as _, bbox)
+    }
+}
+
+fn primitive_vertex(pos: Vec3, normal: Vec3, uv: Vec2) -> super::VkVertex {
+    Vertex {
+        v_nor: normal,
+        v_tex1: uv,
+        v_pos: pos,
+        ..Vertex::empty()
+    }
+    .to_vk_vertex()
+}
+
+/// Проверка замкнутости: после объединения вершин по позиции каждое ребро
+/// принадлежит ровно двум треугольникам с противоположным обходом
+#[cfg(test)]
+fn boundary_edges(builder: &MeshBuilder, base: u32, count: u32) -> usize {
+    let key = |index: &u32| builder._vertices[*index as usize].v_pos.map(|x| (x * 1e4).round() as i32);
+    let mut edges = HashMap::new();
+    for triangle in builder._indices[base as usize..(base + count) as usize].chunks_exact(3) {
+        for i in 0..3 {
+            *edges.entry((key(&triangle[i]), key(&triangle[(i + 1) % 3]))).or_insert(0) += 1;
+        }
+    }
+    assert!(edges.values().all(|count| *count == 1), "duplicated directed edge {:?}", edges.iter().find(|(_, c)| **c > 1));
+    edges.keys().filter(|(a, b)| !edges.contains_key(&(*b, *a))).count()
+}
+
+#[cfg(test)]
+fn check_primitive(builder: &MeshBuilder, (base, count, bbox): (u32, u32, BoundingBox), center: Vec3) {
+    assert!(count > 0 && count.is_multiple_of(3));
+    let mut expected = BoundingBox::initial();
+    for index in &builder._indices[base as usize..(base + count) as usize] {
+        let v = builder._vertices[*index as usize].to_vertex();
+        expected.add_point(v.v_pos);
+        assert!((v.v_nor.norm() - 1.0).abs() < 1e-3);
+        assert!((v.v_tan.norm() - 1.0).abs() < 1e-3 && v.v_tan.dot(&v.v_nor).abs() < 1e-3);
+        assert!((-1e-4..=1.0001).contains(&v.v_tex1.y), "{:?}", v.v_tex1);
+        if v.v_nor.dot(&(v.v_pos - center)) < -1e-4 {
+            panic!("normal {:?} at {:?} points inwards", v.v_nor, v.v_pos);
+        }
+    }
+    assert_eq!(bbox.begin, expected.begin);
+    assert_eq!(bbox.end, expected.end);
+    for triangle in builder._indices[base as usize..(base + count) as usize].chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| builder._vertices[triangle[i] as usize].to_vertex());
+        let face = (b.v_pos - a.v_pos).cross(&(c.v_pos - a.v_pos));
+        assert!(face.dot(&(a.v_nor + b.v_nor + c.v_nor)) > 0.0);
+    }
+}
+
+#[test]
+fn mesh_primitives_watertight() {
+    let mut builder = super::Mesh::builder("primitives");
+    let zero = Vec3::zeros();
+    let sphere = builder.push_uv_sphere(1.0, 16, 8);
+    check_primitive(&builder, sphere, zero);
+    assert_eq!(boundary_edges(&builder, sphere.0, sphere.1), 0);
+    assert_eq!(sphere.1 / 3, 16 * 8 * 2 - 16 * 2);
+    assert!((sphere.2.end - Vec3::new(1.0, 1.0, 1.0)).norm() < 1e-3);
+
+    let icosphere = builder.push_icosphere(2.0, 2);
+    check_primitive(&builder, icosphere, zero);
+    assert_eq!(boundary_edges(&builder, icosphere.0, icosphere.1), 0);
+    assert_eq!(icosphere.1 / 3, 20 * 16);
+
+    let cylinder = builder.push_cylinder(0.5, 2.0, 12);
+    check_primitive(&builder, cylinder, zero);
+    assert_eq!(boundary_edges(&builder, cylinder.0, cylinder.1), 0);
+    assert!((cylinder.2.begin.z + 1.0).abs() < 1e-6 && (cylinder.2.end.z - 1.0).abs() < 1e-6);
+
+    let cone = builder.push_cone(1.0, 2.0, 12);
+    check_primitive(&builder, cone, Vec3::new(0.0, 0.0, -0.5));
+    assert_eq!(boundary_edges(&builder, cone.0, cone.1), 0);
+
+    let capsule = builder.push_capsule(0.5, 1.0, 12, 4);
+    check_primitive(&builder, capsule, zero);
+    assert_eq!(boundary_edges(&builder, capsule.0, capsule.1), 0);
+    assert!((capsule.2.end.z - 1.0).abs() < 1e-6);
+
+    let torus = builder.push_torus(2.0, 0.5, 24, 12);
+    assert_eq!(boundary_edges(&builder, torus.0, torus.1), 0);
+    assert!((torus.2.end - Vec3::new(2.5, 2.5, 0.5)).norm() < 1e-3);
+
+    let arrow = builder.push_arrow(2.0, 0.05, 0.5, 0.15, 8);
+    assert_eq!(boundary_edges(&builder, arrow.0, arrow.1), 0);
+    assert!(arrow.2.begin.z.abs() < 1e-6 && (arrow.2.end.z - 2.0).abs() < 1e-6);
+
+    // Плоскость открыта: граничные рёбра только по периметру
+    let plane = builder.push_plane(Vec2::new(4.0, 2.0), 4, 3);
+    check_primitive(&builder, plane, Vec3::new(0.0, 0.0, -1.0));
+    assert_eq!(plane.1 / 3, 4 * 3 * 2);
+    assert_eq!(boundary_edges(&builder, plane.0, plane.1), 2 * (4 + 3));
+    assert_eq!(plane.2.end, Vec3::new(2.0, 1.0, 0.0));
+}
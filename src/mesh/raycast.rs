@@ -0,0 +1,239 @@
+//! Пересечение лучей с габаритами и треугольниками мешей на CPU.
+//!
+//! Треугольники берутся из копий вершин и индексов, сохранённых при сборке меша
+//! (см. [`super::MeshView::cpu_geometry`]). Деформация скелетом и морфингом
+//! не учитывается: проверяется исходная поза.
+use super::{BoundingBox, VkVertex};
+use crate::components::ProjectionUniformData;
+use crate::types::{Mat4, Vec2, Vec3, Vec4};
+
+/// Луч `origin + direction * t`, `t >= 0`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    /// Луч с нормированным направлением
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    /// Луч из камеры через точку экрана. `point` — координаты в долях окна,
+    /// (0; 0) — левый верхний угол, (1; 1) — правый нижний.
+    /// Луч начинается на ближней плоскости отсечения.
+    pub fn from_screen_point(camera: &ProjectionUniformData, point: Vec2) -> Self {
+        let inverse = camera.full_matrix_inverted();
+        let ndc = Vec2::new(point.x * 2.0 - 1.0, 1.0 - point.y * 2.0);
+        let unproject = |depth: f32| {
+            let world = inverse * Vec4::new(ndc.x, ndc.y, depth, 1.0);
+            world.xyz() / world.w
+        };
+        let near = unproject(-1.0);
+        Self::new(near, unproject(1.0) - near)
+    }
+
+    #[inline]
+    pub fn at(&self, t: f32) -> Vec3 {
+        self.origin + self.direction * t
+    }
+
+    /// Луч в другой системе координат. Направление не нормируется,
+    /// поэтому параметр `t` точек луча сохраняется.
+    pub fn transformed(&self, matrix: &Mat4) -> Self {
+        Self {
+            origin: (matrix * self.origin.push(1.0)).xyz(),
+            direction: (matrix * self.direction.push(0.0)).xyz(),
+        }
+    }
+
+    /// Параметр входа луча в габариты; 0, если начало луча внутри
+    pub fn intersect_bbox(&self, bbox: &BoundingBox) -> Option<f32> {
+        let mut t_min = 0.0f32;
+        let mut t_max = f32::INFINITY;
+        for axis in 0..3 {
+            let inverse = 1.0 / self.direction[axis];
+            let t0 = (bbox.begin[axis] - self.origin[axis]) * inverse;
+            let t1 = (bbox.end[axis] - self.origin[axis]) * inverse;
+            // NaN при нулевой компоненте направления и начале на грани не сужает отрезок
+            t_min = t_min.max(t0.min(t1));
+            t_max = t_max.min(t0.max(t1));
+            if t_min > t_max {
+                return None;
+            }
+        }
+        Some(t_min)
+    }
+
+    /// Пересечение с треугольником с обеих сторон (Möller–Trumbore).
+    /// Возвращает параметр луча и барицентрические координаты точки.
+    pub fn intersect_triangle(&self, [a, b, c]: [Vec3; 3]) -> Option<(f32, Vec3)> {
+        let ab = b - a;
+        let ac = c - a;
+        let p = self.direction.cross(&ac);
+        let det = ab.dot(&p);
+        if det.abs() <= f32::EPSILON * ab.norm() * ac.norm() * self.direction.norm() {
+            return None;
+        }
+        let inverse = 1.0 / det;
+        let s = self.origin - a;
+        let u = s.dot(&p) * inverse;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(&ab);
+        let v = self.direction.dot(&q) * inverse;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = ac.dot(&q) * inverse;
+        (t >= 0.0).then(|| (t, Vec3::new(1.0 - u - v, u, v)))
+    }
+}
+
+/// Пересечение луча с треугольником меша
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeshHit {
+    /// Номер треугольника меша
+    pub triangle: usize,
+    /// Параметр луча
+    pub distance: f32,
+    pub point: Vec3,
+    /// Нормаль грани по обходу треугольника
+    pub normal: Vec3,
+    /// Веса вершин треугольника
+    pub barycentric: Vec3,
+    /// Первый слой текстурных координат в точке пересечения
+    pub uv: Vec2,
+}
+
+impl MeshHit {
+    /// Пересечение в системе координат родителя меша с матрицей `model`.
+    /// `distance` не меняется, поэтому луч должен быть преобразован [`Ray::transformed`].
+    pub fn transformed(&self, model: &Mat4) -> Self {
+        let normal_matrix = model.try_inverse().unwrap_or_else(Mat4::identity).transpose();
+        let normal = (normal_matrix * self.normal.push(0.0)).xyz();
+        Self {
+            point: (model * self.point.push(1.0)).xyz(),
+            normal: normal.try_normalize(1.0e-12).unwrap_or(normal),
+            ..*self
+        }
+    }
+}
+
+/// Ближайшее пересечение луча с треугольниками не дальше `max_distance`.
+/// Индексы отсчитываются от `vertex_offset`, как в [`super::MeshView::vertex_offset`].
+pub fn raycast_triangles(
+    ray: &Ray,
+    vertices: &[VkVertex],
+    indices: &[u32],
+    vertex_offset: u32,
+    max_distance: f32,
+) -> Option<MeshHit> {
+    let mut nearest: Option<(usize, f32, Vec3)> = None;
+    for (triangle, corners) in indices.chunks_exact(3).enumerate() {
+        let Some(points) = triangle_points(vertices, corners, vertex_offset) else {
+            continue;
+        };
+        let limit = nearest.map_or(max_distance, |(_, t, _)| t);
+        if let Some((t, barycentric)) = ray.intersect_triangle(points) {
+            if t <= limit {
+                nearest = Some((triangle, t, barycentric));
+            }
+        }
+    }
+    let (triangle, distance, barycentric) = nearest?;
+    let corners = triangle_vertices(vertices, &indices[triangle * 3..triangle * 3 + 3], vertex_offset)?;
+    let [a, b, c] = corners.map(|v| Vec3::from(v.v_pos));
+    let uv = corners
+        .iter()
+        .zip(barycentric.iter())
+        .map(|(v, weight)| Vec2::from(v.v_tex1) * *weight)
+        .sum();
+    let normal = (b - a).cross(&(c - a));
+    Some(MeshHit {
+        triangle,
+        distance,
+        point: ray.at(distance),
+        normal: normal.try_normalize(1.0e-12).unwrap_or(normal),
+        barycentric,
+        uv,
+    })
+}
+
+/// Положения вершин треугольника, см. [`triangle_vertices`]
+pub(crate) fn triangle_points(vertices: &[VkVertex], corners: &[u32], vertex_offset: u32) -> Option<[Vec3; 3]> {
+    Some(triangle_vertices(vertices, corners, vertex_offset)?.map(|v| Vec3::from(v.v_pos)))
+}
+
+/// Вершины треугольника, `None` при индексе за пределами вершинного буфера
+fn triangle_vertices<'a>(vertices: &'a [VkVertex], corners: &[u32], vertex_offset: u32) -> Option<[&'a VkVertex; 3]> {
+    let vertex = |i: usize| vertices.get(corners[i] as usize + vertex_offset as usize);
+    Some([vertex(0)?, vertex(1)?, vertex(2)?])
+}
+
+#[test]
+fn mesh_raycast_triangles() {
+    let vertex = |x: f32, y: f32, z: f32| VkVertex {
+        v_pos: [x, y, z],
+        v_tex1: [x, y],
+        ..Default::default()
+    };
+    // Два квадрата 1x1 на высотах 0 и 1, второй ближе к лучу сверху
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for z in [0.0, 1.0] {
+        let base = vertices.len() as u32;
+        vertices.extend([vertex(0.0, 0.0, z), vertex(1.0, 0.0, z), vertex(1.0, 1.0, z), vertex(0.0, 1.0, z)]);
+        indices.extend([0, 1, 2, 0, 2, 3].map(|i| base + i));
+    }
+    let ray = Ray::new(Vec3::new(0.25, 0.5, 5.0), Vec3::new(0.0, 0.0, -2.0));
+    let hit = raycast_triangles(&ray, &vertices, &indices, 0, f32::INFINITY).unwrap();
+    assert_eq!(hit.triangle, 3);
+    assert!((hit.distance - 4.0).abs() < 1.0e-5);
+    assert!((hit.point - Vec3::new(0.25, 0.5, 1.0)).norm() < 1.0e-5);
+    assert!((hit.normal - Vec3::z()).norm() < 1.0e-5);
+    assert!((hit.uv - Vec2::new(0.25, 0.5)).norm() < 1.0e-5);
+    assert!((hit.barycentric.sum() - 1.0).abs() < 1.0e-5);
+
+    // Ограничение расстояния и промах мимо
+    assert!(raycast_triangles(&ray, &vertices, &indices, 0, 3.0).is_none());
+    let miss = Ray::new(Vec3::new(2.0, 0.5, 5.0), -Vec3::z());
+    assert!(raycast_triangles(&miss, &vertices, &indices, 0, f32::INFINITY).is_none());
+
+    // Треугольники с индексами за пределами буфера пропускаются
+    let mut broken = indices.clone();
+    broken.extend([u32::MAX, 0, 1]);
+    let hit = raycast_triangles(&ray, &vertices, &broken, 0, f32::INFINITY).unwrap();
+    assert_eq!(hit.triangle, 3);
+    assert!(raycast_triangles(&ray, &vertices, &broken[6..], 4, f32::INFINITY).is_none());
+
+    let bbox = BoundingBox {
+        begin: Vec3::zeros(),
+        end: Vec3::new(1.0, 1.0, 1.0),
+    };
+    assert_eq!(ray.intersect_bbox(&bbox), Some(4.0));
+    assert_eq!(miss.intersect_bbox(&bbox), None);
+
+    // Луч в системе координат объекта, сдвинутого и растянутого вдвое
+    let model = Mat4::new_translation(&Vec3::new(10.0, 0.0, 0.0)) * Mat4::new_scaling(2.0);
+    let world = Ray::new(Vec3::new(10.5, 1.0, 5.0), -Vec3::z());
+    let local = world.transformed(&model.try_inverse().unwrap());
+    let hit = raycast_triangles(&local, &vertices, &indices, 0, f32::INFINITY)
+        .unwrap()
+        .transformed(&model);
+    assert!((hit.distance - 3.0).abs() < 1.0e-5);
+    assert!((hit.point - world.at(3.0)).norm() < 1.0e-5);
+    assert!((hit.normal - Vec3::z()).norm() < 1.0e-5);
+
+    // Луч через центр экрана смотрит вдоль -Z камеры
+    let camera = ProjectionUniformData::default();
+    let center = Ray::from_screen_point(&camera, Vec2::new(0.5, 0.5));
+    assert!((center.direction + Vec3::z()).norm() < 1.0e-5);
+    let corner = Ray::from_screen_point(&camera, Vec2::new(0.0, 0.0));
+    assert!(corner.direction.x < 0.0 && corner.direction.y > 0.0);
+}
@@ -0,0 +1,297 @@
+//! Генерация нормалей и касательного пространства.
+//!
+//! * [`generate_normals`] — плоские, сглаженные или сглаженные с порогом угла
+//!   (auto smooth) нормали, взвешенные по углам треугольников;
+//! * [`generate_tangents`] — касательные по алгоритму MikkTSpace, как при запекании
+//!   карт нормалей в Blender: одинаковые вершины объединяются, вклад треугольника
+//!   проецируется на плоскость нормали и взвешивается углом при вершине,
+//!   направление бинормали определяется ориентацией развёртки треугольника.
+//!
+//! Обе функции работают с диапазоном индексов и при необходимости дублируют
+//! вершины: если вершине достаются разные нормали или касательные, копия
+//! добавляется в конец `vertices`, а индекс в диапазоне заменяется.
+use std::collections::HashMap;
+
+use super::{Vertex, VkVertex};
+use crate::types::Vec3;
+
+type PositionKey = [u32; 3];
+
+fn position_key(vertex: &VkVertex) -> PositionKey {
+    // -0.0 и 0.0 должны совпадать
+    vertex.v_pos.map(|x| (x + 0.0).to_bits())
+}
+
+/// Углы треугольника при вершинах
+fn corner_angles(points: [Vec3; 3]) -> [f32; 3] {
+    [0, 1, 2].map(|i| {
+        let to_next = points[(i + 1) % 3] - points[i];
+        let to_prev = points[(i + 2) % 3] - points[i];
+        match (to_next.try_normalize(1e-12), to_prev.try_normalize(1e-12)) {
+            (Some(a), Some(b)) => a.dot(&b).clamp(-1.0, 1.0).acos(),
+            _ => 0.0,
+        }
+    })
+}
+
+/// Записывает значения углов треугольников в вершины. Вершина, которой
+/// достаются разные значения, дублируется.
+fn assign_corners<K, F>(vertices: &mut Vec<VkVertex>, indices: &mut [u32], corners: Vec<K>, apply: F)
+where
+    K: Copy + Eq + std::hash::Hash,
+    F: Fn(&mut Vertex, K),
+{
+    let mut assigned = HashMap::<u32, K>::new();
+    let mut copies = HashMap::<(u32, K), u32>::new();
+    for (index, value) in indices.iter_mut().zip(corners) {
+        match assigned.get(index) {
+            None => {
+                assigned.insert(*index, value);
+                let mut vertex = vertices[*index as usize].to_vertex();
+                apply(&mut vertex, value);
+                vertices[*index as usize] = vertex.to_vk_vertex();
+            }
+            Some(existing) if *existing == value => (),
+            Some(_) => {
+                *index = *copies.entry((*index, value)).or_insert_with(|| {
+                    let mut vertex = vertices[*index as usize].to_vertex();
+                    apply(&mut vertex, value);
+                    vertices.push(vertex.to_vk_vertex());
+                    vertices.len() as u32 - 1
+                });
+            }
+        }
+    }
+}
+
+/// Нормали для вершин, у которых `filter` возвращает `true`.
+/// Смежные по позиции треугольники сглаживаются, если угол между их
+/// плоскостями не больше `auto_smooth_angle` (в радианах).
+fn generate_normals_filtered<F>(vertices: &mut Vec<VkVertex>, indices: &mut [u32], auto_smooth_angle: f32, filter: F)
+where
+    F: Fn(&VkVertex) -> bool,
+{
+    let triangle_count = indices.len() / 3;
+    let indices = &mut indices[..triangle_count * 3];
+    let mut face_normals = Vec::with_capacity(triangle_count);
+    let mut angles = Vec::with_capacity(triangle_count);
+    let mut fans = HashMap::<PositionKey, Vec<usize>>::new();
+    for (t, triangle) in indices.chunks_exact(3).enumerate() {
+        let points = [0, 1, 2].map(|i| Vec3::from(vertices[triangle[i] as usize].v_pos));
+        let normal = (points[1] - points[0]).cross(&(points[2] - points[0]));
+        face_normals.push(normal.try_normalize(1e-20));
+        angles.push(corner_angles(points));
+        for index in triangle {
+            fans.entry(position_key(&vertices[*index as usize])).or_default().push(t);
+        }
+    }
+    let min_cos = auto_smooth_angle.cos() - 1e-5;
+    let corners = indices
+        .iter()
+        .enumerate()
+        .map(|(corner, index)| {
+            let vertex = &vertices[*index as usize];
+            if !filter(vertex) {
+                return vertex.v_nor.map(f32::to_bits);
+            }
+            let key = position_key(vertex);
+            let own = face_normals[corner / 3];
+            let mut sum = Vec3::zeros();
+            for t in &fans[&key] {
+                let Some(normal) = face_normals[*t] else { continue };
+                if own.is_some_and(|own| own.dot(&normal) < min_cos) {
+                    continue;
+                }
+                // Угол при той вершине треугольника, что совпадает по позиции
+                let local = indices[t * 3..t * 3 + 3]
+                    .iter()
+                    .position(|i| position_key(&vertices[*i as usize]) == key)
+                    .unwrap();
+                sum += normal * angles[*t][local];
+            }
+            let normal = sum.try_normalize(1e-12).or(own).unwrap_or(Vec3::z());
+            [normal.x, normal.y, normal.z].map(f32::to_bits)
+        })
+        .collect::<Vec<_>>();
+    assign_corners(vertices, indices, corners, |vertex, normal| {
+        vertex.v_nor = Vec3::from(normal.map(f32::from_bits));
+    });
+}
+
+/// Нормали треугольников из диапазона `indices`. `auto_smooth_angle` — наибольший
+/// угол между сглаживаемыми гранями: 0 даёт плоское затенение, `PI` — полностью
+/// сглаженное. Вершины на острых рёбрах дублируются.
+pub fn generate_normals(vertices: &mut Vec<VkVertex>, indices: &mut [u32], auto_smooth_angle: f32) {
+    generate_normals_filtered(vertices, indices, auto_smooth_angle, |_| true);
+}
+
+/// Сглаженные нормали только для вершин с нулевой нормалью
+pub fn fill_missing_normals(vertices: &mut Vec<VkVertex>, indices: &mut [u32]) {
+    let missing = |vertex: &VkVertex| vertex.v_nor.iter().map(|x| x * x).sum::<f32>() < 1e-12;
+    if indices.iter().any(|index| missing(&vertices[*index as usize])) {
+        generate_normals_filtered(vertices, indices, std::f32::consts::PI, missing);
+    }
+}
+
+/// Касательные (`v_tan`) и бинормали (`v_bin`) по алгоритму MikkTSpace.
+/// Бинормаль — `cross(n, t)` со знаком ориентации развёртки, т.е. направлена
+/// по возрастанию координаты `v`. Вершины, общие для треугольников с разной
+/// ориентацией развёртки (зеркальные швы), дублируются.
+pub fn generate_tangents(vertices: &mut Vec<VkVertex>, indices: &mut [u32]) {
+    let triangle_count = indices.len() / 3;
+    let indices = &mut indices[..triangle_count * 3];
+    // Одинаковые по позиции, нормали и развёртке вершины получают одну касательную
+    let mut welded = HashMap::new();
+    let weld = indices
+        .iter()
+        .map(|index| {
+            let v = &vertices[*index as usize];
+            let key = (position_key(v), v.v_nor.map(f32::to_bits), v.v_tex1.map(f32::to_bits));
+            let next = welded.len();
+            *welded.entry(key).or_insert(next)
+        })
+        .collect::<Vec<_>>();
+
+    let mut orientations = Vec::with_capacity(triangle_count);
+    let mut accum = HashMap::<(usize, bool), Vec3>::new();
+    for (t, triangle) in indices.chunks_exact(3).enumerate() {
+        let [a, b, c] = [0, 1, 2].map(|i| vertices[triangle[i] as usize].to_vertex());
+        let (d1, d2) = (b.v_pos - a.v_pos, c.v_pos - a.v_pos);
+        let (t1, t2) = (b.v_tex1 - a.v_tex1, c.v_tex1 - a.v_tex1);
+        let signed_area = t1.x * t2.y - t1.y * t2.x;
+        let orientation = signed_area > 0.0;
+        let degenerate = signed_area.abs() <= f32::EPSILON * 0.5;
+        let sign = if orientation { 1.0 } else { -1.0 };
+        let direction = (d1 * t2.y - d2 * t1.y) * sign;
+        orientations.push((!degenerate).then_some(orientation));
+        if degenerate {
+            continue;
+        }
+        let points = [a.v_pos, b.v_pos, c.v_pos];
+        let normals = [a.v_nor, b.v_nor, c.v_nor];
+        for i in 0..3 {
+            let normal = normals[i].try_normalize(1e-12).unwrap_or(Vec3::z());
+            let project = |v: Vec3| (v - normal * normal.dot(&v)).try_normalize(1e-12);
+            let Some(tangent) = project(direction) else { continue };
+            // Угол при вершине в плоскости нормали
+            let angle = match (project(points[(i + 1) % 3] - points[i]), project(points[(i + 2) % 3] - points[i])) {
+                (Some(next), Some(prev)) => next.dot(&prev).clamp(-1.0, 1.0).acos(),
+                _ => 0.0,
+            };
+            *accum.entry((weld[t * 3 + i], orientation)).or_insert_with(Vec3::zeros) += tangent * angle;
+        }
+    }
+
+    let corners = (0..indices.len())
+        .map(|corner| {
+            let vertex = vertices[indices[corner] as usize].to_vertex();
+            let normal = vertex.v_nor.try_normalize(1e-12).unwrap_or(Vec3::z());
+            // Вырожденный по развёртке треугольник берёт касательную соседей
+            let orientation = orientations[corner / 3].unwrap_or_else(|| !accum.contains_key(&(weld[corner], true)));
+            let tangent = accum
+                .get(&(weld[corner], orientation))
+                .and_then(|sum| (sum - normal * normal.dot(sum)).try_normalize(1e-12))
+                .unwrap_or_else(|| {
+                    let axis = if normal.x.abs() < 0.9 { Vec3::x() } else { Vec3::y() };
+                    (axis - normal * normal.dot(&axis)).normalize()
+                });
+            ([tangent.x, tangent.y, tangent.z].map(f32::to_bits), orientation)
+        })
+        .collect::<Vec<_>>();
+    assign_corners(vertices, indices, corners, |vertex, (tangent, orientation)| {
+        vertex.v_nor = vertex.v_nor.try_normalize(1e-12).unwrap_or(Vec3::z());
+        vertex.v_tan = Vec3::from(tangent.map(f32::from_bits));
+        vertex.v_bin = vertex.v_nor.cross(&vertex.v_tan) * if orientation { 1.0 } else { -1.0 };
+    });
+}
+
+#[cfg(test)]
+fn unit_cube() -> (Vec<VkVertex>, Vec<u32>) {
+    let vertices = (0..8)
+        .map(|i| VkVertex {
+            v_pos: [0, 1, 2].map(|axis| if i & (1 << axis) != 0 { 1.0 } else { -1.0 }),
+            ..Default::default()
+        })
+        .collect();
+    #[rustfmt::skip]
+    let indices = vec![
+        0, 2, 3, 0, 3, 1, 4, 5, 7, 4, 7, 6, // -Z, +Z
+        0, 1, 5, 0, 5, 4, 2, 6, 7, 2, 7, 3, // -Y, +Y
+        0, 4, 6, 0, 6, 2, 1, 3, 7, 1, 7, 5, // -X, +X
+    ];
+    (vertices, indices)
+}
+
+#[test]
+fn mesh_normals_auto_smooth() {
+    let (mut vertices, mut indices) = unit_cube();
+    generate_normals(&mut vertices, &mut indices, std::f32::consts::PI);
+    assert_eq!(vertices.len(), 8);
+    for vertex in &vertices {
+        let pos = Vec3::from(vertex.v_pos);
+        assert!((Vec3::from(vertex.v_nor) - pos.normalize()).norm() < 1e-5);
+    }
+
+    // Рёбра куба острее 30 градусов: у каждого угла три разные нормали
+    let (mut vertices, mut indices) = unit_cube();
+    generate_normals(&mut vertices, &mut indices, 30f32.to_radians());
+    assert_eq!(vertices.len(), 24);
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(vertices[triangle[i] as usize].v_pos));
+        let face = (b - a).cross(&(c - a)).normalize();
+        for index in triangle {
+            assert!((Vec3::from(vertices[*index as usize].v_nor) - face).norm() < 1e-5);
+        }
+    }
+
+    // Заполняются только отсутствующие нормали
+    let (mut vertices, mut indices) = unit_cube();
+    vertices[7].v_nor = [0.0, 0.0, 1.0];
+    fill_missing_normals(&mut vertices, &mut indices);
+    assert_eq!(vertices[7].v_nor, [0.0, 0.0, 1.0]);
+    assert!((Vec3::from(vertices[0].v_nor) + Vec3::new(1.0, 1.0, 1.0).normalize()).norm() < 1e-5);
+}
+
+#[test]
+fn mesh_tangents_mikktspace() {
+    // Две грани с общим ребром x = 1, развёртка правой грани отражена
+    let vertex = |x: f32, y: f32, u: f32| VkVertex {
+        v_pos: [x, y, 0.0],
+        v_nor: [0.0, 0.0, 1.0],
+        v_tex1: [u, 1.0 - y],
+        ..Default::default()
+    };
+    let mut vertices = vec![
+        vertex(0.0, 0.0, 0.0),
+        vertex(1.0, 0.0, 1.0),
+        vertex(1.0, 1.0, 1.0),
+        vertex(0.0, 1.0, 0.0),
+        vertex(2.0, 0.0, 0.0),
+        vertex(2.0, 1.0, 0.0),
+    ];
+    let mut indices = vec![0, 1, 2, 0, 2, 3, 1, 4, 5, 1, 5, 2];
+    generate_tangents(&mut vertices, &mut indices);
+    // Вершины на зеркальном шве дублируются
+    assert_eq!(vertices.len(), 8);
+    for (corner, index) in indices.iter().enumerate() {
+        let v = vertices[*index as usize].to_vertex();
+        let mirrored = corner >= 6;
+        let expected_tangent = if mirrored { -Vec3::x() } else { Vec3::x() };
+        assert!((v.v_tan - expected_tangent).norm() < 1e-5, "{corner}: {:?}", v.v_tan);
+        // Бинормаль направлена по возрастанию v, т.е. против оси Y
+        assert!((v.v_bin + Vec3::y()).norm() < 1e-5, "{corner}: {:?}", v.v_bin);
+    }
+
+    // Касательные сферы совпадают с направлением меридианной развёртки
+    let mut builder = super::Mesh::builder("sphere");
+    let (base, count, _) = builder.push_uv_sphere(1.0, 24, 12);
+    for index in &builder._indices[base as usize..(base + count) as usize] {
+        let v = builder._vertices[*index as usize].to_vertex();
+        if v.v_pos.z.abs() > 0.99 {
+            continue;
+        }
+        let expected = Vec3::new(-v.v_pos.y, v.v_pos.x, 0.0).normalize();
+        assert!(v.v_tan.dot(&expected) > 0.99, "{:?} {:?}", v.v_pos, v.v_tan);
+        assert!(v.v_bin.dot(&-Vec3::z()) > 0.0);
+    }
+}
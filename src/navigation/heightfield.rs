@@ -0,0 +1,290 @@
+//! Вокселизация уровня и открытое пространство над поверхностями.
+//!
+//! Треугольники растеризуются в столбцы отрезков (span) по сетке XY,
+//! затем над проходимыми отрезками строятся ячейки с проходами к соседям.
+use super::NavMeshSettings;
+use crate::mesh::BoundingBox;
+use crate::types::Vec3;
+
+/// Соседние столбцы: -X, +Y, +X, -Y
+pub(super) const DIRECTIONS: [(i32, i32); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
+/// Ячейка без прохода в этом направлении
+pub(super) const NO_LINK: u32 = u32::MAX;
+
+/// Сплошной отрезок столбца в единицах высоты ячейки
+#[derive(Clone, Copy, Debug)]
+struct Span {
+    min: i32,
+    max: i32,
+    walkable: bool,
+}
+
+/// Свободное пространство над проходимой поверхностью
+#[derive(Clone, Copy, Debug)]
+pub(super) struct OpenCell {
+    pub floor: i32,
+    pub ceiling: i32,
+    pub links: [u32; 4],
+    pub region: u32,
+}
+
+/// Ячейки столбцов сетки. Ячейки столбца `x + y * width`
+/// лежат в `cells[columns[i].0..columns[i].1]` снизу вверх.
+pub(super) struct OpenHeightfield {
+    pub origin: Vec3,
+    pub width: usize,
+    pub depth: usize,
+    pub columns: Vec<(u32, u32)>,
+    pub cells: Vec<OpenCell>,
+}
+
+impl OpenHeightfield {
+    pub fn column(&self, x: usize, y: usize) -> std::ops::Range<u32> {
+        let (start, end) = self.columns[x + y * self.width];
+        start..end
+    }
+}
+
+/// Размеры сетки по габаритам геометрии
+pub(super) fn grid_size(bbox: &BoundingBox, settings: &NavMeshSettings) -> (usize, usize) {
+    let size = (bbox.end - bbox.begin) / settings.cell_size;
+    (size.x.ceil().max(1.0) as usize, size.y.ceil().max(1.0) as usize)
+}
+
+/// Строит ячейки над проходимыми поверхностями, связывает соседние,
+/// сужает область на радиус агента и делит её на связные регионы.
+pub(super) fn build(triangles: &[[Vec3; 3]], bbox: &BoundingBox, settings: &NavMeshSettings) -> OpenHeightfield {
+    let (width, depth) = grid_size(bbox, settings);
+    let climb = (settings.max_climb / settings.cell_height).floor() as i32;
+    let height = (settings.agent_height / settings.cell_height).ceil() as i32;
+
+    let mut spans = vec![Vec::<Span>::new(); width * depth];
+    let min_normal_z = settings.max_slope.cos();
+    for triangle in triangles {
+        let normal = (triangle[1] - triangle[0]).cross(&(triangle[2] - triangle[0]));
+        let Some(normal) = normal.try_normalize(f32::EPSILON) else {
+            continue;
+        };
+        let walkable = normal.z.abs() >= min_normal_z;
+        rasterize(triangle, walkable, bbox.begin, width, depth, settings, climb, &mut spans);
+    }
+
+    for column in &mut spans {
+        // Низкие препятствия вроде бордюров и ступеней проходимы
+        let mut previous_walkable = false;
+        for i in 0..column.len() {
+            let walkable = column[i].walkable;
+            if !walkable && previous_walkable && column[i].max - column[i - 1].max <= climb {
+                column[i].walkable = true;
+            }
+            previous_walkable = walkable;
+        }
+        // Над поверхностью должен помещаться агент
+        for i in 0..column.len() {
+            if let Some(next) = column.get(i + 1).copied() {
+                column[i].walkable &= next.min - column[i].max >= height;
+            }
+        }
+    }
+
+    let mut columns = Vec::with_capacity(width * depth);
+    let mut cells = Vec::new();
+    for column in &spans {
+        let start = cells.len() as u32;
+        for (i, span) in column.iter().enumerate().filter(|(_, span)| span.walkable) {
+            cells.push(OpenCell {
+                floor: span.max,
+                ceiling: column.get(i + 1).map_or(i32::MAX, |next| next.min),
+                links: [NO_LINK; 4],
+                region: 0,
+            });
+        }
+        columns.push((start, cells.len() as u32));
+    }
+    let mut field = OpenHeightfield {
+        origin: bbox.begin,
+        width,
+        depth,
+        columns,
+        cells,
+    };
+    link_cells(&mut field, climb, height);
+    erode(&mut field, (settings.agent_radius / settings.cell_size).ceil() as u32);
+    build_regions(&mut field, settings.min_region_cells);
+    field
+}
+
+/// Добавляет отрезки треугольника во все столбцы, которые он задевает
+#[allow(clippy::too_many_arguments)]
+fn rasterize(
+    triangle: &[Vec3; 3],
+    walkable: bool,
+    origin: Vec3,
+    width: usize,
+    depth: usize,
+    settings: &NavMeshSettings,
+    climb: i32,
+    spans: &mut [Vec<Span>],
+) {
+    let cell = settings.cell_size;
+    let local = triangle.map(|point| point - origin);
+    let min = local.iter().fold(Vec3::repeat(f32::MAX), |min, point| min.inf(point));
+    let max = local.iter().fold(Vec3::repeat(f32::MIN), |max, point| max.sup(point));
+    let y0 = ((min.y / cell).floor() as i32).clamp(0, depth as i32 - 1);
+    let y1 = ((max.y / cell).floor() as i32).clamp(0, depth as i32 - 1);
+    let x0 = ((min.x / cell).floor() as i32).clamp(0, width as i32 - 1);
+    let x1 = ((max.x / cell).floor() as i32).clamp(0, width as i32 - 1);
+    let polygon = local.to_vec();
+    for y in y0..=y1 {
+        let row = clip(&clip(&polygon, 1, y as f32 * cell, true), 1, (y + 1) as f32 * cell, false);
+        if row.len() < 3 {
+            continue;
+        }
+        for x in x0..=x1 {
+            let part = clip(&clip(&row, 0, x as f32 * cell, true), 0, (x + 1) as f32 * cell, false);
+            if part.len() < 3 {
+                continue;
+            }
+            let (low, high) = part
+                .iter()
+                .fold((f32::MAX, f32::MIN), |(low, high), point| (low.min(point.z), high.max(point.z)));
+            let min = (low / settings.cell_height).floor() as i32;
+            // Допуск не даёт ошибке округления поднять поверхность на целую ячейку
+            let max = ((high / settings.cell_height - 1.0e-3).ceil() as i32).max(min + 1);
+            add_span(&mut spans[x as usize + y as usize * width], Span { min, max, walkable }, climb);
+        }
+    }
+}
+
+/// Часть многоугольника по одну сторону от плоскости `point[axis] = value`
+fn clip(polygon: &[Vec3], axis: usize, value: f32, keep_above: bool) -> Vec<Vec3> {
+    let side = |point: &Vec3| if keep_above { point[axis] - value } else { value - point[axis] };
+    let mut result = Vec::with_capacity(polygon.len() + 1);
+    for (i, current) in polygon.iter().enumerate() {
+        let previous = &polygon[(i + polygon.len() - 1) % polygon.len()];
+        let (dp, dc) = (side(previous), side(current));
+        if (dp >= 0.0) != (dc >= 0.0) {
+            result.push(previous + (current - previous) * (dp / (dp - dc)));
+        }
+        if dc >= 0.0 {
+            result.push(*current);
+        }
+    }
+    result
+}
+
+/// Вставляет отрезок, объединяя его с пересекающимися. Объединённый отрезок
+/// проходим, если проходима одна из поверхностей не ниже его верха на `climb`.
+fn add_span(column: &mut Vec<Span>, span: Span, climb: i32) {
+    let mut merged = vec![span];
+    column.retain(|other| {
+        let disjoint = other.min > span.max || other.max < span.min;
+        if !disjoint {
+            merged.push(*other);
+        }
+        disjoint
+    });
+    let min = merged.iter().map(|span| span.min).min().unwrap();
+    let max = merged.iter().map(|span| span.max).max().unwrap();
+    let walkable = merged.iter().any(|span| span.walkable && max - span.max <= climb);
+    let index = column.partition_point(|other| other.min < min);
+    column.insert(index, Span { min, max, walkable });
+}
+
+/// Проходы между ячейками соседних столбцов с небольшим перепадом высоты
+fn link_cells(field: &mut OpenHeightfield, climb: i32, height: i32) {
+    for y in 0..field.depth {
+        for x in 0..field.width {
+            for cell in field.column(x, y) {
+                for (direction, (dx, dy)) in DIRECTIONS.iter().enumerate() {
+                    let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                    if nx < 0 || ny < 0 || nx >= field.width as i32 || ny >= field.depth as i32 {
+                        continue;
+                    }
+                    let current = field.cells[cell as usize];
+                    let link = field.column(nx as usize, ny as usize).find(|&other| {
+                        let other = &field.cells[other as usize];
+                        (other.floor - current.floor).abs() <= climb
+                            && other.ceiling.min(current.ceiling) - other.floor.max(current.floor) >= height
+                    });
+                    field.cells[cell as usize].links[direction] = link.unwrap_or(NO_LINK);
+                }
+            }
+        }
+    }
+}
+
+/// Убирает ячейки ближе `radius` ячеек к краю проходимой области
+fn erode(field: &mut OpenHeightfield, radius: u32) {
+    if radius == 0 {
+        return;
+    }
+    let mut distance = vec![u32::MAX; field.cells.len()];
+    let mut queue = std::collections::VecDeque::new();
+    for (index, cell) in field.cells.iter().enumerate() {
+        if cell.links.contains(&NO_LINK) {
+            distance[index] = 0;
+            queue.push_back(index);
+        }
+    }
+    while let Some(index) = queue.pop_front() {
+        for &link in &field.cells[index].links {
+            if link != NO_LINK && distance[link as usize] == u32::MAX {
+                distance[link as usize] = distance[index] + 1;
+                queue.push_back(link as usize);
+            }
+        }
+    }
+    let removed = distance.iter().map(|&distance| distance < radius).collect::<Vec<_>>();
+    for cell in &mut field.cells {
+        for link in &mut cell.links {
+            if *link != NO_LINK && removed[*link as usize] {
+                *link = NO_LINK;
+            }
+        }
+    }
+    // Удалённые ячейки остаются в столбцах, но без региона и проходов
+    for (cell, removed) in field.cells.iter_mut().zip(removed) {
+        if removed {
+            cell.links = [NO_LINK; 4];
+            cell.region = u32::MAX;
+        }
+    }
+}
+
+/// Связные области ячеек. Области меньше `min_cells` ячеек отбрасываются,
+/// у их ячеек, как и у удалённых, регион 0.
+fn build_regions(field: &mut OpenHeightfield, min_cells: usize) {
+    let mut next = 1;
+    let mut stack = Vec::new();
+    for start in 0..field.cells.len() {
+        if field.cells[start].region != 0 {
+            continue;
+        }
+        let mut members = vec![start];
+        field.cells[start].region = next;
+        stack.push(start);
+        while let Some(index) = stack.pop() {
+            for link in field.cells[index].links {
+                if link != NO_LINK && field.cells[link as usize].region == 0 {
+                    field.cells[link as usize].region = next;
+                    members.push(link as usize);
+                    stack.push(link as usize);
+                }
+            }
+        }
+        if members.len() < min_cells {
+            for index in members {
+                field.cells[index].region = u32::MAX;
+                field.cells[index].links = [NO_LINK; 4];
+            }
+        } else {
+            next += 1;
+        }
+    }
+    for cell in &mut field.cells {
+        if cell.region == u32::MAX {
+            cell.region = 0;
+        }
+    }
+}
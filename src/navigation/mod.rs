@@ -0,0 +1,273 @@
+//! Навигационная сетка для поиска пути по статичной геометрии уровня.
+//!
+//! Сетка строится как в Recast: треугольники вокселизируются, проходимые
+//! поверхности с учётом уклона, высоты ступеней и роста агента сужаются на его
+//! радиус и делятся на регионы, а регионы — на выпуклые многоугольники.
+//! Путь ищется A* по многоугольникам и выпрямляется по порталам (string pulling).
+mod heightfield;
+mod path;
+mod polygons;
+
+use crate::error::DsgeError;
+use crate::mesh::BoundingBox;
+use crate::types::Vec3;
+
+use self::heightfield::OpenHeightfield;
+use self::polygons::NO_POLYGON;
+
+/// Наибольшее число ячеек сетки по одной оси
+const MAX_GRID_CELLS: usize = 4096;
+
+/// Параметры агента и вокселизации
+#[derive(Clone, Debug, PartialEq)]
+pub struct NavMeshSettings {
+    pub agent_radius: f32,
+    pub agent_height: f32,
+    /// Наибольший уклон поверхности, радианы
+    pub max_slope: f32,
+    /// Наибольшая высота ступени
+    pub max_climb: f32,
+    /// Размер ячейки по горизонтали
+    pub cell_size: f32,
+    /// Размер ячейки по вертикали
+    pub cell_height: f32,
+    /// Области меньше этого числа ячеек отбрасываются
+    pub min_region_cells: usize,
+}
+
+impl Default for NavMeshSettings {
+    fn default() -> Self {
+        Self {
+            agent_radius: 0.3,
+            agent_height: 1.8,
+            max_slope: 45.0f32.to_radians(),
+            max_climb: 0.3,
+            cell_size: 0.15,
+            cell_height: 0.1,
+            min_region_cells: 8,
+        }
+    }
+}
+
+/// Проход из многоугольника в соседний через отрезок `start`–`end`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Portal {
+    pub polygon: usize,
+    pub start: Vec3,
+    pub end: Vec3,
+    /// Сторона многоугольника: 0 — -X, 1 — +Y, 2 — +X, 3 — -Y
+    direction: usize,
+}
+
+impl Portal {
+    /// Направление из многоугольника через портал
+    fn outward(&self) -> Vec3 {
+        let (dx, dy) = heightfield::DIRECTIONS[self.direction];
+        Vec3::new(dx as f32, dy as f32, 0.0)
+    }
+
+    fn midpoint(&self) -> Vec3 {
+        (self.start + self.end) * 0.5
+    }
+}
+
+/// Выпуклый многоугольник сетки, прямоугольник в плане
+#[derive(Clone, Debug)]
+pub struct NavPolygon {
+    vertices: [Vec3; 4],
+    region: u32,
+    portals: Vec<Portal>,
+}
+
+impl NavPolygon {
+    /// Вершины против часовой стрелки, если смотреть сверху
+    pub fn vertices(&self) -> &[Vec3; 4] {
+        &self.vertices
+    }
+
+    /// Связная область, в которой лежит многоугольник
+    pub fn region(&self) -> u32 {
+        self.region
+    }
+
+    pub fn portals(&self) -> &[Portal] {
+        &self.portals
+    }
+
+    pub fn center(&self) -> Vec3 {
+        self.vertices.iter().sum::<Vec3>() * 0.25
+    }
+}
+
+/// Навигационная сетка. Строится один раз для статичной геометрии.
+pub struct NavMesh {
+    settings: NavMeshSettings,
+    field: OpenHeightfield,
+    /// Многоугольник каждой ячейки
+    cell_polygons: Vec<u32>,
+    polygons: Vec<NavPolygon>,
+}
+
+#[allow(dead_code)]
+impl NavMesh {
+    /// Строит сетку по треугольникам в мировых координатах, ось Z направлена вверх
+    pub fn build(triangles: &[[Vec3; 3]], settings: &NavMeshSettings) -> Result<Self, DsgeError> {
+        let positive = [settings.agent_height, settings.cell_size, settings.cell_height];
+        let non_negative = [settings.agent_radius, settings.max_climb, settings.max_slope];
+        if positive.iter().any(|value| !value.is_finite() || *value <= 0.0)
+            || non_negative.iter().any(|value| !value.is_finite() || *value < 0.0)
+        {
+            return Err(DsgeError::InvalidArgument(format!("Недопустимые параметры навигационной сетки: {settings:?}")));
+        }
+        if triangles.is_empty() {
+            return Err(DsgeError::InvalidArgument("Нет треугольников для навигационной сетки".to_owned()));
+        }
+        let mut bbox = BoundingBox::initial();
+        for triangle in triangles {
+            bbox.add_points(triangle);
+        }
+        let (width, depth) = heightfield::grid_size(&bbox, settings);
+        if width > MAX_GRID_CELLS || depth > MAX_GRID_CELLS {
+            return Err(DsgeError::InvalidArgument(format!(
+                "Навигационная сетка {width}x{depth} слишком велика, увеличьте размер ячейки"
+            )));
+        }
+        let field = heightfield::build(triangles, &bbox, settings);
+        let (polygons, cell_polygons) = polygons::build(&field, settings.cell_size, settings.cell_height);
+        log::debug!(target: "navigation", "NavMesh {width}x{depth}: {} cells, {} polygons", field.cells.len(), polygons.len());
+        Ok(Self {
+            settings: settings.clone(),
+            field,
+            cell_polygons,
+            polygons,
+        })
+    }
+
+    pub fn settings(&self) -> &NavMeshSettings {
+        &self.settings
+    }
+
+    pub fn polygons(&self) -> &[NavPolygon] {
+        &self.polygons
+    }
+
+    /// Ближайшая к `point` ячейка сетки в пределах двух радиусов агента по горизонтали.
+    /// Возвращает многоугольник и точку на поверхности.
+    pub fn nearest_point(&self, point: &Vec3) -> Option<(usize, Vec3)> {
+        let settings = &self.settings;
+        let local = point - self.field.origin;
+        let range = ((settings.agent_radius * 2.0 / settings.cell_size).ceil() as i32).max(1);
+        let (cx, cy) = ((local.x / settings.cell_size).floor() as i32, (local.y / settings.cell_size).floor() as i32);
+        let mut best: Option<(f32, usize, Vec3)> = None;
+        for y in cy - range..=cy + range {
+            for x in cx - range..=cx + range {
+                if x < 0 || y < 0 || x >= self.field.width as i32 || y >= self.field.depth as i32 {
+                    continue;
+                }
+                for cell in self.field.column(x as usize, y as usize) {
+                    let polygon = self.cell_polygons[cell as usize];
+                    if polygon == NO_POLYGON {
+                        continue;
+                    }
+                    let floor = self.field.origin.z + self.field.cells[cell as usize].floor as f32 * settings.cell_height;
+                    let begin = self.field.origin.xy() + nalgebra::Vector2::new(x as f32, y as f32) * settings.cell_size;
+                    let clamped = point.xy().sup(&begin).inf(&(begin.add_scalar(settings.cell_size)));
+                    // Поверхность под точкой на высоте роста агента не штрафуется
+                    let above = point.z - floor;
+                    let vertical = if (-settings.max_climb..=settings.agent_height).contains(&above) { 0.0 } else { above.abs() };
+                    let distance = (clamped - point.xy()).norm_squared() + vertical * vertical;
+                    if best.is_none_or(|(best, _, _)| distance < best) {
+                        best = Some((distance, polygon as usize, Vec3::new(clamped.x, clamped.y, floor)));
+                    }
+                }
+            }
+        }
+        best.map(|(_, polygon, point)| (polygon, point))
+    }
+
+    /// Высота поверхности сетки под точкой или `None` вне сетки
+    pub fn height_at(&self, point: &Vec3) -> Option<f32> {
+        self.nearest_point(point).map(|(_, surface)| surface.z)
+    }
+
+    /// Путь от `start` до `end` по сетке: концы проецируются на сетку,
+    /// промежуточные точки лежат на углах порталов.
+    /// `None`, если точки вне сетки или между ними нет прохода.
+    pub fn find_path(&self, start: &Vec3, end: &Vec3) -> Option<Vec<Vec3>> {
+        let (start_polygon, start) = self.nearest_point(start)?;
+        let (end_polygon, end) = self.nearest_point(end)?;
+        if self.polygons[start_polygon].region != self.polygons[end_polygon].region {
+            return None;
+        }
+        let corridor = path::search(&self.polygons, start_polygon, &start, end_polygon, &end)?;
+        Some(path::string_pull(&start, &end, &corridor))
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn test_box(center: Vec3, half_extents: Vec3) -> Vec<[Vec3; 3]> {
+    let corner = |i: usize| center + Vec3::from_fn(|axis, _| if i >> axis & 1 == 1 { half_extents[axis] } else { -half_extents[axis] });
+    [
+        [0, 2, 1, 1, 2, 3],
+        [4, 5, 6, 5, 7, 6],
+        [0, 1, 4, 1, 5, 4],
+        [2, 6, 3, 3, 6, 7],
+        [0, 4, 2, 2, 4, 6],
+        [1, 3, 5, 3, 7, 5],
+    ]
+    .iter()
+    .flat_map(|face| [[face[0], face[1], face[2]], [face[3], face[4], face[5]]])
+    .map(|triangle| triangle.map(corner))
+    .collect()
+}
+
+#[test]
+fn navmesh_build_and_find_path() {
+    // Пол 10x10, стена поперёк с проходом у края, ступень и отдельная площадка на высоте
+    let mut level = test_box(Vec3::new(0.0, 0.0, -0.5), Vec3::new(5.0, 5.0, 0.5));
+    level.extend(test_box(Vec3::new(-1.0, 0.0, 1.0), Vec3::new(4.0, 0.2, 1.0)));
+    level.extend(test_box(Vec3::new(3.5, -3.0, 0.1), Vec3::new(1.5, 1.0, 0.1)));
+    level.extend(test_box(Vec3::new(0.0, 0.0, 5.0), Vec3::new(1.0, 1.0, 0.1)));
+    let settings = NavMeshSettings::default();
+    let navmesh = NavMesh::build(&level, &settings).unwrap();
+    assert!(!navmesh.polygons().is_empty());
+
+    // Вокруг стены через проход: путь огибает её конец
+    let start = Vec3::new(-3.0, -3.0, 0.0);
+    let end = Vec3::new(-3.0, 3.0, 0.0);
+    let path = navmesh.find_path(&start, &end).unwrap();
+    assert!((path[0] - start).norm() < 0.2 && (path.last().unwrap() - end).norm() < 0.2);
+    let length = path.windows(2).map(|pair| (pair[1] - pair[0]).norm()).sum::<f32>();
+    let around = 2.0 * 6.3f32.hypot(3.0);
+    assert!(path.len() >= 3 && length > around - 0.5 && length < around + 1.0, "{path:?}");
+    for point in &path[1..path.len() - 1] {
+        assert!(point.x > 3.0 + 0.3 - 0.2, "{point}");
+    }
+    for pair in path.windows(2) {
+        // Отрезки пути не пересекают стену
+        if pair[0].y.signum() != pair[1].y.signum() {
+            let t = -pair[0].y / (pair[1].y - pair[0].y);
+            assert!(pair[0].x + (pair[1].x - pair[0].x) * t > 3.0);
+        }
+    }
+
+    // Ступень высотой 0.2 проходима, высота берётся с её верха
+    let on_step = navmesh.height_at(&Vec3::new(3.5, -3.0, 1.0)).unwrap();
+    assert!((on_step - 0.2).abs() < settings.cell_height + 1.0e-3, "{on_step}");
+    assert!(navmesh.find_path(&start, &Vec3::new(3.5, -3.0, 0.2)).is_some());
+
+    // На площадку без подъёма не попасть, у стены сетки нет
+    assert!(navmesh.height_at(&Vec3::new(0.0, 0.0, 5.2)).is_some());
+    assert!(navmesh.find_path(&start, &Vec3::new(0.0, 0.0, 5.2)).is_none());
+    assert!(navmesh.nearest_point(&Vec3::new(-1.0, 0.0, 0.0)).is_none_or(|(_, point)| point.y.abs() > 0.2 + 0.3 - 0.15));
+
+    assert!(NavMesh::build(&[], &settings).is_err());
+    let level = test_box(Vec3::new(0.0, 0.0, -0.5), Vec3::new(5.0, 5.0, 0.5));
+    for broken in [
+        NavMeshSettings { agent_radius: f32::NAN, ..settings.clone() },
+        NavMeshSettings { max_slope: f32::NAN, ..settings.clone() },
+        NavMeshSettings { cell_size: f32::INFINITY, ..settings.clone() },
+    ] {
+        assert!(NavMesh::build(&level, &broken).is_err(), "{broken:?}");
+    }
+}
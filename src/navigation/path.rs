@@ -0,0 +1,185 @@
+//! A* по многоугольникам сетки и выпрямление пути по порталам.
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use super::{NavPolygon, Portal};
+use crate::types::Vec3;
+
+/// Вершина открытого списка A*
+struct Node {
+    cost: f32,
+    polygon: usize,
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl Eq for Node {}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Node {
+    /// Обратный порядок: куча возвращает вершину с наименьшей оценкой
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost).then(other.polygon.cmp(&self.polygon))
+    }
+}
+
+/// Состояние многоугольника при поиске
+#[derive(Clone, Copy)]
+struct Visit {
+    cost: f32,
+    /// Точка входа: середина портала или начало пути
+    position: Vec3,
+    /// Предыдущий многоугольник и его портал
+    parent: Option<(usize, usize)>,
+    closed: bool,
+}
+
+/// Порталы коридора от `start_polygon` до `end_polygon`.
+/// Стоимость перехода — расстояние между серединами порталов.
+pub(super) fn search(
+    polygons: &[NavPolygon],
+    start_polygon: usize,
+    start: &Vec3,
+    end_polygon: usize,
+    end: &Vec3,
+) -> Option<Vec<Portal>> {
+    let mut visits = vec![
+        Visit {
+            cost: f32::INFINITY,
+            position: Vec3::zeros(),
+            parent: None,
+            closed: false,
+        };
+        polygons.len()
+    ];
+    visits[start_polygon] = Visit {
+        cost: 0.0,
+        position: *start,
+        parent: None,
+        closed: false,
+    };
+    let mut open = BinaryHeap::new();
+    open.push(Node {
+        cost: (end - start).norm(),
+        polygon: start_polygon,
+    });
+    while let Some(Node { polygon, .. }) = open.pop() {
+        if polygon == end_polygon {
+            break;
+        }
+        if visits[polygon].closed {
+            continue;
+        }
+        visits[polygon].closed = true;
+        let current = visits[polygon];
+        for (index, portal) in polygons[polygon].portals.iter().enumerate() {
+            let next = portal.polygon;
+            if visits[next].closed {
+                continue;
+            }
+            let position = portal.midpoint();
+            let mut cost = current.cost + (position - current.position).norm();
+            if next == end_polygon {
+                cost += (end - position).norm();
+            }
+            if cost < visits[next].cost {
+                visits[next] = Visit {
+                    cost,
+                    position,
+                    parent: Some((polygon, index)),
+                    closed: false,
+                };
+                let estimate = if next == end_polygon { 0.0 } else { (end - position).norm() };
+                open.push(Node {
+                    cost: cost + estimate,
+                    polygon: next,
+                });
+            }
+        }
+    }
+    if start_polygon != end_polygon && visits[end_polygon].parent.is_none() {
+        return None;
+    }
+    let mut corridor = Vec::new();
+    let mut polygon = end_polygon;
+    while let Some((parent, portal)) = visits[polygon].parent {
+        corridor.push(polygons[parent].portals[portal]);
+        polygon = parent;
+    }
+    corridor.reverse();
+    Some(corridor)
+}
+
+/// Удвоенная площадь треугольника в плане, положительная при обходе против часовой стрелки
+fn area(a: &Vec3, b: &Vec3, c: &Vec3) -> f32 {
+    (b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y)
+}
+
+fn same(a: &Vec3, b: &Vec3) -> bool {
+    (a - b).xy().norm_squared() < 1.0e-8
+}
+
+/// Кратчайший путь через порталы коридора методом воронки
+pub(super) fn string_pull(start: &Vec3, end: &Vec3, corridor: &[Portal]) -> Vec<Vec3> {
+    // Края порталов слева и справа по ходу движения
+    let mut portals = vec![(*start, *start)];
+    for portal in corridor {
+        let outward = portal.outward();
+        let side = outward.x * (portal.start.y - portal.end.y) - outward.y * (portal.start.x - portal.end.x);
+        if side > 0.0 {
+            portals.push((portal.start, portal.end));
+        } else {
+            portals.push((portal.end, portal.start));
+        }
+    }
+    portals.push((*end, *end));
+
+    let mut path = vec![*start];
+    let (mut apex, mut left, mut right) = (*start, *start, *start);
+    let (mut left_index, mut right_index) = (0, 0);
+    let mut i = 1;
+    while i < portals.len() {
+        let (next_left, next_right) = portals[i];
+        // Сужение воронки справа
+        if area(&apex, &right, &next_right) >= 0.0 {
+            if same(&apex, &right) || area(&apex, &left, &next_right) < 0.0 {
+                right = next_right;
+                right_index = i;
+            } else {
+                // Правый край перешёл за левый: левый край становится углом пути
+                path.push(left);
+                apex = left;
+                (right, right_index) = (apex, left_index);
+                i = left_index + 1;
+                continue;
+            }
+        }
+        // Сужение воронки слева
+        if area(&apex, &left, &next_left) <= 0.0 {
+            if same(&apex, &left) || area(&apex, &right, &next_left) > 0.0 {
+                left = next_left;
+                left_index = i;
+            } else {
+                path.push(right);
+                apex = right;
+                (left, left_index) = (apex, right_index);
+                i = right_index + 1;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    if !same(path.last().unwrap(), end) {
+        path.push(*end);
+    }
+    path
+}
@@ -0,0 +1,146 @@
+//! Разбиение регионов на выпуклые многоугольники и проходы между ними.
+//!
+//! Ячейки каждого региона жадно объединяются в прямоугольники сетки,
+//! общие участки сторон соседних прямоугольников становятся порталами.
+use super::heightfield::{OpenHeightfield, DIRECTIONS, NO_LINK};
+use super::{NavPolygon, Portal};
+use crate::types::Vec3;
+
+/// Наибольшая сторона прямоугольника в ячейках
+const MAX_POLYGON_CELLS: usize = 32;
+/// Ячейка вне многоугольников
+pub(super) const NO_POLYGON: u32 = u32::MAX;
+
+/// Прямоугольник сетки: ячейки `cells[row][column]`, строки по +Y, столбцы по +X
+struct Rectangle {
+    x: usize,
+    y: usize,
+    cells: Vec<Vec<u32>>,
+}
+
+/// Многоугольники и номер многоугольника для каждой ячейки
+pub(super) fn build(field: &OpenHeightfield, cell_size: f32, cell_height: f32) -> (Vec<NavPolygon>, Vec<u32>) {
+    let mut owner = vec![NO_POLYGON; field.cells.len()];
+    let mut rectangles = Vec::new();
+    for y in 0..field.depth {
+        for x in 0..field.width {
+            for start in field.column(x, y) {
+                let region = field.cells[start as usize].region;
+                if region == 0 || owner[start as usize] != NO_POLYGON {
+                    continue;
+                }
+                let free = |cell: u32, owner: &[u32]| {
+                    cell != NO_LINK && owner[cell as usize] == NO_POLYGON && field.cells[cell as usize].region == region
+                };
+                // Строка вдоль +X
+                let mut row = vec![start];
+                while row.len() < MAX_POLYGON_CELLS {
+                    let next = field.cells[*row.last().unwrap() as usize].links[2];
+                    if !free(next, &owner) {
+                        break;
+                    }
+                    row.push(next);
+                }
+                // Следующие строки вдоль +Y той же длины
+                let mut cells = vec![row];
+                while cells.len() < MAX_POLYGON_CELLS {
+                    let last = cells.last().unwrap();
+                    let next = last.iter().map(|&cell| field.cells[cell as usize].links[1]).collect::<Vec<_>>();
+                    let connected = next.iter().all(|&cell| free(cell, &owner))
+                        && next.windows(2).all(|pair| field.cells[pair[0] as usize].links[2] == pair[1]);
+                    if !connected {
+                        break;
+                    }
+                    cells.push(next);
+                }
+                let polygon = rectangles.len() as u32;
+                for &cell in cells.iter().flatten() {
+                    owner[cell as usize] = polygon;
+                }
+                rectangles.push(Rectangle { x, y, cells });
+            }
+        }
+    }
+
+    let corner = |x: usize, y: usize, cell: u32| {
+        let floor = field.cells[cell as usize].floor as f32 * cell_height;
+        field.origin + Vec3::new(x as f32 * cell_size, y as f32 * cell_size, floor)
+    };
+    let polygons = rectangles
+        .iter()
+        .map(|rectangle| {
+            let (rows, columns) = (rectangle.cells.len(), rectangle.cells[0].len());
+            let (x0, y0, x1, y1) = (rectangle.x, rectangle.y, rectangle.x + columns, rectangle.y + rows);
+            let cells = &rectangle.cells;
+            let region = field.cells[cells[0][0] as usize].region;
+            NavPolygon {
+                vertices: [
+                    corner(x0, y0, cells[0][0]),
+                    corner(x1, y0, cells[0][columns - 1]),
+                    corner(x1, y1, cells[rows - 1][columns - 1]),
+                    corner(x0, y1, cells[rows - 1][0]),
+                ],
+                region,
+                portals: portals(field, rectangle, &owner, cell_size, cell_height),
+            }
+        })
+        .collect();
+    (polygons, owner)
+}
+
+/// Порталы прямоугольника: непрерывные участки сторон, за которыми
+/// лежит один и тот же соседний многоугольник
+fn portals(field: &OpenHeightfield, rectangle: &Rectangle, owner: &[u32], cell_size: f32, cell_height: f32) -> Vec<Portal> {
+    let cells = &rectangle.cells;
+    let (rows, columns) = (cells.len(), cells[0].len());
+    let own = owner[cells[0][0] as usize];
+    let mut result = Vec::new();
+    for (direction, &(dx, dy)) in DIRECTIONS.iter().enumerate() {
+        // Ячейки стороны в порядке обхода и их координаты на сетке
+        let side = match direction {
+            0 => (0..rows).map(|row| (cells[row][0], 0, row)).collect::<Vec<_>>(),
+            1 => (0..columns).map(|column| (cells[rows - 1][column], column, rows - 1)).collect(),
+            2 => (0..rows).map(|row| (cells[row][columns - 1], columns - 1, row)).collect(),
+            _ => (0..columns).map(|column| (cells[0][column], column, 0)).collect(),
+        };
+        let neighbour = |cell: u32| {
+            let link = field.cells[cell as usize].links[direction];
+            (link != NO_LINK && owner[link as usize] != own && owner[link as usize] != NO_POLYGON)
+                .then(|| (owner[link as usize], link))
+        };
+        let mut run: Option<(u32, usize)> = None;
+        for i in 0..=side.len() {
+            let current = side.get(i).and_then(|&(cell, _, _)| neighbour(cell));
+            if let Some((polygon, start)) = run {
+                if current.map(|(other, _)| other) == Some(polygon) {
+                    continue;
+                }
+                // Участок от ячейки `start` до ячейки `i - 1` включительно
+                let point = |index: usize, at_end: bool| {
+                    let (cell, column, row) = side[index];
+                    let link = neighbour(cell).unwrap().1;
+                    let floor = (field.cells[cell as usize].floor + field.cells[link as usize].floor) as f32 * 0.5;
+                    let mut grid = Vec3::new((rectangle.x + column) as f32, (rectangle.y + row) as f32, 0.0);
+                    // Сторона проходит по границе ячейки в направлении `direction`
+                    grid.x += if dx > 0 { 1.0 } else { 0.0 };
+                    grid.y += if dy > 0 { 1.0 } else { 0.0 };
+                    if at_end {
+                        grid[if dx != 0 { 1 } else { 0 }] += 1.0;
+                    }
+                    field.origin + Vec3::new(grid.x * cell_size, grid.y * cell_size, floor * cell_height)
+                };
+                result.push(Portal {
+                    polygon: polygon as usize,
+                    start: point(start, false),
+                    end: point(i - 1, true),
+                    direction,
+                });
+                run = None;
+            }
+            if let Some((polygon, _)) = current {
+                run = Some((polygon, i));
+            }
+        }
+    }
+    result
+}
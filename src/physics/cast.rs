@@ -0,0 +1,131 @@
+//! Сдвиг выпуклого коллайдера до первого касания (shape cast).
+//!
+//! Время касания ищется консервативным продвижением: форма сдвигается
+//! на расстояние до препятствия, делённое на скорость сближения,
+//! пока расстояние не станет меньше допуска.
+use super::gjk::{self, Proximity, SupportMap};
+use super::WorldShape;
+use crate::components::collider::{Collider, ColliderShape};
+use crate::types::{Mat4, Vec3};
+
+/// Расстояние, при котором формы считаются коснувшимися
+const CAST_TOLERANCE: f32 = 1.0e-3;
+const MAX_ITERATIONS: usize = 32;
+
+/// Первое касание при сдвиге
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShapeHit {
+    /// Доля сдвига до касания, от 0 до 1
+    pub fraction: f32,
+    /// Нормаль поверхности препятствия, направленная к движущейся форме
+    pub normal: Vec3,
+    /// Точка касания на препятствии
+    pub point: Vec3,
+}
+
+/// Касание выпуклого коллайдера `collider`, сдвигаемого на `motion`, с коллайдером `other`.
+/// Если формы пересекаются в начале, `fraction` равна нулю, а нормаль указывает выход.
+pub fn cast_collider(
+    collider: &Collider,
+    global: &Mat4,
+    motion: &Vec3,
+    other: &Collider,
+    other_global: &Mat4,
+) -> Option<ShapeHit> {
+    if !collider.shape().is_convex() || motion.norm_squared() <= f32::EPSILON * f32::EPSILON {
+        return None;
+    }
+    let ColliderShape::TriangleMesh(bvh) = other.shape() else {
+        return cast_convex(collider, global, motion, &WorldShape::new(other, other_global));
+    };
+    // Треугольники меша вдоль всего пути
+    let matrix = other.world_matrix(other_global);
+    let inverse = matrix.try_inverse()?;
+    let start = collider.world_bbox(global);
+    let end = start.transformed(&Mat4::new_translation(motion));
+    let local_bbox = start.union(&end).transformed(&inverse);
+    bvh.overlap_bbox(&local_bbox)
+        .into_iter()
+        .filter_map(|triangle| {
+            let triangle = bvh.triangle(triangle).map(|point| (matrix * point.push(1.0)).xyz());
+            cast_convex(collider, global, motion, &triangle)
+        })
+        .min_by(|a, b| a.fraction.total_cmp(&b.fraction))
+}
+
+fn cast_convex(collider: &Collider, global: &Mat4, motion: &Vec3, other: &dyn SupportMap) -> Option<ShapeHit> {
+    let mut fraction = 0.0;
+    for _ in 0..MAX_ITERATIONS {
+        let moved = Mat4::new_translation(&(motion * fraction)) * global;
+        let shape = WorldShape::new(collider, &moved);
+        let closest = match gjk::proximity(&shape, other) {
+            Proximity::Penetrating(penetration) => {
+                return Some(ShapeHit {
+                    fraction,
+                    normal: -penetration.normal,
+                    point: penetration.point_b,
+                })
+            }
+            Proximity::Separated(closest) => closest,
+        };
+        let normal = (closest.point_b - closest.point_a) / closest.distance.max(f32::EPSILON);
+        let approach = motion.dot(&normal);
+        if approach <= 0.0 {
+            return None;
+        }
+        if closest.distance <= CAST_TOLERANCE {
+            return Some(ShapeHit {
+                fraction,
+                normal: -normal,
+                point: closest.point_b,
+            });
+        }
+        fraction += (closest.distance - CAST_TOLERANCE * 0.5) / approach;
+        if fraction > 1.0 {
+            return None;
+        }
+    }
+    None
+}
+
+#[test]
+fn physics_shape_cast() {
+    use crate::mesh::bvh::MeshBvh;
+    use crate::mesh::VkVertex;
+    use std::sync::Arc;
+    let sphere = Collider::new(ColliderShape::Sphere { radius: 0.5 });
+    let cube = Collider::new(ColliderShape::Box {
+        half_extents: Vec3::repeat(1.0),
+    });
+    let wall = Mat4::new_translation(&Vec3::new(5.0, 0.0, 0.0));
+
+    // Шар летит в куб и касается его грани
+    let hit = cast_collider(&sphere, &Mat4::identity(), &Vec3::new(10.0, 0.0, 0.0), &cube, &wall).unwrap();
+    assert!((hit.fraction - 0.35).abs() < 1.0e-3);
+    assert!((hit.normal + Vec3::x()).norm() < 1.0e-3);
+    assert!((hit.point.x - 4.0).abs() < 1.0e-3);
+    // Мимо и от препятствия
+    assert!(cast_collider(&sphere, &Mat4::identity(), &Vec3::new(10.0, 5.0, 0.0), &cube, &wall).is_none());
+    assert!(cast_collider(&sphere, &Mat4::identity(), &Vec3::new(-10.0, 0.0, 0.0), &cube, &wall).is_none());
+    assert!(cast_collider(&sphere, &Mat4::identity(), &Vec3::new(2.0, 0.0, 0.0), &cube, &wall).is_none());
+
+    // Капсула падает на пол из треугольников
+    let vertex = |x: f32, y: f32| VkVertex {
+        v_pos: [x, y, 0.0],
+        ..Default::default()
+    };
+    let vertices = [vertex(-5.0, -5.0), vertex(5.0, -5.0), vertex(5.0, 5.0), vertex(-5.0, 5.0)];
+    let floor = Collider::new(ColliderShape::TriangleMesh(Arc::new(MeshBvh::new(
+        &vertices,
+        &[0, 1, 2, 0, 2, 3],
+        0,
+    ))));
+    let capsule = Collider::new(ColliderShape::Capsule {
+        radius: 0.5,
+        half_height: 0.5,
+    });
+    let above = Mat4::new_translation(&Vec3::new(1.0, 1.0, 3.0));
+    let hit = cast_collider(&capsule, &above, &Vec3::new(0.0, 0.0, -4.0), &floor, &Mat4::identity()).unwrap();
+    assert!((hit.fraction - 0.5).abs() < 1.0e-3);
+    assert!((hit.normal - Vec3::z()).norm() < 1.0e-3);
+}
@@ -0,0 +1,407 @@
+//! Расстояние между выпуклыми формами (GJK) и глубина их проникновения (EPA).
+//!
+//! Форма задаётся опорной функцией ядра и скруглением. GJK ищет ближайшие
+//! точки ядер; если ядра пересекаются, EPA достраивает многогранник их разности
+//! Минковского до грани, ближайшей к началу координат, а к глубине
+//! прибавляются скругления.
+use crate::types::Vec3;
+
+const MAX_ITERATIONS: usize = 64;
+/// Квадрат расстояния, при котором ядра считаются пересекающимися
+const OVERLAP_EPSILON: f32 = 1.0e-12;
+/// Относительная точность сходимости GJK
+const GJK_TOLERANCE: f32 = 1.0e-6;
+/// Точность сходимости EPA
+const EPA_TOLERANCE: f32 = 1.0e-4;
+
+/// Выпуклая форма в мировых координатах
+pub trait SupportMap {
+    /// Самая дальняя точка ядра в направлении `direction`
+    fn support(&self, direction: &Vec3) -> Vec3;
+
+    /// Скругление ядра
+    fn margin(&self) -> f32 {
+        0.0
+    }
+}
+
+/// Треугольник без скругления
+impl SupportMap for [Vec3; 3] {
+    fn support(&self, direction: &Vec3) -> Vec3 {
+        self.iter()
+            .copied()
+            .max_by(|a, b| a.dot(direction).total_cmp(&b.dot(direction)))
+            .unwrap()
+    }
+}
+
+/// Вершина разности Минковского `a - b` вместе с точками форм
+#[derive(Clone, Copy, Debug)]
+struct SupportPoint {
+    w: Vec3,
+    a: Vec3,
+    b: Vec3,
+}
+
+fn support(a: &dyn SupportMap, b: &dyn SupportMap, direction: &Vec3) -> SupportPoint {
+    let pa = a.support(direction);
+    let pb = b.support(&-direction);
+    SupportPoint { w: pa - pb, a: pa, b: pb }
+}
+
+/// Ближайшие точки форм
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClosestPoints {
+    pub distance: f32,
+    pub point_a: Vec3,
+    pub point_b: Vec3,
+}
+
+/// Проникновение форм. `normal` направлена от `a` к `b`:
+/// сдвиг `b` на `normal * depth` разделяет формы.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Penetration {
+    pub normal: Vec3,
+    pub depth: f32,
+    pub point_a: Vec3,
+    pub point_b: Vec3,
+}
+
+/// Результат сравнения форм с учётом скругления
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Proximity {
+    Separated(ClosestPoints),
+    Penetrating(Penetration),
+}
+
+enum Gjk {
+    Separated(ClosestPoints),
+    Overlapping(Vec<SupportPoint>),
+}
+
+/// Ближайшие точки форм или их проникновение с учётом скругления
+pub fn proximity(a: &dyn SupportMap, b: &dyn SupportMap) -> Proximity {
+    let margin = a.margin() + b.margin();
+    match gjk(a, b) {
+        Gjk::Separated(closest) if closest.distance > margin => {
+            let normal = (closest.point_b - closest.point_a) / closest.distance;
+            Proximity::Separated(ClosestPoints {
+                distance: closest.distance - margin,
+                point_a: closest.point_a + normal * a.margin(),
+                point_b: closest.point_b - normal * b.margin(),
+            })
+        }
+        Gjk::Separated(closest) if closest.distance > 0.0 => {
+            // Ядра разделены, пересекаются только скругления
+            let normal = (closest.point_b - closest.point_a) / closest.distance;
+            Proximity::Penetrating(Penetration {
+                normal,
+                depth: margin - closest.distance,
+                point_a: closest.point_a + normal * a.margin(),
+                point_b: closest.point_b - normal * b.margin(),
+            })
+        }
+        Gjk::Separated(closest) => {
+            let simplex = vec![SupportPoint {
+                w: closest.point_a - closest.point_b,
+                a: closest.point_a,
+                b: closest.point_b,
+            }];
+            Proximity::Penetrating(core_penetration(a, b, simplex))
+        }
+        Gjk::Overlapping(simplex) => Proximity::Penetrating(core_penetration(a, b, simplex)),
+    }
+}
+
+/// Проникновение пересекающихся ядер, дополненное скруглениями
+fn core_penetration(a: &dyn SupportMap, b: &dyn SupportMap, simplex: Vec<SupportPoint>) -> Penetration {
+    let fallback = simplex[0];
+    let core = epa(a, b, simplex).unwrap_or(Penetration {
+        normal: Vec3::x(),
+        depth: 0.0,
+        point_a: fallback.a,
+        point_b: fallback.b,
+    });
+    Penetration {
+        normal: core.normal,
+        depth: core.depth + a.margin() + b.margin(),
+        point_a: core.point_a + core.normal * a.margin(),
+        point_b: core.point_b - core.normal * b.margin(),
+    }
+}
+
+/// Ближайшие точки ядер
+fn gjk(a: &dyn SupportMap, b: &dyn SupportMap) -> Gjk {
+    let mut simplex = vec![support(a, b, &Vec3::x())];
+    let mut weights = vec![1.0];
+    for _ in 0..MAX_ITERATIONS {
+        weights = closest_on_simplex(&simplex);
+        let mut index = 0;
+        simplex.retain(|_| {
+            index += 1;
+            weights[index - 1] > 0.0
+        });
+        weights.retain(|weight| *weight > 0.0);
+        let closest = weighted(&simplex, &weights, |point| point.w);
+        let distance_squared = closest.norm_squared();
+        if simplex.len() == 4 || distance_squared <= OVERLAP_EPSILON {
+            return Gjk::Overlapping(simplex);
+        }
+        let next = support(a, b, &-closest);
+        let converged = distance_squared - closest.dot(&next.w) <= GJK_TOLERANCE * distance_squared;
+        let repeated = simplex.iter().any(|point| (point.w - next.w).norm_squared() <= OVERLAP_EPSILON);
+        if converged || repeated {
+            break;
+        }
+        simplex.push(next);
+    }
+    if weights.len() != simplex.len() {
+        // Итерации исчерпаны после добавления вершины
+        weights = closest_on_simplex(&simplex);
+    }
+    let point_a = weighted(&simplex, &weights, |point| point.a);
+    let point_b = weighted(&simplex, &weights, |point| point.b);
+    Gjk::Separated(ClosestPoints {
+        distance: (point_a - point_b).norm(),
+        point_a,
+        point_b,
+    })
+}
+
+fn weighted(simplex: &[SupportPoint], weights: &[f32], field: impl Fn(&SupportPoint) -> Vec3) -> Vec3 {
+    simplex.iter().zip(weights).map(|(point, weight)| field(point) * *weight).sum()
+}
+
+/// Веса вершин симплекса в его ближайшей к началу координат точке
+fn closest_on_simplex(simplex: &[SupportPoint]) -> Vec<f32> {
+    match *simplex {
+        [_] => vec![1.0],
+        [a, b] => {
+            let [u, v] = closest_on_segment(a.w, b.w);
+            vec![u, v]
+        }
+        [a, b, c] => closest_on_triangle(a.w, b.w, c.w).to_vec(),
+        [a, b, c, d] => closest_on_tetrahedron([a.w, b.w, c.w, d.w]).to_vec(),
+        _ => unreachable!(),
+    }
+}
+
+fn closest_on_segment(a: Vec3, b: Vec3) -> [f32; 2] {
+    let ab = b - a;
+    let length_squared = ab.norm_squared();
+    if length_squared <= f32::EPSILON * f32::EPSILON {
+        return [1.0, 0.0];
+    }
+    let t = (-a.dot(&ab) / length_squared).clamp(0.0, 1.0);
+    [1.0 - t, t]
+}
+
+/// Барицентрические координаты ближайшей к началу координат точки треугольника
+fn closest_on_triangle(a: Vec3, b: Vec3, c: Vec3) -> [f32; 3] {
+    let ab = b - a;
+    let ac = c - a;
+    let d1 = ab.dot(&-a);
+    let d2 = ac.dot(&-a);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return [1.0, 0.0, 0.0];
+    }
+    let d3 = ab.dot(&-b);
+    let d4 = ac.dot(&-b);
+    if d3 >= 0.0 && d4 <= d3 {
+        return [0.0, 1.0, 0.0];
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        let v = d1 / (d1 - d3);
+        return [1.0 - v, v, 0.0];
+    }
+    let d5 = ab.dot(&-c);
+    let d6 = ac.dot(&-c);
+    if d6 >= 0.0 && d5 <= d6 {
+        return [0.0, 0.0, 1.0];
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        let w = d2 / (d2 - d6);
+        return [1.0 - w, 0.0, w];
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return [0.0, 1.0 - w, w];
+    }
+    let sum = va + vb + vc;
+    if sum.abs() <= f32::MIN_POSITIVE {
+        // Вырожденный треугольник
+        let [u, v] = closest_on_segment(a, b);
+        return [u, v, 0.0];
+    }
+    let v = vb / sum;
+    let w = vc / sum;
+    [1.0 - v - w, v, w]
+}
+
+/// Веса вершин; все положительны, если начало координат внутри тетраэдра
+fn closest_on_tetrahedron(points: [Vec3; 4]) -> [f32; 4] {
+    const FACES: [[usize; 4]; 4] = [[0, 1, 2, 3], [0, 3, 1, 2], [0, 2, 3, 1], [1, 3, 2, 0]];
+    let volume = (points[1] - points[0]).cross(&(points[2] - points[0])).dot(&(points[3] - points[0]));
+    let flat = volume.abs() <= f32::EPSILON * (points[1] - points[0]).norm().powi(3).max(f32::MIN_POSITIVE);
+    let mut best: Option<(f32, [f32; 4])> = None;
+    for [i, j, k, opposite] in FACES {
+        let (a, b, c) = (points[i], points[j], points[k]);
+        let normal = (b - a).cross(&(c - a));
+        // Начало координат и противолежащая вершина по разные стороны грани
+        let outside = normal.dot(&-a) * normal.dot(&(points[opposite] - a)) < 0.0;
+        if !outside && !flat {
+            continue;
+        }
+        let face = closest_on_triangle(a, b, c);
+        let distance = (a * face[0] + b * face[1] + c * face[2]).norm_squared();
+        if best.is_none_or(|(best_distance, _)| distance < best_distance) {
+            let mut weights = [0.0; 4];
+            weights[i] = face[0];
+            weights[j] = face[1];
+            weights[k] = face[2];
+            best = Some((distance, weights));
+        }
+    }
+    best.map_or([0.25; 4], |(_, weights)| weights)
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Face {
+    vertices: [usize; 3],
+    normal: Vec3,
+    distance: f32,
+}
+
+fn make_face(points: &[SupportPoint], vertices: [usize; 3]) -> Option<Face> {
+    let [a, b, c] = vertices.map(|index| points[index].w);
+    let normal = (b - a).cross(&(c - a)).try_normalize(f32::EPSILON * f32::EPSILON)?;
+    Some(Face {
+        vertices,
+        normal,
+        distance: normal.dot(&a),
+    })
+}
+
+/// Достраивает симплекс до тетраэдра опорными точками ядер.
+/// Если разность ядер плоская, возвращает перпендикулярное ей направление.
+fn expand_simplex(a: &dyn SupportMap, b: &dyn SupportMap, simplex: &mut Vec<SupportPoint>) -> Result<(), Vec3> {
+    const EPSILON: f32 = 1.0e-6;
+    let axes = [Vec3::x(), Vec3::y(), Vec3::z()];
+    if simplex.len() == 1 {
+        let origin = simplex[0].w;
+        let found = axes
+            .iter()
+            .flat_map(|axis| [*axis, -axis])
+            .map(|direction| support(a, b, &direction))
+            .find(|point| (point.w - origin).norm() > EPSILON);
+        simplex.push(found.ok_or_else(Vec3::x)?);
+    }
+    if simplex.len() == 2 {
+        let line = simplex[1].w - simplex[0].w;
+        let axis = axes.iter().min_by(|x, y| x.dot(&line).abs().total_cmp(&y.dot(&line).abs())).unwrap();
+        let first = line.cross(axis);
+        let second = line.cross(&first);
+        let origin = simplex[0].w;
+        let found = [first, -first, second, -second]
+            .iter()
+            .map(|direction| support(a, b, direction))
+            .find(|point| line.cross(&(point.w - origin)).norm() > EPSILON * line.norm());
+        simplex.push(found.ok_or_else(|| first.normalize())?);
+    }
+    if simplex.len() == 3 {
+        let origin = simplex[0].w;
+        let normal = (simplex[1].w - origin).cross(&(simplex[2].w - origin));
+        let normal = normal.try_normalize(f32::EPSILON * f32::EPSILON).ok_or_else(Vec3::x)?;
+        let found = [normal, -normal]
+            .iter()
+            .map(|direction| support(a, b, direction))
+            .find(|point| normal.dot(&(point.w - origin)).abs() > EPSILON);
+        simplex.push(found.ok_or(normal)?);
+    }
+    Ok(())
+}
+
+/// Глубина проникновения ядер, начиная с симплекса внутри их разности
+fn epa(a: &dyn SupportMap, b: &dyn SupportMap, mut points: Vec<SupportPoint>) -> Option<Penetration> {
+    if let Err(normal) = expand_simplex(a, b, &mut points) {
+        // Плоская разность: ядра касаются, выход — по перпендикуляру к ней
+        let weights = closest_on_simplex(&points);
+        return Some(Penetration {
+            normal,
+            depth: 0.0,
+            point_a: weighted(&points, &weights, |point| point.a),
+            point_b: weighted(&points, &weights, |point| point.b),
+        });
+    }
+    let center = points.iter().map(|point| point.w).sum::<Vec3>() / 4.0;
+    let mut faces = Vec::new();
+    for [i, j, k] in [[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]] {
+        let face = make_face(&points, [i, j, k])?;
+        // Нормали граней наружу
+        if face.normal.dot(&(points[i].w - center)) < 0.0 {
+            faces.push(make_face(&points, [i, k, j])?);
+        } else {
+            faces.push(face);
+        }
+    }
+
+    let mut best = faces[0];
+    for _ in 0..MAX_ITERATIONS {
+        best = *faces.iter().min_by(|x, y| x.distance.total_cmp(&y.distance))?;
+        let next = support(a, b, &best.normal);
+        if next.w.dot(&best.normal) - best.distance <= EPA_TOLERANCE * best.distance.abs().max(1.0) {
+            break;
+        }
+        // Грани, видимые из новой точки, заменяются веером от горизонта
+        let mut horizon: Vec<(usize, usize)> = Vec::new();
+        faces.retain(|face| {
+            if face.normal.dot(&(next.w - points[face.vertices[0]].w)) <= 0.0 {
+                return true;
+            }
+            for edge in [0, 1, 2].map(|i| (face.vertices[i], face.vertices[(i + 1) % 3])) {
+                match horizon.iter().position(|&(from, to)| (to, from) == edge) {
+                    Some(shared) => {
+                        horizon.swap_remove(shared);
+                    }
+                    None => horizon.push(edge),
+                }
+            }
+            false
+        });
+        let index = points.len();
+        points.push(next);
+        faces.extend(horizon.iter().filter_map(|&(from, to)| make_face(&points, [from, to, index])));
+        if faces.is_empty() {
+            break;
+        }
+    }
+
+    // Проекция начала координат на ближайшую грань
+    let [i, j, k] = best.vertices;
+    let projected = best.normal * best.distance;
+    let weights = barycentric(projected, points[i].w, points[j].w, points[k].w);
+    let corners = [points[i], points[j], points[k]];
+    let weights = weights.to_vec();
+    Some(Penetration {
+        normal: best.normal,
+        depth: best.distance.max(0.0),
+        point_a: weighted(&corners, &weights, |point| point.a),
+        point_b: weighted(&corners, &weights, |point| point.b),
+    })
+}
+
+fn barycentric(point: Vec3, a: Vec3, b: Vec3, c: Vec3) -> [f32; 3] {
+    let (ab, ac, ap) = (b - a, c - a, point - a);
+    let (d00, d01, d11) = (ab.dot(&ab), ab.dot(&ac), ac.dot(&ac));
+    let (d20, d21) = (ap.dot(&ab), ap.dot(&ac));
+    let denominator = d00 * d11 - d01 * d01;
+    if denominator.abs() <= f32::MIN_POSITIVE {
+        return [1.0, 0.0, 0.0];
+    }
+    let v = (d11 * d20 - d01 * d21) / denominator;
+    let w = (d00 * d21 - d01 * d20) / denominator;
+    [1.0 - v - w, v, w]
+}
@@ -0,0 +1,199 @@
+//! Соединения тел: жёсткое, шарнирное и шаровое.
+//!
+//! Крепление задаётся точкой (и осью для шарнира) в мировых координатах
+//! и фиксируется в системах тел на первом шаге симуляции.
+use nalgebra::UnitQuaternion;
+
+use super::world::{tangents, Velocities};
+use crate::types::{Mat3, Vec3};
+
+/// Идентификатор соединения в [`super::PhysicsWorld`]
+pub type JointId = usize;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JointKind {
+    /// Тела не двигаются друг относительно друга
+    Fixed,
+    /// Вращение только вокруг оси `axis`
+    Hinge { axis: Vec3 },
+    /// Вращение вокруг точки крепления
+    Ball,
+}
+
+/// Крепление в системах координат тел
+#[derive(Clone, Copy, Debug)]
+struct JointFrame {
+    anchor_a: Vec3,
+    anchor_b: Vec3,
+    axis_a: Vec3,
+    axis_b: Vec3,
+    /// Поворот `b` относительно `a` при креплении
+    rotation: UnitQuaternion<f32>,
+}
+
+/// Соединение тела `body_a` с телом `body_b` или, если его нет, с миром.
+/// Тела задаются ключами объектов, см. [`crate::references::MutexLockBox::box_id`].
+#[derive(Clone, Debug)]
+pub struct Joint {
+    kind: JointKind,
+    body_a: i32,
+    body_b: Option<i32>,
+    anchor: Vec3,
+    frame: Option<JointFrame>,
+    linear_impulse: Vec3,
+    angular_impulse: Vec3,
+}
+
+/// Положение тела на шаге симуляции
+pub(super) type Pose = (Vec3, UnitQuaternion<f32>);
+
+#[allow(dead_code)]
+impl Joint {
+    pub fn new(kind: JointKind, body_a: i32, body_b: Option<i32>, anchor: Vec3) -> Self {
+        Self {
+            kind,
+            body_a,
+            body_b,
+            anchor,
+            frame: None,
+            linear_impulse: Vec3::zeros(),
+            angular_impulse: Vec3::zeros(),
+        }
+    }
+
+    #[inline]
+    pub fn kind(&self) -> JointKind {
+        self.kind
+    }
+
+    #[inline]
+    pub fn bodies(&self) -> (i32, Option<i32>) {
+        (self.body_a, self.body_b)
+    }
+
+    /// Точка крепления в мировых координатах при создании
+    #[inline]
+    pub fn anchor(&self) -> Vec3 {
+        self.anchor
+    }
+
+    /// Подготавливает ограничение и применяет накопленные импульсы прошлого шага
+    pub(super) fn prepare(
+        &mut self,
+        (position_a, rotation_a): Pose,
+        (position_b, rotation_b): Pose,
+        [a, b]: [usize; 2],
+        velocities: &mut Velocities,
+        bias_factor: f32,
+    ) -> JointConstraint {
+        let axis = match self.kind {
+            JointKind::Hinge { axis } => axis.try_normalize(f32::EPSILON).unwrap_or_else(Vec3::z),
+            _ => Vec3::z(),
+        };
+        let anchor = self.anchor;
+        let frame = *self.frame.get_or_insert_with(|| JointFrame {
+            anchor_a: rotation_a.inverse_transform_vector(&(anchor - position_a)),
+            anchor_b: rotation_b.inverse_transform_vector(&(anchor - position_b)),
+            axis_a: rotation_a.inverse_transform_vector(&axis),
+            axis_b: rotation_b.inverse_transform_vector(&axis),
+            rotation: rotation_a.inverse() * rotation_b,
+        });
+        let r_a = rotation_a * frame.anchor_a;
+        let r_b = rotation_b * frame.anchor_b;
+        let (inertia_a, inertia_b) = (velocities.inverse_inertia[a], velocities.inverse_inertia[b]);
+        let mass = velocities.inverse_mass[a] + velocities.inverse_mass[b];
+        let k = Mat3::identity() * mass
+            - r_a.cross_matrix() * inertia_a * r_a.cross_matrix()
+            - r_b.cross_matrix() * inertia_b * r_b.cross_matrix();
+        let error = (position_b + r_b) - (position_a + r_a);
+        let angular = match self.kind {
+            JointKind::Ball => AngularRows::Free,
+            JointKind::Hinge { .. } => {
+                let axis_a = rotation_a * frame.axis_a;
+                let axis_b = rotation_b * frame.axis_b;
+                let error = axis_a.cross(&axis_b);
+                let axes = tangents(&axis_a);
+                AngularRows::Hinge {
+                    axes,
+                    masses: axes.map(|axis| inverse_or_zero(axis.dot(&((inertia_a + inertia_b) * axis)))),
+                    biases: axes.map(|axis| -bias_factor * error.dot(&axis)),
+                }
+            }
+            JointKind::Fixed => {
+                let delta = rotation_b * (rotation_a * frame.rotation).inverse();
+                let error = delta.imag() * 2.0 * delta.w.signum();
+                AngularRows::Fixed {
+                    mass: (inertia_a + inertia_b).try_inverse().unwrap_or_else(Mat3::zeros),
+                    bias: -bias_factor * error,
+                }
+            }
+        };
+        velocities.apply(a, b, &self.linear_impulse, &r_a, &r_b);
+        velocities.apply_angular(a, b, &self.angular_impulse);
+        JointConstraint {
+            bodies: [a, b],
+            r_a,
+            r_b,
+            point_mass: k.try_inverse().unwrap_or_else(Mat3::zeros),
+            point_bias: -bias_factor * error,
+            angular,
+        }
+    }
+
+    pub(super) fn solve(&mut self, constraint: &JointConstraint, velocities: &mut Velocities) {
+        let [a, b] = constraint.bodies;
+        match constraint.angular {
+            AngularRows::Free => (),
+            AngularRows::Hinge { axes, masses, biases } => {
+                for ((axis, mass), bias) in axes.iter().zip(masses).zip(biases) {
+                    let velocity = (velocities.angular[b] - velocities.angular[a]).dot(axis);
+                    let impulse = axis * (mass * (bias - velocity));
+                    velocities.apply_angular(a, b, &impulse);
+                    self.angular_impulse += impulse;
+                }
+            }
+            AngularRows::Fixed { mass, bias } => {
+                let velocity = velocities.angular[b] - velocities.angular[a];
+                let impulse = mass * (bias - velocity);
+                velocities.apply_angular(a, b, &impulse);
+                self.angular_impulse += impulse;
+            }
+        }
+        let velocity = velocities.relative(a, b, &constraint.r_a, &constraint.r_b);
+        let impulse = constraint.point_mass * (constraint.point_bias - velocity);
+        velocities.apply(a, b, &impulse, &constraint.r_a, &constraint.r_b);
+        self.linear_impulse += impulse;
+    }
+}
+
+fn inverse_or_zero(value: f32) -> f32 {
+    if value > f32::EPSILON {
+        1.0 / value
+    } else {
+        0.0
+    }
+}
+
+#[derive(Clone, Copy)]
+enum AngularRows {
+    Free,
+    Hinge {
+        axes: [Vec3; 2],
+        masses: [f32; 2],
+        biases: [f32; 2],
+    },
+    Fixed {
+        mass: Mat3,
+        bias: Vec3,
+    },
+}
+
+/// Ограничение соединения на одном подшаге
+pub(super) struct JointConstraint {
+    bodies: [usize; 2],
+    r_a: Vec3,
+    r_b: Vec3,
+    point_mass: Mat3,
+    point_bias: Vec3,
+    angular: AngularRows,
+}
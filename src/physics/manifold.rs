@@ -0,0 +1,190 @@
+//! Несколько точек контакта для устойчивого опирания тел.
+//!
+//! Нормаль находится через GJK/EPA, после чего опорные грани форм
+//! обрезаются друг о друга, как в Sutherland–Hodgman.
+use super::{convex_contact, mesh_contacts, Contact, Feature, WorldShape};
+use crate::components::collider::Collider;
+use crate::types::{Mat4, Vec3};
+
+/// Наибольшее число точек контакта пары
+const MAX_POINTS: usize = 4;
+
+/// Контакты двух коллайдеров с опорными гранями: до четырёх точек на пару
+/// выпуклых форм, у треугольного меша — до четырёх точек на все треугольники.
+pub fn contact_manifold(a: &Collider, global_a: &Mat4, b: &Collider, global_b: &Mat4) -> Vec<Contact> {
+    match (a.shape().is_convex(), b.shape().is_convex()) {
+        (true, true) => convex_manifold(&WorldShape::new(a, global_a), &WorldShape::new(b, global_b)),
+        (false, true) => reduce(mesh_contacts(a, global_a, b, global_b, |triangle, shape| {
+            convex_manifold(triangle, shape)
+        })),
+        (true, false) => reduce(mesh_contacts(b, global_b, a, global_a, |triangle, shape| {
+            convex_manifold(triangle, shape)
+        }))
+        .iter()
+        .map(Contact::flipped)
+        .collect(),
+        (false, false) => Vec::new(),
+    }
+}
+
+fn convex_manifold<A: Feature, B: Feature>(a: &A, b: &B) -> Vec<Contact> {
+    let Some(contact) = convex_contact(a, b) else {
+        return Vec::new();
+    };
+    let contacts = clip_faces(&a.face(&contact.normal), &b.face(&-contact.normal), &contact.normal);
+    if contacts.is_empty() {
+        vec![contact]
+    } else {
+        reduce(contacts)
+    }
+}
+
+/// Контакты по опорным граням. Пусто, если ни одна из граней не многоугольник.
+fn clip_faces(face_a: &[Vec3], face_b: &[Vec3], normal: &Vec3) -> Vec<Contact> {
+    if face_a.len() < 2 || face_b.len() < 2 || face_a.len().max(face_b.len()) < 3 {
+        return Vec::new();
+    }
+    // Грань-многоугольник служит опорной, вторая обрезается по её рёбрам
+    let (reference, incident, sign) = if face_b.len() >= 3 {
+        (face_b, face_a, -1.0)
+    } else {
+        (face_a, face_b, 1.0)
+    };
+    let Some(plane) = face_normal(reference).map(|plane| plane * plane.dot(normal).signum() * sign) else {
+        return Vec::new();
+    };
+    let denominator = normal.dot(&plane);
+    if denominator.abs() < 1.0e-3 {
+        return Vec::new();
+    }
+    clip_to_face(incident, reference, normal)
+        .into_iter()
+        .filter_map(|point| {
+            // Расстояние вдоль нормали до плоскости опорной грани
+            let depth = -sign * (point - reference[0]).dot(&plane) / denominator;
+            let other = point + normal * depth * sign;
+            let (point_a, point_b) = if sign < 0.0 { (point, other) } else { (other, point) };
+            (depth >= 0.0).then_some(Contact {
+                point: (point_a + point_b) * 0.5,
+                normal: *normal,
+                depth,
+            })
+        })
+        .collect()
+}
+
+/// Нормаль многоугольника по Ньюэллу
+fn face_normal(face: &[Vec3]) -> Option<Vec3> {
+    let center = face.iter().sum::<Vec3>() / face.len() as f32;
+    let normal = (0..face.len())
+        .map(|i| (face[i] - center).cross(&(face[(i + 1) % face.len()] - center)))
+        .sum::<Vec3>();
+    normal.try_normalize(f32::EPSILON)
+}
+
+/// Часть `subject`, лежащая внутри призмы над `reference` вдоль `normal`
+fn clip_to_face(subject: &[Vec3], reference: &[Vec3], normal: &Vec3) -> Vec<Vec3> {
+    let center = reference.iter().sum::<Vec3>() / reference.len() as f32;
+    let mut points = subject.to_vec();
+    for i in 0..reference.len() {
+        let (start, end) = (reference[i], reference[(i + 1) % reference.len()]);
+        let mut plane = (end - start).cross(normal);
+        if plane.dot(&(center - start)) < 0.0 {
+            plane = -plane;
+        }
+        points = clip_by_plane(&points, &start, &plane);
+        if points.is_empty() {
+            break;
+        }
+    }
+    points
+}
+
+/// Оставляет часть многоугольника или отрезка с положительной стороны плоскости
+fn clip_by_plane(points: &[Vec3], origin: &Vec3, plane: &Vec3) -> Vec<Vec3> {
+    let distance = |point: &Vec3| (point - origin).dot(plane);
+    let intersection = |a: &Vec3, b: &Vec3| {
+        let (da, db) = (distance(a), distance(b));
+        a + (b - a) * (da / (da - db))
+    };
+    match points {
+        [a, b] => match (distance(a) >= 0.0, distance(b) >= 0.0) {
+            (true, true) => vec![*a, *b],
+            (true, false) => vec![*a, intersection(a, b)],
+            (false, true) => vec![intersection(a, b), *b],
+            (false, false) => Vec::new(),
+        },
+        _ => {
+            let mut result = Vec::with_capacity(points.len() + 1);
+            for (i, current) in points.iter().enumerate() {
+                let previous = &points[(i + points.len() - 1) % points.len()];
+                match (distance(previous) >= 0.0, distance(current) >= 0.0) {
+                    (true, true) => result.push(*current),
+                    (true, false) => result.push(intersection(previous, current)),
+                    (false, true) => result.extend([intersection(previous, current), *current]),
+                    (false, false) => (),
+                }
+            }
+            result
+        }
+    }
+}
+
+/// Оставляет самую глубокую точку и точки, охватывающие наибольшую площадь
+fn reduce(mut contacts: Vec<Contact>) -> Vec<Contact> {
+    if contacts.len() <= MAX_POINTS {
+        return contacts;
+    }
+    let argmax = |contacts: &[Contact], score: &dyn Fn(&Contact) -> f32| {
+        (0..contacts.len()).max_by(|&i, &j| score(&contacts[i]).total_cmp(&score(&contacts[j]))).unwrap()
+    };
+    let mut result = Vec::with_capacity(MAX_POINTS);
+    result.push(contacts.swap_remove(argmax(&contacts, &|contact| contact.depth)));
+    let first = result[0].point;
+    result.push(contacts.swap_remove(argmax(&contacts, &|contact| (contact.point - first).norm_squared())));
+    let second = result[1].point;
+    let area = |a: &Vec3, b: &Vec3, c: &Vec3| (b - a).cross(&(c - a)).norm();
+    result.push(contacts.swap_remove(argmax(&contacts, &|contact| area(&first, &second, &contact.point))));
+    let third = result[2].point;
+    result.push(contacts.swap_remove(argmax(&contacts, &|contact| {
+        let point = &contact.point;
+        area(&first, &second, point) + area(&second, &third, point) + area(&third, &first, point)
+    })));
+    result
+}
+
+#[test]
+fn physics_contact_manifold() {
+    use crate::components::collider::ColliderShape;
+    let translation = |x: f32, y: f32, z: f32| Mat4::new_translation(&Vec3::new(x, y, z));
+    let cube = Collider::new(ColliderShape::Box {
+        half_extents: Vec3::new(1.0, 1.0, 1.0),
+    });
+
+    // Куб на кубе со сдвигом: четыре точки по углам перекрытия граней
+    let contacts = contact_manifold(&cube, &Mat4::identity(), &cube, &translation(0.5, 0.0, 1.9));
+    assert_eq!(contacts.len(), 4);
+    for contact in &contacts {
+        assert!((contact.normal - Vec3::z()).norm() < 1.0e-4);
+        assert!((contact.depth - 0.1).abs() < 1.0e-4);
+        assert!((contact.point.z - 0.95).abs() < 1.0e-4);
+        assert!(contact.point.x > -0.5 - 1.0e-4 && contact.point.x < 1.0 + 1.0e-4);
+    }
+
+    // Капсула боком на кубе: отрезок, обрезанный по грани
+    let capsule = Collider::new(ColliderShape::Capsule {
+        radius: 0.5,
+        half_height: 2.0,
+    });
+    let lying = translation(0.0, 0.0, 1.4) * Mat4::from_euler_angles(0.0, std::f32::consts::FRAC_PI_2, 0.0);
+    let contacts = contact_manifold(&cube, &Mat4::identity(), &capsule, &lying);
+    assert_eq!(contacts.len(), 2);
+    for contact in &contacts {
+        assert!((contact.depth - 0.1).abs() < 1.0e-4);
+        assert!((contact.point.x.abs() - 1.0).abs() < 1.0e-4);
+    }
+
+    // Шар даёт одну точку
+    let sphere = Collider::new(ColliderShape::Sphere { radius: 1.0 });
+    assert_eq!(contact_manifold(&cube, &Mat4::identity(), &sphere, &translation(0.0, 0.0, 1.5)).len(), 1);
+}
@@ -0,0 +1,234 @@
+//! Проверка столкновений коллайдеров и симуляция твёрдых тел.
+//!
+//! Выпуклые формы сравниваются через [`gjk`], треугольные меши — по треугольникам,
+//! отобранным BVH меша. Меши между собой не сталкиваются.
+mod cast;
+pub mod gjk;
+pub mod joint;
+mod manifold;
+mod world;
+
+pub use self::cast::{cast_collider, ShapeHit};
+pub use self::joint::{Joint, JointId, JointKind};
+pub use self::manifold::contact_manifold;
+pub(crate) use self::world::Body;
+pub use self::world::{PhysicsSettings, PhysicsWorld};
+
+use crate::components::collider::{Collider, ColliderShape};
+use crate::types::{Mat3, Mat4, Vec3};
+
+use self::gjk::{Proximity, SupportMap};
+
+/// Точка контакта двух форм. `normal` направлена от первой формы ко второй,
+/// сдвиг второй формы на `normal * depth` разделяет их.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+    /// Середина между точками поверхностей форм
+    pub point: Vec3,
+    pub normal: Vec3,
+    pub depth: f32,
+}
+
+impl Contact {
+    /// Тот же контакт с точки зрения второй формы
+    pub fn flipped(&self) -> Self {
+        Self {
+            normal: -self.normal,
+            ..*self
+        }
+    }
+}
+
+/// Выпуклая форма коллайдера в мировых координатах
+pub struct WorldShape<'a> {
+    shape: &'a ColliderShape,
+    matrix: Mat4,
+    /// Транспонированная линейная часть `matrix` для переноса направлений в систему формы
+    direction_matrix: Mat3,
+    margin: f32,
+}
+
+impl<'a> WorldShape<'a> {
+    pub fn new(collider: &'a Collider, global: &Mat4) -> Self {
+        let matrix = collider.world_matrix(global);
+        Self {
+            shape: collider.shape(),
+            direction_matrix: matrix.fixed_slice::<3, 3>(0, 0).transpose(),
+            margin: collider.world_margin(global),
+            matrix,
+        }
+    }
+}
+
+impl SupportMap for WorldShape<'_> {
+    fn support(&self, direction: &Vec3) -> Vec3 {
+        let local = self.shape.core_support(&(self.direction_matrix * direction));
+        (self.matrix * local.push(1.0)).xyz()
+    }
+
+    fn margin(&self) -> f32 {
+        self.margin
+    }
+}
+
+/// Форма с опорной гранью для построения нескольких точек контакта
+trait Feature: SupportMap {
+    /// Вершины грани поверхности, обращённой в направлении `direction`
+    fn face(&self, direction: &Vec3) -> Vec<Vec3>;
+}
+
+impl Feature for WorldShape<'_> {
+    fn face(&self, direction: &Vec3) -> Vec<Vec3> {
+        let offset = direction.try_normalize(f32::EPSILON).unwrap_or_default() * self.margin;
+        self.shape
+            .supporting_face(&(self.direction_matrix * direction))
+            .iter()
+            .map(|point| (self.matrix * point.push(1.0)).xyz() + offset)
+            .collect()
+    }
+}
+
+impl Feature for [Vec3; 3] {
+    fn face(&self, _direction: &Vec3) -> Vec<Vec3> {
+        self.to_vec()
+    }
+}
+
+fn convex_contact(a: &dyn SupportMap, b: &dyn SupportMap) -> Option<Contact> {
+    match gjk::proximity(a, b) {
+        Proximity::Separated(_) => None,
+        Proximity::Penetrating(penetration) => Some(Contact {
+            point: (penetration.point_a + penetration.point_b) * 0.5,
+            normal: penetration.normal,
+            depth: penetration.depth,
+        }),
+    }
+}
+
+/// Контакты треугольного меша `mesh` с выпуклой формой,
+/// `contacts` строит контакты одного треугольника
+fn mesh_contacts(
+    mesh: &Collider,
+    mesh_global: &Mat4,
+    convex: &Collider,
+    convex_global: &Mat4,
+    contacts: impl Fn(&[Vec3; 3], &WorldShape) -> Vec<Contact>,
+) -> Vec<Contact> {
+    let ColliderShape::TriangleMesh(bvh) = mesh.shape() else {
+        return Vec::new();
+    };
+    let matrix = mesh.world_matrix(mesh_global);
+    let Some(inverse) = matrix.try_inverse() else {
+        return Vec::new();
+    };
+    let shape = WorldShape::new(convex, convex_global);
+    let local_bbox = convex.world_bbox(convex_global).transformed(&inverse);
+    bvh.overlap_bbox(&local_bbox)
+        .into_iter()
+        .flat_map(|triangle| {
+            let triangle = bvh.triangle(triangle).map(|point| (matrix * point.push(1.0)).xyz());
+            contacts(&triangle, &shape)
+        })
+        .collect()
+}
+
+fn single_contact(triangle: &[Vec3; 3], shape: &WorldShape) -> Vec<Contact> {
+    convex_contact(triangle, shape).into_iter().collect()
+}
+
+/// Контакты двух коллайдеров объектов с глобальными матрицами `global_a` и `global_b`
+pub fn collide(a: &Collider, global_a: &Mat4, b: &Collider, global_b: &Mat4) -> Vec<Contact> {
+    match (a.shape().is_convex(), b.shape().is_convex()) {
+        (true, true) => {
+            convex_contact(&WorldShape::new(a, global_a), &WorldShape::new(b, global_b)).into_iter().collect()
+        }
+        (false, true) => mesh_contacts(a, global_a, b, global_b, single_contact),
+        (true, false) => mesh_contacts(b, global_b, a, global_a, single_contact)
+            .iter()
+            .map(Contact::flipped)
+            .collect(),
+        (false, false) => Vec::new(),
+    }
+}
+
+#[test]
+fn physics_convex_contacts() {
+    let translation = |x: f32, y: f32, z: f32| Mat4::new_translation(&Vec3::new(x, y, z));
+    let sphere = Collider::new(ColliderShape::Sphere { radius: 1.0 });
+    let cube = Collider::new(ColliderShape::Box {
+        half_extents: Vec3::new(1.0, 1.0, 1.0),
+    });
+
+    // Сферы: разделены, касаются скруглениями, центры совпадают
+    assert!(collide(&sphere, &translation(0.0, 0.0, 0.0), &sphere, &translation(2.5, 0.0, 0.0)).is_empty());
+    let contact = collide(&sphere, &translation(0.0, 0.0, 0.0), &sphere, &translation(1.5, 0.0, 0.0))[0];
+    assert!((contact.normal - Vec3::x()).norm() < 1.0e-5);
+    assert!((contact.depth - 0.5).abs() < 1.0e-5);
+    assert!((contact.point - Vec3::new(0.75, 0.0, 0.0)).norm() < 1.0e-5);
+    let contact = collide(&sphere, &translation(0.0, 0.0, 0.0), &sphere, &translation(0.0, 0.0, 0.0))[0];
+    assert!((contact.depth - 2.0).abs() < 0.05);
+
+    // Кубы, второй выше и чуть сдвинут: проникновение по Z
+    let contact = collide(&cube, &translation(0.0, 0.0, 0.0), &cube, &translation(0.3, 0.2, 1.8))[0];
+    assert!((contact.normal - Vec3::z()).norm() < 1.0e-4);
+    assert!((contact.depth - 0.2).abs() < 1.0e-4);
+
+    // Повёрнутый куб (OBB) касается ребром
+    let rotated = translation(0.0, 0.0, 2.3) * Mat4::from_euler_angles(std::f32::consts::FRAC_PI_4, 0.0, 0.0);
+    let contact = collide(&cube, &Mat4::identity(), &cube, &rotated)[0];
+    assert!((contact.depth - (2.0f32.sqrt() - 1.3)).abs() < 1.0e-3);
+    assert!(collide(&cube, &Mat4::identity(), &cube, &(translation(0.0, 0.0, 0.2) * rotated)).is_empty());
+
+    // Капсула лежит на кубе боком, сфера входит в грань куба центром
+    let capsule = Collider::new(ColliderShape::Capsule {
+        radius: 0.5,
+        half_height: 1.0,
+    });
+    let lying = translation(0.0, 0.0, 1.4) * Mat4::from_euler_angles(0.0, std::f32::consts::FRAC_PI_2, 0.0);
+    let contact = collide(&cube, &Mat4::identity(), &capsule, &lying)[0];
+    assert!((contact.normal - Vec3::z()).norm() < 1.0e-4);
+    assert!((contact.depth - 0.1).abs() < 1.0e-4);
+    let contact = collide(&cube, &Mat4::identity(), &sphere, &translation(0.0, 0.0, 0.8))[0];
+    assert!((contact.normal - Vec3::z()).norm() < 1.0e-3);
+    assert!((contact.depth - 1.2).abs() < 1.0e-3);
+
+    // Выпуклая оболочка вершин куба ведёт себя как куб
+    let hull = Collider::new(ColliderShape::ConvexHull(
+        cube.shape().core_bbox().corners().to_vec().into(),
+    ));
+    let contact = collide(&hull, &Mat4::identity(), &sphere, &translation(1.7, 0.0, 0.0))[0];
+    assert!((contact.normal - Vec3::x()).norm() < 1.0e-4);
+    assert!((contact.depth - 0.3).abs() < 1.0e-4);
+}
+
+#[test]
+fn physics_mesh_contacts() {
+    use crate::mesh::bvh::MeshBvh;
+    use crate::mesh::VkVertex;
+    use std::sync::Arc;
+    // Пол 10x10 из двух треугольников на высоте 0
+    let vertex = |x: f32, y: f32| VkVertex {
+        v_pos: [x, y, 0.0],
+        ..Default::default()
+    };
+    let vertices = [vertex(-5.0, -5.0), vertex(5.0, -5.0), vertex(5.0, 5.0), vertex(-5.0, 5.0)];
+    let floor = Collider::new(ColliderShape::TriangleMesh(Arc::new(MeshBvh::new(
+        &vertices,
+        &[0, 1, 2, 0, 2, 3],
+        0,
+    ))));
+    let sphere = Collider::new(ColliderShape::Sphere { radius: 1.0 });
+    let above = Mat4::new_translation(&Vec3::new(1.0, 2.0, 0.75));
+    let contacts = collide(&floor, &Mat4::identity(), &sphere, &above);
+    assert!(!contacts.is_empty());
+    for contact in &contacts {
+        assert!((contact.normal - Vec3::z()).norm() < 1.0e-4);
+        assert!((contact.depth - 0.25).abs() < 1.0e-4);
+    }
+    // Порядок пары меняет направление нормали
+    let flipped = collide(&sphere, &above, &floor, &Mat4::identity());
+    assert!((flipped[0].normal + Vec3::z()).norm() < 1.0e-4);
+    let high = Mat4::new_translation(&Vec3::new(1.0, 2.0, 1.5));
+    assert!(collide(&floor, &Mat4::identity(), &sphere, &high).is_empty());
+    assert!(collide(&floor, &Mat4::identity(), &floor, &Mat4::identity()).is_empty());
+}
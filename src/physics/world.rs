@@ -0,0 +1,594 @@
+//! Симуляция твёрдых тел фиксированными подшагами.
+//!
+//! Решатель — последовательные импульсы с тёплым стартом, коррекцией
+//! проникновения по Баумгарте, трением и упругостью. Неподвижные группы
+//! связанных тел засыпают и не считаются, пока их не разбудят.
+//! При одинаковых входных данных и подшаге результат повторяется.
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use nalgebra::UnitQuaternion;
+
+use super::joint::{Joint, JointId};
+use super::manifold::contact_manifold;
+use crate::components::collider::Collider;
+use crate::scene::SpatialIndex;
+use crate::types::{Mat3, Mat4, Vec3};
+
+/// Глубина проникновения, которую решатель не исправляет
+const PENETRATION_SLOP: f32 = 0.005;
+/// Доля исправляемого за подшаг проникновения
+const BAUMGARTE: f32 = 0.2;
+/// Скорость сближения, ниже которой удар неупругий, м/с
+const RESTITUTION_THRESHOLD: f32 = 1.0;
+/// Расстояние, на котором точка контакта наследует импульсы прошлого подшага
+const WARM_START_DISTANCE: f32 = 0.05;
+
+/// Параметры симуляции
+#[derive(Clone, Debug, PartialEq)]
+pub struct PhysicsSettings {
+    pub gravity: Vec3,
+    /// Длительность подшага, с
+    pub substep: f32,
+    /// Наибольшее число подшагов за кадр, остаток времени отбрасывается
+    pub max_substeps: u32,
+    pub velocity_iterations: u32,
+    /// Скорости, ниже которых тело считается покоящимся
+    pub sleep_linear_velocity: f32,
+    pub sleep_angular_velocity: f32,
+    /// Время покоя перед засыпанием, с
+    pub time_to_sleep: f32,
+}
+
+impl Default for PhysicsSettings {
+    fn default() -> Self {
+        Self {
+            gravity: Vec3::new(0.0, 0.0, -9.81),
+            substep: 1.0 / 120.0,
+            max_substeps: 8,
+            velocity_iterations: 10,
+            sleep_linear_velocity: 0.05,
+            sleep_angular_velocity: 0.05,
+            time_to_sleep: 0.5,
+        }
+    }
+}
+
+/// Тело на время симуляции кадра. Объекты без [`RigidBody`](crate::components::RigidBody)
+/// и статичные объекты участвуют как неподвижные.
+#[derive(Clone, Debug)]
+pub(crate) struct Body {
+    pub key: i32,
+    pub position: Vec3,
+    pub orientation: UnitQuaternion<f32>,
+    pub scale: Vec3,
+    pub linear_velocity: Vec3,
+    pub angular_velocity: Vec3,
+    pub force: Vec3,
+    pub torque: Vec3,
+    pub inverse_mass: f32,
+    /// Обратные главные моменты инерции
+    pub inverse_inertia: Vec3,
+    pub friction: f32,
+    pub restitution: f32,
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+    pub gravity_scale: f32,
+    pub collider: Option<Collider>,
+    pub layers: u32,
+    /// Статичный объект: габариты в индексе не обновляются после первого добавления.
+    /// Нестатичные тела без массы двигаются извне, их габариты обновляются каждый подшаг.
+    pub is_static: bool,
+    pub can_sleep: bool,
+    pub sleeping: bool,
+    pub sleep_timer: f32,
+}
+
+impl Body {
+    /// Неподвижное тело с положением и масштабом из матрицы `global`
+    pub fn fixed(key: i32, global: &Mat4, collider: Option<Collider>, layers: u32) -> Self {
+        let linear: Mat3 = global.fixed_slice::<3, 3>(0, 0).into();
+        let scale = Vec3::from_fn(|axis, _| linear.column(axis).norm());
+        let rotation = Mat3::from_fn(|row, column| linear[(row, column)] / scale[column].max(f32::EPSILON));
+        Self {
+            key,
+            position: global.column(3).xyz(),
+            orientation: UnitQuaternion::from_matrix(&rotation),
+            scale,
+            linear_velocity: Vec3::zeros(),
+            angular_velocity: Vec3::zeros(),
+            force: Vec3::zeros(),
+            torque: Vec3::zeros(),
+            inverse_mass: 0.0,
+            inverse_inertia: Vec3::zeros(),
+            friction: 0.5,
+            restitution: 0.0,
+            linear_damping: 0.0,
+            angular_damping: 0.0,
+            gravity_scale: 0.0,
+            collider,
+            layers,
+            is_static: true,
+            can_sleep: true,
+            sleeping: false,
+            sleep_timer: 0.0,
+        }
+    }
+
+    #[inline]
+    pub fn is_dynamic(&self) -> bool {
+        self.inverse_mass > 0.0
+    }
+
+    #[inline]
+    pub fn is_awake(&self) -> bool {
+        self.is_dynamic() && !self.sleeping
+    }
+
+    /// Глобальная матрица тела
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::new_translation(&self.position)
+            * self.orientation.to_homogeneous()
+            * Mat4::new_nonuniform_scaling(&self.scale)
+    }
+
+    pub fn inverse_inertia_world(&self) -> Mat3 {
+        let rotation = self.orientation.to_rotation_matrix();
+        rotation.matrix() * Mat3::from_diagonal(&self.inverse_inertia) * rotation.matrix().transpose()
+    }
+
+    /// Мгновенное изменение линейного и углового импульсов
+    pub fn apply_impulse(&mut self, linear: &Vec3, angular: &Vec3) {
+        self.linear_velocity += linear * self.inverse_mass;
+        self.angular_velocity += self.inverse_inertia_world() * angular;
+    }
+}
+
+/// Скорости тел на подшаге. Последний элемент — неподвижный мир.
+pub(super) struct Velocities {
+    pub linear: Vec<Vec3>,
+    pub angular: Vec<Vec3>,
+    pub inverse_mass: Vec<f32>,
+    pub inverse_inertia: Vec<Mat3>,
+}
+
+impl Velocities {
+    /// Импульс `impulse`, приложенный к `b` в точке `r_b` и обратный ему к `a` в `r_a`
+    pub fn apply(&mut self, a: usize, b: usize, impulse: &Vec3, r_a: &Vec3, r_b: &Vec3) {
+        self.linear[a] -= impulse * self.inverse_mass[a];
+        self.angular[a] -= self.inverse_inertia[a] * r_a.cross(impulse);
+        self.linear[b] += impulse * self.inverse_mass[b];
+        self.angular[b] += self.inverse_inertia[b] * r_b.cross(impulse);
+    }
+
+    pub fn apply_angular(&mut self, a: usize, b: usize, impulse: &Vec3) {
+        self.angular[a] -= self.inverse_inertia[a] * impulse;
+        self.angular[b] += self.inverse_inertia[b] * impulse;
+    }
+
+    /// Скорость точки `b` относительно точки `a`
+    pub fn relative(&self, a: usize, b: usize, r_a: &Vec3, r_b: &Vec3) -> Vec3 {
+        self.linear[b] + self.angular[b].cross(r_b) - self.linear[a] - self.angular[a].cross(r_a)
+    }
+
+    fn effective_mass(&self, a: usize, b: usize, r_a: &Vec3, r_b: &Vec3, direction: &Vec3) -> f32 {
+        let arm_a = r_a.cross(direction);
+        let arm_b = r_b.cross(direction);
+        let k = self.inverse_mass[a]
+            + self.inverse_mass[b]
+            + arm_a.dot(&(self.inverse_inertia[a] * arm_a))
+            + arm_b.dot(&(self.inverse_inertia[b] * arm_b));
+        if k > f32::EPSILON {
+            1.0 / k
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Касательные направления, однозначно определяемые нормалью
+pub(super) fn tangents(normal: &Vec3) -> [Vec3; 2] {
+    let tangent = if normal.x.abs() >= 0.57735 {
+        Vec3::new(normal.y, -normal.x, 0.0)
+    } else {
+        Vec3::new(0.0, normal.z, -normal.y)
+    }
+    .normalize();
+    [tangent, normal.cross(&tangent)]
+}
+
+/// Накопленные импульсы точки контакта для тёплого старта
+#[derive(Clone, Copy, Debug)]
+struct CachedImpulse {
+    /// Точка в системе первого тела
+    local: Vec3,
+    normal: f32,
+    tangent: [f32; 2],
+}
+
+struct ContactPoint {
+    local: Vec3,
+    r_a: Vec3,
+    r_b: Vec3,
+    normal: Vec3,
+    tangents: [Vec3; 2],
+    normal_mass: f32,
+    tangent_mass: [f32; 2],
+    /// Целевая скорость расхождения
+    bias: f32,
+    normal_impulse: f32,
+    tangent_impulse: [f32; 2],
+}
+
+struct ContactConstraint {
+    bodies: [usize; 2],
+    friction: f32,
+    points: Vec<ContactPoint>,
+}
+
+/// Состояние симуляции сцены: параметры, соединения и кэш контактов
+#[derive(Default)]
+pub struct PhysicsWorld {
+    settings: PhysicsSettings,
+    accumulator: f32,
+    joints: BTreeMap<JointId, Joint>,
+    next_joint: JointId,
+    contacts: HashMap<(i32, i32), Vec<CachedImpulse>>,
+    index: SpatialIndex<i32>,
+}
+
+#[allow(dead_code)]
+impl PhysicsWorld {
+    #[inline]
+    pub fn settings(&self) -> &PhysicsSettings {
+        &self.settings
+    }
+
+    #[inline]
+    pub fn settings_mut(&mut self) -> &mut PhysicsSettings {
+        &mut self.settings
+    }
+
+    pub fn add_joint(&mut self, joint: Joint) -> JointId {
+        let id = self.next_joint;
+        self.next_joint += 1;
+        self.joints.insert(id, joint);
+        id
+    }
+
+    pub fn remove_joint(&mut self, id: JointId) -> Option<Joint> {
+        self.joints.remove(&id)
+    }
+
+    pub fn joint(&self, id: JointId) -> Option<&Joint> {
+        self.joints.get(&id)
+    }
+
+    pub fn joints(&self) -> impl Iterator<Item = (JointId, &Joint)> {
+        self.joints.iter().map(|(id, joint)| (*id, joint))
+    }
+
+    /// Продвигает тела на `delta` секунд целым числом подшагов,
+    /// остаток переносится на следующий вызов. Возвращает число подшагов.
+    pub(crate) fn simulate(&mut self, bodies: &mut [Body], delta: f32) -> u32 {
+        let step = self.settings.substep;
+        if step <= 0.0 {
+            return 0;
+        }
+        self.accumulator += delta.max(0.0);
+        let lookup = bodies
+            .iter()
+            .enumerate()
+            .map(|(index, body)| (body.key, index))
+            .collect::<HashMap<_, _>>();
+        self.index
+            .retain(|key| lookup.get(key).is_some_and(|index| bodies[*index].collider.is_some()));
+        self.contacts
+            .retain(|(a, b), _| lookup.contains_key(a) && lookup.contains_key(b));
+        let mut steps = 0;
+        while self.accumulator >= step && steps < self.settings.max_substeps {
+            self.substep(bodies, &lookup, step);
+            self.accumulator -= step;
+            steps += 1;
+        }
+        if steps == self.settings.max_substeps {
+            self.accumulator %= step;
+        }
+        steps
+    }
+
+    fn substep(&mut self, bodies: &mut [Body], lookup: &HashMap<i32, usize>, step: f32) {
+        let settings = self.settings.clone();
+        for body in bodies.iter_mut().filter(|body| body.is_awake()) {
+            let acceleration = settings.gravity * body.gravity_scale + body.force * body.inverse_mass;
+            body.linear_velocity += acceleration * step;
+            body.angular_velocity += body.inverse_inertia_world() * body.torque * step;
+            body.linear_velocity /= 1.0 + step * body.linear_damping;
+            body.angular_velocity /= 1.0 + step * body.angular_damping;
+        }
+
+        // Соединения тел, которые есть в сцене
+        let joints = self
+            .joints
+            .iter()
+            .filter_map(|(id, joint)| {
+                let (a, b) = joint.bodies();
+                let a = *lookup.get(&a)?;
+                let b = match b {
+                    Some(b) => Some(*lookup.get(&b)?),
+                    None => None,
+                };
+                Some((*id, a, b))
+            })
+            .collect::<Vec<_>>();
+        let mut constraints = self.find_contacts(bodies, &joints);
+
+        // Группы тел, связанных контактами и соединениями, спят и просыпаются вместе.
+        // Контакты спящих тел не пересчитываются, а берутся из кэша.
+        let mut islands = UnionFind::new(bodies.len());
+        let sleeping_links = self.contacts.keys().filter_map(|(a, b)| {
+            let (a, b) = (lookup[a], lookup[b]);
+            (!bodies[a].is_awake() && !bodies[b].is_awake()).then_some((a, Some(b)))
+        });
+        let links = constraints
+            .iter()
+            .map(|constraint| (constraint.bodies[0], Some(constraint.bodies[1])))
+            .chain(joints.iter().map(|&(_, a, b)| (a, b)))
+            .chain(sleeping_links)
+            .collect::<Vec<_>>();
+        for (a, b) in links {
+            if let Some(b) = b.filter(|b| bodies[*b].is_dynamic() && bodies[a].is_dynamic()) {
+                islands.union(a, b);
+            }
+        }
+        let mut awake_islands = HashSet::new();
+        for (index, body) in bodies.iter().enumerate() {
+            if body.is_awake() {
+                awake_islands.insert(islands.find(index));
+            }
+        }
+        for (index, body) in bodies.iter_mut().enumerate() {
+            if body.is_dynamic() && body.sleeping && awake_islands.contains(&islands.find(index)) {
+                body.sleeping = false;
+                body.sleep_timer = 0.0;
+            }
+        }
+
+        let world = bodies.len();
+        let mut velocities = Velocities {
+            linear: bodies.iter().map(|body| body.linear_velocity).chain([Vec3::zeros()]).collect(),
+            angular: bodies.iter().map(|body| body.angular_velocity).chain([Vec3::zeros()]).collect(),
+            inverse_mass: bodies
+                .iter()
+                .map(|body| if body.is_awake() { body.inverse_mass } else { 0.0 })
+                .chain([0.0])
+                .collect(),
+            inverse_inertia: bodies
+                .iter()
+                .map(|body| if body.is_awake() { body.inverse_inertia_world() } else { Mat3::zeros() })
+                .chain([Mat3::zeros()])
+                .collect(),
+        };
+        constraints.retain(|constraint| constraint.bodies.iter().any(|index| bodies[*index].is_awake()));
+        for constraint in &mut constraints {
+            self.prepare_contact(bodies, constraint, &mut velocities, step);
+        }
+        let bias_factor = BAUMGARTE / step;
+        let pose = |index: Option<usize>| match index {
+            Some(index) => (bodies[index].position, bodies[index].orientation),
+            None => (Vec3::zeros(), UnitQuaternion::identity()),
+        };
+        let mut joint_constraints = Vec::new();
+        for &(id, a, b) in &joints {
+            if bodies[a].is_awake() || b.is_some_and(|b| bodies[b].is_awake()) {
+                let joint = self.joints.get_mut(&id).unwrap();
+                let bodies = [a, b.unwrap_or(world)];
+                joint_constraints.push((id, joint.prepare(pose(Some(a)), pose(b), bodies, &mut velocities, bias_factor)));
+            }
+        }
+
+        for _ in 0..settings.velocity_iterations {
+            for (id, constraint) in &joint_constraints {
+                self.joints.get_mut(id).unwrap().solve(constraint, &mut velocities);
+            }
+            for constraint in &mut constraints {
+                solve_contact(constraint, &mut velocities);
+            }
+        }
+
+        for (index, body) in bodies.iter_mut().enumerate().filter(|(_, body)| body.is_awake()) {
+            body.linear_velocity = velocities.linear[index];
+            body.angular_velocity = velocities.angular[index];
+            body.position += body.linear_velocity * step;
+            let rotation = body.angular_velocity * step;
+            body.orientation = UnitQuaternion::new_normalize(
+                body.orientation.into_inner()
+                    + nalgebra::Quaternion::from_imag(rotation * 0.5) * body.orientation.into_inner(),
+            );
+        }
+
+        self.contacts.retain(|(a, b), _| !bodies[lookup[a]].is_awake() && !bodies[lookup[b]].is_awake());
+        for constraint in &constraints {
+            let [a, b] = constraint.bodies;
+            let cached = constraint
+                .points
+                .iter()
+                .map(|point| CachedImpulse {
+                    local: point.local,
+                    normal: point.normal_impulse,
+                    tangent: point.tangent_impulse,
+                })
+                .collect();
+            self.contacts.insert((bodies[a].key, bodies[b].key), cached);
+        }
+
+        // Засыпание групп, все тела которых покоятся достаточно долго
+        let mut island_timers: BTreeMap<usize, f32> = BTreeMap::new();
+        for (index, body) in bodies.iter_mut().enumerate().filter(|(_, body)| body.is_awake()) {
+            let resting = body.linear_velocity.norm() < settings.sleep_linear_velocity
+                && body.angular_velocity.norm() < settings.sleep_angular_velocity;
+            body.sleep_timer = if resting && body.can_sleep {
+                body.sleep_timer + step
+            } else {
+                0.0
+            };
+            let timer = island_timers.entry(islands.find(index)).or_insert(f32::MAX);
+            *timer = timer.min(body.sleep_timer);
+        }
+        for (index, body) in bodies.iter_mut().enumerate().filter(|(_, body)| body.is_awake()) {
+            if island_timers[&islands.find(index)] >= settings.time_to_sleep {
+                body.sleeping = true;
+                body.linear_velocity = Vec3::zeros();
+                body.angular_velocity = Vec3::zeros();
+            }
+        }
+    }
+
+    /// Пары пересекающихся тел, среди которых хотя бы одно не спит
+    fn find_contacts(&mut self, bodies: &[Body], joints: &[(JointId, usize, Option<usize>)]) -> Vec<ContactConstraint> {
+        let matrices = bodies.iter().map(Body::matrix).collect::<Vec<_>>();
+        let mut lookup = HashMap::new();
+        for (index, body) in bodies.iter().enumerate() {
+            let Some(collider) = &body.collider else {
+                continue;
+            };
+            lookup.insert(body.key, index);
+            if !body.is_static || self.index.is_static(&body.key) != Some(true) {
+                self.index.update(body.key, collider.world_bbox(&matrices[index]), body.is_static);
+            }
+        }
+        let connected = joints
+            .iter()
+            .filter_map(|&(_, a, b)| Some((a.min(b?), a.max(b?))))
+            .collect::<HashSet<_>>();
+        let mut pairs = BTreeSet::new();
+        for (index, body) in bodies.iter().enumerate().filter(|(_, body)| body.is_awake()) {
+            let Some(collider) = &body.collider else {
+                continue;
+            };
+            for other in self.index.query_bbox(&collider.world_bbox(&matrices[index])) {
+                let other = lookup[&other];
+                if other != index {
+                    pairs.insert((index.min(other), index.max(other)));
+                }
+            }
+        }
+
+        let mut constraints = Vec::new();
+        for (a, b) in pairs {
+            let (first, second) = (&bodies[a], &bodies[b]);
+            let (Some(collider_a), Some(collider_b)) = (&first.collider, &second.collider) else {
+                continue;
+            };
+            if collider_a.is_trigger()
+                || collider_b.is_trigger()
+                || first.layers & collider_b.collision_mask() == 0
+                || second.layers & collider_a.collision_mask() == 0
+                || connected.contains(&(a, b))
+            {
+                continue;
+            }
+            let contacts = contact_manifold(collider_a, &matrices[a], collider_b, &matrices[b]);
+            if contacts.is_empty() {
+                continue;
+            }
+            let cached = self.contacts.get(&(first.key, second.key));
+            let points = contacts
+                .iter()
+                .map(|contact| {
+                    let local = first.orientation.inverse_transform_vector(&(contact.point - first.position));
+                    let previous = cached.and_then(|cached| {
+                        cached
+                            .iter()
+                            .find(|point| (point.local - local).norm() < WARM_START_DISTANCE)
+                    });
+                    ContactPoint {
+                        local,
+                        r_a: contact.point - first.position,
+                        r_b: contact.point - second.position,
+                        normal: contact.normal,
+                        tangents: tangents(&contact.normal),
+                        normal_mass: 0.0,
+                        tangent_mass: [0.0; 2],
+                        bias: contact.depth,
+                        normal_impulse: previous.map_or(0.0, |point| point.normal),
+                        tangent_impulse: previous.map_or([0.0; 2], |point| point.tangent),
+                    }
+                })
+                .collect();
+            constraints.push(ContactConstraint {
+                bodies: [a, b],
+                friction: (first.friction * second.friction).sqrt(),
+                points,
+            });
+        }
+        constraints
+    }
+
+    /// Массы и целевые скорости точек, тёплый старт.
+    /// До вызова в `bias` точки лежит глубина проникновения.
+    fn prepare_contact(&self, bodies: &[Body], constraint: &mut ContactConstraint, velocities: &mut Velocities, step: f32) {
+        let [a, b] = constraint.bodies;
+        let restitution = bodies[a].restitution.max(bodies[b].restitution);
+        for point in &mut constraint.points {
+            point.normal_mass = velocities.effective_mass(a, b, &point.r_a, &point.r_b, &point.normal);
+            point.tangent_mass = point
+                .tangents
+                .map(|tangent| velocities.effective_mass(a, b, &point.r_a, &point.r_b, &tangent));
+            let approach = velocities.relative(a, b, &point.r_a, &point.r_b).dot(&point.normal);
+            let correction = BAUMGARTE / step * (point.bias - PENETRATION_SLOP).max(0.0);
+            let bounce = if approach < -RESTITUTION_THRESHOLD {
+                -restitution * approach
+            } else {
+                0.0
+            };
+            point.bias = correction.max(bounce);
+            let impulse = point.normal * point.normal_impulse
+                + point.tangents[0] * point.tangent_impulse[0]
+                + point.tangents[1] * point.tangent_impulse[1];
+            velocities.apply(a, b, &impulse, &point.r_a, &point.r_b);
+        }
+    }
+}
+
+fn solve_contact(constraint: &mut ContactConstraint, velocities: &mut Velocities) {
+    let [a, b] = constraint.bodies;
+    for point in &mut constraint.points {
+        // Трение ограничено нормальным импульсом
+        let limit = constraint.friction * point.normal_impulse;
+        for axis in 0..2 {
+            let tangent = point.tangents[axis];
+            let velocity = velocities.relative(a, b, &point.r_a, &point.r_b).dot(&tangent);
+            let accumulated = (point.tangent_impulse[axis] - point.tangent_mass[axis] * velocity).clamp(-limit, limit);
+            let impulse = accumulated - point.tangent_impulse[axis];
+            point.tangent_impulse[axis] = accumulated;
+            velocities.apply(a, b, &(tangent * impulse), &point.r_a, &point.r_b);
+        }
+        let velocity = velocities.relative(a, b, &point.r_a, &point.r_b).dot(&point.normal);
+        let accumulated = (point.normal_impulse + point.normal_mass * (point.bias - velocity)).max(0.0);
+        let impulse = accumulated - point.normal_impulse;
+        point.normal_impulse = accumulated;
+        velocities.apply(a, b, &(point.normal * impulse), &point.r_a, &point.r_b);
+    }
+}
+
+struct UnionFind(Vec<usize>);
+
+impl UnionFind {
+    fn new(len: usize) -> Self {
+        Self((0..len).collect())
+    }
+
+    fn find(&mut self, mut index: usize) -> usize {
+        while self.0[index] != index {
+            self.0[index] = self.0[self.0[index]];
+            index = self.0[index];
+        }
+        index
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        // Меньший индекс становится корнем, чтобы порядок не зависел от входа
+        self.0[a.max(b)] = a.min(b);
+    }
+}
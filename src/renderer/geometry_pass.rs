@@ -1,3 +1,4 @@
+use crate::error::DsgeError;
 use std::cmp::Ordering;
 use std::default;
 use std::mem::size_of;
@@ -25,9 +26,11 @@ use crate::components::GOTransformUniform;
 use crate::components::ProjectionUniformData;
 use crate::framebuffer::{Framebuffer, FramebufferBinder};
 
-use crate::material::MaterialShaderProgramType;
+use crate::components::skeleton::MAX_BONES;
+use crate::material::{MaterialShaderProgramType, SHADER_MESH_SET, SHADER_SKELETON_SET};
+use crate::mesh::morph::{MorphDelta, MAX_MORPH_TARGETS};
 use crate::resource_manager::ResourceManager;
-use crate::shader::ShaderProgramBinder;
+use crate::shader::{ShaderProgramBinder, ShaderProgramUniformBuffer};
 use crate::time::UniformTime;
 use crate::types::Vec4;
 use crate::types::{ArrayInto, Mat4, Vec3};
@@ -165,6 +168,37 @@ pub fn cull_objects(projection_data: ProjectionUniformData, draw_list: &DrawList
         .collect()
 }
 
+/// Матрицы скининга и данные морфинга для варианта шейдера `DEFORMED`.
+/// Набор привязок должен быть полным, поэтому отсутствующие данные заменяются пустыми.
+fn bind_deformation(visual: &MeshVisual, uniforms: &mut ShaderProgramUniformBuffer, allocator: Arc<BumpMemoryAllocator>) {
+    let mut palette = match visual.skin() {
+        Some(skin) => skin.lock().iter().flat_map(|matrix| matrix.as_slice().to_vec()).collect::<Vec<f32>>(),
+        None => Vec::new(),
+    };
+    palette.resize(MAX_BONES * 16, 0.0);
+    uniforms.uniform_structure(allocator.clone(), &[&palette], 0, SHADER_SKELETON_SET, 0);
+
+    // Веса и смещения целей относятся к вершинам исходного меша. Уровни детализации
+    // из того же буфера используют его вершины, из другого буфера рисуются без морфинга.
+    let base_mesh = visual.base_mesh();
+    let targets = base_mesh
+        .morph_targets()
+        .filter(|_| visual.mesh().buffer_id() == base_mesh.buffer_id());
+    let (count, vertex_count) = targets
+        .map(|targets| (targets.len() as u32, targets.vertex_count()))
+        .unwrap_or((0, 0));
+    let mut morph = [count, vertex_count, 0, 0].map(f32::from_bits).to_vec();
+    morph.extend_from_slice(visual.morph_weights());
+    morph.resize(4 + MAX_MORPH_TARGETS, 0.0);
+    uniforms.uniform_structure(allocator.clone(), &[&morph], 0, SHADER_SKELETON_SET, 1);
+
+    let deltas = match targets {
+        Some(targets) => targets.deltas().clone(),
+        None => new_cpu_buffer_from_iter(BufferUsage::STORAGE_BUFFER, allocator, [MorphDelta::default()]).unwrap(),
+    };
+    uniforms.storage_subbuffer(deltas, SHADER_SKELETON_SET, 2);
+}
+
 pub(super) fn build_geometry_pass(
     framebuffer: &mut Framebuffer,
     projection_data: ProjectionUniformData,
@@ -175,7 +209,8 @@ pub(super) fn build_geometry_pass(
     command_buffer_father: &CommandBufferFather,
     allocator: Arc<BumpMemoryAllocator>,
     ds_allocator: Arc<StandardDescriptorSetAllocator>,
-) -> Result<Arc<PrimaryAutoCommandBuffer>, String> {
+) -> Result<Arc<PrimaryAutoCommandBuffer>, DsgeError> {
+    // Точная проверка углов габаритов у кандидатов, отобранных пространственным индексом
     let mut draw_list = cull_objects(projection_data, &draw_list);
     /*let cam_pos = Vec3::new(
         projection_data.transform[12],
@@ -201,8 +236,8 @@ pub(super) fn build_geometry_pass(
         return Ok(cb);
     }
     draw_list.sort_by(|(_, a), (_, b)| {
-        let a_hash = a.shader_hash(shader_type);
-        let b_hash = b.shader_hash(shader_type);
+        let a_hash = a.shader_hash(a.shader_type(shader_type));
+        let b_hash = b.shader_hash(b.shader_type(shader_type));
         match a_hash.cmp(&b_hash) {
             Ordering::Equal => (),
             nequal => return nequal,
@@ -265,7 +300,7 @@ pub(super) fn build_geometry_pass(
                     command_buffer_father,
                     allocator.clone(),
                     ds_allocator.clone(),
-                    &shader_type,
+                    &draw_list[0].1.shader_type(shader_type),
                     subpass.clone()
                 )
                 .clone();
@@ -290,6 +325,8 @@ pub(super) fn build_geometry_pass(
             camera_uniform_buffer.clear_uniform_set(crate::material::SHADER_TEXTURE_SET);
             (shader_program, camera_uniform_buffer.clone())
         };
+        // Пустой набор переменных программы текущей группы материала для данных вершинного буфера
+        let mut mesh_uniforms = None;
         let mut _draw_call_count = 0;
         let mut _shader_switches = 0;
         let mut _vbo_switches = 0;
@@ -305,13 +342,17 @@ pub(super) fn build_geometry_pass(
                 ..Default::default()
             },
         );
+        // Каждый деформируемый объект рисуется отдельно со своими матрицами скининга и весами морфинга
+        let skinned = |i: usize| draw_list[i].1.is_deformed();
+        // Меши с разным форматом вершин рисуются разными вариантами шейдера материала
+        let same_shader = |i: usize, j: usize| draw_list[i].1.shader_type(shader_type) == draw_list[j].1.shader_type(shader_type);
         for (i, (_, visual)) in draw_list.iter().enumerate() {
             let mesh = visual.mesh().clone();
             let material = visual.material().clone();
             let new_material_group = if i == 0 {
                 true
             } else {
-                material.box_id() != draw_list[i - 1].1.material().box_id()
+                material.box_id() != draw_list[i - 1].1.material().box_id() || skinned(i) || skinned(i - 1) || !same_shader(i, i - 1)
             };
             let new_mesh_group = if i == 0 {
                 true
@@ -321,12 +362,12 @@ pub(super) fn build_geometry_pass(
             let new_instance_group = if i == 0 {
                 true
             } else {
-                mesh.ref_id() != draw_list[i - 1].1.mesh().ref_id()
+                mesh.ref_id() != draw_list[i - 1].1.mesh().ref_id() || skinned(i) || skinned(i - 1)
             };
             let end_instance_group = if i == last_index {
                 true
             } else {
-                mesh.ref_id() != draw_list[i + 1].1.mesh().ref_id()
+                mesh.ref_id() != draw_list[i + 1].1.mesh().ref_id() || skinned(i) || skinned(i + 1)
             };
             let end_mesh_group = if i == last_index {
                 true
@@ -336,7 +377,7 @@ pub(super) fn build_geometry_pass(
             let end_material_group = if i == last_index {
                 true
             } else {
-                material.box_id() != draw_list[i + 1].1.material().box_id()
+                material.box_id() != draw_list[i + 1].1.material().box_id() || skinned(i) || skinned(i + 1) || !same_shader(i, i + 1)
             };
             unsafe {
 
@@ -344,7 +385,7 @@ pub(super) fn build_geometry_pass(
                     let (shd, mut uni) = visual
                         .material()
                         .lock()
-                        .use_in_subpass(command_buffer_father, allocator.clone(), ds_allocator.clone(), &shader_type, subpass.clone())
+                        .use_in_subpass(command_buffer_father, allocator.clone(), ds_allocator.clone(), &visual.shader_type(shader_type), subpass.clone())
                         .clone();
                     if shd.hash() != last_shader {
                         command_buffer_builder
@@ -358,13 +399,30 @@ pub(super) fn build_geometry_pass(
                     command_buffer_builder
                         .bind_shader_uniforms(ds_allocator.clone(), &mut uni, false)
                         .unwrap();
+                    if visual.is_deformed() {
+                        let mut deform_uniforms = shd.new_uniform_buffer();
+                        bind_deformation(visual, &mut deform_uniforms, allocator.clone());
+                        command_buffer_builder
+                            .bind_shader_uniforms(ds_allocator.clone(), &mut deform_uniforms, true)
+                            .unwrap();
+                    }
+                    mesh_uniforms = Some(shd.new_uniform_buffer());
                 }
                 if new_mesh_group {
                     indirect_commands.clear();
                     command_buffer_builder
-                        .bind_vertex_buffers_unchecked(0, (mesh.vertex_buffer().clone(), instance_buffer.clone()))
+                        .bind_vertex_buffers_unchecked(0, (mesh.vertex_buffer().as_bytes().clone(), instance_buffer.clone()))
                         .bind_index_buffer_unchecked(mesh.index_buffer().clone());
                 }
+                // Позиции компактных вершин распаковываются по габаритам своего буфера
+                let quantization = mesh.vertex_buffer().quantization().filter(|_| new_mesh_group || new_material_group);
+                if let (Some(quantization), Some(uniforms)) = (quantization, &mesh_uniforms) {
+                    let mut uniforms = uniforms.clone();
+                    uniforms.uniform_structure(allocator.clone(), &[&quantization.uniform_data()], 0, SHADER_MESH_SET, 0);
+                    command_buffer_builder
+                        .bind_shader_uniforms(ds_allocator.clone(), &mut uniforms, true)
+                        .unwrap();
+                }
                 if new_instance_group {
                     first_instance_index = i as _;
                     instance_counter = 0;
@@ -592,7 +650,7 @@ impl GeometryPass {
         camera_data: ProjectionUniformData,
         timer: UniformTime,
         draw_list: DrawList,
-    ) -> Result<Arc<PrimaryAutoCommandBuffer>, String> {
+    ) -> Result<Arc<PrimaryAutoCommandBuffer>, DsgeError> {
         build_geometry_pass(
             &mut self._frame_buffer,
             camera_data.clone(),
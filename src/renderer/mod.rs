@@ -1,15 +1,17 @@
 mod geometry_pass;
 mod postprocessor;
 mod shadowmap_pass;
+use crate::console::Cvars;
+use crate::error::DsgeError;
 use crate::{
     command_buffer::CommandBufferFather,
-    components::light::{LightShaderStruct, LightsUniformData, PointLightUniform, ShadowBuffer, ShadowMapMode, SpotlightUniform, SunLightUniform},
+    components::light::{LightShaderStruct, LightType, LightsUniformData, PointLightUniform, ShadowBuffer, ShadowMapMode, SpotlightUniform, SunLightUniform},
     resource_manager::{ResourceManager, ResourceManagerRef, MAX_POINT_LIGHTS, MAX_SPOTLIGHTS, MAX_SUN_LIGHTS},
     texture::{Texture, TextureCommandSet, TextureUseCase},
     types::{ArrayInto, Mat4, Vec4},
     vulkano::device::DeviceOwned,
 };
-use std::{cmp::Ordering, collections::HashMap, sync::Arc};
+use std::{cmp::Ordering, collections::{BTreeMap, HashMap}, sync::Arc};
 #[allow(unused_imports)]
 use vulkano::command_buffer::{
     AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer,
@@ -32,12 +34,15 @@ use winit::window::Window;
 
 use crate::components::*;
 use crate::game_object::*;
+use crate::mesh::lod::{projected_size, LodLevels, LodSettings};
 use crate::mesh::{Mesh, MeshRef};
 use crate::references::*;
+use crate::scene::{AabbTree, Frustum, SpatialIndex};
 use crate::texture::{TextureDimensions, TexturePixelFormat};
 use crate::time::UniformTime;
 use crate::texture::TextureViewType;
 
+use geometry_pass::DrawList;
 pub use geometry_pass::GeometryPass;
 pub use postprocessor::{PostprocessingPass, RenderResolution};
 pub use shadowmap_pass::ShadowMapPass;
@@ -164,7 +169,7 @@ impl RenderSurface {
         surface: Arc<Surface>,
         device: Arc<Device>,
         vsync: bool,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, DsgeError> {
         let window = surface.object().unwrap().downcast_ref::<Window>().unwrap();
         let physical_device = device.physical_device();
         let caps = physical_device
@@ -177,7 +182,7 @@ impl RenderSurface {
             .unwrap()
             .into_iter()
             .filter_map(|a| {
-                println!("{:?}: {:?}", a.0, a.1);
+                log::debug!(target: "renderer", "Surface format {:?}: {:?}", a.0, a.1);
                 match a {
                     (
                         vulkano::format::Format::R8G8B8_SRGB
@@ -303,7 +308,7 @@ impl RenderSurface {
         allocator: Arc<StandardMemoryAllocator>,
         dimensions: [u16; 2],
         pix_fmt: TexturePixelFormat,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, DsgeError> {
         Ok(Self::Offscreen {
             image: crate::texture::Texture::new(
                 "Offscreen surface",
@@ -319,6 +324,92 @@ impl RenderSurface {
     }
 }
 
+/// Переменные консоли, управляющие разрешением динамических карт теней
+const SHADOW_MAP_CVARS: [(&str, LightType); 3] = [
+    ("r_shadow_spot_size", LightType::Spot),
+    ("r_shadow_sun_size", LightType::Sun),
+    ("r_shadow_point_size", LightType::Point),
+];
+
+fn postprocess_cvar(uniform: &str) -> String {
+    format!("pp_{uniform}")
+}
+
+/// Настройки рендера, управляемые переменными консоли
+#[derive(Clone, Debug, PartialEq)]
+struct RenderCvars {
+    fxaa: bool,
+    super_resolution: bool,
+    lod_settings: LodSettings,
+    /// Разрешение карт теней в порядке [`SHADOW_MAP_CVARS`]
+    shadow_map_resolution: [u32; 3],
+    /// Наибольшее разрешение карт теней, поддерживаемое устройством
+    max_shadow_map_resolution: [u32; 3],
+    /// Значения uniform-переменных постобработки по их именам
+    postprocess: BTreeMap<String, f32>,
+}
+
+impl RenderCvars {
+    fn register(&self, cvars: &Cvars) -> Result<(), DsgeError> {
+        cvars.register("r_fxaa", self.fxaa, "FXAA antialiasing")?;
+        cvars.register(
+            "r_super_resolution",
+            self.super_resolution,
+            "Render geometry at half resolution and upscale",
+        )?;
+        cvars.register("r_lod_bias", self.lod_settings.bias, "LOD screen size multiplier")?;
+        cvars.register(
+            "r_lod_hysteresis",
+            self.lod_settings.hysteresis,
+            "Relative LOD threshold band without switching",
+        )?;
+        for ((name, _), resolution) in SHADOW_MAP_CVARS.into_iter().zip(self.shadow_map_resolution) {
+            cvars.register(name, resolution, "Dynamic shadow map resolution in pixels")?;
+        }
+        Ok(())
+    }
+
+    /// Новые настройки по значениям переменных.
+    /// Незарегистрированные и недопустимые переменные оставляют текущее значение,
+    /// разрешение карт теней ограничивается возможностями устройства.
+    fn read(&self, cvars: &Cvars) -> Self {
+        let flag = |name: &str| cvars.get(name).and_then(|value| value.as_bool());
+        let float = |name: &str| cvars.get(name).and_then(|value| value.as_float());
+        let resolution = |name: &str| {
+            cvars
+                .get(name)
+                .and_then(|value| value.as_int())
+                .and_then(|value| u32::try_from(value).ok())
+                .filter(|value| *value > 0)
+        };
+        let mut shadow_map_resolution = self.shadow_map_resolution;
+        for (((name, _), current), max) in SHADOW_MAP_CVARS
+            .into_iter()
+            .zip(&mut shadow_map_resolution)
+            .zip(self.max_shadow_map_resolution)
+        {
+            *current = resolution(name).map_or(*current, |value| value.min(max));
+        }
+        Self {
+            fxaa: flag("r_fxaa").unwrap_or(self.fxaa),
+            super_resolution: flag("r_super_resolution").unwrap_or(self.super_resolution),
+            lod_settings: LodSettings {
+                bias: float("r_lod_bias").unwrap_or(self.lod_settings.bias),
+                hysteresis: float("r_lod_hysteresis").unwrap_or(self.lod_settings.hysteresis),
+            },
+            shadow_map_resolution,
+            max_shadow_map_resolution: self.max_shadow_map_resolution,
+            postprocess: self
+                .postprocess
+                .iter()
+                .map(|(uniform, value)| {
+                    (uniform.clone(), float(&postprocess_cvar(uniform)).unwrap_or(*value))
+                })
+                .collect(),
+        }
+    }
+}
+
 struct RenderableLight
 {
     uniform_var: LightShaderStruct,
@@ -344,6 +435,10 @@ pub struct Renderer {
     _need_to_update_sc: bool,
 
     _draw_list: Vec<(GOTransform, Arc<MeshVisual>)>,
+    /// Номер объекта в `_draw_list` по ключу индекса
+    _draw_keys: HashMap<i32, usize>,
+    /// Габариты отрисовываемых объектов для отсечения камерой и проекциями теней
+    _spatial_index: SpatialIndex<i32>,
     _lights_list: Vec<RenderableLight>,
 
     _aspect: f32,
@@ -354,6 +449,8 @@ pub struct Renderer {
 
     _super_resolution: bool,
     _fxaa: bool,
+    _lod_settings: LodSettings,
+    _lod_levels: LodLevels,
 
     _postprocessor: PostprocessingPass,
     _shadowmap_pass: ShadowMapPass,
@@ -365,6 +462,9 @@ pub struct Renderer {
     _dummy_texture_cube: Texture,
 
     _timer: UniformTime,
+
+    _cvars: Option<Cvars>,
+    _postprocess_uniforms: BTreeMap<String, f32>,
 }
 
 impl Renderer {
@@ -394,18 +494,60 @@ impl Renderer {
         self._camera_data
     }*/
 
+    /// Добавляет меш в кадр. `key` идентифицирует объект в пространственном индексе
+    /// между кадрами, габариты статичных объектов пересчитываются только при первом добавлении.
     pub fn add_renderable_component(
         &mut self,
+        key: i32,
         transform_data: GOTransform,
-        component: Arc<MeshVisual>,
+        mut component: Arc<MeshVisual>,
     ) {
+        if component.lod_count() > 1 {
+            Arc::make_mut(&mut component).set_lod(self._lod_levels.get(key));
+        }
+        let is_static = transform_data.is_static();
+        if !is_static || self._spatial_index.is_static(&key) != Some(true) {
+            let bbox = component.bbox().transformed(&transform_data.global);
+            self._spatial_index.update(key, bbox, is_static);
+        }
+        self._draw_keys.insert(key, self._draw_list.len());
         self._draw_list.push((transform_data, component))
     }
+
+    /// Выбор уровней детализации объектов кадра по размеру проекции их габаритов для камеры.
+    /// Уровни хранятся по ключам объектов, поэтому копии одного меша выбирают их независимо.
+    fn select_lods(&mut self) {
+        let view_projection = self._camera_data.full_matrix();
+        for (&key, &index) in &self._draw_keys {
+            let (transform, visual) = &mut self._draw_list[index];
+            if visual.lod_count() > 1 {
+                let model_view_projection = view_projection * transform.global;
+                let screen_size = projected_size(&visual.bbox_corners(), model_view_projection);
+                let level = self._lod_levels.select(key, &visual.lod_thresholds(), screen_size, self._lod_settings);
+                Arc::make_mut(visual).set_lod(level);
+            }
+        }
+        let draw_keys = &self._draw_keys;
+        self._lod_levels.retain(|key| draw_keys.contains_key(&key));
+    }
+
+    /// Объекты кадра из листьев `tree`, попадающие в проекцию
+    fn visible_objects(&self, tree: &AabbTree<i32>, projection_data: &ProjectionUniformData) -> DrawList {
+        tree.query_frustum(&Frustum::from_projection(projection_data))
+            .iter()
+            .filter_map(|key| self._draw_keys.get(key))
+            .map(|&index| {
+                let (transform, visual) = &self._draw_list[index];
+                (transform.uniform_value(), visual.clone())
+            })
+            .collect()
+    }
 }
 
 use self::geometry_pass::check_in_frustum;
 #[allow(dead_code)]
 impl Renderer {
+    /// Устройство для вывода в окно
     pub fn default_device(
         vk_instance: Arc<Instance>,
     ) -> Result<(Arc<Device>, impl ExactSizeIterator<Item = Arc<Queue>>), Validated<vulkano::VulkanError>> {
@@ -415,6 +557,29 @@ impl Renderer {
             //khr_draw_indirect_count: true,
             ..DeviceExtensions::empty()
         };
+        Self::select_device(vk_instance, device_extensions, false)
+    }
+
+    /// Устройство для рендеринга без окна.
+    /// Не требует поддержки swapchain, поэтому подходит и для программных
+    /// реализаций Vulkan (например, lavapipe). Если `prefer_cpu`, то программная
+    /// реализация выбирается в первую очередь.
+    pub fn headless_device(
+        vk_instance: Arc<Instance>,
+        prefer_cpu: bool,
+    ) -> Result<(Arc<Device>, impl ExactSizeIterator<Item = Arc<Queue>>), Validated<vulkano::VulkanError>> {
+        let device_extensions = DeviceExtensions {
+            khr_buffer_device_address: true,
+            ..DeviceExtensions::empty()
+        };
+        Self::select_device(vk_instance, device_extensions, prefer_cpu)
+    }
+
+    fn select_device(
+        vk_instance: Arc<Instance>,
+        device_extensions: DeviceExtensions,
+        prefer_cpu: bool,
+    ) -> Result<(Arc<Device>, impl ExactSizeIterator<Item = Arc<Queue>>), Validated<vulkano::VulkanError>> {
         let (physical_device, queue_family_index) = vk_instance
             .enumerate_physical_devices()
             .unwrap()
@@ -428,6 +593,7 @@ impl Renderer {
                     .map(|i| (p, i as u32))
             })
             .min_by_key(|(p, _)| match p.properties().device_type {
+                PhysicalDeviceType::Cpu if prefer_cpu => 0,
                 PhysicalDeviceType::DiscreteGpu => 1,
                 PhysicalDeviceType::IntegratedGpu => 2,
                 PhysicalDeviceType::VirtualGpu => 3,
@@ -435,7 +601,7 @@ impl Renderer {
                 PhysicalDeviceType::Other => 5,
                 _ => 5,
             })
-            .unwrap();
+            .ok_or(Validated::Error(VulkanError::InitializationFailed))?;
         let features = Features {
             sampler_anisotropy: true,
             //draw_indirect_count: true,
@@ -455,84 +621,35 @@ impl Renderer {
             ..Default::default()
         };
 
-        println!("Используется {}", physical_device.properties().device_name);
+        log::info!(target: "renderer", "Using device {}", physical_device.properties().device_name);
         //println!("{dev_info:?}");
         Device::new(physical_device, dev_info)
     }
 
-    // pub fn offscreen(
-    //     vk_instance: Arc<Instance>,
-    //     dimensions: [u16; 2],
-    //     super_resolution: bool,
-    //     fxaa: bool,
-    // ) -> Self {
-    //     let (device, mut queues) = Self::default_device(vk_instance.clone()).unwrap();
-    //     let queue = queues.next().unwrap();
-    //     let allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
-    //     let command_buffer_father = CommandBufferFather::new(queue.clone());
-    //     let surface = RenderSurface::offscreen(
-    //         allocator.clone(),
-    //         dimensions,
-    //         TexturePixelFormat::B8G8R8A8_SRGB,
-    //     )
-    //     .unwrap();
-    //     let result = Renderer {
-    //         _vk_surface: surface,
-    //         _aspect: dimensions[0] as f32 / dimensions[1] as f32,
-    //         _context: vk_instance,
-    //         _device: device.clone(),
-    //         _queue: queue.clone(),
-    //         _geometry_pass: GeometryPass::new(dimensions[0], dimensions[1], queue.clone()),
-    //         _super_resolution: super_resolution,
-    //         _fxaa: fxaa,
-    //         _need_to_update_sc: true,
-    //         _frame_finish_event: Some(sync::now(device.clone()).boxed()),
-    //         _draw_list: Vec::new(),
-    //         _lights_list: Vec::new(),
-
-    //         _camera: None,
-    //         _camera_data: ProjectionUniformData::default(),
-    //         _postprocessor: PostprocessingPass::new(queue.clone(), dimensions[0], dimensions[1]),
-    //         _shadowmap_pass: ShadowMapPass::new(queue.clone()),
-    //         _timer: Default::default(),
-
-    //         _dummy_texture_2d: Texture::new(
-    //             "dummy_2d",
-    //             [1, 1, 1],
-    //             false,
-    //             TextureViewType::Dim2d,
-    //             TexturePixelFormat::R8G8B8A8_UINT,
-    //             TextureUseCase::ReadOnly,
-    //             allocator.clone()
-    //         )
-    //         .unwrap(),
-    //         _dummy_shadowmap: Texture::new(
-    //             "dummy_2d",
-    //             [1, 1, 1],
-    //             false,
-    //             TextureViewType::Dim2d,
-    //             TexturePixelFormat::D16_UNORM,
-    //             TextureUseCase::ReadOnly,
-    //             allocator.clone()
-    //         )
-    //         .unwrap(),
-    //         _dummy_texture_cube: Texture::new(
-    //             "dummy_cube",
-    //             [1, 1, 6],
-    //             false,
-    //             TextureViewType::Dim2d,
-    //             TexturePixelFormat::D16_UNORM,
-    //             TextureUseCase::ReadOnly,
-    //             allocator.clone()
-    //         )
-    //         .unwrap(),
-
-    //         _allocator: allocator,
-    //         _command_buffer_father: command_buffer_father,
-    //         _ds_allocator: Arc::new(StandardDescriptorSetAllocator::new(device.clone(), StandardDescriptorSetAllocatorCreateInfo::default())),
-    //     };
-    //     result
-    // }
+    /// Рендер в текстуру без окна.
+    /// Результат каждого кадра доступен через [`Renderer::render_result`].
+    pub fn offscreen(
+        vk_instance: Arc<Instance>,
+        resource_manager: RcBox<ResourceManager>,
+        dimensions: [u16; 2],
+        super_resolution: bool,
+        fxaa: bool,
+    ) -> Result<Self, DsgeError> {
+        let allocator = resource_manager.lock().allocator().clone();
+        let surface = RenderSurface::offscreen(
+            allocator,
+            dimensions,
+            TexturePixelFormat::B8G8R8A8_SRGB,
+        )?;
+        Ok(Self::with_surface(
+            vk_instance,
+            resource_manager,
+            surface,
+            dimensions,
+            super_resolution,
+            fxaa,
+        ))
+    }
 
     pub fn winit(
         vk_instance: Arc<Instance>,
@@ -542,12 +659,31 @@ impl Renderer {
         vsync: bool,
         super_resolution: bool,
         fxaa: bool,
+    ) -> Self {
+        let device = resource_manager.lock().device().clone();
+        let surface = RenderSurface::winit(surface, device, vsync).unwrap();
+        Self::with_surface(
+            vk_instance,
+            resource_manager,
+            surface,
+            dimensions,
+            super_resolution,
+            fxaa,
+        )
+    }
+
+    fn with_surface(
+        vk_instance: Arc<Instance>,
+        resource_manager: RcBox<ResourceManager>,
+        surface: RenderSurface,
+        dimensions: [u16; 2],
+        super_resolution: bool,
+        fxaa: bool,
     ) -> Self {
         let _resource_manager = resource_manager.lock();
         let queue = _resource_manager.queue();
         let device = _resource_manager.device();
         let command_buffer_father = CommandBufferFather::new(queue.clone());
-        let surface = RenderSurface::winit(surface, device.clone(), vsync).unwrap();
 
         let shadowmap_pass = ShadowMapPass::new(queue.clone());
         let geometry_pass = GeometryPass::new(dimensions[0], dimensions[1], &_resource_manager);
@@ -583,6 +719,8 @@ impl Renderer {
             _frame_finish_event: Some(sync::now(device.clone()).boxed()),
 
             _draw_list: Vec::new(),
+            _draw_keys: HashMap::new(),
+            _spatial_index: SpatialIndex::default(),
             _lights_list: Vec::new(),
 
             _camera: None,
@@ -590,6 +728,8 @@ impl Renderer {
             _shadowmap_pass: shadowmap_pass,
             _super_resolution: super_resolution,
             _fxaa: fxaa,
+            _lod_settings: LodSettings::default(),
+            _lod_levels: LodLevels::default(),
             _geometry_pass: geometry_pass,
             _postprocessor: postprocessor,
             _timer: Default::default(),
@@ -603,11 +743,13 @@ impl Renderer {
             _allocator: Arc::new(bump_memory_allocator_new_default(device.clone())),
             _command_buffer_father: command_buffer_father,
             _ds_allocator: Arc::new(StandardDescriptorSetAllocator::new(device.clone(), Default::default())),
+            _cvars: None,
+            _postprocess_uniforms: BTreeMap::new(),
         };
         result
     }
 
-    fn resize(&mut self, width: u16, height: u16) -> Result<(), String>
+    fn resize(&mut self, width: u16, height: u16) -> Result<(), DsgeError>
     {
         let stsr = self._super_resolution;
         let gwidth = if stsr { width / 2 } else { width };
@@ -739,6 +881,7 @@ impl Renderer {
     /// Начинает проход геометрии
     pub fn begin_geametry_pass(&mut self) {
         self._draw_list.clear();
+        self._draw_keys.clear();
         self._lights_list.clear();
     }
 
@@ -748,7 +891,7 @@ impl Renderer {
         let owner_transform = owner.transform.clone();
         match owner.visual() {
             Some(visual) => {
-                self.add_renderable_component(owner.transform().clone(), Arc::new(visual.clone()));
+                self.add_renderable_component(obj.box_id(), owner.transform().clone(), Arc::new(visual.clone()));
             }
             None => (),
         }
@@ -855,6 +998,78 @@ impl Renderer {
         (light_compile_cb, LightsUniformData::new(spotlights, point_lights, sun_lights))
     }*/
 
+    pub fn lod_settings(&self) -> LodSettings {
+        self._lod_settings
+    }
+
+    /// Смещение и гистерезис выбора уровней детализации, см. [`MeshVisual::with_lods`]
+    pub fn set_lod_settings(&mut self, settings: LodSettings) {
+        self._lod_settings = settings;
+    }
+
+    /// Регистрирует переменные рендера `r_fxaa`, `r_super_resolution`,
+    /// `r_lod_bias`, `r_lod_hysteresis` и разрешения карт теней
+    /// `r_shadow_spot_size`, `r_shadow_sun_size`, `r_shadow_point_size`.
+    /// Изменения применяются в начале следующего [`Renderer::execute`].
+    pub fn register_cvars(&mut self, cvars: &Cvars) -> Result<(), DsgeError> {
+        self.render_cvars().register(cvars)?;
+        self._cvars = Some(cvars.clone());
+        self.apply_cvars();
+        Ok(())
+    }
+
+    /// Регистрирует переменную `pp_<uniform>`, значение которой каждый кадр
+    /// передаётся во все стадии постобработки как `uniform float <uniform>`.
+    pub fn register_postprocess_cvar(
+        &mut self,
+        cvars: &Cvars,
+        uniform: &str,
+        default: f32,
+        description: &str,
+    ) -> Result<(), DsgeError> {
+        cvars.register(&postprocess_cvar(uniform), default, description)?;
+        self._postprocess_uniforms.insert(uniform.to_owned(), default);
+        self._cvars = Some(cvars.clone());
+        self.apply_cvars();
+        Ok(())
+    }
+
+    fn render_cvars(&self) -> RenderCvars {
+        let resource_manager = self._resource_manager.lock();
+        RenderCvars {
+            fxaa: self._fxaa,
+            super_resolution: self._super_resolution,
+            lod_settings: self._lod_settings,
+            shadow_map_resolution: SHADOW_MAP_CVARS.map(|(_, light)| resource_manager.shadow_map_resolution(light)),
+            max_shadow_map_resolution: SHADOW_MAP_CVARS
+                .map(|(_, light)| resource_manager.max_shadow_map_resolution(light)),
+            postprocess: self._postprocess_uniforms.clone(),
+        }
+    }
+
+    fn apply_cvars(&mut self) {
+        let Some(cvars) = &self._cvars else { return };
+        let current = self.render_cvars();
+        let next = current.read(cvars);
+        if next == current {
+            return;
+        }
+        if next.fxaa != current.fxaa || next.super_resolution != current.super_resolution {
+            self._fxaa = next.fxaa;
+            self._super_resolution = next.super_resolution;
+            self._need_to_update_sc = true;
+        }
+        self._lod_settings = next.lod_settings;
+        let mut resource_manager = self._resource_manager.lock();
+        for ((_, light), resolution) in SHADOW_MAP_CVARS.into_iter().zip(next.shadow_map_resolution) {
+            if let Err(err) = resource_manager.set_shadow_map_resolution(light, resolution) {
+                log::error!(target: "renderer", "Failed to resize shadow maps: {err}");
+            }
+        }
+        drop(resource_manager);
+        self._postprocess_uniforms = next.postprocess;
+    }
+
     /// Выполняет все сформированные буферы команд
     pub fn execute(
         &mut self,
@@ -866,6 +1081,7 @@ impl Renderer {
             .unwrap()
             .cleanup_finished();
 
+        self.apply_cvars();
         if self._need_to_update_sc {
             self.update_swapchain(None);
             self._need_to_update_sc = false;
@@ -924,33 +1140,13 @@ impl Renderer {
         //let lights = lights[0..lights.len().min(16)].to_vec();
         //let lights = self._lights_list.clone();
 
+        // Объекты, не попавшие в кадр, убираются из индекса
+        let draw_keys = &self._draw_keys;
+        self._spatial_index.retain(|key| draw_keys.contains_key(key));
+
         // Проход карт теней
         let mut sm_command_buffers: Vec<Arc<PrimaryAutoCommandBuffer>> = Vec::new();
 
-        let static_objects = self
-            ._draw_list
-            .iter()
-            .filter_map(|(transform, mesh_visual)| {
-                if transform.is_static() {
-                    Some((transform.uniform_value(), mesh_visual.clone()))
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>();
-
-        let dynamic_objects = self
-            ._draw_list
-            .iter()
-            .filter_map(|(transform, mesh_visual)| {
-                if !transform.is_static() {
-                    Some((transform.uniform_value(), mesh_visual.clone()))
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>();
-
         for li in &mut lights {
             /*if let ShadowMapMode::None = li.shadow_map_mode {
                 li.framebuffers(owner_transform);
@@ -961,6 +1157,7 @@ impl Renderer {
             if li.refresh_flag {
                 let mut framebuffers = li.static_shadow_buffer.as_ref().unwrap().frame_buffers().clone();
                 for (projection_data, framebuffer) in li.projections.iter_mut().zip(framebuffers.iter_mut()) {
+                    let static_objects = self.visible_objects(self._spatial_index.static_tree(), projection_data);
                     let clear_pacb = self
                         ._command_buffer_father
                         .new_primary_instant(|pacbb| {
@@ -974,7 +1171,7 @@ impl Renderer {
                             framebuffer,
                             projection_data.clone(),
                             self._postprocessor.timer,
-                            static_objects.clone(),
+                            static_objects,
                             &self._command_buffer_father,
                             self._allocator.clone(),
                             self._ds_allocator.clone()
@@ -984,13 +1181,6 @@ impl Renderer {
                 }
             }
             // Обновление динамической карты теней.
-            let shadowmap_objects = match li.shadow_map_mode {
-                ShadowMapMode::FullyDynamic(_) => {
-                    [dynamic_objects.clone(), static_objects.clone()].concat()
-                }
-                ShadowMapMode::SemiDynamic(_) => dynamic_objects.clone(),
-                _ => Vec::new(),
-            };
             if let ShadowMapMode::None = li.shadow_map_mode {
                 continue;
             }
@@ -1018,13 +1208,24 @@ impl Renderer {
                 sm_command_buffers.push(pacb);
                 let mut frame_buffers = sb.frame_buffers().clone();
                 for (projection_data, shadow_framebuffer) in li.projections.iter_mut().zip(frame_buffers.iter_mut()) {
+                    let shadowmap_objects = match li.shadow_map_mode {
+                        ShadowMapMode::FullyDynamic(_) => [
+                            self.visible_objects(self._spatial_index.dynamic_tree(), projection_data),
+                            self.visible_objects(self._spatial_index.static_tree(), projection_data),
+                        ]
+                        .concat(),
+                        ShadowMapMode::SemiDynamic(_) => {
+                            self.visible_objects(self._spatial_index.dynamic_tree(), projection_data)
+                        }
+                        _ => Vec::new(),
+                    };
                     let command_buffer = self
                         ._shadowmap_pass
                         .build_shadow_map_pass(
                             shadow_framebuffer,
                             *projection_data,
                             self._postprocessor.timer,
-                            shadowmap_objects.clone(),
+                            shadowmap_objects,
                             &self._command_buffer_father,
                             self._allocator.clone(),
                             self._ds_allocator.clone()
@@ -1078,6 +1279,9 @@ impl Renderer {
         self._postprocessor.image_to_all("sun_shadowmaps", resource_manager.lock().sun_light_shadow_map_array());
         self._postprocessor.image_to_all("point_shadowmaps", resource_manager.lock().point_light_shadow_map_array());
         self._postprocessor.image_to_all("spot_shadowmaps", resource_manager.lock().spotlight_shadow_map_array());
+        for (name, value) in &self._postprocess_uniforms {
+            self._postprocessor.uniform_to_all(name, *value);
+        }
 
         // Сборка буфера с информацией об источниках света
         //let (light_compile_cb, _light_count) = self.build_lights_buffer();
@@ -1093,13 +1297,20 @@ impl Renderer {
             self._camera_data = component_camera.uniform_data(&*cam_obj);
         }
 
+        self.select_lods();
+
         // Построение прохода геометрии
+        let camera_objects = [
+            self.visible_objects(self._spatial_index.static_tree(), &self._camera_data),
+            self.visible_objects(self._spatial_index.dynamic_tree(), &self._camera_data),
+        ]
+        .concat();
         let gp_command_buffer = self
             ._geometry_pass
             .build_geometry_pass(
                 self._camera_data,
                 self._postprocessor.timer,
-                [static_objects, dynamic_objects].concat(),
+                camera_objects,
             )
             .unwrap();
 
@@ -1190,7 +1401,7 @@ impl Renderer {
                 self._frame_finish_event = Some(sync::now(self._device.clone()).boxed());
             }
             Err(e) => {
-                println!("Failed to flush future: {:?}", e);
+                log::error!(target: "renderer", "Failed to flush future: {:?}", e);
                 self._frame_finish_event = Some(sync::now(self._device.clone()).boxed());
             }
         };
@@ -1269,3 +1480,33 @@ impl Renderer {
         )
     }
 }
+
+#[test]
+fn render_cvars_follow_console() {
+    let current = RenderCvars {
+        fxaa: true,
+        super_resolution: false,
+        lod_settings: LodSettings::default(),
+        shadow_map_resolution: [512, 1024, 256],
+        max_shadow_map_resolution: [4096, 4096, 2048],
+        postprocess: BTreeMap::from([("exposure".to_owned(), 1.0)]),
+    };
+    let cvars = Cvars::new();
+    current.register(&cvars).unwrap();
+    cvars.register(&postprocess_cvar("exposure"), 1.0, "Exposure").unwrap();
+    assert_eq!(current.read(&cvars), current);
+
+    cvars.set("r_fxaa", false).unwrap();
+    cvars.set("r_shadow_sun_size", 2048).unwrap();
+    cvars.set("r_shadow_point_size", -1).unwrap();
+    cvars.set("pp_exposure", 2.5).unwrap();
+    let next = current.read(&cvars);
+    assert!(!next.fxaa);
+    assert_eq!(next.shadow_map_resolution, [512, 2048, 256]);
+    assert_eq!(next.postprocess["exposure"], 2.5);
+    assert_eq!(next.lod_settings, current.lod_settings);
+
+    cvars.set("r_shadow_sun_size", 100000).unwrap();
+    cvars.set("r_shadow_point_size", 3000).unwrap();
+    assert_eq!(current.read(&cvars).shadow_map_resolution, [512, 4096, 2048]);
+}
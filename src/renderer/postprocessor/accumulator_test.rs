@@ -1,3 +1,4 @@
+use crate::error::DsgeError;
 use super::PostprocessingPass;
 use super::StageIndex;
 use crate::texture::{TextureFilter, TexturePixelFormat, TextureView};
@@ -9,7 +10,7 @@ impl PostprocessingPass {
         width: u16,
         height: u16,
         sc_format: TexturePixelFormat,
-    ) -> Result<StageIndex, String> {
+    ) -> Result<StageIndex, DsgeError> {
         let mut stage_builder = Self::stage_builder(self._device.clone());
         stage_builder
             .dimenstions(width, height)
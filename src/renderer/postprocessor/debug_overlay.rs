@@ -1,3 +1,4 @@
+use crate::error::DsgeError;
 use super::PostprocessingPass;
 use super::StageIndex;
 use crate::components::light::{LightsUniformData, SpotlightUniform};
@@ -11,7 +12,7 @@ impl PostprocessingPass {
         width: u16,
         height: u16,
         sc_pix_fmt: TexturePixelFormat,
-    ) -> Result<StageIndex, String> {
+    ) -> Result<StageIndex, DsgeError> {
         let mut builder = Self::stage_builder(self.device().clone());
         builder
             .dimenstions(width, height)
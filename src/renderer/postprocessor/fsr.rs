@@ -1,3 +1,4 @@
+use crate::error::DsgeError;
 use super::PostprocessingPass;
 use super::{StageIndex, StageInputIndex, StageOutputIndex};
 use crate::texture::{TextureFilter, TexturePixelFormat, TextureView};
@@ -22,7 +23,7 @@ impl PostprocessingPass {
         Ok(((easu, "albedo".to_owned()), (rcas, 0)))
     }
 
-    fn make_easu_stage(&mut self, width: u16, height: u16) -> Result<StageIndex, String> {
+    fn make_easu_stage(&mut self, width: u16, height: u16) -> Result<StageIndex, DsgeError> {
         let mut stage_builder = Self::stage_builder(self.device().clone());
         stage_builder
             .dimenstions(width, height)
@@ -226,7 +227,7 @@ impl PostprocessingPass {
         stage_builder.build(self)
     }
 
-    fn make_rcas_stage(&mut self, width: u16, height: u16) -> Result<StageIndex, String> {
+    fn make_rcas_stage(&mut self, width: u16, height: u16) -> Result<StageIndex, DsgeError> {
         let mut stage_builder = Self::stage_builder(self.device().clone());
         stage_builder
             .dimenstions(width, height)
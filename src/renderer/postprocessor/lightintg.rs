@@ -1,3 +1,4 @@
+use crate::error::DsgeError;
 use super::PostprocessingPass;
 use super::StageIndex;
 use super::StageInputIndex;
@@ -39,7 +40,7 @@ pub struct ScreenSpaceReflections
 }
 
 impl PostprocessingPass {
-    fn lighting_filter_pass(&mut self, width: u16, height: u16) -> Result<StageIndex, String> {
+    fn lighting_filter_pass(&mut self, width: u16, height: u16) -> Result<StageIndex, DsgeError> {
         let mut stage_builder = Self::stage_builder(self.device().clone());
         stage_builder
             .dimenstions(width, height)
@@ -63,7 +64,7 @@ impl PostprocessingPass {
         max_sun_lights: u32,
         max_pointlights: u32,
         sc_format: TexturePixelFormat,
-    ) -> Result<DeferredLighting, String> {
+    ) -> Result<DeferredLighting, DsgeError> {
         let mut stage_builder = Self::stage_builder(self.device().clone());
         stage_builder
             .dimenstions(width, height)
@@ -113,7 +114,7 @@ impl PostprocessingPass {
         &mut self,
         width: u16,
         height: u16,
-    ) -> Result<ScreenSpaceReflections, String> {
+    ) -> Result<ScreenSpaceReflections, DsgeError> {
         let mut stage_builder = Self::stage_builder(self.device().clone());
         stage_builder
             .dimenstions(width, height)
@@ -146,7 +147,7 @@ impl PostprocessingPass {
         &mut self,
         width: u16,
         height: u16,
-    ) -> Result<Composer, String>
+    ) -> Result<Composer, DsgeError>
     {
         let mut stage_builder = Self::stage_builder(self.device().clone());
         stage_builder
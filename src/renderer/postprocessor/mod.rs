@@ -1,3 +1,4 @@
+use crate::error::DsgeError;
 use crate::command_buffer::{CommandBufferFather, PrimaryCommandBufferAssembler};
 use crate::framebuffer::*;
 use crate::mesh::{Mesh, MeshCommandSet, MeshRef};
@@ -222,7 +223,7 @@ impl RenderStage {
             .shift_stack(new_buff)
     }
 
-    fn attach_image(&mut self, name: &str, image: &Texture) -> Result<(), String>
+    fn attach_image(&mut self, name: &str, image: &Texture) -> Result<(), DsgeError>
     {
         if let Some(filter) = self._input_filters.get(name) {
             let mut image = image.clone();
@@ -328,7 +329,7 @@ impl RenderStageBuilder {
     pub fn build(
         mut self,
         pp_graph: &mut PostprocessingPass
-    ) -> Result<StageIndex, String> {
+    ) -> Result<StageIndex, DsgeError> {
         let queue = pp_graph._command_buffer_father.queue().clone();
         let device = queue.device().clone();
         let mut program = ShaderProgram::builder();
@@ -736,7 +737,7 @@ impl PostprocessingPass {
         stack_index: Option<usize>,
         to: StageIndex,
         input: StageInputIndex,
-    ) -> Result<(), String> {
+    ) -> Result<(), DsgeError> {
         let stack_index = match stack_index {
             Some(index) => index,
             None => 0,
@@ -755,7 +756,7 @@ impl PostprocessingPass {
         };
         let links = [self._links.as_slice(), &[link.clone()]].concat();
         if self.check_loop(&links, from, from) || self.check_loop(&links, to, to) {
-            Err("Обнаружена петля без стековых буферов.".to_owned())
+            Err(DsgeError::LayoutMismatch("Обнаружена петля без стековых буферов.".to_owned()))
         } else {
             self._links = links;
             Ok(())
@@ -1062,7 +1063,7 @@ impl PostprocessingPass {
     }
 
     /// Стандартный вершинный шейдер для фильтров постобработки
-    fn vertex_plane_shader(&self) -> Result<Shader, String> {
+    fn vertex_plane_shader(&self) -> Result<Shader, DsgeError> {
         let mut shader = Shader::builder(ShaderType::Vertex, self.device().clone());
         shader
             .default_vertex_attributes()
@@ -1,3 +1,4 @@
+use crate::error::DsgeError;
 use super::PostprocessingPass;
 use super::StageIndex;
 use crate::texture::{TextureFilter, TexturePixelFormat, TextureView};
@@ -11,7 +12,7 @@ impl PostprocessingPass {
         input_name: &str,
         count: u8,
         pix_fmt: TexturePixelFormat,
-    ) -> Result<StageIndex, String> {
+    ) -> Result<StageIndex, DsgeError> {
         let mut stage_builder = Self::stage_builder(self.device().clone());
         let comps = pix_fmt.components();
         let mut swizzle = String::with_capacity(8);
@@ -1,3 +1,4 @@
+use crate::error::DsgeError;
 use super::{PostprocessingPass, StageInputIndex, StageOutputIndex, StageIndex};
 use crate::components::visual::ProjectionUniformData;
 use crate::texture::{TexturePixelFormat, TextureFilter, TextureView};
@@ -12,7 +13,7 @@ pub struct TemporalDeoiser {
 impl PostprocessingPass
 {
     pub fn new_temporal_denoiser(&mut self, width: u16, height: u16, sc_pix_fmt: TexturePixelFormat, filtering: TextureFilter)
-        -> Result<TemporalDeoiser, String>
+        -> Result<TemporalDeoiser, DsgeError>
     {
         let mut denoiser_builder = Self::stage_builder(self._device.clone());
         denoiser_builder
@@ -1,3 +1,4 @@
+use crate::error::DsgeError;
 use super::{PostprocessingPass, StageIndex, StageInputIndex, StageOutputIndex};
 use crate::components::visual::ProjectionUniformData;
 use crate::shader::ShaderUniformArrayLength;
@@ -17,7 +18,7 @@ pub struct FxaaFilter {
 
 #[allow(dead_code)]
 impl PostprocessingPass {
-    pub fn new_fxaa(&mut self, width: u16, height: u16) -> Result<FxaaFilter, String> {
+    pub fn new_fxaa(&mut self, width: u16, height: u16) -> Result<FxaaFilter, DsgeError> {
         let mut node = Self::stage_builder(self.device().clone());
         node.dimenstions(width, height)
             .input("orig", TextureView::Dim2d, TextureFilter::Nearest, false)
@@ -49,7 +50,7 @@ impl PostprocessingPass {
         width: u16,
         height: u16,
         super_resolution: bool,
-    ) -> Result<TemporalDeoiser, String> {
+    ) -> Result<TemporalDeoiser, DsgeError> {
         let mut denoiser_builder = Self::stage_builder(self.device().clone());
         denoiser_builder
             .dimenstions(width, height)
@@ -1,3 +1,4 @@
+use crate::error::DsgeError;
 use super::PostprocessingPass;
 use super::StageIndex;
 use crate::texture::{TextureFilter, TexturePixelFormat, TextureView};
@@ -9,7 +10,7 @@ impl PostprocessingPass {
         width: u16,
         height: u16,
         sc_pix_fmt: TexturePixelFormat,
-    ) -> Result<StageIndex, String> {
+    ) -> Result<StageIndex, DsgeError> {
         let mut flipper_builder = Self::stage_builder(self.device().clone());
         let mut swizzle = "r".to_owned();
         if sc_pix_fmt.components()[1] != 0 {
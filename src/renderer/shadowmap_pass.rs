@@ -1,3 +1,4 @@
+use crate::error::DsgeError;
 use std::sync::Arc;
 
 use vulkano::command_buffer::PrimaryAutoCommandBuffer;
@@ -63,7 +64,7 @@ impl ShadowMapPass {
         command_buffer_father: &CommandBufferFather,
         allocator: Arc<BumpMemoryAllocator>,
         ds_allocator: Arc<StandardDescriptorSetAllocator>
-    ) -> Result<Arc<PrimaryAutoCommandBuffer>, String> {
+    ) -> Result<Arc<PrimaryAutoCommandBuffer>, DsgeError> {
         build_geometry_pass(
             shadow_map,
             light_projection_data,
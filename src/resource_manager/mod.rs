@@ -1,5 +1,7 @@
+use crate::error::DsgeError;
 use std::{collections::HashMap, path::Path, sync::Arc};
 
+use serde::{Deserialize, Serialize};
 use vulkano::{
     device::{Device, Queue},
     sync::GpuFuture, memory::allocator::StandardMemoryAllocator, image::sampler::SamplerMipmapMode,
@@ -9,7 +11,7 @@ use crate::{
     command_buffer::CommandBufferFather,
     components::light::{ShadowBuffer, LightType},
     material::{MaterialBuilder, MaterialRef},
-    mesh::{Mesh, MeshRef, SubMesh},
+    mesh::{bvh::MeshBvh, obj, Mesh, MeshRef, SubMesh},
     references::{MutexLockBox, RcBox},
     texture::{
         Texture, TextureRepeatMode, TexturePixelFormat, TextureFilter,
@@ -21,19 +23,19 @@ pub const MAX_SPOTLIGHTS: u32 = 8;
 pub const MAX_POINT_LIGHTS: u32 = 4;
 pub const MAX_SUN_LIGHTS: u32 = 2;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ResourceManagerConfig {
     pub max_spotlights: u32,
     pub max_sun_lights: u32,
     pub max_point_lights: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub anisotrophy: Option<f32>,
-    pub super_resolution: bool,
 }
 
 impl Default for ResourceManagerConfig {
     fn default() -> Self {
         Self {
-            super_resolution: false,
             anisotrophy: None,
             max_spotlights: MAX_SPOTLIGHTS,
             max_sun_lights: MAX_SUN_LIGHTS,
@@ -61,12 +63,14 @@ pub struct ResourceManager {
     materials: HashMap<String, MaterialRef>,
     textures: HashMap<String, Texture>,
     meshes: HashMap<String, MeshRef>,
+    mesh_bvhs: HashMap<String, Arc<MeshBvh>>,
 
     point_shadowmaps: DynamicShadowMapManager,
     spot_shadowmaps: DynamicShadowMapManager,
     sun_shadowmaps: DynamicShadowMapManager,
 
     config: ResourceManagerConfig,
+    super_resolution: bool,
 
     futures: Vec<Box<dyn GpuFuture>>,
 }
@@ -78,7 +82,8 @@ impl ResourceManager {
         device: Arc<Device>,
         queue: Arc<Queue>,
         config: ResourceManagerConfig,
-    ) -> Result<Self, String> {
+        super_resolution: bool,
+    ) -> Result<Self, DsgeError> {
         let allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
         let command_buffer_father = CommandBufferFather::new(queue.clone());
         let (dt, _) = Texture::from_data(
@@ -113,7 +118,7 @@ impl ResourceManager {
             .add_numeric_parameter("use_emission_map", 0.into())
             .add_numeric_parameter("blend_method", 0.into())
             .add_numeric_parameter("shadow_method", 0.into());
-        let material = material.build_mutex(device.clone(), config.super_resolution);
+        let material = material.build_mutex(device.clone(), super_resolution);
 
         Ok(Self {
             device: device.clone(),
@@ -125,12 +130,14 @@ impl ResourceManager {
             textures_path: "textures".to_owned(),
             meshes_path: "mesh".to_owned(),
             config,
+            super_resolution,
 
             default_material: material,
 
             materials: HashMap::new(),
             textures: HashMap::new(),
             meshes: HashMap::new(),
+            mesh_bvhs: HashMap::new(),
             
             point_shadowmaps: DynamicShadowMapManager::new(
                 device.clone(),
@@ -140,7 +147,7 @@ impl ResourceManager {
                 config.max_point_lights,
                 TexturePixelFormat::D16_UNORM,
                 true,
-            ),
+            )?,
             spot_shadowmaps: DynamicShadowMapManager::new(
                 device.clone(),
                 queue.clone(),
@@ -149,7 +156,7 @@ impl ResourceManager {
                 config.max_spotlights,
                 TexturePixelFormat::D16_UNORM,
                 false,
-            ),
+            )?,
             sun_shadowmaps: DynamicShadowMapManager::new(
                 device.clone(),
                 queue.clone(),
@@ -158,7 +165,7 @@ impl ResourceManager {
                 config.max_sun_lights,
                 TexturePixelFormat::D16_UNORM,
                 false,
-            ),
+            )?,
 
             futures: Vec::new(),
         })
@@ -186,6 +193,11 @@ impl ResourceManager {
         &self.config
     }
 
+    /// Материалы собираются для рендера с повышением разрешения
+    pub fn super_resolution(&self) -> bool {
+        self.super_resolution
+    }
+
     pub fn new_material(&mut self, name: &str) -> MaterialRef {
         let mat = RcBox::construct(self.default_material.lock().fork(name));
         self.materials.insert(name.to_owned(), mat.clone());
@@ -196,20 +208,26 @@ impl ResourceManager {
         self.materials.get(name).cloned()
     }
 
-    pub fn get_texture(&mut self, name: &str) -> Result<Texture, String> {
+    pub fn get_texture(&mut self, name: &str) -> Result<Texture, DsgeError> {
         // let _name = name.replace(".jpg", ".dds").replace(".png", ".dds");
         // let name = _name.as_str();
-        if let Some(texture) = self.textures.get(name) {
-            return Ok(texture.clone());
-        }
         let fname = Path::new(self.fs_path.as_str())
             .join(self.textures_path.as_str())
             .join(name);
+        self.load_texture(name, &fname)
+    }
+
+    /// Загрузка текстуры из файла `fname`, `name` - ключ в кэше текстур
+    fn load_texture(&mut self, name: &str, fname: &Path) -> Result<Texture, DsgeError> {
+        if let Some(texture) = self.textures.get(name) {
+            return Ok(texture.clone());
+        }
         if !fname.is_file() {
-            return Err(format!("Файл {fname:?} не найден."));
+            return Err(DsgeError::AssetNotFound(format!("{fname:?}")));
         }
         let fname = fname.as_os_str().to_str().unwrap();
-        let (mut texture, future) = Texture::from_file(&self.command_buffer_father, self.allocator.clone(), fname, true, true).unwrap();
+        log::debug!(target: "resource_manager", "Loading texture {fname}");
+        let (mut texture, future) = Texture::from_file(&self.command_buffer_father, self.allocator.clone(), fname, true, true)?;
         texture.set_mipmap_mode(SamplerMipmapMode::Linear);
         texture.set_mag_filter(TextureFilter::Linear);
         texture.set_min_filter(TextureFilter::Linear);
@@ -219,6 +237,84 @@ impl ResourceManager {
         Ok(texture)
     }
 
+    /// Создание материалов из MTL-файла.
+    /// Пути к текстурам отсчитываются от каталога MTL-файла. Если текстура
+    /// не загрузилась, материал остаётся без неё.
+    pub fn load_mtl<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<MaterialRef>, DsgeError> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|err| DsgeError::open(path, err))?;
+        let mtl = obj::parse_mtl(&source).map_err(|err| err.context(path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut materials = Vec::with_capacity(mtl.len());
+        for desc in mtl {
+            log::debug!(target: "resource_manager", "Creating material {} from {path:?}", desc.name);
+            let mut load_map = |map: &Option<String>| {
+                let fname = dir.join(map.as_ref()?);
+                let texture = self.load_texture(&fname.to_string_lossy(), &fname);
+                if let Err(ref err) = texture {
+                    log::warn!(target: "resource_manager", "Material {}: {err}", desc.name);
+                }
+                texture.ok()
+            };
+            let diffuse_map = load_map(&desc.diffuse_map);
+            let normal_map = load_map(&desc.normal_map);
+
+            let material = self.new_material(&desc.name);
+            {
+                let mut mat = material.lock();
+                mat.set_parameter("diffuse", desc.diffuse.into())?;
+                mat.set_parameter("roughness", desc.roughness.into())?;
+                mat.set_parameter("use_diffuse_map", (diffuse_map.is_some() as i32).into())?;
+                if let Some(texture) = diffuse_map {
+                    mat.replace_texture("fDiffuseMap", &texture)?;
+                }
+                mat.set_parameter("use_normal_map", (normal_map.is_some() as i32).into())?;
+                if let Some(texture) = normal_map {
+                    mat.replace_texture("fNornalMap", &texture)?;
+                }
+            }
+            materials.push(material);
+        }
+        Ok(materials)
+    }
+
+    /// Загрузка OBJ-файла из каталога мешей вместе с материалами из его MTL-файлов.
+    /// Каждая группа становится подмешем с именем `файл:группа` и материалом
+    /// из `usemtl`, если он нашёлся.
+    pub fn get_obj(&mut self, name: &str) -> Result<Vec<(MeshRef, Option<MaterialRef>)>, DsgeError> {
+        let fname = Path::new(self.fs_path.as_str())
+            .join(self.meshes_path.as_str())
+            .join(name);
+        log::debug!(target: "resource_manager", "Loading OBJ {fname:?}");
+        let mut builder = Mesh::builder(name);
+        let model = builder.push_obj(&fname.to_string_lossy())?;
+        let dir = fname.parent().unwrap_or(Path::new(""));
+        for lib in &model.material_libs {
+            if let Err(err) = self.load_mtl(dir.join(lib)) {
+                log::warn!(target: "resource_manager", "{name}: failed to load {lib}: {err}");
+            }
+        }
+        let mesh = builder.build(&self.command_buffer_father, self.allocator.clone())?;
+
+        let mut result = Vec::with_capacity(model.groups.len());
+        for group in &model.groups {
+            let mut key = format!("{name}:{}", group.name);
+            let mut n = 1;
+            while result.iter().any(|(mesh, _): &(MeshRef, _)| *mesh.name() == key) {
+                n += 1;
+                key = format!("{name}:{}.{n}", group.name);
+            }
+            let submesh = SubMesh::from_mesh(key.clone(), &mesh, group.bbox, group.base_index, group.index_count, 0);
+            self.insert_mesh(key, submesh.clone());
+            let material = group.material.as_ref().and_then(|name| self.get_material(name));
+            if let (Some(material), None) = (&group.material, &material) {
+                log::warn!(target: "resource_manager", "{name}: material {material} not found");
+            }
+            result.push((submesh, material));
+        }
+        Ok(result)
+    }
+
     pub fn get_mesh(&mut self, name: &str) -> Option<MeshRef> {
         if let Some(mesh) = self.meshes.get(name) {
             return Some(mesh.clone());
@@ -230,16 +326,44 @@ impl ResourceManager {
             return None;
         }
         let fname = fname.as_os_str().to_str().unwrap();
+        log::debug!(target: "resource_manager", "Loading mesh {fname}");
         let mut mesh = Mesh::builder(name);
-        mesh.push_from_file(fname).unwrap();
+        if let Err(err) = mesh.push_from_file(fname) {
+            log::error!(target: "resource_manager", "Failed to load mesh {name}: {err}");
+            return None;
+        }
         let mesh = mesh.build_mutex(
             &self.command_buffer_father,
             self.allocator.clone()
         ).unwrap();
-        self.meshes.insert(name.to_owned(), mesh.clone());
+        self.insert_mesh(name.to_owned(), mesh.clone());
         Some(mesh)
     }
 
+    /// Добавляет или заменяет меш, построенный для прежнего меша BVH сбрасывается
+    fn insert_mesh(&mut self, name: String, mesh: MeshRef) {
+        self.mesh_bvhs.remove(&name);
+        self.meshes.insert(name, mesh);
+    }
+
+    /// BVH меша, строится при первом запросе и кешируется.
+    /// `None`, если меш не найден или его копии на CPU не сохранены.
+    pub fn get_mesh_bvh(&mut self, name: &str) -> Option<Arc<MeshBvh>> {
+        if let Some(bvh) = self.mesh_bvhs.get(name) {
+            return Some(bvh.clone());
+        }
+        let mesh = self.get_mesh(name)?;
+        let bvh = Arc::new(MeshBvh::from_mesh(&*mesh)?);
+        log::debug!(
+            target: "resource_manager",
+            "Built BVH for mesh {name}: {} triangles, {} nodes",
+            bvh.triangle_count(),
+            bvh.node_count()
+        );
+        self.mesh_bvhs.insert(name.to_owned(), bvh.clone());
+        Some(bvh)
+    }
+
     pub fn get_batch_of_meshes(&mut self, names: &[String]) -> HashMap<String, MeshRef> {
         let mesh_path = Path::new(self.fs_path.as_str()).join(self.meshes_path.as_str());
         let unloaded = names
@@ -261,25 +385,26 @@ impl ResourceManager {
             if !fname.is_file() {
                 panic!("Файл {fname:?} не найден.");
             }
-            let submesh = mesh_builder
-                .push_from_file(fname.to_str().unwrap())
-                .unwrap();
-            submeshes.insert(name, submesh);
+            match mesh_builder.push_from_file(fname.to_str().unwrap()) {
+                Ok(submesh) => {
+                    submeshes.insert(name, submesh);
+                }
+                Err(err) => log::error!(target: "resource_manager", "Failed to load mesh {name}: {err}"),
+            }
         }
-        println!("Сборка буфера полигональных сеток");
+        log::debug!(target: "resource_manager", "Building shared buffer for {} meshes", unloaded.len());
         let mesh_buffer = mesh_builder.build(&self.command_buffer_father, self.allocator.clone()).unwrap();
-        println!("Сборка буфера завершена");
+        log::debug!(target: "resource_manager", "Shared mesh buffer built");
 
-        for name in &unloaded {
-            let (base, count, bbox) = submeshes[name];
+        for (name, (base, count, bbox)) in submeshes {
             let submesh =
                 SubMesh::from_mesh((*name).to_owned(), &mesh_buffer, bbox, base, count, 0);
-            self.meshes.insert((*name).to_owned(), submesh);
+            self.insert_mesh((*name).to_owned(), submesh);
         }
 
         names
-            .into_iter()
-            .map(|e| (e.clone(), self.meshes[e].clone()))
+            .iter()
+            .filter_map(|e| Some((e.clone(), self.meshes.get(e)?.clone())))
             .collect()
     }
 
@@ -310,13 +435,65 @@ impl ResourceManager {
         &self.point_shadowmaps.data
     }
 
+    /// Разрешение динамических карт теней для источников света данного типа
+    pub fn shadow_map_resolution(&self, light: LightType) -> u32 {
+        let shadow_buffer_pool = match light {
+            LightType::Spot => &self.spot_shadowmaps,
+            LightType::Sun => &self.sun_shadowmaps,
+            LightType::Point => &self.point_shadowmaps,
+        };
+        shadow_buffer_pool.data.dims()[0]
+    }
+
+    /// Наибольшее разрешение карт теней для источников света данного типа,
+    /// которое поддерживает устройство
+    pub fn max_shadow_map_resolution(&self, light: LightType) -> u32 {
+        let limits = self.device.physical_device().properties();
+        match light {
+            LightType::Point => limits.max_image_dimension_cube,
+            LightType::Spot | LightType::Sun => limits.max_image_dimension2_d,
+        }
+    }
+
+    /// Пересоздаёт динамические карты теней для источников света данного типа.
+    /// Выданные ранее буферы теней становятся недействительными,
+    /// поэтому вызывать можно только между кадрами.
+    /// При ошибке остаются прежние карты теней.
+    pub fn set_shadow_map_resolution(&mut self, light: LightType, resolution: u32) -> Result<(), DsgeError> {
+        let max_resolution = self.max_shadow_map_resolution(light);
+        if resolution == 0 || resolution > max_resolution {
+            return Err(DsgeError::InvalidArgument(format!(
+                "shadow map resolution {resolution} for {light:?} lights is out of range 1..={max_resolution}"
+            )));
+        }
+        let shadow_buffer_pool = match light {
+            LightType::Spot => &mut self.spot_shadowmaps,
+            LightType::Sun => &mut self.sun_shadowmaps,
+            LightType::Point => &mut self.point_shadowmaps,
+        };
+        if shadow_buffer_pool.data.dims()[0] == resolution {
+            return Ok(());
+        }
+        shadow_buffer_pool.flush_futures();
+        *shadow_buffer_pool = DynamicShadowMapManager::new(
+            self.device.clone(),
+            self.queue.clone(),
+            resolution,
+            resolution,
+            shadow_buffer_pool.layers(),
+            shadow_buffer_pool.data.pix_fmt(),
+            shadow_buffer_pool.cube,
+        )?;
+        Ok(())
+    }
+
     pub fn free_all_shadow_buffers(&mut self) {
         self.spot_shadowmaps.free_all();
         self.point_shadowmaps.free_all();
         self.sun_shadowmaps.free_all();
     }
 
-    /*pub fn attach_shadow_buffer(&mut self, light: &mut Light) -> Result<(), String>
+    /*pub fn attach_shadow_buffer(&mut self, light: &mut Light) -> Result<(), DsgeError>
     {
         match light.shadow_map_mode() {
             crate::components::light::ShadowMapMode::Static(_) |
@@ -344,7 +521,7 @@ impl ResourceManager {
                 false
             },
             Light::Spot (spot) => {
-                println!("detach_shadow_buffer {:?}", spot.dynamic_shadow_buffer().is_some());
+                log::trace!(target: "resource_manager", "Detaching spotlight shadow buffer: {:?}", spot.dynamic_shadow_buffer().is_some());
                 if let Some(sb) = spot.dynamic_shadow_buffer() {
                     self.spot_shadowmaps.free(sb.buffer().image_view().subresource_range().array_layers.start);
                     true
@@ -388,7 +565,7 @@ impl DynamicShadowMapManager {
         array_layers: u32,
         pix_fmt: TexturePixelFormat,
         cube: bool,
-    ) -> Self {
+    ) -> Result<Self, DsgeError> {
         debug_assert!(
             pix_fmt.is_depth(),
             "Буфер глубины не может иметь формат {pix_fmt:?}"
@@ -400,7 +577,7 @@ impl DynamicShadowMapManager {
         } else {
             array_layers
         };
-        println!("Dynamic shadow buffer {width}, {height}, {cube_array_layers}\n");
+        log::debug!(target: "resource_manager", "Dynamic shadow buffer {width}x{height}, {cube_array_layers} layers");
         let mut data = Texture::new(
             "DynamicShadowMapManager",
             [width, height, cube_array_layers],
@@ -409,17 +586,16 @@ impl DynamicShadowMapManager {
             pix_fmt,
             TextureUseCase::Attachment,
             allocator.clone(),
-        )
-        .unwrap();
+        )?;
         data.set_address_mode([TextureRepeatMode::ClampToBorder, TextureRepeatMode::ClampToBorder, TextureRepeatMode::ClampToBorder]);
-        data.clear_depth_stencil(&command_buffer_father, 1.0.into()).unwrap();
+        data.clear_depth_stencil(&command_buffer_father, 1.0.into())?;
         let free_layers = (0..array_layers).collect();
-        Self {
+        Ok(Self {
             data: data,
             free_layers,
             cube: cube,
             future: None,
-        }
+        })
     }
 
     fn get(&mut self) -> Option<(ShadowBuffer, u32)> {
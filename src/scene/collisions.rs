@@ -0,0 +1,238 @@
+//! Столкновения коллайдеров сцены и события объёмов-триггеров.
+//!
+//! Пары ищутся по габаритам коллайдеров в отдельном [`SpatialIndex`],
+//! статичные объекты между собой не проверяются.
+use std::collections::{HashMap, HashSet};
+
+use super::spatial::SpatialIndex;
+use super::Scene;
+use crate::components::collider::Collider;
+use crate::game_logic::{AbstractEvent, TriggerEvent};
+use crate::game_object::GameObjectRef;
+use crate::mesh::BoundingBox;
+use crate::physics::{self, Contact};
+use crate::references::MutexLockBox;
+use crate::types::Mat4;
+
+/// Столкновение двух твёрдых коллайдеров за последний шаг сцены.
+/// Нормали контактов направлены от `a` к `b`.
+#[derive(Clone)]
+pub struct Collision {
+    pub a: GameObjectRef,
+    pub b: GameObjectRef,
+    pub contacts: Vec<Contact>,
+}
+
+impl Collision {
+    /// То же столкновение с `a` и `b`, поменянными местами
+    pub fn flipped(&self) -> Self {
+        Self {
+            a: self.b.clone(),
+            b: self.a.clone(),
+            contacts: self.contacts.iter().map(Contact::flipped).collect(),
+        }
+    }
+}
+
+struct ColliderState {
+    object: GameObjectRef,
+    collider: Collider,
+    global: Mat4,
+    layers: u32,
+    is_static: bool,
+}
+
+#[derive(Default)]
+pub(super) struct SceneCollisions {
+    index: SpatialIndex<i32>,
+    collisions: Vec<Collision>,
+    /// Пересечения триггеров на последнем шаге
+    triggers: HashSet<TriggerEvent>,
+}
+
+impl Scene {
+    /// Столкновения за последний шаг сцены
+    pub fn collisions(&self) -> &[Collision] {
+        &self.collision_state.collisions
+    }
+
+    /// Столкновения объекта за последний шаг, объект в них всегда `a`
+    pub fn collisions_of(&self, object: &GameObjectRef) -> Vec<Collision> {
+        let id = object.box_id();
+        self.collision_state
+            .collisions
+            .iter()
+            .filter_map(|collision| {
+                if collision.a.box_id() == id {
+                    Some(collision.clone())
+                } else if collision.b.box_id() == id {
+                    Some(collision.flipped())
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Объект сцены по [`MutexLockBox::box_id`], например из [`TriggerEvent`]
+    pub fn object_by_id(&self, id: i32) -> Option<GameObjectRef> {
+        self.spatial_index.objects.get(&id).cloned()
+    }
+
+    /// Объекты с коллайдерами из слоёв `layers`, рамки которых пересекают `bbox`.
+    /// Индекс коллайдеров обновляется в [`Scene::step`].
+    pub fn colliders_in_bbox(&self, bbox: &BoundingBox, layers: u32) -> Vec<GameObjectRef> {
+        let mut keys = self.collision_state.index.query_bbox(bbox);
+        keys.sort_unstable();
+        self.spatial_objects(keys, layers)
+    }
+
+    /// Ищет столкновения объектов из пространственного индекса
+    /// и отправляет события триггеров. Вызывается из [`Scene::step`].
+    pub(super) fn update_collisions(&mut self) {
+        let mut states = HashMap::new();
+        for (key, object) in &self.spatial_index.objects {
+            let obj = object.lock();
+            if let Some(collider) = obj.collider() {
+                let state = ColliderState {
+                    object: object.clone(),
+                    collider: collider.clone(),
+                    global: obj.transform().global,
+                    layers: obj.layers(),
+                    is_static: obj.is_static(),
+                };
+                states.insert(*key, state);
+            }
+        }
+        let state = &mut self.collision_state;
+        for (key, collider) in &states {
+            if !collider.is_static || state.index.is_static(key) != Some(true) {
+                let bbox = collider.collider.world_bbox(&collider.global);
+                state.index.update(*key, bbox, collider.is_static);
+            }
+        }
+        state.index.retain(|key| states.contains_key(key));
+
+        let mut pairs = HashSet::new();
+        for (key, collider) in states.iter().filter(|(_, collider)| !collider.is_static) {
+            let bbox = collider.collider.world_bbox(&collider.global);
+            for other in state.index.query_bbox(&bbox) {
+                if other != *key {
+                    pairs.insert((other.min(*key), other.max(*key)));
+                }
+            }
+        }
+        let mut pairs = pairs.into_iter().collect::<Vec<_>>();
+        pairs.sort_unstable();
+
+        let mut collisions = Vec::new();
+        let mut triggers = HashSet::new();
+        for (a, b) in pairs {
+            let (first, second) = (&states[&a], &states[&b]);
+            if first.layers & second.collider.collision_mask() == 0
+                || second.layers & first.collider.collision_mask() == 0
+            {
+                continue;
+            }
+            let contacts = physics::collide(&first.collider, &first.global, &second.collider, &second.global);
+            if contacts.is_empty() {
+                continue;
+            }
+            if first.collider.is_trigger() {
+                triggers.insert(TriggerEvent { trigger: a, other: b });
+            }
+            if second.collider.is_trigger() {
+                triggers.insert(TriggerEvent { trigger: b, other: a });
+            }
+            if !first.collider.is_trigger() && !second.collider.is_trigger() {
+                collisions.push(Collision {
+                    a: first.object.clone(),
+                    b: second.object.clone(),
+                    contacts,
+                });
+            }
+        }
+
+        let mut events = triggers
+            .iter()
+            .map(|event| match state.triggers.contains(event) {
+                true => AbstractEvent::TriggerStay(*event),
+                false => AbstractEvent::TriggerEnter(*event),
+            })
+            .chain(state.triggers.difference(&triggers).map(|event| AbstractEvent::TriggerExit(*event)))
+            .collect::<Vec<_>>();
+        events.sort_by_key(|event| event.recipients());
+        for event in events {
+            self.event_processor.send_event(event);
+        }
+        state.collisions = collisions;
+        state.triggers = triggers;
+    }
+}
+
+#[test]
+fn scene_collisions_and_triggers() {
+    use crate::components::collider::ColliderShape;
+    use crate::game_object::GameObject;
+    use crate::types::Vec3;
+    let scene = Scene::new();
+    let place = |name: &str, collider: Collider, location: Vec3, is_static: bool| {
+        let object = GameObject::new(name);
+        let mut obj = object.lock();
+        obj.add_component(collider);
+        obj.transform.local = Mat4::new_translation(&location);
+        obj.set_static(is_static);
+        drop(obj);
+        scene.lock().add_object(object.clone()).unwrap();
+        object
+    };
+    let sphere = ColliderShape::Sphere { radius: 1.0 };
+    let ball = place("ball", Collider::new(sphere.clone()), Vec3::new(0.0, 0.0, 0.0), false);
+    let wall = place("wall", Collider::new(sphere.clone()), Vec3::new(1.5, 0.0, 0.0), true);
+    let zone = place("zone", Collider::trigger(sphere.clone()), Vec3::new(-5.0, 0.0, 0.0), true);
+    place("rock", Collider::new(sphere), Vec3::new(2.0, 1.0, 0.0), true);
+
+    let mut scene = scene.lock();
+    let trigger_events = |scene: &mut Scene| {
+        scene.step();
+        let mut events = Vec::new();
+        let mut stack = scene.event_processor.event_stack.lock();
+        for event in stack.drain(..) {
+            match event {
+                AbstractEvent::TriggerEnter(event) => events.push(("enter", event)),
+                AbstractEvent::TriggerStay(event) => events.push(("stay", event)),
+                AbstractEvent::TriggerExit(event) => events.push(("exit", event)),
+                _ => (),
+            }
+        }
+        events
+    };
+
+    // Статичные объекты друг с другом не проверяются
+    assert!(trigger_events(&mut scene).is_empty());
+    assert_eq!(scene.collisions().len(), 1);
+    let collision = &scene.collisions_of(&ball)[0];
+    assert_eq!(collision.b.box_id(), wall.box_id());
+    assert!((collision.contacts[0].normal - Vec3::x()).norm() < 1.0e-5);
+    assert!((scene.collisions_of(&wall)[0].contacts[0].normal + Vec3::x()).norm() < 1.0e-5);
+
+    let event = TriggerEvent {
+        trigger: zone.box_id(),
+        other: ball.box_id(),
+    };
+    assert_eq!(event.counterpart(&zone), ball.box_id());
+    assert_eq!(scene.object_by_id(event.trigger).unwrap().box_id(), zone.box_id());
+    let move_ball = |x: f32| ball.lock().transform.local = Mat4::new_translation(&Vec3::new(x, 0.0, 0.0));
+    move_ball(-4.0);
+    assert_eq!(trigger_events(&mut scene), [("enter", event)]);
+    assert!(scene.collisions().is_empty());
+    assert_eq!(trigger_events(&mut scene), [("stay", event)]);
+    move_ball(0.0);
+    assert_eq!(trigger_events(&mut scene), [("exit", event)]);
+    assert!(trigger_events(&mut scene).is_empty());
+
+    // Маска столкновений отключает пару
+    ball.lock().collider_mut().unwrap().set_collision_mask(0);
+    scene.step();
+    assert!(scene.collisions().is_empty());
+}
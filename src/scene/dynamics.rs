@@ -0,0 +1,291 @@
+//! Симуляция твёрдых тел сцены.
+//!
+//! Перед каждым шагом сцены тела собираются из объектов с [`RigidBody`]
+//! и [`Collider`], продвигаются в [`PhysicsWorld`] фиксированными подшагами
+//! и записываются обратно в `GOTransform::local` и компонент тела.
+use super::Scene;
+use crate::components::{Collider, RigidBody};
+use crate::game_object::{GOParent, GameObject, GameObjectRef};
+use crate::physics::{Body, Joint, JointId, JointKind, PhysicsWorld};
+use crate::references::MutexLockBox;
+use crate::types::{Mat4, Vec3};
+
+/// Глобальная матрица по текущей локальной, до пересчёта в [`GameObject::step`]
+fn current_global(obj: &GameObject) -> Mat4 {
+    match &obj.transform._parent {
+        GOParent::Object(parent) => parent.lock().transform().global * obj.transform.local,
+        _ => obj.transform.local,
+    }
+}
+
+/// Тело объекта. Накопленные импульсы компонента применяются сразу.
+fn make_body(key: i32, obj: &mut GameObject) -> Body {
+    let collider = obj.collider().cloned();
+    let is_static = obj.is_static();
+    let mut body = Body::fixed(key, &current_global(obj), collider, obj.layers());
+    body.is_static = is_static;
+    let Some(rigid_body) = obj.rigid_body_mut() else {
+        return body;
+    };
+    body.friction = rigid_body.friction();
+    body.restitution = rigid_body.restitution();
+    if is_static || !rigid_body.is_dynamic() {
+        return body;
+    }
+    let shape = body.collider.as_ref().map(Collider::shape);
+    let inertia = rigid_body.principal_inertia(shape, &body.scale);
+    body.inverse_mass = rigid_body.inverse_mass();
+    body.inverse_inertia = inertia.map(|moment| if moment > 0.0 { 1.0 / moment } else { 0.0 });
+    body.linear_velocity = rigid_body.linear_velocity();
+    body.angular_velocity = rigid_body.angular_velocity();
+    (body.force, body.torque) = rigid_body.forces();
+    (body.linear_damping, body.angular_damping) = rigid_body.damping();
+    body.gravity_scale = rigid_body.gravity_scale();
+    body.can_sleep = rigid_body.can_sleep();
+    body.sleeping = rigid_body.is_sleeping();
+    body.sleep_timer = rigid_body.sleep_timer();
+    let (linear, angular) = rigid_body.take_impulses();
+    if linear != Vec3::zeros() || angular != Vec3::zeros() {
+        body.apply_impulse(&linear, &angular);
+    }
+    body
+}
+
+#[allow(dead_code)]
+impl Scene {
+    pub fn physics(&self) -> &PhysicsWorld {
+        &self.physics
+    }
+
+    /// Параметры симуляции доступны через [`PhysicsWorld::settings_mut`]
+    pub fn physics_mut(&mut self) -> &mut PhysicsWorld {
+        &mut self.physics
+    }
+
+    /// Соединяет объект `a` с объектом `b` или, если его нет, с миром.
+    /// Точка `anchor` и ось шарнира задаются в мировых координатах.
+    pub fn add_joint(&mut self, kind: JointKind, a: &GameObjectRef, b: Option<&GameObjectRef>, anchor: Vec3) -> JointId {
+        let joint = Joint::new(kind, a.box_id(), b.map(|b| b.box_id()), anchor);
+        self.physics.add_joint(joint)
+    }
+
+    pub fn remove_joint(&mut self, id: JointId) -> bool {
+        self.physics.remove_joint(id).is_some()
+    }
+
+    /// Продвигает физику сцены на `delta` секунд фиксированными подшагами.
+    /// Вызывается из [`Scene::step`] с длительностью прошлого кадра.
+    pub fn simulate(&mut self, delta: f32) {
+        let mut objects = Vec::new();
+        let mut pending = self.root_objects.values().cloned().collect::<Vec<_>>();
+        while let Some(object) = pending.pop() {
+            let obj = object.lock();
+            pending.extend(obj.children());
+            if obj.collider().is_some() || obj.rigid_body().is_some() {
+                let name = obj.name().clone();
+                drop(obj);
+                objects.push((name, object.box_id(), object));
+            }
+        }
+        // Порядок тел не зависит от адресов объектов при разных именах
+        objects.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+
+        let mut bodies = Vec::with_capacity(objects.len());
+        for (_, key, object) in &objects {
+            bodies.push(make_body(*key, &mut object.lock()));
+        }
+        if !bodies.iter().any(Body::is_dynamic) {
+            return;
+        }
+        let was_sleeping = bodies.iter().map(|body| body.sleeping).collect::<Vec<_>>();
+        let steps = self.physics.simulate(&mut bodies, delta);
+
+        for (((_, _, object), body), was_sleeping) in objects.iter().zip(&bodies).zip(was_sleeping) {
+            if !body.is_dynamic() {
+                continue;
+            }
+            let mut obj = object.lock();
+            if !(was_sleeping && body.sleeping) {
+                let global = body.matrix();
+                let parent = match &obj.transform._parent {
+                    GOParent::Object(parent) => parent.lock().transform().global.try_inverse(),
+                    _ => Some(Mat4::identity()),
+                };
+                if let Some(parent) = parent {
+                    obj.transform.local = parent * global;
+                }
+            }
+            let rigid_body: &mut RigidBody = obj.rigid_body_mut().unwrap();
+            rigid_body.store_motion(body.linear_velocity, body.angular_velocity, body.sleeping, body.sleep_timer);
+            if steps > 0 {
+                rigid_body.clear_forces();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn physics_test_object(scene: &mut Scene, name: &str, shape: crate::components::ColliderShape, location: Vec3, mass: Option<f32>) -> GameObjectRef {
+    let object = GameObject::new(name);
+    let mut obj = object.lock();
+    obj.add_component(Collider::new(shape));
+    if let Some(mass) = mass {
+        obj.add_component(RigidBody::new(mass));
+    }
+    obj.transform.local = Mat4::new_translation(&location);
+    obj.set_static(mass.is_none());
+    drop(obj);
+    scene.add_object(object.clone()).unwrap();
+    object
+}
+
+#[cfg(test)]
+fn physics_test_stack(boxes: usize) -> (super::SceneRef, Vec<GameObjectRef>) {
+    use crate::components::ColliderShape;
+    let scene = Scene::new();
+    let mut scn = scene.lock();
+    let ground = ColliderShape::Box {
+        half_extents: Vec3::new(10.0, 10.0, 0.5),
+    };
+    physics_test_object(&mut scn, "ground", ground, Vec3::new(0.0, 0.0, -0.5), None);
+    let cube = ColliderShape::Box {
+        half_extents: Vec3::repeat(0.5),
+    };
+    let boxes = (0..boxes)
+        .map(|i| {
+            let location = Vec3::new(0.02 * i as f32, 0.0, 0.5 + 1.05 * i as f32);
+            physics_test_object(&mut scn, &format!("box {i}"), cube.clone(), location, Some(1.0))
+        })
+        .collect();
+    drop(scn);
+    (scene, boxes)
+}
+
+#[test]
+fn scene_physics_stack_settles() {
+    let (scene, boxes) = physics_test_stack(4);
+    let mut scene = scene.lock();
+    for _ in 0..600 {
+        scene.simulate(1.0 / 60.0);
+    }
+    for (i, object) in boxes.iter().enumerate() {
+        let obj = object.lock();
+        let local = obj.transform.local;
+        let location = local.column(3).xyz();
+        assert!((location.z - (0.5 + i as f32)).abs() < 0.03, "{i}: {location}");
+        assert!((location.x - 0.02 * i as f32).abs() < 0.05, "{i}: {location}");
+        assert!((local.column(2).xyz() - Vec3::z()).norm() < 0.01);
+        assert!(obj.rigid_body().unwrap().is_sleeping());
+    }
+
+    // Импульс будит тело, и оно снова засыпает на земле
+    boxes[0].lock().rigid_body_mut().unwrap().apply_impulse(Vec3::new(0.0, 2.0, 0.0));
+    scene.simulate(1.0 / 60.0);
+    assert!(!boxes[0].lock().rigid_body().unwrap().is_sleeping());
+    assert!(!boxes[3].lock().rigid_body().unwrap().is_sleeping());
+    for _ in 0..600 {
+        scene.simulate(1.0 / 60.0);
+    }
+    assert!(boxes.iter().all(|object| object.lock().rigid_body().unwrap().is_sleeping()));
+    assert!(boxes[3].lock().transform.local[(2, 3)] < 0.5 + 3.0 + 0.03);
+}
+
+#[test]
+fn scene_physics_is_deterministic() {
+    let run = || {
+        let (scene, boxes) = physics_test_stack(3);
+        boxes[2].lock().rigid_body_mut().unwrap().apply_impulse_at(Vec3::new(1.5, 0.5, 0.0), Vec3::new(0.0, 0.3, 0.5));
+        let mut scene = scene.lock();
+        for frame in 0..120 {
+            // Неровные кадры дают одинаковое число подшагов
+            scene.simulate(if frame % 2 == 0 { 0.01 } else { 0.02 });
+        }
+        boxes.iter().map(|object| object.lock().transform.local).collect::<Vec<_>>()
+    };
+    assert_eq!(run(), run());
+}
+
+#[test]
+fn scene_physics_moving_platform() {
+    use crate::components::ColliderShape;
+    let scene = Scene::new();
+    let mut scene = scene.lock();
+    let platform_shape = ColliderShape::Box {
+        half_extents: Vec3::new(2.0, 2.0, 0.5),
+    };
+    let platform = physics_test_object(&mut scene, "platform", platform_shape, Vec3::new(0.0, 0.0, -0.5), None);
+    platform.lock().set_static(false);
+    let cube = ColliderShape::Box {
+        half_extents: Vec3::repeat(0.5),
+    };
+    let cube = physics_test_object(&mut scene, "box", cube, Vec3::new(10.0, 0.0, 1.0), Some(1.0));
+    scene.simulate(1.0 / 60.0);
+
+    // Платформа без массы перемещается извне под падающий ящик,
+    // её габариты в индексе должны следовать за ней
+    platform.lock().transform.local = Mat4::new_translation(&Vec3::new(10.0, 0.0, -0.5));
+    for _ in 0..120 {
+        scene.simulate(1.0 / 60.0);
+    }
+    let location = cube.lock().transform.local.column(3).xyz();
+    assert!((location.z - 0.5).abs() < 0.03, "{location}");
+}
+
+#[test]
+fn scene_physics_joints_and_bounce() {
+    use crate::components::ColliderShape;
+    let scene = Scene::new();
+    let mut scene = scene.lock();
+    let sphere = ColliderShape::Sphere { radius: 0.25 };
+
+    // Маятник на шаровом соединении с миром сохраняет длину подвеса
+    let pendulum = physics_test_object(&mut scene, "pendulum", sphere.clone(), Vec3::new(1.0, 0.0, 5.0), Some(1.0));
+    let anchor = Vec3::new(0.0, 0.0, 5.0);
+    scene.add_joint(JointKind::Ball, &pendulum, None, anchor);
+
+    // Жёстко закреплённое тело не падает, шарнир держит ось
+    let fixed = physics_test_object(&mut scene, "fixed", sphere.clone(), Vec3::new(5.0, 0.0, 5.0), Some(1.0));
+    scene.add_joint(JointKind::Fixed, &fixed, None, Vec3::new(5.0, 0.0, 5.0));
+    let wheel = physics_test_object(&mut scene, "wheel", sphere.clone(), Vec3::new(-5.0, 0.0, 5.0), Some(1.0));
+    let hinge = scene.add_joint(JointKind::Hinge { axis: Vec3::z() }, &wheel, None, Vec3::new(-5.0, 0.0, 5.0));
+    wheel
+        .lock()
+        .rigid_body_mut()
+        .unwrap()
+        .apply_angular_impulse(Vec3::new(0.01, 0.0, 0.02));
+
+    // Упругий шар отскакивает от пола почти на исходную высоту
+    let floor = ColliderShape::Box {
+        half_extents: Vec3::new(100.0, 100.0, 0.5),
+    };
+    physics_test_object(&mut scene, "floor", floor, Vec3::new(0.0, 0.0, -0.5), None);
+    let ball = physics_test_object(&mut scene, "ball", sphere, Vec3::new(0.0, 10.0, 2.25), Some(1.0));
+    {
+        let mut obj = ball.lock();
+        let body = obj.rigid_body_mut().unwrap();
+        body.set_restitution(1.0);
+        body.set_damping(0.0, 0.0);
+    }
+
+    let mut highest_after_bounce: f32 = 0.0;
+    let mut bounced = false;
+    for _ in 0..120 {
+        scene.simulate(1.0 / 60.0);
+        let location = pendulum.lock().transform.local.column(3).xyz();
+        assert!(((location - anchor).norm() - 1.0).abs() < 0.02);
+        let velocity = ball.lock().rigid_body().unwrap().linear_velocity();
+        bounced |= velocity.z > 0.0;
+        if bounced {
+            highest_after_bounce = highest_after_bounce.max(ball.lock().transform.local[(2, 3)]);
+        }
+    }
+    assert!(pendulum.lock().transform.local[(2, 3)] < 4.5);
+    assert!((fixed.lock().transform.local.column(3).xyz() - Vec3::new(5.0, 0.0, 5.0)).norm() < 0.01);
+    let local = wheel.lock().transform.local;
+    assert!((local.column(2).xyz() - Vec3::z()).norm() < 0.01);
+    assert!(wheel.lock().rigid_body().unwrap().angular_velocity().z > 0.1);
+    assert!(bounced && highest_after_bounce > 1.9, "{highest_after_bounce}");
+
+    assert!(scene.remove_joint(hinge));
+    assert!(!scene.remove_joint(hinge));
+}
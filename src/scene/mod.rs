@@ -1,19 +1,32 @@
+use crate::error::DsgeError;
 use std::collections::HashMap;
 
 use crate::{
     game_logic::events::EventProcessor,
     game_object::{GOParent, GameObjectRef},
+    physics::PhysicsWorld,
     references::{MutexLockBox, RcBox},
     resource_manager::ResourceManager,
     time::UniformTime,
 };
 
 use self::scene_loader::read_scene;
+pub use self::collisions::Collision;
+pub use self::raycast::RaycastHit;
+pub use self::spatial::{AabbTree, Frustum, SpatialIndex};
 pub type SceneRef = RcBox<Scene>;
+mod collisions;
+mod dynamics;
+mod navigation;
+mod raycast;
 mod scene_loader;
+pub mod spatial;
 pub struct Scene {
     pub(crate) root_objects: HashMap<i32, GameObjectRef>,
     pub(crate) event_processor: EventProcessor,
+    spatial_index: spatial::SceneSpatialIndex,
+    collision_state: collisions::SceneCollisions,
+    physics: PhysicsWorld,
     instance: Option<SceneRef>,
 }
 
@@ -22,6 +35,9 @@ impl Scene {
         let scene = Self {
             root_objects: HashMap::new(),
             event_processor: Default::default(),
+            spatial_index: Default::default(),
+            collision_state: Default::default(),
+            physics: Default::default(),
             instance: None,
         };
         let instance = RcBox::construct(scene);
@@ -53,16 +69,16 @@ impl Scene {
         (scene, camera)
     }
 
-    pub fn add_object(&mut self, object: GameObjectRef) -> Result<(), String> {
+    pub fn add_object(&mut self, object: GameObjectRef) -> Result<(), DsgeError> {
         self.event_processor.update_object(&*object.lock());
         let mut obj = object.lock();
         match obj.scene {
             Some(ref scene) => {
                 if self.ref_id() != scene.box_id() {
-                    return Err(
+                    return Err(DsgeError::InvalidArgument(
                         "Нельзя добавлять объект с другой сцены. Может когда-нибудь разрешу."
                             .to_owned(),
-                    );
+                    ));
                 };
             }
             None => {
@@ -81,17 +97,21 @@ impl Scene {
     }
 
     pub fn unlink_object(&mut self, obj: GameObjectRef) {
-        let mut object = obj.lock_write();
+        // remove_object сам блокирует объект
         self.event_processor.remove_object(obj.clone());
-        object.scene = None;
+        obj.lock_write().scene = None;
         self.root_objects.remove(&obj.box_id());
+        self.remove_from_spatial_index(&obj);
     }
 
     pub fn step(&mut self) {
+        self.simulate(self.time().delta());
         for (_, obj) in &self.root_objects {
             let mut _obj = obj.lock();
             _obj.step();
         }
+        self.update_spatial_index();
+        self.update_collisions();
         self.event_processor.step();
     }
 
@@ -99,6 +119,27 @@ impl Scene {
         self.root_objects.values().map(|obj| obj.clone()).collect()
     }
 
+    /// Поиск объекта по имени среди всех объектов сцены, включая дочерние
+    pub fn find_object(&self, name: &str) -> Option<GameObjectRef> {
+        fn find_in(objects: Vec<GameObjectRef>, name: &str) -> Option<GameObjectRef> {
+            for obj in objects {
+                let children = {
+                    let _obj = obj.lock();
+                    if _obj.name() == name {
+                        drop(_obj);
+                        return Some(obj);
+                    }
+                    _obj.children()
+                };
+                if let Some(found) = find_in(children, name) {
+                    return Some(found);
+                }
+            }
+            None
+        }
+        find_in(self.root_objects(), name)
+    }
+
     pub(crate) fn ref_id(&self) -> i32 {
         self as *const Self as i32
     }
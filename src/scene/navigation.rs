@@ -0,0 +1,43 @@
+//! Навигационная сетка по статичной геометрии сцены.
+use super::Scene;
+use crate::error::DsgeError;
+use crate::mesh::raycast::triangle_points;
+use crate::navigation::{NavMesh, NavMeshSettings};
+use crate::references::MutexLockBox;
+use crate::types::{Mat4, Vec3};
+
+impl Scene {
+    /// Треугольники мешей статичных объектов из слоёв `layers` в мировых координатах.
+    /// Учитываются меши с сохранёнными на CPU копиями, как в [`Scene::raycast`].
+    pub fn static_triangles(&self, layers: u32) -> Vec<[Vec3; 3]> {
+        let mut triangles = Vec::new();
+        // Глобальные матрицы считаются от корня: до первого шага сцены они не обновлены
+        let mut objects = self.root_objects().into_iter().map(|object| (Mat4::identity(), object)).collect::<Vec<_>>();
+        while let Some((parent, object)) = objects.pop() {
+            let obj = object.lock();
+            let global = parent * obj.transform().local;
+            objects.extend(obj.children().into_iter().map(|child| (global, child)));
+            if !obj.is_static() || obj.layers() & layers == 0 {
+                continue;
+            }
+            let Some(visual) = obj.visual() else {
+                continue;
+            };
+            let mesh = visual.base_mesh();
+            let Some((vertices, indices)) = mesh.cpu_geometry() else {
+                continue;
+            };
+            // Треугольники с индексами за пределами вершинного буфера пропускаются, как в `raycast_triangles`
+            triangles.extend(indices.chunks_exact(3).filter_map(|corners| {
+                let points = triangle_points(vertices, corners, mesh.vertex_offset())?;
+                Some(points.map(|point| (global * point.push(1.0)).xyz()))
+            }));
+        }
+        triangles
+    }
+
+    /// Строит навигационную сетку по статичным объектам из слоёв `layers`
+    pub fn build_navmesh(&self, settings: &NavMeshSettings, layers: u32) -> Result<NavMesh, DsgeError> {
+        NavMesh::build(&self.static_triangles(layers), settings)
+    }
+}
@@ -0,0 +1,73 @@
+//! Запросы лучей к объектам сцены: сначала габариты объектов,
+//! затем треугольники их мешей.
+use super::Scene;
+use crate::components::ProjectionUniformData;
+use crate::game_object::GameObjectRef;
+use crate::mesh::raycast::Ray;
+use crate::references::MutexLockBox;
+use crate::types::{Vec2, Vec3};
+
+/// Пересечение луча с объектом сцены в мировых координатах
+#[derive(Clone)]
+pub struct RaycastHit {
+    pub object: GameObjectRef,
+    /// Номер треугольника меша объекта
+    pub triangle: usize,
+    pub distance: f32,
+    pub point: Vec3,
+    /// Нормаль грани
+    pub normal: Vec3,
+    /// Веса вершин треугольника
+    pub barycentric: Vec3,
+    /// Текстурные координаты в точке пересечения
+    pub uv: Vec2,
+}
+
+impl Scene {
+    /// Ближайший объект с мешем на пути луча не дальше `max_distance`.
+    /// Учитываются объекты, слои которых пересекаются с маской `layers`,
+    /// и меши с сохранёнными на CPU копиями (без уровней детализации).
+    pub fn raycast(&self, ray: &Ray, max_distance: f32, layers: u32) -> Option<RaycastHit> {
+        let ray = Ray::new(ray.origin, ray.direction);
+        let mut nearest: Option<RaycastHit> = None;
+        let mut objects = self.root_objects();
+        while let Some(object) = objects.pop() {
+            let obj = object.lock();
+            objects.extend(obj.children());
+            if obj.layers() & layers == 0 {
+                continue;
+            }
+            let Some(visual) = obj.visual() else {
+                continue;
+            };
+            let model = obj.transform().global;
+            let Some(inverse) = model.try_inverse() else {
+                continue;
+            };
+            let limit = nearest.as_ref().map_or(max_distance, |hit| hit.distance);
+            let local = ray.transformed(&inverse);
+            let mesh = visual.base_mesh();
+            if !local.intersect_bbox(&mesh.bbox()).is_some_and(|t| t <= limit) {
+                continue;
+            }
+            if let Some(hit) = mesh.raycast(&local, limit) {
+                let hit = hit.transformed(&model);
+                nearest = Some(RaycastHit {
+                    object: object.clone(),
+                    triangle: hit.triangle,
+                    distance: hit.distance,
+                    point: hit.point,
+                    normal: hit.normal,
+                    barycentric: hit.barycentric,
+                    uv: hit.uv,
+                });
+            }
+        }
+        nearest
+    }
+
+    /// Объект под точкой экрана, см. [`Ray::from_screen_point`]
+    pub fn pick(&self, camera: &ProjectionUniformData, point: Vec2, layers: u32) -> Option<RaycastHit> {
+        self.raycast(&Ray::from_screen_point(camera, point), f32::INFINITY, layers)
+    }
+}
@@ -3,7 +3,7 @@ use std::path::Path;
 
 use byteorder::ReadBytesExt;
 
-use crate::components::{light::*, CameraComponent, MeshVisual};
+use crate::components::{light::*, CameraComponent, Collider, ColliderShape, MeshVisual, RigidBody};
 use crate::game_object::*;
 use crate::material::MaterialRef;
 use crate::mesh::*;
@@ -62,7 +62,7 @@ fn read_texture(
         .replace("./data/textures/", "")
         .replace("data/textures/", "");
     let result = resource_manager.get_texture(&filepath).unwrap();
-    println!("Текстура {name} {filepath}, {}x{}, mip {:?}", result.width(), result.height(), result._vk_image_access.mip_levels());
+    log::debug!(target: "scene", "Texture {name} {filepath}, {}x{}, mip {:?}", result.width(), result.height(), result._vk_image_access.mip_levels());
     (name, result)
 }
 
@@ -167,7 +167,7 @@ fn read_object(
     let has_camera: bool = reader.read_u8().unwrap() != 0;
     let has_light: bool = reader.read_u8().unwrap() != 0;
     let _has_skeleton: bool = reader.read_u8().unwrap() != 0;
-    let _has_physics: bool = reader.read_u8().unwrap() != 0;
+    let has_physics: bool = reader.read_u8().unwrap() != 0;
 
     if has_mesh {
         let mesh_name = read_string(reader);
@@ -183,6 +183,25 @@ fn read_object(
         let mesh_component = MeshVisual::new(mesh, material, true);
         //println!("Тип: полисетка");
         obj.add_component(mesh_component);
+        if has_physics {
+            // Статичным объектам — треугольники меша, подвижным — выпуклая оболочка и твёрдое тело
+            let shape = if is_static {
+                resource_manager.get_mesh_bvh(&mesh_name).map(ColliderShape::TriangleMesh)
+            } else {
+                ColliderShape::convex_hull_from_mesh(&*meshes[&mesh_name])
+            };
+            match shape {
+                Some(shape) => {
+                    obj.add_component(Collider::new(shape));
+                    if !is_static {
+                        obj.add_component(RigidBody::default());
+                    }
+                }
+                None => log::warn!(target: "scene", "Object {}: mesh {mesh_name} has no CPU copy for a collider", obj.name()),
+            }
+        }
+    } else if has_physics {
+        log::warn!(target: "scene", "Object {}: physics without a mesh is not supported", obj.name());
     };
     if has_camera {
         let camera_component = CameraComponent::new(1.0, 60.0 * 3.1415926535 / 180.0, 0.1, 30.0);
@@ -277,13 +296,13 @@ pub(super) fn read_scene<P: AsRef<Path> + ToString + Clone>(
         Err(_) => panic!("Сцена {:?} не найдена.", path.as_ref()),
     };
     let textures_count: u32 = read_struct(&mut reader).unwrap();
-    println!("Загрузка текстур ({})", textures_count);
+    log::info!(target: "scene", "Loading textures ({})", textures_count);
     let textures: HashMap<String, Texture> = (0..textures_count)
         .map(|_| read_texture(&mut reader, resource_manager))
         .collect();
 
     let materials_count: u32 = read_struct(&mut reader).unwrap();
-    println!("Загрузка материалов ({})", materials_count);
+    log::info!(target: "scene", "Loading materials ({})", materials_count);
     let materials: HashMap<String, MaterialRef> = (0..materials_count)
         .map(|_| {
             let material = read_material(&mut reader, &textures, resource_manager);
@@ -293,11 +312,11 @@ pub(super) fn read_scene<P: AsRef<Path> + ToString + Clone>(
         .collect();
 
     let meshes_count: u32 = read_struct(&mut reader).unwrap();
-    println!("Загрузка мешей ({})", meshes_count);
+    log::info!(target: "scene", "Loading meshes ({})", meshes_count);
     let mesh_names = (0..meshes_count)
         .map(|_| {
             let name = read_string(&mut reader);
-            println!("Меш {name}");
+            log::debug!(target: "scene", "Mesh {name}");
             name.replace("data/mesh/", "")
         })
         .collect::<Vec<_>>();
@@ -305,7 +324,7 @@ pub(super) fn read_scene<P: AsRef<Path> + ToString + Clone>(
     let meshes = resource_manager.get_batch_of_meshes(&mesh_names);
 
     let objects_count: u32 = read_struct(&mut reader).unwrap();
-    println!("Загрузка объектов ({})", objects_count);
+    log::info!(target: "scene", "Loading objects ({})", objects_count);
     let objects: Vec<GameObjectRef> = (0..objects_count)
         .map(|_| read_object(&mut reader, resource_manager, &materials, &meshes))
         .collect();
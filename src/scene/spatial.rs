@@ -0,0 +1,696 @@
+//! Пространственный индекс объектов: динамические деревья габаритов (AABB-деревья).
+//!
+//! Статичные и подвижные объекты хранятся в разных деревьях. Габариты подвижных
+//! расширяются на запас, и лист перестраивается, только когда объект выходит за него
+//! или заметно уменьшается. Статичные габариты хранятся точно.
+//! Запросы отбирают объекты по габаритам и могут возвращать лишние объекты
+//! на величину запаса, точная проверка остаётся за вызывающим кодом.
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use super::Scene;
+use crate::components::ProjectionUniformData;
+use crate::game_object::GameObjectRef;
+use crate::mesh::BoundingBox;
+use crate::references::MutexLockBox;
+use crate::types::{Mat4, Vec3, Vec4};
+
+const NULL: usize = usize::MAX;
+/// Запас габаритов подвижных объектов по умолчанию
+pub const DEFAULT_MARGIN: f32 = 0.1;
+
+/// Пирамида видимости из шести плоскостей `dot(plane.xyz, p) + plane.w >= 0`
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Плоскости отсечения матрицы проекции-вида (метод Грибба—Хартманна).
+    /// Ближняя плоскость `z >= -w` консервативна и для глубины в диапазоне [0; 1].
+    pub fn from_matrix(matrix: &Mat4) -> Self {
+        let row = |i: usize| matrix.row(i).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        Self {
+            planes: [w + x, w - x, w + y, w - y, w + z, w - z],
+        }
+    }
+
+    pub fn from_projection(projection: &ProjectionUniformData) -> Self {
+        Self::from_matrix(&projection.full_matrix())
+    }
+
+    #[inline]
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        self.planes.iter().all(|plane| plane.xyz().dot(&point) + plane.w >= 0.0)
+    }
+
+    /// Консервативная проверка: габариты у угла пирамиды могут пройти, не пересекая её
+    pub fn intersects_bbox(&self, bbox: &BoundingBox) -> bool {
+        self.planes.iter().all(|plane| {
+            // Угол габаритов, дальше всех выступающий по нормали плоскости
+            let corner = Vec3::from_fn(|axis, _| if plane[axis] >= 0.0 { bbox.end[axis] } else { bbox.begin[axis] });
+            plane.xyz().dot(&corner) + plane.w >= 0.0
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+struct TreeNode<T> {
+    /// У листа — расширенные габариты объекта
+    bbox: BoundingBox,
+    parent: usize,
+    children: [usize; 2],
+    /// 0 у листа
+    height: u32,
+    item: Option<T>,
+}
+
+impl<T> TreeNode<T> {
+    #[inline]
+    fn is_leaf(&self) -> bool {
+        self.children[0] == NULL
+    }
+}
+
+/// Динамическое AABB-дерево. Листья хранят элементы и не меняют номер
+/// до удаления, внутренние узлы балансируются поворотами.
+#[derive(Clone, Debug)]
+pub struct AabbTree<T> {
+    nodes: Vec<TreeNode<T>>,
+    free: Vec<usize>,
+    root: usize,
+    margin: f32,
+    len: usize,
+}
+
+impl<T> Default for AabbTree<T> {
+    fn default() -> Self {
+        Self::new(0.0)
+    }
+}
+
+#[allow(dead_code)]
+impl<T> AabbTree<T> {
+    /// Дерево, расширяющее габарит листьев на `margin`
+    pub fn new(margin: f32) -> Self {
+        Self {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            root: NULL,
+            margin,
+            len: 0,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub fn margin(&self) -> f32 {
+        self.margin
+    }
+
+    /// Высота дерева, 0 у пустого и у дерева из одного листа
+    pub fn height(&self) -> u32 {
+        self.nodes.get(self.root).map_or(0, |node| node.height)
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.free.clear();
+        self.root = NULL;
+        self.len = 0;
+    }
+
+    /// Добавляет элемент, возвращает номер его листа
+    pub fn insert(&mut self, bbox: BoundingBox, item: T) -> usize {
+        let leaf = self.allocate(TreeNode {
+            bbox: bbox.expanded(self.margin),
+            parent: NULL,
+            children: [NULL, NULL],
+            height: 0,
+            item: Some(item),
+        });
+        self.insert_leaf(leaf);
+        self.len += 1;
+        leaf
+    }
+
+    pub fn remove(&mut self, leaf: usize) -> Option<T> {
+        let item = self.nodes.get_mut(leaf)?.item.take()?;
+        self.remove_leaf(leaf);
+        self.free.push(leaf);
+        self.len -= 1;
+        Some(item)
+    }
+
+    /// Обновляет габариты элемента. Лист переставляется, только если габариты
+    /// вышли за расширенные или стали меньше них больше чем на запас.
+    /// Возвращает `true`, если лист переставлен.
+    pub fn update(&mut self, leaf: usize, bbox: BoundingBox) -> bool {
+        let fat = self.nodes[leaf].bbox;
+        if fat.contains(&bbox) && bbox.expanded(2.0 * self.margin).contains(&fat) {
+            return false;
+        }
+        self.remove_leaf(leaf);
+        self.nodes[leaf].bbox = bbox.expanded(self.margin);
+        self.insert_leaf(leaf);
+        true
+    }
+
+    pub fn item(&self, leaf: usize) -> Option<&T> {
+        self.nodes.get(leaf)?.item.as_ref()
+    }
+
+    /// Расширенные габариты листа
+    pub fn fat_bbox(&self, leaf: usize) -> Option<BoundingBox> {
+        let node = self.nodes.get(leaf)?;
+        node.item.as_ref().map(|_| node.bbox)
+    }
+
+    /// Обход листьев, габариты которых проходят `test`.
+    /// `test` вызывается и для внутренних узлов и должен быть консервативным.
+    pub fn query(&self, mut test: impl FnMut(&BoundingBox) -> bool, mut visit: impl FnMut(usize, &T)) {
+        if self.root == NULL {
+            return;
+        }
+        let mut stack = vec![self.root];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !test(&node.bbox) {
+                continue;
+            }
+            match node.item {
+                Some(ref item) => visit(index, item),
+                None => stack.extend(node.children),
+            }
+        }
+    }
+
+    fn allocate(&mut self, node: TreeNode<T>) -> usize {
+        match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn insert_leaf(&mut self, leaf: usize) {
+        if self.root == NULL {
+            self.root = leaf;
+            self.nodes[leaf].parent = NULL;
+            return;
+        }
+        // Спуск к соседу, объединение с которым дешевле всего по площади поверхности
+        let leaf_box = self.nodes[leaf].bbox;
+        let mut index = self.root;
+        while !self.nodes[index].is_leaf() {
+            let node = &self.nodes[index];
+            let area = node.bbox.surface_area();
+            let combined = node.bbox.union(&leaf_box).surface_area();
+            let cost = 2.0 * combined;
+            let inherited = 2.0 * (combined - area);
+            let child_cost = |child: usize| {
+                let child = &self.nodes[child];
+                let union_area = child.bbox.union(&leaf_box).surface_area();
+                if child.is_leaf() {
+                    union_area + inherited
+                } else {
+                    union_area - child.bbox.surface_area() + inherited
+                }
+            };
+            let [left, right] = node.children;
+            let (left_cost, right_cost) = (child_cost(left), child_cost(right));
+            if cost < left_cost && cost < right_cost {
+                break;
+            }
+            index = if left_cost < right_cost { left } else { right };
+        }
+
+        let sibling = index;
+        let old_parent = self.nodes[sibling].parent;
+        let new_parent = self.allocate(TreeNode {
+            bbox: self.nodes[sibling].bbox.union(&leaf_box),
+            parent: old_parent,
+            children: [sibling, leaf],
+            height: self.nodes[sibling].height + 1,
+            item: None,
+        });
+        self.replace_child(old_parent, sibling, new_parent);
+        self.nodes[sibling].parent = new_parent;
+        self.nodes[leaf].parent = new_parent;
+        self.refit_ancestors(new_parent);
+    }
+
+    fn remove_leaf(&mut self, leaf: usize) {
+        if leaf == self.root {
+            self.root = NULL;
+            return;
+        }
+        let parent = self.nodes[leaf].parent;
+        let grandparent = self.nodes[parent].parent;
+        let [left, right] = self.nodes[parent].children;
+        let sibling = if left == leaf { right } else { left };
+        self.replace_child(grandparent, parent, sibling);
+        self.nodes[sibling].parent = grandparent;
+        self.free.push(parent);
+        if grandparent != NULL {
+            self.refit_ancestors(grandparent);
+        }
+    }
+
+    fn replace_child(&mut self, parent: usize, old: usize, new: usize) {
+        if parent == NULL {
+            self.root = new;
+            return;
+        }
+        let children = &mut self.nodes[parent].children;
+        if children[0] == old {
+            children[0] = new;
+        } else {
+            children[1] = new;
+        }
+    }
+
+    /// Пересчёт габаритов и высот от узла до корня с балансировкой
+    fn refit_ancestors(&mut self, mut index: usize) {
+        while index != NULL {
+            index = self.balance(index);
+            self.refit(index);
+            index = self.nodes[index].parent;
+        }
+    }
+
+    fn refit(&mut self, index: usize) {
+        let [left, right] = self.nodes[index].children;
+        let (left, right) = (&self.nodes[left], &self.nodes[right]);
+        let bbox = left.bbox.union(&right.bbox);
+        let height = 1 + left.height.max(right.height);
+        let node = &mut self.nodes[index];
+        node.bbox = bbox;
+        node.height = height;
+    }
+
+    /// Поворот, поднимающий более высокого потомка на место узла,
+    /// если высоты потомков различаются больше чем на 1.
+    /// Возвращает узел, оказавшийся на месте `index`.
+    fn balance(&mut self, index: usize) -> usize {
+        let node = &self.nodes[index];
+        if node.is_leaf() || node.height < 2 {
+            return index;
+        }
+        let [left, right] = node.children;
+        let difference = self.nodes[right].height as i32 - self.nodes[left].height as i32;
+        let side = match difference {
+            2.. => 1,
+            ..=-2 => 0,
+            _ => return index,
+        };
+        let up = node.children[side];
+        let parent = node.parent;
+        let [first, second] = self.nodes[up].children;
+        let (taller, shorter) = if self.nodes[first].height > self.nodes[second].height {
+            (first, second)
+        } else {
+            (second, first)
+        };
+        self.nodes[up].children = [index, taller];
+        self.nodes[up].parent = parent;
+        self.nodes[index].parent = up;
+        self.nodes[index].children[side] = shorter;
+        self.nodes[shorter].parent = index;
+        self.replace_child(parent, index, up);
+        self.refit(index);
+        self.refit(up);
+        up
+    }
+}
+
+impl<T: Clone> AabbTree<T> {
+    pub fn query_bbox(&self, bbox: &BoundingBox) -> Vec<T> {
+        let mut result = Vec::new();
+        self.query(|node| node.overlaps(bbox), |_, item| result.push(item.clone()));
+        result
+    }
+
+    pub fn query_sphere(&self, center: Vec3, radius: f32) -> Vec<T> {
+        let mut result = Vec::new();
+        self.query(|node| node.overlaps_sphere(center, radius), |_, item| result.push(item.clone()));
+        result
+    }
+
+    pub fn query_frustum(&self, frustum: &Frustum) -> Vec<T> {
+        let mut result = Vec::new();
+        self.query(|node| frustum.intersects_bbox(node), |_, item| result.push(item.clone()));
+        result
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct IndexEntry {
+    leaf: usize,
+    is_static: bool,
+}
+
+/// Индекс объектов по ключам с раздельными деревьями статичных и подвижных объектов
+#[derive(Clone, Debug)]
+pub struct SpatialIndex<K> {
+    static_tree: AabbTree<K>,
+    dynamic_tree: AabbTree<K>,
+    entries: HashMap<K, IndexEntry>,
+}
+
+impl<K: Copy + Eq + Hash> Default for SpatialIndex<K> {
+    fn default() -> Self {
+        Self::new(DEFAULT_MARGIN)
+    }
+}
+
+#[allow(dead_code)]
+impl<K: Copy + Eq + Hash> SpatialIndex<K> {
+    /// Индекс с запасом `margin` для габаритов подвижных объектов
+    pub fn new(margin: f32) -> Self {
+        Self {
+            static_tree: AabbTree::new(0.0),
+            dynamic_tree: AabbTree::new(margin),
+            entries: HashMap::new(),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    #[inline]
+    pub fn contains(&self, key: &K) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// `Some(true)`, если объект хранится в дереве статичных
+    #[inline]
+    pub fn is_static(&self, key: &K) -> Option<bool> {
+        self.entries.get(key).map(|entry| entry.is_static)
+    }
+
+    pub fn static_tree(&self) -> &AabbTree<K> {
+        &self.static_tree
+    }
+
+    pub fn dynamic_tree(&self) -> &AabbTree<K> {
+        &self.dynamic_tree
+    }
+
+    /// Добавляет объект или обновляет его габариты.
+    /// При смене статичности объект переносится в другое дерево.
+    pub fn update(&mut self, key: K, bbox: BoundingBox, is_static: bool) {
+        if let Some(entry) = self.entries.get(&key).copied() {
+            if entry.is_static == is_static {
+                self.tree_mut(is_static).update(entry.leaf, bbox);
+                return;
+            }
+            self.tree_mut(entry.is_static).remove(entry.leaf);
+        }
+        let leaf = self.tree_mut(is_static).insert(bbox, key);
+        self.entries.insert(key, IndexEntry { leaf, is_static });
+    }
+
+    pub fn remove(&mut self, key: &K) -> bool {
+        match self.entries.remove(key) {
+            Some(entry) => {
+                self.tree_mut(entry.is_static).remove(entry.leaf);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Удаляет объекты, для ключей которых `keep` возвращает `false`
+    pub fn retain(&mut self, mut keep: impl FnMut(&K) -> bool) {
+        let removed = self.entries.keys().filter(|key| !keep(key)).copied().collect::<Vec<_>>();
+        for key in removed {
+            self.remove(&key);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.static_tree.clear();
+        self.dynamic_tree.clear();
+        self.entries.clear();
+    }
+
+    pub fn query_bbox(&self, bbox: &BoundingBox) -> Vec<K> {
+        let mut result = self.static_tree.query_bbox(bbox);
+        result.extend(self.dynamic_tree.query_bbox(bbox));
+        result
+    }
+
+    pub fn query_sphere(&self, center: Vec3, radius: f32) -> Vec<K> {
+        let mut result = self.static_tree.query_sphere(center, radius);
+        result.extend(self.dynamic_tree.query_sphere(center, radius));
+        result
+    }
+
+    pub fn query_frustum(&self, frustum: &Frustum) -> Vec<K> {
+        let mut result = self.static_tree.query_frustum(frustum);
+        result.extend(self.dynamic_tree.query_frustum(frustum));
+        result
+    }
+
+    fn tree_mut(&mut self, is_static: bool) -> &mut AabbTree<K> {
+        if is_static {
+            &mut self.static_tree
+        } else {
+            &mut self.dynamic_tree
+        }
+    }
+}
+
+/// Индекс объектов сцены по [`crate::references::MutexLockBox::box_id`]
+#[derive(Default)]
+pub(super) struct SceneSpatialIndex {
+    index: SpatialIndex<i32>,
+    pub(super) objects: HashMap<i32, GameObjectRef>,
+}
+
+impl Scene {
+    /// Пространственный индекс объектов, обновляемый в [`Scene::step`]
+    pub fn spatial_index(&self) -> &SpatialIndex<i32> {
+        &self.spatial_index.index
+    }
+
+    /// Обновляет индекс по глобальным трансформациям объектов.
+    /// Габариты статичных объектов, уже попавших в индекс, не пересчитываются.
+    pub fn update_spatial_index(&mut self) {
+        let spatial = &mut self.spatial_index;
+        let mut visited = HashMap::with_capacity(spatial.objects.len());
+        let mut objects = self.root_objects.values().cloned().collect::<Vec<_>>();
+        while let Some(object) = objects.pop() {
+            let key = object.box_id();
+            {
+                let obj = object.lock();
+                objects.extend(obj.children());
+                let is_static = obj.is_static();
+                if !is_static || spatial.index.is_static(&key) != Some(true) {
+                    spatial.index.update(key, obj.bbox(), is_static);
+                }
+            }
+            visited.insert(key, object);
+        }
+        spatial.index.retain(|key| visited.contains_key(key));
+        spatial.objects = visited;
+    }
+
+    /// Удаляет из индекса объект вместе с дочерними
+    pub(super) fn remove_from_spatial_index(&mut self, object: &GameObjectRef) {
+        let mut objects = vec![object.clone()];
+        while let Some(object) = objects.pop() {
+            let key = object.box_id();
+            self.spatial_index.index.remove(&key);
+            self.spatial_index.objects.remove(&key);
+            objects.extend(object.lock().children());
+        }
+    }
+
+    /// Объекты, габариты которых пересекают `bbox`
+    pub fn objects_in_bbox(&self, bbox: &BoundingBox, layers: u32) -> Vec<GameObjectRef> {
+        self.spatial_objects(self.spatial_index.index.query_bbox(bbox), layers)
+    }
+
+    /// Объекты, габариты которых пересекают сферу
+    pub fn objects_in_sphere(&self, center: Vec3, radius: f32, layers: u32) -> Vec<GameObjectRef> {
+        self.spatial_objects(self.spatial_index.index.query_sphere(center, radius), layers)
+    }
+
+    /// Объекты, габариты которых попадают в пирамиду видимости проекции
+    pub fn objects_in_frustum(&self, projection: &ProjectionUniformData, layers: u32) -> Vec<GameObjectRef> {
+        let frustum = Frustum::from_projection(projection);
+        self.spatial_objects(self.spatial_index.index.query_frustum(&frustum), layers)
+    }
+
+    pub(super) fn spatial_objects(&self, keys: Vec<i32>, layers: u32) -> Vec<GameObjectRef> {
+        keys.iter()
+            .filter_map(|key| self.spatial_index.objects.get(key))
+            .filter(|object| object.lock().layers() & layers != 0)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+fn check_tree<T>(tree: &AabbTree<T>) {
+    let mut leaves = 0;
+    let mut stack = vec![(tree.root, NULL)];
+    while let Some((index, parent)) = stack.pop() {
+        if index == NULL {
+            continue;
+        }
+        let node = &tree.nodes[index];
+        assert_eq!(node.parent, parent);
+        if node.is_leaf() {
+            assert!(node.item.is_some());
+            leaves += 1;
+            continue;
+        }
+        let [left, right] = node.children.map(|child| &tree.nodes[child]);
+        assert!(node.bbox.contains(&left.bbox) && node.bbox.contains(&right.bbox));
+        assert_eq!(node.height, 1 + left.height.max(right.height));
+        assert!(left.height.abs_diff(right.height) <= 1);
+        stack.extend(node.children.map(|child| (child, index)));
+    }
+    assert_eq!(leaves, tree.len());
+}
+
+#[test]
+fn scene_spatial_index_matches_brute_force() {
+    use rand::{Rng, SeedableRng};
+    let mut rng = rand::rngs::StdRng::seed_from_u64(43);
+    let random_box = |rng: &mut rand::rngs::StdRng| {
+        let begin = Vec3::from_fn(|_, _| rng.gen_range(-50.0..50.0));
+        BoundingBox {
+            begin,
+            end: begin + Vec3::from_fn(|_, _| rng.gen_range(0.0..4.0)),
+        }
+    };
+    let mut index = SpatialIndex::<usize>::new(0.5);
+    let mut boxes = HashMap::new();
+    for key in 0..500 {
+        let bbox = random_box(&mut rng);
+        index.update(key, bbox, key % 3 == 0);
+        boxes.insert(key, bbox);
+    }
+    // Перемещение подвижных, смена статичности и удаление части объектов
+    for key in 0..500 {
+        match key % 7 {
+            0 => {
+                index.remove(&key);
+                boxes.remove(&key);
+            }
+            1 => {
+                let bbox = random_box(&mut rng);
+                index.update(key, bbox, key % 3 != 0);
+                boxes.insert(key, bbox);
+            }
+            _ if key % 3 != 0 => {
+                let shift = Vec3::from_fn(|_, _| rng.gen_range(-0.3..0.3));
+                let bbox = boxes[&key];
+                let bbox = BoundingBox {
+                    begin: bbox.begin + shift,
+                    end: bbox.end + shift,
+                };
+                index.update(key, bbox, false);
+                boxes.insert(key, bbox);
+            }
+            _ => (),
+        }
+    }
+    check_tree(&index.static_tree);
+    check_tree(&index.dynamic_tree);
+    assert_eq!(index.len(), boxes.len());
+    assert!(index.dynamic_tree.height() < 20);
+
+    // Все точные попадания найдены, лишние — только в пределах двойного запаса
+    let check = |mut found: Vec<usize>, test: &dyn Fn(&BoundingBox) -> bool| {
+        found.sort_unstable();
+        let mut expected = boxes.iter().filter(|(_, bbox)| test(bbox)).map(|(key, _)| *key).collect::<Vec<_>>();
+        expected.sort_unstable();
+        assert!(expected.iter().all(|key| found.binary_search(key).is_ok()));
+        assert!(found.iter().all(|key| test(&boxes[key].expanded(1.0))));
+        found.len()
+    };
+    let mut total = 0;
+    for _ in 0..50 {
+        let query = random_box(&mut rng).expanded(5.0);
+        total += check(index.query_bbox(&query), &|bbox| bbox.overlaps(&query));
+        let center = Vec3::from_fn(|_, _| rng.gen_range(-50.0..50.0));
+        total += check(index.query_sphere(center, 8.0), &|bbox| bbox.overlaps_sphere(center, 8.0));
+    }
+    assert!(total > 0);
+
+    let view = Mat4::look_at_rh(&Vec3::new(0.0, 0.0, 60.0).into(), &Vec3::zeros().into(), &Vec3::y());
+    let projection = nalgebra::Perspective3::new(1.0, 0.5, 1.0, 100.0).to_homogeneous();
+    let frustum = Frustum::from_matrix(&(projection * view));
+    assert!(frustum.contains_point(Vec3::zeros()));
+    assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, 70.0)));
+    assert!(!frustum.contains_point(Vec3::new(40.0, 0.0, 0.0)));
+    let visible = check(index.query_frustum(&frustum), &|bbox| frustum.intersects_bbox(bbox));
+    assert!(visible > 0 && visible < boxes.len());
+}
+
+#[test]
+fn scene_spatial_queries() {
+    use crate::game_object::{GameObject, ALL_LAYERS};
+    let scene = Scene::new();
+    let place = |name: &str, location: Vec3, layers: u32| {
+        let object = GameObject::new(name);
+        let mut obj = object.lock();
+        obj.set_static(false);
+        obj.set_layers(layers);
+        obj.transform_mut().unwrap().local = Mat4::new_translation(&location);
+        drop(obj);
+        scene.lock().add_object(object.clone()).unwrap();
+        object
+    };
+    let near = place("near", Vec3::new(1.0, 0.0, 0.0), 1);
+    let far = place("far", Vec3::new(20.0, 0.0, 0.0), 2);
+    let mut scene = scene.lock();
+    scene.step();
+    let names = |objects: Vec<GameObjectRef>| {
+        let mut names = objects.iter().map(|object| object.lock().name().to_owned()).collect::<Vec<_>>();
+        names.sort();
+        names
+    };
+    assert_eq!(names(scene.objects_in_sphere(Vec3::zeros(), 2.0, ALL_LAYERS)), ["near"]);
+    assert_eq!(names(scene.objects_in_sphere(Vec3::zeros(), 30.0, ALL_LAYERS)), ["far", "near"]);
+    assert_eq!(names(scene.objects_in_sphere(Vec3::zeros(), 30.0, 2)), ["far"]);
+    let bbox = BoundingBox {
+        begin: Vec3::new(15.0, -1.0, -1.0),
+        end: Vec3::new(25.0, 1.0, 1.0),
+    };
+    assert_eq!(names(scene.objects_in_bbox(&bbox, ALL_LAYERS)), ["far"]);
+
+    // Перемещение учитывается после шага сцены, удалённые объекты пропадают сразу
+    far.lock().transform_mut().unwrap().local = Mat4::new_translation(&Vec3::new(-20.0, 0.0, 0.0));
+    scene.step();
+    assert!(scene.objects_in_bbox(&bbox, ALL_LAYERS).is_empty());
+    scene.unlink_object(near);
+    assert_eq!(names(scene.objects_in_sphere(Vec3::zeros(), 30.0, ALL_LAYERS)), ["far"]);
+}
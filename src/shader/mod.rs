@@ -1,10 +1,11 @@
 pub use super::glenums::{AttribType, GLSLType, GLSLVersion, ShaderType};
+use crate::error::DsgeError;
 use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Display};
 use std::sync::Arc;
 use byteorder::ReadBytesExt;
 use vulkano::buffer::{
-    Buffer, BufferContents, BufferUsage, BufferCreateInfo,
+    Buffer, BufferContents, BufferUsage, BufferCreateInfo, Subbuffer,
 };
 use vulkano::command_buffer::AutoCommandBufferBuilder;
 use vulkano::descriptor_set::PersistentDescriptorSet;
@@ -179,6 +180,36 @@ impl Shader {
                 FragmentInterpolation::default(),
             )
             .input("v_grp", AttribType::UVec3, FragmentInterpolation::default())
+            .input("v_wgt", AttribType::FVec3, FragmentInterpolation::default())
+    }
+
+    /// Атрибуты компактных вершин [`crate::mesh::VkCompactVertex`].
+    /// Распакованные значения доступны под теми же именами, что и в
+    /// [`Self::default_vertex_attributes`]. Для позиции нужна uniform-структура
+    /// `quantization` из [`crate::mesh::compact::PositionQuantization::uniform_data`].
+    pub fn compact_vertex_attributes(&mut self) -> &mut Self {
+        self.input("c_pos", AttribType::FVec4, FragmentInterpolation::default())
+            .input("c_nt", AttribType::FVec4, FragmentInterpolation::default())
+            .input("c_tex", AttribType::FVec4, FragmentInterpolation::default())
+            .input("c_grp", AttribType::UVec4, FragmentInterpolation::default())
+            .input("c_wgt", AttribType::FVec4, FragmentInterpolation::default())
+            .code(
+                "vec3 oct_decode(vec2 e)
+{
+    vec3 n = vec3(e, 1.0 - abs(e.x) - abs(e.y));
+    float t = max(-n.z, 0.0);
+    n.xy += mix(vec2(t), vec2(-t), greaterThanEqual(n.xy, vec2(0.0)));
+    return normalize(n);
+}",
+            )
+            .define("v_pos", "(quantization.offset.xyz + c_pos.xyz * quantization.scale.xyz)")
+            .define("v_nor", "oct_decode(c_nt.xy)")
+            .define("v_tan", "oct_decode(c_nt.zw)")
+            .define("v_bin", "cross(v_nor, v_tan) * c_pos.w")
+            .define("v_tex1", "c_tex.xy")
+            .define("v_tex2", "c_tex.zw")
+            .define("v_grp", "c_grp.xyz")
+            .define("v_wgt", "c_wgt.xyz")
     }
 
     pub fn instance_attributes(&mut self) -> &mut Self {
@@ -213,7 +244,7 @@ impl Shader {
         array_length: ShaderUniformArrayLength,
         set: u32,
         binding: u32,
-    ) -> Result<&mut Self, String> {
+    ) -> Result<&mut Self, DsgeError> {
         self._uniform_structure(
             name,
             "readonly buffer",
@@ -230,7 +261,7 @@ impl Shader {
         name: &str,
         array_length: ShaderUniformArrayLength,
         set: u32,
-    ) -> Result<&mut Self, String> {
+    ) -> Result<&mut Self, DsgeError> {
         let binding = self.last_set_index(set);
         self.storage_buffer::<T>(name, array_length, set, binding)
     }
@@ -242,7 +273,7 @@ impl Shader {
         array_length: ShaderUniformArrayLength,
         set: u32,
         binding: u32,
-    ) -> Result<&mut Self, String> {
+    ) -> Result<&mut Self, DsgeError> {
         self._uniform_structure(
             name,
             "uniform",
@@ -258,9 +289,9 @@ impl Shader {
     pub fn uniform_constant<T: ShaderStructUniform>(
         &mut self,
         name: &str,
-    ) -> Result<&mut Self, String> {
+    ) -> Result<&mut Self, DsgeError> {
         if self.push_constants.is_some() {
-            return Err(format!("Можно объявить только одну константу."));
+            return Err(DsgeError::LayoutMismatch(format!("Можно объявить только одну константу.")));
         }
         let name = name.to_owned();
 
@@ -287,7 +318,7 @@ impl Shader {
         name: &str,
         array_length: ShaderUniformArrayLength,
         set: u32,
-    ) -> Result<&mut Self, String> {
+    ) -> Result<&mut Self, DsgeError> {
         let binding = self.last_set_index(set);
         self.uniform::<T>(name, array_length, set, binding)
     }
@@ -302,10 +333,10 @@ impl Shader {
         structure: &str,
         set: u32,
         binding: u32,
-    ) -> Result<&mut Self, String> {
+    ) -> Result<&mut Self, DsgeError> {
         let name = name.to_owned();
         if self.uniforms.contains_key(&name) {
-            return Err(format!("Переменная {} уже объявлена в этом шейдере.", name));
+            return Err(DsgeError::LayoutMismatch(format!("Переменная {} уже объявлена в этом шейдере.", name)));
         }
         let uniform_source = ShaderSourceUniform {
             name: name.clone(),
@@ -331,7 +362,7 @@ impl Shader {
         structure: &str,
         set: u32,
         binding: u32,
-    ) -> Result<&mut Self, String> {
+    ) -> Result<&mut Self, DsgeError> {
         self._uniform_structure(
             name,
             "uniform",
@@ -351,7 +382,7 @@ impl Shader {
         array_length: ShaderUniformArrayLength,
         structure: &str,
         set: u32,
-    ) -> Result<&mut Self, String> {
+    ) -> Result<&mut Self, DsgeError> {
         let binding = self.last_set_index(set);
         self.uniform_structure(name, _type, array_length, structure, set, binding)
     }
@@ -363,10 +394,10 @@ impl Shader {
         binding: u32,
         dims: TextureView,
         shadowmap: bool,
-    ) -> Result<&mut Self, String> {
+    ) -> Result<&mut Self, DsgeError> {
         let name = name.to_owned();
         if self.uniforms.contains_key(&name) {
-            return Err(format!("Переменная {} уже объявлена в этом шейдере.", name));
+            return Err(DsgeError::LayoutMismatch(format!("Переменная {} уже объявлена в этом шейдере.", name)));
         }
 
         let utype = dims.glsl_sampler_name().to_owned();
@@ -396,7 +427,7 @@ impl Shader {
         set: u32,
         dims: TextureView,
         shadowmap: bool,
-    ) -> Result<&mut Self, String> {
+    ) -> Result<&mut Self, DsgeError> {
         let binding = self.last_set_index(set);
         self.uniform_sampler(name, set, binding, dims, shadowmap)
     }
@@ -497,7 +528,7 @@ impl Shader {
 
     /// Строит шейдер.
     /// Здесь GLSL код компилируется в SPIR-V.
-    pub fn build(&mut self) -> Result<&Self, String> {
+    pub fn build(&mut self) -> Result<&Self, DsgeError> {
         let hash = self.source_hash();
         let fname = format!("./shader_cache/{:X}.spv", hash);
         let cache_path = Path::new("./shader_cache/");
@@ -513,7 +544,7 @@ impl Shader {
                 let glslc = ".\\vulkan_sdk\\Bin\\glslc.exe";
                 let spirv_opt = ".\\vulkan_sdk\\Bin\\spirv-opt.exe";
                 if !std::fs::metadata(glslc).unwrap().is_file() {
-                    return Err(format!("Компилятор шейдеров {glslc} не найден."));
+                    return Err(DsgeError::AssetNotFound(format!("Компилятор шейдеров {glslc} не найден.")));
                 }
                 if std::fs::metadata(glslc).unwrap().is_file() {
                     (glslc, spirv_opt)
@@ -527,7 +558,9 @@ impl Shader {
             }
         };
 
-        if !spv_path.is_file() {
+        if spv_path.is_file() {
+            log::trace!(target: "shader", "Using cached shader {fname}");
+        } else {
             let sh_type = match self.sh_type {
                 ShaderType::Vertex => "vertex",
                 ShaderType::Fragment => "fragment",
@@ -537,8 +570,9 @@ impl Shader {
                 ShaderType::Geometry => "geometry",
             };
 
+            log::debug!(target: "shader", "Compiling {sh_type} shader {fname}");
             let source = self.source.as_str().as_bytes();
-            
+
             // let mut file = OpenOptions::new()
             //     .write(true)
             //     .create_new(true)
@@ -565,7 +599,7 @@ impl Shader {
                 .spawn();
             let mut child_compiler = match child_compiler {
                 Ok(child) => child,
-                Err(_) => return Err("Не установлен компилятор шейдеров glslc.".to_owned()),
+                Err(_) => return Err(DsgeError::AssetNotFound("Не установлен компилятор шейдеров glslc.".to_owned())),
             };
             let mut spirv_log = String::new();
             {
@@ -583,10 +617,13 @@ impl Shader {
                             numbered_src += format!("{}: {}\n", line_num, line).as_str();
                             line_num += 1;
                         }
-                        return Err(format!("{}\nОшибка шейдера (исходник с нуменованными строками представлен выше)\n{}", numbered_src, spirv_log));
+                        return Err(DsgeError::ShaderCompile {
+                            name: fname,
+                            log: format!("{}\nОшибка шейдера (исходник с нуменованными строками представлен выше)\n{}", numbered_src, spirv_log),
+                        });
                     }
                 }
-                Err(_) => return Err(format!("Шейдер не скомпилирован")),
+                Err(err) => return Err(DsgeError::io(glslc, err)),
             }
         };
 
@@ -647,7 +684,10 @@ impl Shader {
             return Ok(self);
         };
 
-        Err(format!("Шейдер не скомпилирован"))
+        Err(DsgeError::ShaderCompile {
+            name: fname,
+            log: "Шейдер не скомпилирован".to_owned(),
+        })
     }
 }
 
@@ -700,7 +740,7 @@ impl ShaderProgramBuilder {
     }
 
     /// Фрагментный шейдер
-    pub fn fragment(&mut self, shader: &Shader) -> Result<&mut Self, String> {
+    pub fn fragment(&mut self, shader: &Shader) -> Result<&mut Self, DsgeError> {
         let mut uniforms_locations = HashMap::new();
         let mut uniforms_types = HashMap::new();
         for (
@@ -721,12 +761,12 @@ impl ShaderProgramBuilder {
         let type_compat = Self::check_uniforms_compatibility(&self.uniforms_types, &uniforms_types);
 
         if !loc_compat {
-            return Err(format!("Фрагментный шейдер использует те же имена uniform-переменных с разным расположением."));
+            return Err(DsgeError::LayoutMismatch(format!("Фрагментный шейдер использует те же имена uniform-переменных с разным расположением.")));
         }
         if !type_compat {
-            return Err(format!(
+            return Err(DsgeError::LayoutMismatch(format!(
                 "Фрагментный шейдер использует те же имена uniform-переменных с другими типами."
-            ));
+            )));
         }
 
         self.hash ^= shader.spirv_hash();
@@ -741,9 +781,9 @@ impl ShaderProgramBuilder {
         }
         match self.uniform_constant {
             Some(_) => {
-                return Err(format!(
+                return Err(DsgeError::LayoutMismatch(format!(
                     "Uniform-константу можно объявить только в одном шейдере."
-                ))
+                )))
             }
             None => {
                 self.uniform_constant = match &shader.push_constants {
@@ -756,7 +796,7 @@ impl ShaderProgramBuilder {
     }
 
     /// Вершинный шейдер
-    pub fn vertex(&mut self, shader: &Shader) -> Result<&mut Self, String> {
+    pub fn vertex(&mut self, shader: &Shader) -> Result<&mut Self, DsgeError> {
         let mut uniforms_locations = HashMap::new();
         let mut uniforms_types = HashMap::new();
         for (
@@ -778,10 +818,10 @@ impl ShaderProgramBuilder {
         let type_compat = Self::check_uniforms_compatibility(&self.uniforms_types, &uniforms_types);
 
         if !loc_compat {
-            return Err(format!("Вершинный шейдер использует существующие имена uniform-переменных с разными расположениями."));
+            return Err(DsgeError::LayoutMismatch(format!("Вершинный шейдер использует существующие имена uniform-переменных с разными расположениями.")));
         }
         if !type_compat {
-            return Err(format!("Вершинный шейдер использует существующие имена uniform-переменных с разными типами."));
+            return Err(DsgeError::LayoutMismatch(format!("Вершинный шейдер использует существующие имена uniform-переменных с разными типами.")));
         }
 
         self.hash ^= shader.spirv_hash();
@@ -794,7 +834,7 @@ impl ShaderProgramBuilder {
             self.uniforms_types.insert(name.clone(), _type.clone());
         }
         /*match self.uniform_constant {
-            Some(_) => return Err(format!("Uniform-константу можно объявить только в одном шейдере.")),
+            Some(_) => return Err(DsgeError::LayoutMismatch(format!("Uniform-константу можно объявить только в одном шейдере."))),
             None => self.uniform_constant =
             match &shader.push_constants {
                 Some(pc) => Some((pc.1.name.to_owned(), pc.1.type_name.to_owned())),
@@ -809,7 +849,7 @@ impl ShaderProgramBuilder {
         &'static mut self,
         eval: &Shader,
         control: &Shader,
-    ) -> Result<&mut Self, String> {
+    ) -> Result<&'static mut Self, DsgeError> {
         let mut eval_uniforms_locations = HashMap::new();
         let mut eval_uniforms_types = HashMap::new();
         let mut control_uniforms_locations = HashMap::new();
@@ -857,10 +897,10 @@ impl ShaderProgramBuilder {
                 );
 
         if !loc_compat {
-            return Err(format!("Шейдеры тесселяции использует одни и те же имена uniform-переменных с разными расположениями."));
+            return Err(DsgeError::LayoutMismatch(format!("Шейдеры тесселяции использует одни и те же имена uniform-переменных с разными расположениями.")));
         }
         if !type_compat {
-            return Err(format!("Шейдеры тесселяции использует одни и те же имена uniform-переменных с разными типами."));
+            return Err(DsgeError::LayoutMismatch(format!("Шейдеры тесселяции использует одни и те же имена uniform-переменных с разными типами.")));
         }
         self.hash ^= eval.spirv_hash();
         self.hash ^= control.spirv_hash();
@@ -886,9 +926,9 @@ impl ShaderProgramBuilder {
 
         match self.uniform_constant {
             Some(_) => {
-                return Err(format!(
+                return Err(DsgeError::LayoutMismatch(format!(
                     "Uniform-константу можно объявить только в одном шейдере."
-                ))
+                )))
             }
             None => {
                 self.uniform_constant = match &eval.push_constants {
@@ -899,9 +939,9 @@ impl ShaderProgramBuilder {
         }
         match self.uniform_constant {
             Some(_) => {
-                return Err(format!(
+                return Err(DsgeError::LayoutMismatch(format!(
                     "Uniform-константу можно объявить только в одном шейдере."
-                ))
+                )))
             }
             None => {
                 self.uniform_constant = match &control.push_constants {
@@ -920,12 +960,12 @@ impl ShaderProgramBuilder {
         panic!("Не реализовано");
     }
 
-    pub fn build_mutex(self, device: Arc<Device>) -> Result<ShaderProgramRef, String> {
+    pub fn build_mutex(self, device: Arc<Device>) -> Result<ShaderProgramRef, DsgeError> {
         Ok(RcBox::construct(self.build(device)?))
     }
 
     /// Строит шейдерную программу
-    pub fn build(self, device: Arc<Device>) -> Result<ShaderProgram, String> {
+    pub fn build(self, device: Arc<Device>) -> Result<ShaderProgram, DsgeError> {
         Ok(ShaderProgram {
             device: device.clone(),
             pipeline: PipelineType::None,
@@ -999,13 +1039,13 @@ impl ShaderProgramUniformBuffer {
         &mut self,
         ds_allocator: Arc<StandardDescriptorSetAllocator>,
         sets: &[u32]
-    ) -> Result<(), String> {
+    ) -> Result<(), DsgeError> {
         let layouts = match self.pipeline.layout() {
             Some(layout) => layout,
             None => {
-                return Err(format!(
+                return Err(DsgeError::InvalidArgument(format!(
                 "Не получилось собрать буфер uniform-переменных: неподдерживаемый тип конвейера."
-            ))
+            )))
             }
         }
         .set_layouts();
@@ -1239,7 +1279,7 @@ impl ShaderProgramUniformBuffer {
     }
 
     #[inline]
-    pub fn uniform_by_name<T>(&mut self, allocator: Arc<BumpMemoryAllocator>, obj: T, name: &str) -> Result<(), String>
+    pub fn uniform_by_name<T>(&mut self, allocator: Arc<BumpMemoryAllocator>, obj: T, name: &str) -> Result<(), DsgeError>
     where
         T: ShaderStructUniform
             + std::fmt::Debug
@@ -1252,10 +1292,10 @@ impl ShaderProgramUniformBuffer {
         let (set_num, binding_num) = *match self.uniforms_locations.get(name) {
             Some(val) => val,
             None => {
-                return Err(format!(
+                return Err(DsgeError::LayoutMismatch(format!(
                     "Uniform-переменная {} не объявлена в этом шейдере.",
                     name
-                ))
+                )))
             }
         };
         self.uniform(allocator, obj, set_num, binding_num);
@@ -1269,7 +1309,7 @@ impl ShaderProgramUniformBuffer {
         objs: &[T],
         index: u32,
         name: &str,
-    ) -> Result<(), String>
+    ) -> Result<(), DsgeError>
     where
         T: ShaderStructUniform
             + std::fmt::Debug
@@ -1282,10 +1322,10 @@ impl ShaderProgramUniformBuffer {
         let (set_num, binding_num) = *match self.uniforms_locations.get(name) {
             Some(val) => val,
             None => {
-                return Err(format!(
+                return Err(DsgeError::LayoutMismatch(format!(
                     "Uniform-переменная {} не объявлена в этом шейдере.",
                     name
-                ))
+                )))
             }
         };
         self.uniform_array(allocator, objs, index, set_num, binding_num);
@@ -1297,15 +1337,15 @@ impl ShaderProgramUniformBuffer {
         &mut self,
         texture: &Texture,
         name: &str,
-    ) -> Result<(u32, u32), String> {
+    ) -> Result<(u32, u32), DsgeError> {
         //println!("uniform {}", name);
         let (set_num, binding_num) = *match self.uniforms_locations.get(name) {
             Some(val) => val,
             None => {
-                return Err(format!(
+                return Err(DsgeError::LayoutMismatch(format!(
                     "Uniform-переменная {} не объявлена в этом шейдере.",
                     name
-                ));
+                )));
             }
         };
         self.uniform_sampler(texture, set_num, binding_num);
@@ -1325,14 +1365,14 @@ impl ShaderProgramUniformBuffer {
         textures: &[&Texture],
         first_index: u32,
         name: &str,
-    ) -> Result<(u32, u32), String> {
+    ) -> Result<(u32, u32), DsgeError> {
         let (set_num, binding_num) = *match self.uniforms_locations.get(name) {
             Some(val) => val,
             None => {
-                return Err(format!(
+                return Err(DsgeError::LayoutMismatch(format!(
                     "Uniform-переменная {} не объявлена в этом шейдере.",
                     name
-                ));
+                )));
             }
         };
         self.uniform_sampler_array(textures, first_index, set_num, binding_num);
@@ -1345,14 +1385,14 @@ impl ShaderProgramUniformBuffer {
         num_elements: u32,
         first_array_element: u32,
         name: &str,
-    ) -> Result<(u32, u32), String> {
+    ) -> Result<(u32, u32), DsgeError> {
         let (set_num, binding_num) = *match self.uniforms_locations.get(name) {
             Some(val) => val,
             None => {
-                return Err(format!(
+                return Err(DsgeError::LayoutMismatch(format!(
                     "Uniform-переменная {} не объявлена в этом шейдере.",
                     name
-                ));
+                )));
             }
         };
         self.uniform_none_array(num_elements, first_array_element, set_num, binding_num);
@@ -1390,22 +1430,31 @@ impl ShaderProgramUniformBuffer {
         };
     }
 
-    pub fn storage_buffer_by_name<T>(&mut self, allocator: Arc<StandardMemoryAllocator>, obj: T, name: &str) -> Result<(), String>
+    pub fn storage_buffer_by_name<T>(&mut self, allocator: Arc<StandardMemoryAllocator>, obj: T, name: &str) -> Result<(), DsgeError>
     where
         T: std::marker::Send + std::marker::Sync + Pod + 'static,
     {
         let (set_num, binding_num) = *match self.uniforms_locations.get(name) {
             Some(val) => val,
             None => {
-                return Err(format!(
+                return Err(DsgeError::LayoutMismatch(format!(
                     "Uniform-переменная {} не объявлена в этом шейдере.",
                     name
-                ))
+                )))
             }
         };
         self.storage_buffer(allocator, obj, set_num, binding_num);
         Ok(())
     }
+
+    /// Передаёт в шейдер уже созданный буфер хранения, например из меша
+    pub fn storage_subbuffer<T>(&mut self, buffer: Subbuffer<[T]>, set_num: u32, binding_num: u32)
+    where
+        T: std::marker::Send + std::marker::Sync + Pod + 'static,
+    {
+        let ub = WriteDescriptorSet::buffer(binding_num, buffer);
+        self.write_set_descriptors.entry(set_num).or_default().push(ub);
+    }
 }
 
 /// Шейдерная программа
@@ -1549,10 +1598,7 @@ impl ShaderProgram {
         if subpass_full_id == self.subpass_id {
             return (self.pipeline.clone(), false);
         }
-        #[cfg(debug)]
-        {
-            println!("Исользуется новый subpass {:?}", subpass_full_id);
-        }
+        log::trace!(target: "shader", "Using new subpass {:?}", subpass_full_id);
         self.subpass_id = subpass_full_id;
         /*self.uniforms_sets.clear();*/
         let depth_test = subpass.subpass_desc().depth_stencil_attachment.is_some();
@@ -1645,7 +1691,7 @@ impl ShaderProgram {
 /// trait для удобной передачи шейдеров и uniform-переменные в `AutoCommandBufferBuilder`
 pub trait ShaderProgramBinder {
     /// Присоединение шейдерной программы (`GraphicsPipeline`) к `AutoCommandBufferBuilder`'у
-    fn bind_shader_program(&mut self, shader: &ShaderProgram) -> Result<&mut Self, String>;
+    fn bind_shader_program(&mut self, shader: &ShaderProgram) -> Result<&mut Self, DsgeError>;
 
     /// Присоединение uniform-переменных к `AutoCommandBufferBuilder`'у
     fn bind_shader_uniforms(
@@ -1653,22 +1699,22 @@ pub trait ShaderProgramBinder {
         allocator: Arc<StandardDescriptorSetAllocator>,
         uniform_buffer: &mut ShaderProgramUniformBuffer,
         only_dynamic: bool,
-    ) -> Result<&mut Self, String>;
+    ) -> Result<&mut Self, DsgeError>;
 
     fn bind_uniform_constant<T: BufferContents>(
         &mut self,
         shader: &ShaderProgram,
         data: T,
-    ) -> Result<&mut Self, String>;
+    ) -> Result<&mut Self, DsgeError>;
 }
 
 impl<BufferType> ShaderProgramBinder for AutoCommandBufferBuilder<BufferType> {
     #[inline]
-    fn bind_shader_program(&mut self, shader: &ShaderProgram) -> Result<&mut Self, String> {
+    fn bind_shader_program(&mut self, shader: &ShaderProgram) -> Result<&mut Self, DsgeError> {
         match shader.pipeline() {
-            PipelineType::Graphics(pipeline) => self.bind_pipeline_graphics(pipeline).map_err(|e| e.to_string()),
-            PipelineType::Compute(pipeline) => self.bind_pipeline_compute(pipeline).map_err(|e| e.to_string()),
-            PipelineType::None => Err("Не установлен Subpass".to_owned()),
+            PipelineType::Graphics(pipeline) => self.bind_pipeline_graphics(pipeline).map_err(|e| DsgeError::vulkan("Failed to bind pipeline", *e)),
+            PipelineType::Compute(pipeline) => self.bind_pipeline_compute(pipeline).map_err(|e| DsgeError::vulkan("Failed to bind pipeline", *e)),
+            PipelineType::None => Err(DsgeError::InvalidArgument("Не установлен Subpass".to_owned())),
         }
     }
 
@@ -1677,11 +1723,11 @@ impl<BufferType> ShaderProgramBinder for AutoCommandBufferBuilder<BufferType> {
         &mut self,
         shader: &ShaderProgram,
         data: T,
-    ) -> Result<&mut Self, String> {
+    ) -> Result<&mut Self, DsgeError> {
         let pipeline_layout = shader.pipeline.layout().unwrap();
         self
             .push_constants(pipeline_layout.clone(), 0, data)
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| DsgeError::vulkan("Failed to push constants", *e))?;
         Ok(self)
     }
 
@@ -1691,7 +1737,7 @@ impl<BufferType> ShaderProgramBinder for AutoCommandBufferBuilder<BufferType> {
         allocator: Arc<StandardDescriptorSetAllocator>,
         uniform_buffer: &mut ShaderProgramUniformBuffer,
         only_dynamic: bool,
-    ) -> Result<&mut Self, String> {
+    ) -> Result<&mut Self, DsgeError> {
         let pipeline_layout = uniform_buffer.pipeline.layout().unwrap();
         let layouts = pipeline_layout.set_layouts();
         let mut desc_sets = Vec::new();
@@ -1709,7 +1755,7 @@ impl<BufferType> ShaderProgramBinder for AutoCommandBufferBuilder<BufferType> {
             let layout = match layouts.get(set_num as usize)
             {
                 Some(layout) => layout,
-                None => return Err(format!("В шейдере есть неиспользуемые uniform-переменные. Набор {} не используется нигде.", set_num))
+                None => return Err(DsgeError::LayoutMismatch(format!("В шейдере есть неиспользуемые uniform-переменные. Набор {} не используется нигде.", set_num)))
             };
 
             let set = PersistentDescriptorSet::new(&allocator, layout.clone(), descriptor_writes.clone(), []);
@@ -1733,9 +1779,9 @@ impl<BufferType> ShaderProgramBinder for AutoCommandBufferBuilder<BufferType> {
                     } else {
                         "Validated::Error".to_owned()
                     };
-                    return Err(format!(
-                        "{ve}: Не удалось сформировать набор uniform-переменных №{}: {:?}",
-                        set_num, e
+                    return Err(DsgeError::vulkan(
+                        format!("{ve}: Не удалось сформировать набор uniform-переменных №{}", set_num),
+                        e,
                     ));
                 }
             };
@@ -1771,7 +1817,7 @@ impl<BufferType> ShaderProgramBinder for AutoCommandBufferBuilder<BufferType> {
             pipeline_layout.clone(),
             first_set_num as _,
             desc_sets,
-        ).map_err(|e| e.to_string())?;
+        ).map_err(|e| DsgeError::vulkan("Failed to bind descriptor sets", *e))?;
         Ok(self)
     }
 }
@@ -3,6 +3,7 @@ mod ktx_header;
 mod pixel_format;
 mod types;
 
+use crate::error::DsgeError;
 use crate::command_buffer::CommandBufferFather;
 pub use crate::references::*;
 pub use crate::shader::ShaderStructUniform;
@@ -172,7 +173,7 @@ impl Texture {
         pix_fmt: TexturePixelFormat,
         use_case: TextureUseCase,
         allocator: Arc<GenericMemoryAllocator<A>>,
-    ) -> Result<Self, String> 
+    ) -> Result<Self, DsgeError> 
     where A: Suballocator + Send + 'static
     {
         // let size = dims[0] * dims[1] * dims[2] * pix_fmt.block_size() as u32 / pix_fmt.texels_per_block() as u32;
@@ -236,7 +237,7 @@ impl Texture {
             (TextureUseCase::General, _) => (ImageLayout::General, ImageUsage::TRANSFER_SRC | ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED),
             (TextureUseCase::Attachment, false) => (
                 ImageLayout::ColorAttachmentOptimal,
-                ImageUsage::SAMPLED | ImageUsage::COLOR_ATTACHMENT,
+                ImageUsage::SAMPLED | ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC,
             ),
             (TextureUseCase::Attachment, true) => (
                 ImageLayout::DepthStencilAttachmentOptimal,
@@ -282,7 +283,7 @@ impl Texture {
             ..Default::default()
         };
         let image = Image::new(allocator.clone(), create_info, allocation_info)
-            .map_err(|e| e.unwrap().to_string())?;
+            .map_err(|e| DsgeError::vulkan(format!("Failed to create image {name}"), e))?;
 
         //let image_view = ImageView::new_default(image.clone()).unwrap();
         let ivci = ImageViewCreateInfo {
@@ -303,7 +304,7 @@ impl Texture {
                 ..Default::default()
             },
         )
-        .map_err(|e| e.unwrap().to_string())?;
+        .map_err(|e| DsgeError::vulkan(format!("Failed to create sampler for {name}"), e))?;
 
         Ok(Self {
             name: name.to_owned(),
@@ -335,13 +336,13 @@ impl Texture {
         use_case: TextureUseCase,
         command_buffer_father: &CommandBufferFather,
         allocator: Arc<StandardMemoryAllocator>,
-    ) -> Result<(Self, Box<dyn GpuFuture>), String>
+    ) -> Result<(Self, Box<dyn GpuFuture>), DsgeError>
     where
         R: Read + Seek + BufRead,
     {
         let img_rdr = ImageReader::new(reader).with_guessed_format();
         if img_rdr.is_err() {
-            return Err(String::from("Неизвестный формат изображения"));
+            return Err(DsgeError::FormatUnsupported(String::from("Неизвестный формат изображения")));
         }
         let img_rdr = img_rdr.unwrap();
         let image = img_rdr.decode().unwrap();
@@ -417,7 +418,7 @@ impl Texture {
                         mip_level,
                         0
                     ) {
-                        println!("{e}");
+                        log::warn!(target: "resource_manager", "Failed to upload mip level {mip_level}: {e}");
                         //println!("{}, {}x{} => {}x{}", &e[e.find("VUID").unwrap()..], width >> mip_level, height >> mip_level, _image.width(), _image.height());
                     };
                 }
@@ -432,7 +433,7 @@ impl Texture {
         reader: &mut R,
         command_buffer_father: &CommandBufferFather,
         allocator: Arc<StandardMemoryAllocator>,
-    ) -> Result<(Self, Box<dyn GpuFuture>), String>
+    ) -> Result<(Self, Box<dyn GpuFuture>), DsgeError>
     where
         R: Read + Seek + BufRead,
     {
@@ -443,7 +444,7 @@ impl Texture {
             Ok(hdr) => header = Box::new(hdr),
             Err(dds_error) => match KTXHeader::from_bytes(header_bytes.as_slice()) {
                 Ok(hdr) => header = Box::new(hdr),
-                Err(ktx_error) => return Err(format!("{}; {}", dds_error, ktx_error)),
+                Err(ktx_error) => return Err(DsgeError::FormatUnsupported(format!("{}; {}", dds_error, ktx_error))),
             },
         };
 
@@ -505,7 +506,7 @@ impl Texture {
         use_case: TextureUseCase,
         command_buffer_father: &CommandBufferFather,
         allocator: Arc<StandardMemoryAllocator>,
-    ) -> Result<(Self, Box<dyn GpuFuture>), String> {
+    ) -> Result<(Self, Box<dyn GpuFuture>), DsgeError> {
         let texture = Self::new(
             name,
             dims,
@@ -531,7 +532,7 @@ impl Texture {
         self._vk_image_view.view_type()
     }
 
-    pub fn array_layer_as_texture(&self, layer: u32) -> Result<Texture, String> {
+    pub fn array_layer_as_texture(&self, layer: u32) -> Result<Texture, DsgeError> {
         let view_type: ImageViewType = match self.image_view().view_type() {
             ImageViewType::Dim1d => ImageViewType::Dim1d,
             ImageViewType::Dim1dArray => ImageViewType::Dim1d,
@@ -539,7 +540,7 @@ impl Texture {
             ImageViewType::Dim2dArray => ImageViewType::Dim2d,
             ImageViewType::Cube => ImageViewType::Dim2d,
             ImageViewType::CubeArray => ImageViewType::Cube,
-            other => return Err(format!("{other:?} не является массивом")),
+            other => return Err(DsgeError::InvalidArgument(format!("{other:?} не является массивом"))),
         };
         let subresource_range = self._vk_image_view.subresource_range();
         Texture::from_vk_image_view(
@@ -561,7 +562,7 @@ impl Texture {
         )
     }
 
-    pub fn array_slice_as_texture(&self, layers: Range<u32>) -> Result<Texture, String> {
+    pub fn array_slice_as_texture(&self, layers: Range<u32>) -> Result<Texture, DsgeError> {
         let view_type = match (layers.end - layers.start, self.image_view().view_type()) {
             (1, ImageViewType::Dim1dArray) => ImageViewType::Dim1d,
             (1, ImageViewType::Dim2dArray) => ImageViewType::Dim2d,
@@ -598,31 +599,28 @@ impl Texture {
         command_buffer_father: &CommandBufferFather,
         allocator: Arc<StandardMemoryAllocator>,
         _path: P,
-    ) -> Result<(), String> {
+    ) -> Result<(), DsgeError> {
         #[cfg(feature = "use_image")]
         {
             let extension = _path.as_ref().extension();
             //let mut texture_builder = Texture::builder();
             //texture_builder.name(path.to_owned().as_str());
             return match extension {
-                None => Err(String::from("Неизвестный формат изображения")),
+                None => Err(DsgeError::FormatUnsupported(String::from("Неизвестный формат изображения"))),
                 Some(os_str) => {
-                    let reader = std::fs::File::open(_path.as_ref());
-                    if reader.is_err() {
-                        return Err(format!("Файл {} не найден.", _path.to_string()));
-                    }
-                    let reader = reader.unwrap();
+                    let reader = std::fs::File::open(_path.as_ref())
+                        .map_err(|err| DsgeError::open(_path.as_ref(), err))?;
                     let buf_reader = std::io::BufReader::new(reader);
                     let img_rdr = ImageReader::new(buf_reader).with_guessed_format();
                     if img_rdr.is_err() {
-                        return Err(String::from("Неизвестный формат изображения"));
+                        return Err(DsgeError::FormatUnsupported(String::from("Неизвестный формат изображения")));
                     }
                     let img_rdr = img_rdr.unwrap();
                     let image = img_rdr.decode().unwrap().to_rgba8();
 
                     match os_str.to_str() {
                         Some("dds") | Some("ktx") => {
-                            Err("load_data не поддерживает обновление сжатых текстур".to_owned())
+                            Err(DsgeError::FormatUnsupported("load_data не поддерживает обновление сжатых текстур".to_owned()))
                         }
                         _ => {
                             let data = image.as_raw().clone();
@@ -637,7 +635,7 @@ impl Texture {
         }
         #[cfg(not(feature = "use_image"))]
         {
-            Err("Поддержка обычных изображений отключена.".to_owned())
+            Err(DsgeError::FormatUnsupported("Поддержка обычных изображений отключена.".to_owned()))
         }
     }
 
@@ -647,7 +645,7 @@ impl Texture {
         command_buffer_father: &CommandBufferFather,
         allocator: Arc<StandardMemoryAllocator>,
         _path: P,
-    ) -> Result<(), String> {
+    ) -> Result<(), DsgeError> {
         #[cfg(feature = "use_image")]
         {
             //if self._pix_fmt.compression().is_some() || self._pix_fmt.block_extent() != [1,1,1] {
@@ -689,9 +687,11 @@ impl Texture {
                     
                 ).unwrap()
             };
-            println!(
-                "Создан новый временный буфер текстуры размером {} байт",
-                cpuab.size()
+            log::debug!(
+                target: "resource_manager",
+                "Created staging buffer of {} bytes for saving texture {}",
+                cpuab.size(),
+                self.name
             );
             {
                 command_buffer_father.execute_in_new_primary(None, |cbb| {
@@ -737,7 +737,7 @@ impl Texture {
     /// Создаёт `Texture` на основе `ImageViewAbstract`.
     /// В основном используется для представления swapchain изображения в виде текстуры
     /// для вывода результата рендеринга
-    pub fn from_vk_image_view(img: Arc<ImageView>, device: Arc<Device>) -> Result<Texture, String> {
+    pub fn from_vk_image_view(img: Arc<ImageView>, device: Arc<Device>) -> Result<Texture, DsgeError> {
         let img_dims = img.image().extent();
         let sampler = TextureSampler::new(
             device.clone(),
@@ -793,7 +793,7 @@ impl Texture {
         path: P,
         srgb: bool,
         mipmaps: bool
-    ) -> Result<(Texture, Box<dyn GpuFuture>), String>
+    ) -> Result<(Texture, Box<dyn GpuFuture>), DsgeError>
     where
         P: AsRef<std::path::Path> + ToString,
     {
@@ -809,13 +809,10 @@ impl Texture {
         .name(path.to_string().as_str());*/
 
         match extension {
-            None => return Err(String::from("Неизвестный формат изображения")),
+            None => return Err(DsgeError::FormatUnsupported(String::from("Неизвестный формат изображения"))),
             Some(os_str) => {
-                let reader = std::fs::File::open(path.as_ref());
-                if reader.is_err() {
-                    return Err(format!("Файл {} не найден.", path.to_string()));
-                }
-                let reader = reader.unwrap();
+                let reader = std::fs::File::open(path.as_ref())
+                    .map_err(|err| DsgeError::open(path.as_ref(), err))?;
                 let mut buf_reader = std::io::BufReader::new(reader);
                 match os_str.to_str() {
                     Some("dds") | Some("ktx") => Self::compressed_from_file(
@@ -842,9 +839,9 @@ impl Texture {
         }
     }
 
-    pub fn as_cubemap(&self) -> Result<Texture, String> {
+    pub fn as_cubemap(&self) -> Result<Texture, DsgeError> {
         if self.dims()[2] > 0 && self.dims()[2] % 6 != 0 {
-            return Err("Из этого буфера не получится сделать кубическую текстуру: количество слоёв массива не кратно 6".to_owned());
+            return Err(DsgeError::InvalidArgument("Из этого буфера не получится сделать кубическую текстуру: количество слоёв массива не кратно 6".to_owned()));
         }
         let mut ivci = ImageViewCreateInfo::from_image(self._vk_image_access.as_ref());
         ivci.view_type = ImageViewType::Cube;
@@ -899,7 +896,7 @@ impl Texture {
         self._vk_image_dims
     }
 
-    /*pub fn clear(&self, queue: Arc<Queue>) -> Result<Box<dyn GpuFuture>, String>
+    /*pub fn clear(&self, queue: Arc<Queue>) -> Result<Box<dyn GpuFuture>, DsgeError>
     {
         let value: ClearColorValue = match self._pix_fmt.components() {
 
@@ -911,7 +908,7 @@ impl Texture {
         &self,
         command_buffer_father: &CommandBufferFather,
         value: ClearColorValue,
-    ) -> Result<Box<dyn GpuFuture>, String> {
+    ) -> Result<Box<dyn GpuFuture>, DsgeError> {
         Ok(command_buffer_father
             .execute_in_new_primary(None, |cbb| {
                 cbb.clear_color(self, value).unwrap();
@@ -923,7 +920,7 @@ impl Texture {
         &self,
         command_buffer_father: &CommandBufferFather,
         value: ClearDepthStencilValue,
-    ) -> Result<Box<dyn GpuFuture>, String> {
+    ) -> Result<Box<dyn GpuFuture>, DsgeError> {
         Ok(command_buffer_father
             .execute_in_new_primary(None, |cbb| {
                 cbb.clear_depth_stencil(self, value).unwrap();
@@ -935,7 +932,7 @@ impl Texture {
         &self,
         command_buffer_father: &CommandBufferFather,
         texture: &Texture,
-    ) -> Result<Box<dyn GpuFuture>, String> {
+    ) -> Result<Box<dyn GpuFuture>, DsgeError> {
         Ok(command_buffer_father
             .execute_in_new_primary(None, |cbb| {
                 cbb.copy_texture(texture, self).unwrap();
@@ -1007,18 +1004,18 @@ pub(crate) trait TextureCommandSet {
         data: &[u8],
         lod_level: u32,
         array_layer: u32,
-    ) -> Result<&mut Self, String>;
-    fn copy_texture(&mut self, from: &Texture, to: &Texture) -> Result<&mut Self, String>;
+    ) -> Result<&mut Self, DsgeError>;
+    fn copy_texture(&mut self, from: &Texture, to: &Texture) -> Result<&mut Self, DsgeError>;
     fn clear_color(
         &mut self,
         texture: &Texture,
         value: ClearColorValue,
-    ) -> Result<&mut Self, String>;
+    ) -> Result<&mut Self, DsgeError>;
     fn clear_depth_stencil(
         &mut self,
         texture: &Texture,
         value: ClearDepthStencilValue,
-    ) -> Result<&mut Self, String>;
+    ) -> Result<&mut Self, DsgeError>;
 }
 
 impl<Cbbt> TextureCommandSet for AutoCommandBufferBuilder<Cbbt> {
@@ -1029,7 +1026,7 @@ impl<Cbbt> TextureCommandSet for AutoCommandBufferBuilder<Cbbt> {
         data: &[u8],
         lod_level: u32,
         array_layer: u32,
-    ) -> Result<&mut Self, String> {
+    ) -> Result<&mut Self, DsgeError> {
         let upload_buffer = Buffer::new_slice(
             allocator,
             BufferCreateInfo {
@@ -1072,11 +1069,14 @@ impl<Cbbt> TextureCommandSet for AutoCommandBufferBuilder<Cbbt> {
         };
         match self.copy_buffer_to_image(copy_info.clone()) {
             Ok(s) => Ok(s),
-            Err(e) => Err(format!(
-                "{e}. Mip level {:?}, {}x{}",
-                copy_info.regions[0].image_subresource.mip_level,
-                copy_info.regions[0].image_extent[0],
-                copy_info.regions[0].image_extent[1],
+            Err(e) => Err(DsgeError::vulkan(
+                format!(
+                    "Failed to upload mip level {}, {}x{}",
+                    copy_info.regions[0].image_subresource.mip_level,
+                    copy_info.regions[0].image_extent[0],
+                    copy_info.regions[0].image_extent[1],
+                ),
+                *e,
             )),
         }
     }
@@ -1085,7 +1085,7 @@ impl<Cbbt> TextureCommandSet for AutoCommandBufferBuilder<Cbbt> {
         &mut self,
         texture: &Texture,
         value: ClearDepthStencilValue,
-    ) -> Result<&mut Self, String> {
+    ) -> Result<&mut Self, DsgeError> {
         let subresource_range = texture.image_view().subresource_range();
         let mut ccii = ClearDepthStencilImageInfo::image(texture._vk_image_access.clone());
         ccii.clear_value = value;
@@ -1098,7 +1098,7 @@ impl<Cbbt> TextureCommandSet for AutoCommandBufferBuilder<Cbbt> {
         &mut self,
         texture: &Texture,
         value: ClearColorValue,
-    ) -> Result<&mut Self, String> {
+    ) -> Result<&mut Self, DsgeError> {
         let mut ccii = ClearColorImageInfo::image(texture._vk_image_access.clone());
         let subresource_range = texture.image_view().subresource_range();
         ccii.clear_value = value;
@@ -1107,7 +1107,7 @@ impl<Cbbt> TextureCommandSet for AutoCommandBufferBuilder<Cbbt> {
         Ok(self)
     }
 
-    fn copy_texture(&mut self, src: &Texture, dst: &Texture) -> Result<&mut Self, String> {
+    fn copy_texture(&mut self, src: &Texture, dst: &Texture) -> Result<&mut Self, DsgeError> {
         let blit_info = BlitImageInfo::images(
             src._vk_image_view.image().clone(),
             dst._vk_image_view.image().clone(),
@@ -1137,7 +1137,7 @@ impl<Cbbt> TextureCommandSet for AutoCommandBufferBuilder<Cbbt> {
         });
         match blit_result {
             Ok(acbb) => Ok(acbb),
-            Err(err) => Err(format!("{:?}", err)),
+            Err(err) => Err(DsgeError::vulkan("Failed to copy texture", *err)),
         }
     }
 }
@@ -1,3 +1,4 @@
+use crate::error::DsgeError;
 use byteorder::{ByteOrder, LittleEndian};
 //use super::pixel_format::*;
 use crate::texture::pixel_format::TexturePixelFormat;
@@ -34,22 +35,22 @@ pub struct DDSHeader {
 }
 
 impl DDSHeader {
-    pub fn from_bytes(first_128_bytes: &[u8]) -> Result<Self, String> {
+    pub fn from_bytes(first_128_bytes: &[u8]) -> Result<Self, DsgeError> {
         debug_assert!(first_128_bytes.len() >= 128);
         let mut vals: [u32; 32] = [0; 32];
         LittleEndian::read_u32_into(&first_128_bytes[0..128], &mut vals);
         if vals[0] != 0x20534444 {
-            return Err(String::from("Не DDS файл"));
+            return Err(DsgeError::FormatUnsupported(String::from("Не DDS файл")));
         }
         match vals[21] {
             0x31545844 => (),
             0x33545844 => (),
             0x35545844 => (),
             _ => {
-                return Err(format!(
+                return Err(DsgeError::FormatUnsupported(format!(
                     "Неизвестный формат сжатия в DDS изображении {:X}",
                     vals[21]
-                ));
+                )));
             }
         }
         Ok(Self {
@@ -1,3 +1,4 @@
+use crate::error::DsgeError;
 use super::pixel_format::TexturePixelFormat;
 use byteorder::{BigEndian, ByteOrder, LittleEndian};
 
@@ -31,13 +32,13 @@ pub struct Ktx2Header
 
 impl Ktx2Header
 {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String>
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DsgeError>
     {
         if size_of::<Ktx2Header>() != bytes.len() {
-            return Err(format!("Длина байтового массива ({}) не равна {}",
+            return Err(DsgeError::FormatUnsupported(format!("Длина байтового массива ({}) не равна {}",
                 bytes.len(),
                 size_of::<Ktx2Header>()
-            ));
+            )));
         }
         let header: Ktx2Header = unsafe {
             from_raw_parts(bytes as *const [u8] as _, 1)
@@ -88,7 +89,7 @@ pub struct KTXHeader {
 #[allow(dead_code)]
 impl KTXHeader {
     /// Reads first 64 bytes to parse KTX header data, returns a `KtxHeader`.
-    pub fn from_bytes(first_64_bytes: &[u8]) -> Result<Self, String> {
+    pub fn from_bytes(first_64_bytes: &[u8]) -> Result<Self, DsgeError> {
         debug_assert!(first_64_bytes.len() >= 64);
         debug_assert_eq!(&first_64_bytes[..12], &KTX1_IDENTIFIER, "Not KTX1");
 
@@ -65,6 +65,7 @@ impl TPF
 {
 
 }*/
+use crate::error::DsgeError;
 use vulkano::format::*;
 pub type TexturePixelFormat = Format;
 
@@ -73,14 +74,14 @@ pub trait TexturePixelFormatFeatures {
     fn subpixels(&self) -> u32;
     fn subpixel_size(&self) -> u32;
     fn size(&self) -> u32;
-    fn from_vk_format(fmt: Format) -> Result<Self, String>
+    fn from_vk_format(fmt: Format) -> Result<Self, DsgeError>
     where
         Self: Sized;
     fn vk_format(&self) -> Format;
 }
 
 impl TexturePixelFormatFeatures for TexturePixelFormat {
-    fn from_vk_format(fmt: Format) -> Result<Self, String> {
+    fn from_vk_format(fmt: Format) -> Result<Self, DsgeError> {
         Ok(fmt)
     }
 